@@ -0,0 +1,235 @@
+//! An arrival-price execution algo taking liquidity from the engine.
+//!
+//! A parent buy order is worked in child slices sized from the visible ask depth within a
+//! slippage band around the arrival price. A dealer keeps quoting around a fair value that
+//! drifts with the algo's buying, noise traders add market volume, and the fills are fed to
+//! the TCA analyzer for slippage, participation and reversion.
+
+use crossbeam::channel::{unbounded, Receiver};
+use rust_order_execution_engine::{ExecutionEngine, Order, ParentOrder, Price, Side, TcaAnalyzer, Trade};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{info, Level};
+use uuid::Uuid;
+
+const SYMBOL: &str = "BTCUSD";
+const ALGO: &str = "algo";
+const DEALER: &str = "dealer";
+
+/// Arrival-price algo parameters
+struct AlgoConfig {
+    /// Parent order size
+    quantity: u64,
+    /// Highest price paid, in basis points over the arrival price
+    max_slippage_bps: f64,
+    /// Share of the visible depth inside the band taken by one slice
+    depth_fraction: f64,
+    /// Pause between slices, letting the book refill
+    interval: Duration,
+}
+
+/// Buys the parent quantity in slices and tracks its own fills
+struct ArrivalPriceAlgo {
+    config: AlgoConfig,
+    parent: ParentOrder,
+    children: Vec<Uuid>,
+    filled: u64,
+    notional: f64,
+}
+
+impl ArrivalPriceAlgo {
+    fn new(config: AlgoConfig, arrival_price: Price) -> Self {
+        let parent = ParentOrder {
+            id: Uuid::new_v4(),
+            symbol: SYMBOL.to_string(),
+            side: Side::Buy,
+            quantity: config.quantity,
+            arrival_time: chrono::Utc::now(),
+            arrival_price,
+        };
+        Self {
+            config,
+            parent,
+            children: Vec::new(),
+            filled: 0,
+            notional: 0.0,
+        }
+    }
+
+    fn remaining(&self) -> u64 {
+        self.parent.quantity - self.filled
+    }
+
+    fn limit_price(&self) -> Price {
+        Price::from(self.parent.arrival_price.to_f64() * (1.0 + self.config.max_slippage_bps / 10_000.0))
+    }
+
+    /// Running slippage of the average fill against the arrival price, in basis points
+    fn slippage_bps(&self) -> Option<f64> {
+        let arrival = self.parent.arrival_price.to_f64();
+        (self.filled > 0).then(|| (self.notional / self.filled as f64 - arrival) / arrival * 10_000.0)
+    }
+
+    fn on_trade(&mut self, trade: &Trade) {
+        if self.children.contains(&trade.buy_order_id) {
+            self.filled += trade.quantity;
+            self.notional += trade.price.to_f64() * trade.quantity as f64;
+        }
+    }
+
+    /// Size the next child from the asks inside the slippage band and send it; the child is
+    /// priced at the deepest level it targets, so it takes liquidity without resting
+    async fn next_slice(&mut self, engine: &ExecutionEngine, tca: &mut TcaAnalyzer) -> Option<Order> {
+        let limit = self.limit_price();
+        let depth = engine.get_depth(SYMBOL, 10)?;
+        let mut available = 0;
+        let mut deepest = None;
+        for &(price, quantity, _) in depth.asks.iter().take_while(|(price, _, _)| *price <= limit) {
+            available += quantity;
+            deepest = Some(price);
+        }
+
+        let slice = ((available as f64 * self.config.depth_fraction).ceil() as u64).min(self.remaining());
+        if slice == 0 {
+            info!("No liquidity inside {} yet, waiting", limit);
+            return None;
+        }
+
+        let child = Order::new_limit(SYMBOL.to_string(), Side::Buy, slice, deepest?, ALGO.to_string())
+            .with_parent(self.parent.id, None);
+        tca.record_child(&child);
+        self.children.push(child.id);
+        engine.submit_order(child.clone()).await.unwrap();
+        Some(child)
+    }
+}
+
+/// Small deterministic generator so runs are reproducible without extra dependencies
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Replace the dealer's ladder with five levels a side around `fair`
+async fn requote_dealer(engine: &ExecutionEngine, quotes: &mut Vec<Uuid>, fair: f64) {
+    for id in quotes.drain(..) {
+        // Quotes the algo or the noise traders filled completely are already gone
+        let _ = engine.cancel_order(id, SYMBOL.to_string()).await;
+    }
+    for level in 1..=5 {
+        let offset = level as f64 * 2.0;
+        for (side, price) in [(Side::Buy, fair - offset), (Side::Sell, fair + offset)] {
+            let order = Order::new_limit(SYMBOL.to_string(), side, 5 * level, price.round(), DEALER.to_string());
+            quotes.push(order.id);
+            engine.submit_order(order).await.unwrap();
+        }
+    }
+}
+
+fn drain_trades(trades: &Receiver<Trade>, algo: &mut ArrivalPriceAlgo, tca: &mut TcaAnalyzer) -> u64 {
+    let filled = algo.filled;
+    for trade in trades.try_iter() {
+        algo.on_trade(&trade);
+        tca.record_trade(&trade);
+    }
+    algo.filled - filled
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+
+    let (trade_sender, trade_receiver) = unbounded();
+    let engine = ExecutionEngine::new(trade_sender);
+    engine.start().await;
+    let bbo = engine.subscribe_bbo(SYMBOL);
+
+    let mut rng = Lcg(7);
+    let mut fair = 50_000.0;
+    let mut dealer_quotes = Vec::new();
+    requote_dealer(&engine, &mut dealer_quotes, fair).await;
+    sleep(Duration::from_millis(20)).await;
+
+    // Benchmark the parent against the mid when it arrives
+    let touch = *bbo.borrow();
+    let (Some((bid, _)), Some((ask, _))) = (touch.bid, touch.ask) else {
+        panic!("dealer should be quoting both sides");
+    };
+    let arrival_price = Price::from((bid.to_f64() + ask.to_f64()) / 2.0);
+
+    let mut tca = TcaAnalyzer::new(Duration::from_millis(200));
+    let mut algo = ArrivalPriceAlgo::new(
+        AlgoConfig {
+            quantity: 300,
+            max_slippage_bps: 5.0,
+            depth_fraction: 0.5,
+            interval: Duration::from_millis(20),
+        },
+        arrival_price,
+    );
+    tca.add_parent(algo.parent.clone());
+    info!("Buying {} {} from arrival price {}", algo.parent.quantity, SYMBOL, arrival_price);
+
+    while algo.remaining() > 0 {
+        if let Some(child) = algo.next_slice(&engine, &mut tca).await {
+            sleep(Duration::from_millis(5)).await;
+            let filled = drain_trades(&trade_receiver, &mut algo, &mut tca);
+            // Never leave a child working: whatever did not fill straight away is pulled
+            if filled < child.quantity {
+                let _ = engine.cancel_order(child.id, SYMBOL.to_string()).await;
+            }
+            info!(
+                "Child {} at {}: filled {}/{}, parent {}/{}, slippage {:.2} bps",
+                algo.children.len(),
+                child.price.unwrap(),
+                filled,
+                child.quantity,
+                algo.filled,
+                algo.parent.quantity,
+                algo.slippage_bps().unwrap_or_default()
+            );
+            // The algo's buying pushes fair value up
+            fair += filled as f64 * 0.02;
+        }
+
+        // Noise traders add market volume, then the dealer refreshes its ladder
+        let side = if rng.next() < 0.5 { Side::Buy } else { Side::Sell };
+        let noise = Order::new_market(SYMBOL.to_string(), side, 1 + (rng.next() * 10.0) as u64, "noise".to_string());
+        engine.submit_order(noise).await.unwrap();
+        fair += (rng.next() - 0.5) * 2.0;
+        requote_dealer(&engine, &mut dealer_quotes, fair).await;
+
+        sleep(algo.config.interval).await;
+        drain_trades(&trade_receiver, &mut algo, &mut tca);
+    }
+
+    // Keep the market trading past the reversion horizon, with the impact decaying
+    for _ in 0..15 {
+        fair += (arrival_price.to_f64() - fair) * 0.2;
+        requote_dealer(&engine, &mut dealer_quotes, fair).await;
+        let side = if rng.next() < 0.5 { Side::Buy } else { Side::Sell };
+        engine
+            .submit_order(Order::new_market(SYMBOL.to_string(), side, 3, "noise".to_string()))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(20)).await;
+        drain_trades(&trade_receiver, &mut algo, &mut tca);
+    }
+
+    let report = tca.report();
+    info!("=== Arrival Price Execution ===");
+    for order in &report.orders {
+        info!("Filled: {}/{} in {} children", order.filled_quantity, order.quantity, algo.children.len());
+        info!("Average price: {:.2}", order.avg_fill_price.unwrap_or_default());
+        info!("Arrival slippage: {:.2} bps", order.arrival_slippage_bps.unwrap_or_default());
+        info!("Participation: {:.1}%", order.participation_rate.unwrap_or_default() * 100.0);
+        info!("Reversion: {:.2} bps", order.reversion_bps.unwrap_or_default());
+    }
+    info!("TCA CSV:\n{}", report.to_csv());
+
+    engine.stop().await;
+}
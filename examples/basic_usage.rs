@@ -3,7 +3,6 @@ use rust_order_execution_engine::{ExecutionEngine, Order, Side};
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{info, Level};
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() {
@@ -81,8 +80,8 @@ async fn main() {
     // Get order book state
     if let Some((best_bid, best_ask, depth)) = engine.get_order_book("BTCUSD") {
         info!("=== Order Book ===");
-        info!("Best Bid: {:?}", best_bid);
-        info!("Best Ask: {:?}", best_ask);
+        info!("Best Bid: {:?}", best_bid.map(|p| p.to_string()));
+        info!("Best Ask: {:?}", best_ask.map(|p| p.to_string()));
         info!("Total Depth: {}", depth);
     }
 
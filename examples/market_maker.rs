@@ -0,0 +1,277 @@
+//! A two-sided market maker running against the engine.
+//!
+//! Simulated takers trade against the book while the market maker keeps a bid and an ask
+//! around its fair value, skews both quotes against its inventory, replaces them with a
+//! single bulk amend and stops quoting a side once a risk limit is reached.
+
+use crossbeam::channel::{unbounded, Receiver};
+use rust_order_execution_engine::instrument::{InstrumentConfig, TickPolicy};
+use rust_order_execution_engine::{Amendment, ExecutionEngine, Order, Price, Side, Trade};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{info, warn, Level};
+use uuid::Uuid;
+
+const SYMBOL: &str = "BTCUSD";
+const MAKER: &str = "maker";
+const TICK: f64 = 0.5;
+const STEPS: usize = 300;
+
+/// Market maker parameters
+struct MakerConfig {
+    /// Distance of each quote from fair value before skew
+    half_spread: f64,
+    /// Quantity shown on each side
+    quote_size: u64,
+    /// Price shift per unit of inventory, against the position
+    skew_per_unit: f64,
+    /// Largest absolute position before the side that would grow it is pulled
+    max_position: i64,
+    /// Loss at which the maker pulls all quotes and stops
+    max_loss: f64,
+}
+
+/// One working quote
+#[derive(Clone, Copy)]
+struct Quote {
+    id: Uuid,
+    price: Price,
+    /// Quantity the order was last sized to, including fills
+    quantity: u64,
+    filled: u64,
+}
+
+struct MarketMaker {
+    config: MakerConfig,
+    bid: Option<Quote>,
+    ask: Option<Quote>,
+    position: i64,
+    cash: f64,
+    stopped: bool,
+}
+
+impl MarketMaker {
+    fn new(config: MakerConfig) -> Self {
+        Self {
+            config,
+            bid: None,
+            ask: None,
+            position: 0,
+            cash: 0.0,
+            stopped: false,
+        }
+    }
+
+    /// Book our fills and forget quotes that are done
+    fn on_trade(&mut self, trade: &Trade) {
+        let notional = trade.price.to_f64() * trade.quantity as f64;
+        for (side, quote) in [(Side::Buy, &mut self.bid), (Side::Sell, &mut self.ask)] {
+            let Some(current) = quote else {
+                continue;
+            };
+            let ours = match side {
+                Side::Buy => trade.buy_order_id == current.id,
+                Side::Sell => trade.sell_order_id == current.id,
+            };
+            if !ours {
+                continue;
+            }
+
+            current.filled += trade.quantity;
+            match side {
+                Side::Buy => {
+                    self.position += trade.quantity as i64;
+                    self.cash -= notional;
+                }
+                Side::Sell => {
+                    self.position -= trade.quantity as i64;
+                    self.cash += notional;
+                }
+            }
+            if current.filled >= current.quantity {
+                *quote = None;
+            }
+        }
+    }
+
+    fn pnl(&self, mark: f64) -> f64 {
+        self.cash + self.position as f64 * mark
+    }
+
+    /// Target (bid, ask) prices; a side is `None` when it would breach the position limit
+    fn targets(&self, fair: f64) -> (Option<Price>, Option<Price>) {
+        let skew = self.config.skew_per_unit * self.position as f64;
+        let round = |price: f64| Price::from((price / TICK).round() * TICK);
+        let bid = (self.position < self.config.max_position).then(|| round(fair - self.config.half_spread - skew));
+        let ask = (self.position > -self.config.max_position).then(|| round(fair + self.config.half_spread - skew));
+        (bid, ask)
+    }
+
+    /// Bring both quotes to their targets: amend working quotes in one bulk step, submit
+    /// missing ones and cancel sides that are over the limit
+    async fn requote(&mut self, engine: &ExecutionEngine, fair: f64) {
+        if self.stopped {
+            return;
+        }
+        if self.pnl(fair) < -self.config.max_loss {
+            warn!("Loss limit reached at {:.2}: pulling quotes", self.pnl(fair));
+            self.pull(engine).await;
+            self.stopped = true;
+            return;
+        }
+
+        let (bid_target, ask_target) = self.targets(fair);
+        let size = self.config.quote_size;
+        let mut amendments = Vec::new();
+
+        for (side, target, quote) in [(Side::Buy, bid_target, &mut self.bid), (Side::Sell, ask_target, &mut self.ask)] {
+            match (target, quote.as_mut()) {
+                (Some(price), Some(current)) => {
+                    let refill = current.quantity - current.filled < size;
+                    if current.price != price || refill {
+                        // Top the quote back up to full size on top of what already filled
+                        current.quantity = current.filled + size;
+                        current.price = price;
+                        amendments.push(Amendment {
+                            order_id: current.id,
+                            new_price: Some(price),
+                            new_quantity: Some(current.quantity),
+                        });
+                    }
+                }
+                (Some(price), None) => {
+                    let order = Order::new_limit(SYMBOL.to_string(), side, size, price, MAKER.to_string());
+                    *quote = Some(Quote {
+                        id: order.id,
+                        price,
+                        quantity: size,
+                        filled: 0,
+                    });
+                    if let Err(e) = engine.submit_order(order).await {
+                        warn!("Quote rejected: {}", e);
+                        *quote = None;
+                    }
+                }
+                (None, Some(current)) => {
+                    info!("Position limit: pulling {} quote", side);
+                    let _ = engine.cancel_order(current.id, SYMBOL.to_string()).await;
+                    *quote = None;
+                }
+                (None, None) => {}
+            }
+        }
+
+        if !amendments.is_empty() {
+            if let Err(e) = engine.modify_orders(SYMBOL.to_string(), amendments).await {
+                warn!("Quote amend rejected: {}", e);
+            }
+        }
+    }
+
+    async fn pull(&mut self, engine: &ExecutionEngine) {
+        for quote in [self.bid.take(), self.ask.take()].into_iter().flatten() {
+            let _ = engine.cancel_order(quote.id, SYMBOL.to_string()).await;
+        }
+    }
+}
+
+/// Small deterministic generator so runs are reproducible without extra dependencies
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn drain_trades(trades: &Receiver<Trade>, maker: &mut MarketMaker) -> usize {
+    let mut count = 0;
+    for trade in trades.try_iter() {
+        maker.on_trade(&trade);
+        count += 1;
+    }
+    count
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+
+    let (trade_sender, trade_receiver) = unbounded();
+    let engine = ExecutionEngine::new(trade_sender);
+    engine
+        .register_instrument(InstrumentConfig::new(SYMBOL.to_string()).with_tick_size(TICK, TickPolicy::RoundPassive))
+        .unwrap();
+    engine.start().await;
+    let bbo = engine.subscribe_bbo(SYMBOL);
+
+    let mut maker = MarketMaker::new(MakerConfig {
+        half_spread: 5.0,
+        quote_size: 10,
+        skew_per_unit: 0.2,
+        max_position: 40,
+        max_loss: 2_000.0,
+    });
+
+    // Fair value drifts as a random walk; takers hit whichever side is cheaper for them
+    let mut rng = Lcg(42);
+    let mut fair = 50_000.0;
+    let mut trade_count = 0;
+
+    for step in 0..STEPS {
+        fair += (rng.next() - 0.5) * 4.0;
+        maker.requote(&engine, fair).await;
+        sleep(Duration::from_millis(2)).await;
+
+        if rng.next() < 0.6 {
+            // Takers lean towards the side the market is moving to
+            let bbo = *bbo.borrow();
+            let mid = match (bbo.bid, bbo.ask) {
+                (Some((bid, _)), Some((ask, _))) => (bid.to_f64() + ask.to_f64()) / 2.0,
+                _ => fair,
+            };
+            let side = if rng.next() < 0.5 + (fair - mid) / 20.0 { Side::Buy } else { Side::Sell };
+            let quantity = 1 + (rng.next() * 8.0) as u64;
+            let taker = Order::new_market(SYMBOL.to_string(), side, quantity, format!("taker_{}", step % 7));
+            engine.submit_order(taker).await.unwrap();
+        }
+
+        sleep(Duration::from_millis(2)).await;
+        trade_count += drain_trades(&trade_receiver, &mut maker);
+
+        if step % 50 == 0 {
+            let touch = *bbo.borrow();
+            info!(
+                "step {:>3}: fair {:.2}, bbo {:?} / {:?}, position {}, pnl {:.2}",
+                step,
+                fair,
+                touch.bid.map(|(price, quantity)| format!("{} x {}", price, quantity)),
+                touch.ask.map(|(price, quantity)| format!("{} x {}", price, quantity)),
+                maker.position,
+                maker.pnl(fair)
+            );
+        }
+    }
+
+    maker.pull(&engine).await;
+    sleep(Duration::from_millis(50)).await;
+    trade_count += drain_trades(&trade_receiver, &mut maker);
+
+    info!("=== Market Maker ===");
+    info!("Trades: {}", trade_count);
+    info!("Final position: {}", maker.position);
+    info!("PnL at fair value: {:.2}", maker.pnl(fair));
+    if let Some(quoting) = engine.get_quoting_metrics(MAKER, SYMBOL) {
+        info!("Quoting metrics: {:?}", quoting);
+    }
+
+    let metrics = engine.get_metrics();
+    info!("=== Engine ===");
+    info!("Total Orders: {}", metrics.total_orders);
+    info!("Rejected Orders: {}", metrics.rejected_orders);
+    info!("Total Volume: ${:.2}", metrics.total_volume);
+    info!("P99 Latency: {} μs", metrics.p99_latency_micros);
+
+    engine.stop().await;
+}
@@ -0,0 +1,136 @@
+//! Soak run: random order flow across several symbols with the invariant auditor on.
+//!
+//! Runs for the number of seconds given as the first argument (10 by default) and exits
+//! with an error if any audit found a violated invariant or trades went undelivered:
+//!
+//! ```bash
+//! cargo run --release --example soak -- 7200
+//! ```
+
+use crossbeam::channel::Receiver;
+use rust_order_execution_engine::market_data::SubscriberEvent;
+use rust_order_execution_engine::{AuditReport, ExecutionEngine, Order, Price, Side};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{error, Level};
+use uuid::Uuid;
+
+const SYMBOLS: [&str; 3] = ["BTCUSD", "ETHUSD", "SOLUSD"];
+const AUDIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Small deterministic generator so runs are reproducible without extra dependencies
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() * n as f64) as usize % n
+    }
+}
+
+/// Log new reports and update the run's violation total
+fn drain_reports(reports: &Receiver<SubscriberEvent<AuditReport>>, total: &mut u64) {
+    for event in reports.try_iter() {
+        match event {
+            SubscriberEvent::Update(report) => {
+                for violation in &report.violations {
+                    error!("Audit {}: {}", report.audit, violation);
+                }
+                *total = report.total_violations;
+            }
+            SubscriberEvent::DataLoss { dropped } => println!("{} audit reports dropped", dropped),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // Engine warnings about orders that already left the book are expected here
+    tracing_subscriber::fmt().with_max_level(Level::ERROR).init();
+
+    let seconds = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(10);
+    let duration = Duration::from_secs(seconds);
+
+    let (trade_sender, trade_receiver) = crossbeam::channel::unbounded();
+    let engine = ExecutionEngine::new(trade_sender);
+    engine.start().await;
+    let reports = engine.start_soak_audit(AUDIT_INTERVAL);
+    println!("Soak run for {}s, auditing every {:?}", seconds, AUDIT_INTERVAL);
+
+    let mut rng = Lcg(1);
+    let mut resting: Vec<(Uuid, &str)> = Vec::new();
+    let mut trades = 0;
+    let mut violations = 0;
+    let mut sent = 0u64;
+    let start = Instant::now();
+    let mut last_status = Instant::now();
+
+    while start.elapsed() < duration {
+        let symbol = SYMBOLS[rng.below(SYMBOLS.len())];
+        let side = if rng.next() < 0.5 { Side::Buy } else { Side::Sell };
+        let client = format!("client_{}", rng.below(20));
+        let quantity = 1 + rng.below(50) as u64;
+        let price = 1_000.0 + rng.below(40) as f64;
+
+        // Mostly new limit orders, with cancels, amends and market orders mixed in
+        let roll = rng.next();
+        let result = if roll < 0.6 || resting.is_empty() {
+            let order = Order::new_limit(symbol.to_string(), side, quantity, price, client);
+            resting.push((order.id, symbol));
+            engine.submit_order(order).await
+        } else if roll < 0.8 {
+            let (order_id, symbol) = resting.swap_remove(rng.below(resting.len()));
+            engine.cancel_order(order_id, symbol.to_string()).await
+        } else if roll < 0.9 {
+            let (order_id, symbol) = resting[rng.below(resting.len())];
+            engine.modify_order(order_id, symbol.to_string(), Some(Price::from(price)), None).await
+        } else {
+            engine.submit_order(Order::new_market(symbol.to_string(), side, quantity, client)).await
+        };
+        if let Err(e) = result {
+            error!("Command refused: {}", e);
+        }
+        sent += 1;
+        // Forget orders that have most likely filled or been cancelled
+        if resting.len() > 5_000 {
+            resting.drain(..1_000);
+        }
+
+        if sent.is_multiple_of(100) {
+            sleep(Duration::from_millis(1)).await;
+            trades += trade_receiver.try_iter().count();
+            drain_reports(&reports, &mut violations);
+        }
+        if last_status.elapsed() >= Duration::from_secs(5) {
+            let metrics = engine.get_metrics();
+            println!(
+                "{:>6}s  commands {:>9}  trades {:>9}  p99 {:>6} μs  violations {}",
+                start.elapsed().as_secs(),
+                sent,
+                metrics.total_trades,
+                metrics.p99_latency_micros,
+                violations
+            );
+            last_status = Instant::now();
+        }
+    }
+
+    // Let one more audit run over the final state
+    sleep(AUDIT_INTERVAL * 2).await;
+    trades += trade_receiver.try_iter().count();
+    drain_reports(&reports, &mut violations);
+    let shutdown = engine.stop().await.expect("engine was running");
+
+    println!("Commands: {}, trades: {}, invariant violations: {}", sent, trades, violations);
+    println!(
+        "Shutdown: {} commands drained, {} orders resting, {} trades undelivered",
+        shutdown.commands_drained, shutdown.resting_orders, shutdown.unsent_events
+    );
+    if violations > 0 || !shutdown.is_clean() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::types::{Order, OrderType, Price, Side, Trade};
+use serde::{Deserialize, Serialize};
+
+/// Trading phase of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TradingPhase {
+    /// Call phase before the open: orders accumulate without matching
+    PreOpen,
+    /// Continuous price-time matching
+    #[default]
+    Continuous,
+    /// Call phase before the close: orders accumulate without matching
+    PreClose,
+    /// After the closing uncross; new orders are rejected
+    Closed,
+}
+
+impl TradingPhase {
+    /// Whether this is an auction call phase
+    pub fn is_call(&self) -> bool {
+        matches!(self, TradingPhase::PreOpen | TradingPhase::PreClose)
+    }
+
+    /// Auction order type that participates in this call phase's uncross
+    pub fn auction_order_type(&self) -> Option<OrderType> {
+        match self {
+            TradingPhase::PreOpen => Some(OrderType::MarketOnOpen),
+            TradingPhase::PreClose => Some(OrderType::MarketOnClose),
+            TradingPhase::Continuous | TradingPhase::Closed => None,
+        }
+    }
+}
+
+/// Outcome of an auction uncross
+#[derive(Debug, Clone, Default)]
+pub struct AuctionResult {
+    /// Uncross price; `None` when nothing could execute
+    pub price: Option<Price>,
+    pub volume: u64,
+    pub trades: Vec<Trade>,
+}
+
+/// Where the auction would clear if it uncrossed now
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndicativeAuction {
+    /// Indicative uncross price; `None` when nothing would execute
+    pub price: Option<Price>,
+    /// Quantity that would execute at `price`
+    pub volume: u64,
+    /// Quantity left unmatched at `price`
+    pub imbalance: u64,
+    /// Side holding the unmatched quantity, if any
+    pub imbalance_side: Option<Side>,
+}
+
+/// Indicative uncross of `buys` against `sells` without executing anything
+pub fn indicative_auction(buys: &[Order], sells: &[Order]) -> IndicativeAuction {
+    let Some((price, volume)) = equilibrium_price(buys, sells) else {
+        return IndicativeAuction::default();
+    };
+
+    let buy_volume = executable_volume(buys, |p| p >= price);
+    let sell_volume = executable_volume(sells, |p| p <= price);
+    let imbalance_side = match buy_volume.cmp(&sell_volume) {
+        std::cmp::Ordering::Greater => Some(Side::Buy),
+        std::cmp::Ordering::Less => Some(Side::Sell),
+        std::cmp::Ordering::Equal => None,
+    };
+
+    IndicativeAuction {
+        price: Some(price),
+        volume,
+        imbalance: buy_volume.abs_diff(sell_volume),
+        imbalance_side,
+    }
+}
+
+/// Remaining quantity of orders willing to trade at a price accepted by `accepts`; market orders always are
+fn executable_volume(orders: &[Order], accepts: impl Fn(Price) -> bool) -> u64 {
+    orders
+        .iter()
+        .filter(|o| o.price.is_none_or(&accepts))
+        .map(Order::remaining_quantity)
+        .sum()
+}
+
+/// Price that maximizes executable volume between `buys` and `sells`, with that volume.
+///
+/// Orders without a price are market orders and execute at any price. Candidate prices
+/// are the limit prices on either side. Ties on volume go to the price with the smallest
+/// imbalance, then to the side with surplus (highest price for buy surplus, lowest for
+/// sell surplus), then to the lowest price.
+pub fn equilibrium_price(buys: &[Order], sells: &[Order]) -> Option<(Price, u64)> {
+    let mut candidates: Vec<Price> = buys.iter().chain(sells).filter_map(|o| o.price).collect();
+    candidates.sort();
+    candidates.dedup();
+
+    let mut best: Option<(Price, u64, u64)> = None; // (price, volume, imbalance)
+    for price in candidates {
+        let buy_volume = executable_volume(buys, |p| p >= price);
+        let sell_volume = executable_volume(sells, |p| p <= price);
+        let volume = buy_volume.min(sell_volume);
+        if volume == 0 {
+            continue;
+        }
+        let imbalance = buy_volume.abs_diff(sell_volume);
+
+        let better = match best {
+            None => true,
+            Some((_, best_volume, _)) if volume != best_volume => volume > best_volume,
+            Some((_, _, best_imbalance)) if imbalance != best_imbalance => imbalance < best_imbalance,
+            // Candidates are ascending, so a later price only wins under buy pressure
+            Some(_) => buy_volume > sell_volume,
+        };
+        if better {
+            best = Some((price, volume, imbalance));
+        }
+    }
+
+    best.map(|(price, volume, _)| (price, volume))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+
+    fn limit(side: Side, quantity: u64, price: f64) -> Order {
+        Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string())
+    }
+
+    #[test]
+    fn test_equilibrium_price() {
+        let buys = vec![
+            limit(Side::Buy, 10, 101.0),
+            limit(Side::Buy, 10, 100.0),
+            Order::new_market_on_open("BTCUSD".to_string(), Side::Buy, 5, "client2".to_string()),
+        ];
+        let sells = vec![limit(Side::Sell, 10, 99.0), limit(Side::Sell, 15, 100.0), limit(Side::Sell, 10, 102.0)];
+
+        // At 100: 25 bid against 25 offered
+        assert_eq!(equilibrium_price(&buys, &sells), Some((100.0.into(), 25)));
+
+        // Nothing crosses
+        let sells = vec![limit(Side::Sell, 10, 105.0)];
+        assert_eq!(equilibrium_price(&buys[..2], &sells), None);
+    }
+
+    #[test]
+    fn test_indicative_auction() {
+        let buys = vec![limit(Side::Buy, 30, 101.0)];
+        let sells = vec![limit(Side::Sell, 10, 100.0), limit(Side::Sell, 5, 101.0)];
+
+        let indicative = indicative_auction(&buys, &sells);
+        assert_eq!(indicative.price, Some(101.0.into()));
+        assert_eq!(indicative.volume, 15);
+        assert_eq!(indicative.imbalance, 15);
+        assert_eq!(indicative.imbalance_side, Some(Side::Buy));
+
+        assert_eq!(indicative_auction(&buys, &[]), IndicativeAuction::default());
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Instant;
+
+/// Where an event timestamp was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClockSource {
+    /// System wall clock; may step backwards when the clock is adjusted
+    #[default]
+    Wall,
+    /// Monotonic clock anchored to the wall clock at startup; never goes backwards
+    Monotonic,
+    /// PTP-disciplined hardware clock
+    Ptp,
+}
+
+/// Timestamp source for engine events.
+///
+/// Implement this for hardware clocks (e.g. a PTP-synchronized NIC) and report
+/// [`ClockSource::Ptp`] so consumers can tell the timestamps apart.
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+
+    fn source(&self) -> ClockSource;
+}
+
+/// System wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WallClock;
+
+impl Clock for WallClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn source(&self) -> ClockSource {
+        ClockSource::Wall
+    }
+}
+
+/// Wall-clock time at creation plus monotonic elapsed time since.
+///
+/// Timestamps never go backwards, at the cost of drifting from the wall clock over time.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    anchor: DateTime<Utc>,
+    started: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            anchor: Utc::now(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = chrono::Duration::from_std(self.started.elapsed()).unwrap_or(chrono::Duration::MAX);
+        self.anchor + elapsed
+    }
+
+    fn source(&self) -> ClockSource {
+        ClockSource::Monotonic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic_clock() {
+        let clock = MonotonicClock::new();
+        let first = clock.now();
+        let second = clock.now();
+
+        assert!(second >= first);
+        assert!((first - Utc::now()).num_seconds().abs() < 1);
+        assert_eq!(clock.source(), ClockSource::Monotonic);
+    }
+}
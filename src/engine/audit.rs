@@ -0,0 +1,183 @@
+use super::EngineState;
+use crate::types::ExecutionMetrics;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Invariant checked in soak mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Invariant {
+    /// The best bid is below the best ask wherever matching runs continuously
+    UncrossedBook,
+    /// Levels, queues and the order index agree, and no resting order is overfilled
+    BookIntegrity,
+    /// Client positions on each symbol net to zero, as every trade has a buyer and a seller
+    BalancedPositions,
+    /// Counters never go down and agree with each other
+    ConsistentMetrics,
+}
+
+/// One failed invariant check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvariantViolation {
+    pub invariant: Invariant,
+    /// Symbol the violation was found on; `None` for engine-wide checks
+    pub symbol: Option<String>,
+    pub detail: String,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{:?} on {}: {}", self.invariant, symbol, self.detail),
+            None => write!(f, "{:?}: {}", self.invariant, self.detail),
+        }
+    }
+}
+
+/// Outcome of one soak audit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Sequence number of the audit within the run, from 1
+    pub audit: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Books checked
+    pub books: usize,
+    pub violations: Vec<InvariantViolation>,
+    /// Violations found by this and every earlier audit of the run
+    pub total_violations: u64,
+}
+
+/// Books, positions and metrics as seen between two commands
+pub(super) struct StateAudit {
+    pub(super) books: usize,
+    pub(super) violations: Vec<InvariantViolation>,
+    pub(super) metrics: ExecutionMetrics,
+}
+
+impl EngineState {
+    /// Check the state invariants. Runs on the matching thread between commands, so no
+    /// command is ever seen half applied.
+    pub(super) fn audit(&self) -> StateAudit {
+        let violation = |invariant, symbol: Option<&str>, detail: String| InvariantViolation {
+            invariant,
+            symbol: symbol.map(str::to_string),
+            detail,
+        };
+        let mut violations = Vec::new();
+
+        let books = self.order_books.all();
+        for (id, book) in &books {
+            let book = book.lock().unwrap();
+            let symbol = book.symbol();
+            for problem in book.check_integrity() {
+                violations.push(violation(Invariant::BookIntegrity, Some(symbol), problem));
+            }
+            if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+                if bid >= ask && !self.matching_suspended(*id) {
+                    violations.push(violation(Invariant::UncrossedBook, Some(symbol), format!("Bid {} against ask {}", bid, ask)));
+                }
+            }
+        }
+
+        for (symbol, net) in self.positions.lock().unwrap().net_by_symbol() {
+            if net != 0 {
+                violations.push(violation(Invariant::BalancedPositions, Some(&symbol), format!("Positions net to {}", net)));
+            }
+        }
+
+        let metrics = self.metrics.lock().unwrap().clone();
+        for (symbol, histogram) in &metrics.resting_time {
+            let bucketed: u64 = histogram.bucket_counts.iter().sum();
+            if bucketed != histogram.count {
+                violations.push(violation(
+                    Invariant::ConsistentMetrics,
+                    Some(symbol),
+                    format!("Resting time buckets hold {} samples of {}", bucketed, histogram.count),
+                ));
+            }
+        }
+
+        StateAudit {
+            books: books.len(),
+            violations,
+            metrics,
+        }
+    }
+}
+
+/// Check `metrics` for internal agreement and against the previous audit's snapshot
+pub(super) fn audit_metrics(metrics: &ExecutionMetrics, previous: &ExecutionMetrics) -> Vec<InvariantViolation> {
+    let mut problems = Vec::new();
+
+    let counters = [
+        ("total_orders", metrics.total_orders, previous.total_orders),
+        ("filled_orders", metrics.filled_orders, previous.filled_orders),
+        ("cancelled_orders", metrics.cancelled_orders, previous.cancelled_orders),
+        ("rejected_orders", metrics.rejected_orders, previous.rejected_orders),
+        ("expired_orders", metrics.expired_orders, previous.expired_orders),
+        ("total_trades", metrics.total_trades, previous.total_trades),
+    ];
+    for (name, current, before) in counters {
+        if current < before {
+            problems.push(format!("{} went down from {} to {}", name, before, current));
+        }
+    }
+    if !metrics.total_volume.is_finite() || metrics.total_volume < previous.total_volume {
+        problems.push(format!("total_volume went from {} to {}", previous.total_volume, metrics.total_volume));
+    }
+    // Each batch of trades fills at least one order
+    if metrics.filled_orders > metrics.total_trades {
+        problems.push(format!("{} filled orders from {} trades", metrics.filled_orders, metrics.total_trades));
+    }
+    if (metrics.total_trades == 0) != (metrics.total_volume == 0.0) {
+        problems.push(format!("{} trades with volume {}", metrics.total_trades, metrics.total_volume));
+    }
+
+    problems
+        .into_iter()
+        .map(|detail| InvariantViolation {
+            invariant: Invariant::ConsistentMetrics,
+            symbol: None,
+            detail,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ExecutionEngine;
+    use crate::types::{Order, Side};
+    use crossbeam::channel::unbounded;
+
+    #[test]
+    fn test_audit_flags_violations() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let state = &engine.state;
+
+        // A crossed book, as if matching had been skipped
+        let limit = |side, price: f64| Order::new_limit("BTCUSD".to_string(), side, 5, price, "client".to_string());
+        let book = state.order_books.get_or_insert_with(state.symbols.intern("BTCUSD"), || state.new_book("BTCUSD"));
+        book.lock().unwrap().add_order(limit(Side::Buy, 101.0));
+        book.lock().unwrap().add_order(limit(Side::Sell, 100.0));
+
+        let audit = state.audit();
+        let invariants: Vec<Invariant> = audit.violations.iter().map(|v| v.invariant).collect();
+        assert_eq!(audit.books, 1);
+        assert!(invariants.contains(&Invariant::UncrossedBook));
+        assert!(!invariants.contains(&Invariant::BookIntegrity));
+
+        let previous = ExecutionMetrics {
+            total_trades: 3,
+            filled_orders: 1,
+            total_volume: 300.0,
+            ..ExecutionMetrics::default()
+        };
+        // Trades, filled orders and volume all went down
+        let violations = audit_metrics(&ExecutionMetrics::default(), &previous);
+        assert_eq!(violations.len(), 3);
+        assert!(audit_metrics(&previous, &ExecutionMetrics::default()).is_empty());
+    }
+}
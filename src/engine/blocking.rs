@@ -0,0 +1,99 @@
+use super::{ExecutionEngine, OrderEvent, OrderEventConfig, Result, ShutdownReport};
+use crate::market_data::SubscriberEvent;
+use crate::types::{Order, Price};
+use crossbeam::channel::Receiver;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Blocking front end to an [`ExecutionEngine`], for applications without an async runtime.
+///
+/// Matching runs on its own thread either way; the calls here wait on the calling thread
+/// instead of yielding to an executor. Order events are collected from construction on and
+/// handed out by [`poll_events`](Self::poll_events), so a trading loop can submit, then
+/// poll, on one thread. Don't call these from inside an async task: they block it.
+pub struct BlockingEngine {
+    engine: ExecutionEngine,
+    events: Receiver<SubscriberEvent<OrderEvent>>,
+}
+
+impl BlockingEngine {
+    pub fn new(engine: ExecutionEngine) -> Self {
+        Self::with_events(engine, OrderEventConfig::default())
+    }
+
+    /// Collect order events as `config` says (e.g. a single client's, or a larger buffer)
+    pub fn with_events(engine: ExecutionEngine, config: OrderEventConfig) -> Self {
+        let events = engine.subscribe_order_events(config);
+        Self { engine, events }
+    }
+
+    /// The wrapped engine, for queries and settings that don't wait
+    pub fn engine(&self) -> &ExecutionEngine {
+        &self.engine
+    }
+
+    pub fn start(&self) {
+        block_on(self.engine.start())
+    }
+
+    /// Stop once every command accepted so far has been processed
+    pub fn stop(&self) -> Option<ShutdownReport> {
+        block_on(self.engine.stop())
+    }
+
+    /// Submit a new order, waiting for room in the command queue if it is full
+    pub fn submit_order_blocking(&self, order: Order) -> Result<()> {
+        block_on(self.engine.submit_order(order))
+    }
+
+    pub fn cancel_order_blocking(&self, order_id: Uuid, symbol: String) -> Result<()> {
+        block_on(self.engine.cancel_order(order_id, symbol))
+    }
+
+    pub fn modify_order_blocking(
+        &self,
+        order_id: Uuid,
+        symbol: String,
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+    ) -> Result<()> {
+        block_on(self.engine.modify_order(order_id, symbol, new_price, new_quantity))
+    }
+
+    /// Order events published since the last poll, oldest first, without waiting
+    pub fn poll_events(&self) -> Vec<SubscriberEvent<OrderEvent>> {
+        self.events.try_iter().collect()
+    }
+
+    /// Wait up to `timeout` for the next order event
+    pub fn next_event(&self, timeout: Duration) -> Option<SubscriberEvent<OrderEvent>> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+/// Wakes the thread parked in `block_on`
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drive `future` to completion on the calling thread. The engine's futures only wait on
+/// runtime-agnostic channels and semaphores, so parking until woken is all they need.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
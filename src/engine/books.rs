@@ -0,0 +1,97 @@
+use crate::matching::OrderBook;
+use crate::types::SymbolId;
+use std::sync::{Arc, Mutex, RwLock};
+
+pub(crate) type SharedBook = Arc<Mutex<OrderBook>>;
+
+/// Order books by symbol ID, each behind its own lock.
+///
+/// Books sit in a slot per ID, so finding one is an index rather than a hash of its name.
+/// The map lock is only held to look a book up or add one, so readers and matching on
+/// different symbols never wait on each other.
+#[derive(Default)]
+pub(crate) struct BookMap {
+    books: RwLock<Vec<Option<SharedBook>>>,
+}
+
+impl BookMap {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn get(&self, symbol: SymbolId) -> Option<SharedBook> {
+        self.books.read().unwrap().get(symbol.index())?.clone()
+    }
+
+    /// Run `f` on `symbol`'s book while holding its lock
+    pub(crate) fn with<R>(&self, symbol: SymbolId, f: impl FnOnce(&mut OrderBook) -> R) -> Option<R> {
+        self.get(symbol).map(|book| f(&mut book.lock().unwrap()))
+    }
+
+    /// `symbol`'s book, created with `create` if the symbol has none yet
+    pub(crate) fn get_or_insert_with(&self, symbol: SymbolId, create: impl FnOnce() -> OrderBook) -> SharedBook {
+        if let Some(book) = self.get(symbol) {
+            return book;
+        }
+        let mut books = self.books.write().unwrap();
+        let slot = Self::slot(&mut books, symbol);
+        Arc::clone(slot.get_or_insert_with(|| Arc::new(Mutex::new(create()))))
+    }
+
+    /// Add a book for `symbol` unless it has one, atomically with the checks in `create`;
+    /// false if there was a book already or `create` declined
+    pub(crate) fn insert_new(&self, symbol: SymbolId, create: impl FnOnce() -> Option<OrderBook>) -> bool {
+        let mut books = self.books.write().unwrap();
+        let slot = Self::slot(&mut books, symbol);
+        if slot.is_some() {
+            return false;
+        }
+        match create() {
+            Some(book) => {
+                *slot = Some(Arc::new(Mutex::new(book)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every book with its symbol, for sweeps across symbols
+    pub(crate) fn all(&self) -> Vec<(SymbolId, SharedBook)> {
+        let books = self.books.read().unwrap();
+        books
+            .iter()
+            .enumerate()
+            .filter_map(|(index, book)| Some((SymbolId::from_index(index), Arc::clone(book.as_ref()?))))
+            .collect()
+    }
+
+    fn slot(books: &mut Vec<Option<SharedBook>>, symbol: SymbolId) -> &mut Option<SharedBook> {
+        if books.len() <= symbol.index() {
+            books.resize_with(symbol.index() + 1, || None);
+        }
+        &mut books[symbol.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SymbolTable;
+
+    #[test]
+    fn test_books_lock_independently() {
+        let symbols = SymbolTable::new();
+        let (btc, eth, sol) = (symbols.intern("BTCUSD"), symbols.intern("ETHUSD"), symbols.intern("SOLUSD"));
+        let books = BookMap::new();
+        let btc_book = books.get_or_insert_with(btc, || OrderBook::new("BTCUSD".to_string()));
+        assert!(Arc::ptr_eq(&btc_book, &books.get_or_insert_with(btc, || unreachable!())));
+
+        // One book being locked doesn't hold up lookups, new books or work on another book
+        let _btc = btc_book.lock().unwrap();
+        books.get_or_insert_with(eth, || OrderBook::new("ETHUSD".to_string()));
+        assert_eq!(books.with(eth, |book| book.depth()), Some(0));
+        assert!(books.with(sol, |book| book.depth()).is_none());
+        assert!(!books.insert_new(eth, || unreachable!()));
+        assert_eq!(books.all().len(), 2);
+    }
+}
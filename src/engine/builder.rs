@@ -0,0 +1,288 @@
+use super::{
+    BookMap, EngineConfig, MarketFeed, OrderEvents, EngineState, ExecutionEngine, KillSwitches, MarketMakerProtection, MessageThrottle,
+    ProtectionGroup, RecentClientOrderIds, Result, Scheduler, ThrottlePolicy, TradeOverflow, TradeSink, WaitStrategy, DEFAULT_QUEUE_CAPACITY, REFERENCE_PRICE_RETENTION,
+};
+use crate::clock::{Clock, WallClock};
+use crate::instrument::{InstrumentConfig, InstrumentRegistry};
+use crate::market_data::{DailyStatistics, ReferencePrices};
+use crate::matching::{BookConfig, MatchingAlgorithm};
+use crate::positions::PositionTracker;
+use crate::quoting::QuoteTracker;
+use crate::risk::RiskCheck;
+use crate::types::{ExecutionMetrics, LatencyHistogram, SymbolTable};
+use crossbeam::channel::unbounded;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use chrono::NaiveTime;
+use std::time::Duration;
+use tokio::sync::{watch, Semaphore};
+
+/// Order limits applied to every symbol when an order is accepted, on top of its instrument rules
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskLimits {
+    /// Largest quantity of a single order or amendment
+    pub max_order_quantity: Option<u64>,
+    /// Largest price × quantity of a single order; only checked for orders with a price
+    pub max_order_notional: Option<f64>,
+}
+
+/// Configures and creates an [`ExecutionEngine`]
+#[derive(Debug)]
+pub struct EngineBuilder {
+    trade_sink: Box<dyn TradeSink>,
+    trade_overflow: TradeOverflow,
+    clock: Box<dyn Clock>,
+    queue_capacity: usize,
+    wait_strategy: WaitStrategy,
+    config: EngineConfig,
+    record_latency: bool,
+    log_orders: bool,
+    default_book: BookConfig,
+    instruments: Vec<InstrumentConfig>,
+    risk_limits: RiskLimits,
+    snapshot_interval: Option<Duration>,
+    session_rollover: NaiveTime,
+    reference_staleness: Option<Duration>,
+    risk_checks: Vec<Box<dyn RiskCheck>>,
+    duplicate_window: Option<Duration>,
+    cancel_on_disconnect: bool,
+    throttle: Option<ThrottlePolicy>,
+    client_symbols: HashMap<String, HashSet<String>>,
+    protection_groups: Vec<ProtectionGroup>,
+}
+
+impl EngineBuilder {
+    pub fn new(trade_sink: impl TradeSink + 'static) -> Self {
+        Self {
+            trade_sink: Box::new(trade_sink),
+            trade_overflow: TradeOverflow::default(),
+            clock: Box::new(WallClock),
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            wait_strategy: WaitStrategy::default(),
+            config: EngineConfig::default(),
+            record_latency: true,
+            log_orders: true,
+            default_book: BookConfig::default(),
+            instruments: Vec::new(),
+            risk_limits: RiskLimits::default(),
+            snapshot_interval: None,
+            session_rollover: NaiveTime::MIN,
+            reference_staleness: None,
+            risk_checks: Vec::new(),
+            duplicate_window: None,
+            cancel_on_disconnect: false,
+            throttle: None,
+            client_symbols: HashMap::new(),
+            protection_groups: Vec::new(),
+        }
+    }
+
+    /// Stamp trades and other events with `clock`
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// What to do with trades the sink has no room for
+    pub fn with_trade_overflow(mut self, overflow: TradeOverflow) -> Self {
+        self.trade_overflow = overflow;
+        self
+    }
+
+    /// Let up to `capacity` new orders wait in the command queue (at least one)
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.wait_strategy = strategy;
+        self
+    }
+
+    /// Placement and scheduling of the matching thread
+    pub fn with_config(mut self, config: EngineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Record the processing time of every order for the latency metrics (on by default)
+    pub fn with_latency_recording(mut self, record_latency: bool) -> Self {
+        self.record_latency = record_latency;
+        self
+    }
+
+    /// Log order lifecycle events (staged, cancelled, expired, modified) at info level, or at
+    /// debug level when off (on by default)
+    pub fn with_order_logging(mut self, log_orders: bool) -> Self {
+        self.log_orders = log_orders;
+        self
+    }
+
+    /// Matching algorithm for symbols without a registered instrument
+    pub fn with_matching_algorithm(mut self, algorithm: MatchingAlgorithm) -> Self {
+        self.default_book.algorithm = algorithm;
+        self
+    }
+
+    /// Book configuration for symbols without a registered instrument
+    pub fn with_default_book(mut self, book: BookConfig) -> Self {
+        self.default_book = book;
+        self
+    }
+
+    /// Register `instrument` when the engine is built
+    pub fn with_instrument(mut self, instrument: InstrumentConfig) -> Self {
+        self.instruments.push(instrument);
+        self
+    }
+
+    pub fn with_risk_limits(mut self, limits: RiskLimits) -> Self {
+        self.risk_limits = limits;
+        self
+    }
+
+    /// Add `check` to the pre-trade risk checks run on every new order, after those added before
+    pub fn with_risk_check(mut self, check: impl RiskCheck + 'static) -> Self {
+        self.risk_checks.push(Box::new(check));
+        self
+    }
+
+    /// Reject orders reusing a client order ID their client sent within the last `window`,
+    /// so a gateway resend can't execute twice; duplicates are let through by default
+    pub fn with_duplicate_window(mut self, window: Duration) -> Self {
+        self.duplicate_window = Some(window);
+        self
+    }
+
+    /// Cancel a client's orders when it disconnects, unless set otherwise for the client;
+    /// off by default
+    pub fn with_cancel_on_disconnect(mut self, enabled: bool) -> Self {
+        self.cancel_on_disconnect = enabled;
+        self
+    }
+
+    /// Restrict `client_id` to trading `symbols`; its orders for any other symbol are
+    /// rejected. Clients without a list may trade any symbol.
+    pub fn with_client_symbols(mut self, client_id: impl Into<String>, symbols: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.client_symbols.insert(client_id.into(), symbols.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Protect market makers in `group`: once a client's fills there within the group's
+    /// window reach one of its limits, the client's orders in the group are cancelled.
+    /// Groups may share symbols; each counts separately.
+    pub fn with_mm_protection(mut self, group: ProtectionGroup) -> Self {
+        self.protection_groups.push(group);
+        self
+    }
+
+    /// Limit the messages (new orders, cancels and amendments) each client sends per
+    /// `policy`; new orders and amendments over the rate are refused, cancels never are
+    pub fn with_message_throttle(mut self, policy: ThrottlePolicy) -> Self {
+        self.throttle = Some(policy);
+        self
+    }
+
+    /// Publish a full depth snapshot of every book with snapshot subscribers each `interval`
+    pub fn with_snapshot_interval(mut self, interval: Duration) -> Self {
+        self.snapshot_interval = Some(interval);
+        self
+    }
+
+    /// Time of day (UTC) a new trading day starts and daily statistics reset; midnight by default
+    pub fn with_session_rollover(mut self, rollover: NaiveTime) -> Self {
+        self.session_rollover = rollover;
+        self
+    }
+
+    /// Treat external reference prices not updated for `max_age` as stale, falling back to
+    /// the engine's own prices until the next update; they never go stale by default
+    pub fn with_reference_staleness(mut self, max_age: Duration) -> Self {
+        self.reference_staleness = Some(max_age);
+        self
+    }
+
+    /// Create the engine, stopped. Fails if two instruments share a symbol.
+    pub fn build(mut self) -> Result<ExecutionEngine> {
+        let instruments = std::mem::take(&mut self.instruments);
+        let engine = self.build_engine();
+        for instrument in instruments {
+            engine.register_instrument(instrument)?;
+        }
+        Ok(engine)
+    }
+
+    /// The engine without pre-registered instruments
+    pub(super) fn build_engine(self) -> ExecutionEngine {
+        let (order_sender, order_receiver) = unbounded();
+        let (cancel_sender, cancel_receiver) = unbounded();
+
+        ExecutionEngine {
+            state: Arc::new(EngineState {
+                symbols: SymbolTable::new(),
+                order_books: BookMap::new(),
+                trade_sink: self.trade_sink,
+                trade_overflow: self.trade_overflow,
+                pending_trades: Mutex::new(VecDeque::new()),
+                unsent_trades: AtomicU64::new(0),
+                order_events: OrderEvents::new(),
+                queued_ids: Mutex::new(HashSet::new()),
+                sequence: AtomicU64::new(0),
+                applying: AtomicU64::new(0),
+                metrics: Mutex::new(ExecutionMetrics::default()),
+                latency: Mutex::new(LatencyHistogram::new()),
+                record_latency: self.record_latency,
+                log_orders: self.log_orders,
+                positions: Mutex::new(PositionTracker::new()),
+                speed_bumps: Mutex::new(HashMap::new()),
+                deferred: Mutex::new(Scheduler::new()),
+                paused_symbols: Mutex::new(HashSet::new()),
+                short_sale_restricted: Mutex::new(HashSet::new()),
+                halts: Mutex::new(HashMap::new()),
+                instruments: InstrumentRegistry::new(),
+                default_book: self.default_book,
+                risk_limits: self.risk_limits,
+                phases: Mutex::new(HashMap::new()),
+                quotes: Mutex::new(QuoteTracker::new()),
+                reference_prices: Mutex::new(ReferencePrices::new(REFERENCE_PRICE_RETENTION)),
+                daily_stats: Mutex::new(DailyStatistics::new(self.session_rollover)),
+                risk_checks: Mutex::new(self.risk_checks),
+                kill_switches: KillSwitches::new(),
+                cancel_on_disconnect: Mutex::new(HashMap::new()),
+                cancel_on_disconnect_default: self.cancel_on_disconnect,
+                client_symbols: Mutex::new(self.client_symbols),
+                throttle: self.throttle.map(MessageThrottle::new),
+                protection: (!self.protection_groups.is_empty()).then(|| MarketMakerProtection::new(self.protection_groups)),
+                client_order_ids: self.duplicate_window.map(|window| Mutex::new(RecentClientOrderIds::new(window))),
+                staged: Mutex::new(HashMap::new()),
+                bbo_feeds: Mutex::new(HashMap::new()),
+                bbo_updates: MarketFeed::new(),
+                last_bbo: Mutex::new(HashMap::new()),
+                level_updates: MarketFeed::new(),
+                level_sequences: Mutex::new(HashMap::new()),
+                order_updates: MarketFeed::new(),
+                trade_prints: MarketFeed::new(),
+                print_sequences: Mutex::new(HashMap::new()),
+                book_snapshots: MarketFeed::new(),
+                snapshot_interval: self.snapshot_interval,
+                external_references: Mutex::new(HashMap::new()),
+                reference_staleness: self.reference_staleness,
+                resting_since: Mutex::new(HashMap::new()),
+                clock: Arc::from(self.clock),
+            }),
+            order_sender,
+            order_receiver,
+            cancel_sender,
+            cancel_receiver,
+            queue_slots: Arc::new(Semaphore::new(self.queue_capacity)),
+            queue_capacity: self.queue_capacity,
+            wait_strategy: self.wait_strategy,
+            config: self.config,
+            running: watch::Sender::new(false),
+            matching_exit: Mutex::new(None),
+            matching_thread: Arc::new(Semaphore::new(1)),
+        }
+    }
+}
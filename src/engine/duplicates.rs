@@ -0,0 +1,71 @@
+use crate::types::Order;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Client order IDs seen within the last `window`, to catch orders sent twice
+pub(super) struct RecentClientOrderIds {
+    window: Duration,
+    seen: HashMap<(String, String), Uuid>, // (client, client order ID) -> order it was first seen on
+    arrivals: VecDeque<(DateTime<Utc>, (String, String))>, // oldest first
+}
+
+impl RecentClientOrderIds {
+    pub(super) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+            arrivals: VecDeque::new(),
+        }
+    }
+
+    /// Record the order's client order ID; `false` if another order of the same client
+    /// already used it within the window. Orders without one always pass.
+    pub(super) fn record(&mut self, order: &Order, now: DateTime<Utc>) -> bool {
+        let Some(client_order_id) = &order.client_order_id else {
+            return true;
+        };
+
+        let cutoff = now - chrono::Duration::from_std(self.window).unwrap_or(chrono::Duration::MAX);
+        while self.arrivals.front().is_some_and(|(time, _)| *time <= cutoff) {
+            let (_, key) = self.arrivals.pop_front().unwrap();
+            self.seen.remove(&key);
+        }
+
+        let key = (order.client_id.clone(), client_order_id.clone());
+        match self.seen.get(&key) {
+            // A staged order is checked again on release
+            Some(&first) => first == order.id,
+            None => {
+                self.seen.insert(key.clone(), order.id);
+                self.arrivals.push_back((now, key));
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+
+    #[test]
+    fn test_duplicate_client_order_ids() {
+        let mut recent = RecentClientOrderIds::new(Duration::from_secs(60));
+        let start = Utc::now();
+        let order = |client: &str, client_order_id: &str| {
+            Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 100.0, client.to_string()).with_client_order_id(client_order_id)
+        };
+
+        let first = order("client1", "A1");
+        assert!(recent.record(&first, start));
+        assert!(recent.record(&first, start));
+        assert!(!recent.record(&order("client1", "A1"), start + chrono::Duration::seconds(30)));
+        assert!(recent.record(&order("client2", "A1"), start));
+        assert!(recent.record(&Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 100.0, "client1".to_string()), start));
+        // Forgotten once the window has passed
+        assert!(recent.record(&order("client1", "A1"), start + chrono::Duration::seconds(60)));
+    }
+}
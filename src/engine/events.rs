@@ -0,0 +1,449 @@
+use super::publisher::Publisher;
+use crate::market_data::{SlowConsumerPolicy, SubscriberEvent};
+use crate::types::{Order, OrderStatus, Price, Side, Trade};
+use chrono::{DateTime, Utc};
+use crossbeam::channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tracing::debug;
+use uuid::Uuid;
+
+/// A change in the state of one order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub order_id: Uuid,
+    pub client_id: String,
+    pub symbol: String,
+    pub kind: OrderEventKind,
+    /// Event time from the engine's configured clock
+    pub timestamp: DateTime<Utc>,
+    /// Sequence number of the engine command that caused the event; 0 for orders rejected
+    /// before they reached the engine's command queue
+    pub sequence: u64,
+}
+
+/// When an event happened: clock time and the sequence number of its command
+#[derive(Debug, Clone, Copy)]
+pub(super) struct EventStamp {
+    pub(super) timestamp: DateTime<Utc>,
+    pub(super) sequence: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OrderEventKind {
+    /// Passed the engine's checks and went to the book
+    Accepted,
+    Rejected { reason: String },
+    /// Traded `quantity` at `price`, with `remaining` still working
+    PartiallyFilled { quantity: u64, price: Price, filled_quantity: u64, remaining: u64 },
+    /// Traded `quantity` at `price`, completing the order
+    Filled { quantity: u64, price: Price },
+    /// Removed before it was filled: by its client, an amendment, a purge, the book (e.g.
+    /// self-trade prevention or an unfilled immediate remainder) or shutdown
+    Cancelled,
+    Expired,
+    /// An amendment of the order was refused; the order works on unchanged
+    AmendmentRejected { reason: String },
+}
+
+impl OrderEventKind {
+    /// Whether the order is done: no further events follow
+    pub fn is_final(&self) -> bool {
+        !matches!(
+            self,
+            OrderEventKind::Accepted | OrderEventKind::PartiallyFilled { .. } | OrderEventKind::AmendmentRejected { .. }
+        )
+    }
+}
+
+/// How an order ended, as awaited with
+/// [`submit_order_and_wait`](super::ExecutionEngine::submit_order_and_wait)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderOutcome {
+    pub order_id: Uuid,
+    /// `Filled`, `Cancelled`, `Rejected` or `Expired`; `Pending` or `PartiallyFilled` only if
+    /// the engine was dropped before the order ended
+    pub status: OrderStatus,
+    pub filled_quantity: u64,
+    /// Volume-weighted price of the fills
+    pub average_price: Option<f64>,
+    /// Why the order was rejected
+    pub reject_reason: Option<String>,
+}
+
+impl OrderOutcome {
+    pub(super) fn pending(order_id: Uuid) -> Self {
+        Self {
+            order_id,
+            status: OrderStatus::Pending,
+            filled_quantity: 0,
+            average_price: None,
+            reject_reason: None,
+        }
+    }
+
+    pub(super) fn record(&mut self, kind: OrderEventKind) {
+        let (quantity, price) = match kind {
+            OrderEventKind::Accepted | OrderEventKind::AmendmentRejected { .. } => return,
+            OrderEventKind::Rejected { reason } => {
+                self.status = OrderStatus::Rejected;
+                self.reject_reason = Some(reason);
+                return;
+            }
+            OrderEventKind::Cancelled => {
+                self.status = OrderStatus::Cancelled;
+                return;
+            }
+            OrderEventKind::Expired => {
+                self.status = OrderStatus::Expired;
+                return;
+            }
+            OrderEventKind::PartiallyFilled { quantity, price, .. } => {
+                self.status = OrderStatus::PartiallyFilled;
+                (quantity, price)
+            }
+            OrderEventKind::Filled { quantity, price } => {
+                self.status = OrderStatus::Filled;
+                (quantity, price)
+            }
+        };
+
+        let notional = self.average_price.unwrap_or_default() * self.filled_quantity as f64;
+        self.filled_quantity += quantity;
+        self.average_price = Some((notional + price.to_f64() * quantity as f64) / self.filled_quantity as f64);
+    }
+}
+
+/// One entry of the drop copy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DropCopyEntry {
+    OrderEvent(OrderEvent),
+    Execution(Trade),
+}
+
+/// A drop copy entry with its place in the drop copy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropCopyMessage {
+    /// Counts every entry of the drop copy from 1, without gaps, across all subscribers
+    pub sequence: u64,
+    pub entry: DropCopyEntry,
+}
+
+/// Settings for a drop copy subscription
+#[derive(Debug, Clone)]
+pub struct DropCopyConfig {
+    /// Messages buffered for the subscriber before `slow_consumer` applies
+    pub buffer: usize,
+    pub slow_consumer: SlowConsumerPolicy,
+}
+
+impl Default for DropCopyConfig {
+    fn default() -> Self {
+        Self {
+            buffer: 65_536,
+            slow_consumer: SlowConsumerPolicy::Disconnect,
+        }
+    }
+}
+
+/// Drop copy subscribers, and the entries of the command being applied
+#[derive(Default)]
+struct DropCopy {
+    subscribers: Vec<Publisher<DropCopyMessage>>,
+    queued: Vec<DropCopyEntry>,
+    sequence: u64,
+}
+
+/// Settings for an order event subscription
+#[derive(Debug, Clone)]
+pub struct OrderEventConfig {
+    /// Only deliver events for this client's orders; every order's when `None`
+    pub client_id: Option<String>,
+    /// Events buffered for the subscriber before `slow_consumer` applies
+    pub buffer: usize,
+    pub slow_consumer: SlowConsumerPolicy,
+}
+
+impl Default for OrderEventConfig {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            buffer: 1024,
+            slow_consumer: SlowConsumerPolicy::default(),
+        }
+    }
+}
+
+struct Subscriber {
+    client_id: Option<String>,
+    publisher: Publisher<OrderEvent>,
+}
+
+/// Unfilled quantity of working orders: client -> symbol -> (buy, sell)
+type OpenQuantities = HashMap<String, HashMap<String, (u64, u64)>>;
+
+/// An accepted order not yet done
+struct Working {
+    client_id: String,
+    symbol: String,
+    side: Side,
+    quantity: u64,
+    filled: u64,
+}
+
+impl Working {
+    fn remaining(&self) -> u64 {
+        self.quantity.saturating_sub(self.filled)
+    }
+}
+
+/// Order event subscribers, and the fill state needed to tell partial fills from complete ones
+pub(super) struct OrderEvents {
+    subscribers: Mutex<Vec<Subscriber>>,
+    /// Accepted orders not yet done
+    working: Mutex<HashMap<Uuid, Working>>,
+    open: Mutex<OpenQuantities>,
+    /// Report streams of single orders, closed after their final event
+    reports: Mutex<HashMap<Uuid, mpsc::UnboundedSender<OrderEvent>>>,
+    /// Events of the command being applied, delivered by `flush`
+    queued: Mutex<Vec<OrderEvent>>,
+    drop_copy: Mutex<DropCopy>,
+}
+
+impl OrderEvents {
+    pub(super) fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            working: Mutex::new(HashMap::new()),
+            open: Mutex::new(HashMap::new()),
+            reports: Mutex::new(HashMap::new()),
+            queued: Mutex::new(Vec::new()),
+            drop_copy: Mutex::new(DropCopy::default()),
+        }
+    }
+
+    pub(super) fn subscribe(&self, config: OrderEventConfig) -> Receiver<SubscriberEvent<OrderEvent>> {
+        let (publisher, receiver) = Publisher::new(config.buffer, config.slow_consumer);
+        self.subscribers.lock().unwrap().push(Subscriber {
+            client_id: config.client_id,
+            publisher,
+        });
+        receiver
+    }
+
+    pub(super) fn subscribe_drop_copy(&self, config: DropCopyConfig) -> Receiver<SubscriberEvent<DropCopyMessage>> {
+        let (publisher, receiver) = Publisher::new(config.buffer, config.slow_consumer);
+        self.drop_copy.lock().unwrap().subscribers.push(publisher);
+        receiver
+    }
+
+    /// Add stamped trades to the drop copy, after the fill events they caused
+    pub(super) fn executions(&self, trades: &[Trade]) {
+        let mut drop_copy = self.drop_copy.lock().unwrap();
+        if drop_copy.subscribers.is_empty() {
+            return;
+        }
+        drop_copy.queued.extend(trades.iter().cloned().map(DropCopyEntry::Execution));
+    }
+
+    /// Stream the events of order `order_id` until its final one
+    pub(super) fn report(&self, order_id: Uuid) -> mpsc::UnboundedReceiver<OrderEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.reports.lock().unwrap().insert(order_id, sender);
+        receiver
+    }
+
+    /// Close the report stream of an order that never reached the engine
+    pub(super) fn end_report(&self, order_id: Uuid) {
+        self.reports.lock().unwrap().remove(&order_id);
+    }
+
+    /// Unfilled quantity of the client's working orders in `symbol`, as (buy, sell)
+    pub(super) fn open_quantity(&self, client_id: &str, symbol: &str) -> (u64, u64) {
+        self.open
+            .lock()
+            .unwrap()
+            .get(client_id)
+            .and_then(|symbols| symbols.get(symbol))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Unfilled quantity of working order `order_id`; 0 if it isn't working
+    pub(super) fn remaining(&self, order_id: Uuid) -> u64 {
+        self.working.lock().unwrap().get(&order_id).map_or(0, Working::remaining)
+    }
+
+    /// Unfilled quantity of the client's working orders per symbol, as (buy, sell)
+    pub(super) fn open_quantities(&self, client_id: &str) -> HashMap<String, (u64, u64)> {
+        self.open.lock().unwrap().get(client_id).cloned().unwrap_or_default()
+    }
+
+    /// Add `added` to and take `removed` from the open quantity of `working`'s side
+    fn adjust_open(&self, working: &Working, added: u64, removed: u64) {
+        let mut open = self.open.lock().unwrap();
+        let symbols = open.entry(working.client_id.clone()).or_default();
+        let quantity = symbols.entry(working.symbol.clone()).or_default();
+        let side = match working.side {
+            Side::Buy => &mut quantity.0,
+            Side::Sell => &mut quantity.1,
+        };
+        *side = (*side + added).saturating_sub(removed);
+        if *quantity == (0, 0) {
+            symbols.remove(&working.symbol);
+            if symbols.is_empty() {
+                open.remove(&working.client_id);
+            }
+        }
+    }
+
+    pub(super) fn accepted(&self, order: &Order, stamp: EventStamp) {
+        let working = Working {
+            client_id: order.client_id.clone(),
+            symbol: order.symbol.clone(),
+            side: order.side,
+            quantity: order.quantity,
+            filled: order.filled_quantity,
+        };
+        self.adjust_open(&working, working.remaining(), 0);
+        self.working.lock().unwrap().insert(order.id, working);
+        self.publish(order.id, &order.client_id, &order.symbol, OrderEventKind::Accepted, stamp);
+    }
+
+    pub(super) fn rejected(&self, order: &Order, reason: impl Into<String>, stamp: EventStamp) {
+        self.finished(order, OrderEventKind::Rejected { reason: reason.into() }, stamp);
+    }
+
+    pub(super) fn cancelled(&self, order: &Order, stamp: EventStamp) {
+        self.finished(order, OrderEventKind::Cancelled, stamp);
+    }
+
+    pub(super) fn expired(&self, order: &Order, stamp: EventStamp) {
+        self.finished(order, OrderEventKind::Expired, stamp);
+    }
+
+    pub(super) fn amendment_rejected(&self, order: &Order, reason: impl Into<String>, stamp: EventStamp) {
+        let kind = OrderEventKind::AmendmentRejected { reason: reason.into() };
+        self.publish(order.id, &order.client_id, &order.symbol, kind, stamp);
+    }
+
+    /// Track the new size of an amended order
+    pub(super) fn amended(&self, order: &Order) {
+        if let Some(working) = self.working.lock().unwrap().get_mut(&order.id) {
+            let previous = working.remaining();
+            working.quantity = order.quantity;
+            working.filled = order.filled_quantity;
+            self.adjust_open(working, working.remaining(), previous);
+        }
+    }
+
+    /// A fill event for both sides of every trade, in trade order
+    pub(super) fn fills(&self, trades: &[Trade], stamp: EventStamp) {
+        for trade in trades {
+            for (order_id, client_id) in [
+                (trade.buy_order_id, &trade.buy_client_id),
+                (trade.sell_order_id, &trade.sell_client_id),
+            ] {
+                let mut working = self.working.lock().unwrap();
+                let Some(order) = working.get_mut(&order_id) else {
+                    debug!("Fill for untracked order: {:?}", order_id);
+                    continue;
+                };
+                order.filled += trade.quantity;
+                self.adjust_open(order, 0, trade.quantity);
+                let kind = if order.filled >= order.quantity {
+                    working.remove(&order_id);
+                    OrderEventKind::Filled { quantity: trade.quantity, price: trade.price }
+                } else {
+                    OrderEventKind::PartiallyFilled {
+                        quantity: trade.quantity,
+                        price: trade.price,
+                        filled_quantity: order.filled,
+                        remaining: order.remaining(),
+                    }
+                };
+                drop(working);
+                self.publish(order_id, client_id, &trade.symbol, kind, stamp);
+            }
+        }
+    }
+
+    fn finished(&self, order: &Order, kind: OrderEventKind, stamp: EventStamp) {
+        if let Some(working) = self.working.lock().unwrap().remove(&order.id) {
+            self.adjust_open(&working, 0, working.remaining());
+        }
+        self.publish(order.id, &order.client_id, &order.symbol, kind, stamp);
+    }
+
+    /// Queue an event for the next `flush`, if anyone is listening for it
+    fn publish(&self, order_id: Uuid, client_id: &str, symbol: &str, kind: OrderEventKind, stamp: EventStamp) {
+        let listened = !self.subscribers.lock().unwrap().is_empty() || self.reports.lock().unwrap().contains_key(&order_id);
+        let mut drop_copy = self.drop_copy.lock().unwrap();
+        if !listened && drop_copy.subscribers.is_empty() {
+            return;
+        }
+
+        let event = OrderEvent {
+            order_id,
+            client_id: client_id.to_string(),
+            symbol: symbol.to_string(),
+            kind,
+            timestamp: stamp.timestamp,
+            sequence: stamp.sequence,
+        };
+        if !drop_copy.subscribers.is_empty() {
+            drop_copy.queued.push(DropCopyEntry::OrderEvent(event.clone()));
+        }
+        drop(drop_copy);
+        if listened {
+            self.queued.lock().unwrap().push(event);
+        }
+    }
+
+    /// Deliver the queued events, in the order they happened. Called once the command that
+    /// caused them is fully applied, so a listener that sees an order end also finds its
+    /// trades delivered and the metrics updated.
+    pub(super) fn flush(&self) {
+        // Held throughout, so events flushed from two threads can't interleave
+        let mut queued = self.queued.lock().unwrap();
+        self.flush_drop_copy();
+        if queued.is_empty() {
+            return;
+        }
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let mut reports = self.reports.lock().unwrap();
+        for event in queued.drain(..) {
+            if event.kind.is_final() {
+                if let Some(report) = reports.remove(&event.order_id) {
+                    let _ = report.send(event.clone());
+                }
+            } else if let Some(report) = reports.get(&event.order_id) {
+                if report.send(event.clone()).is_err() {
+                    reports.remove(&event.order_id);
+                }
+            }
+
+            subscribers.retain(|subscriber| match &subscriber.client_id {
+                Some(only) if *only != event.client_id => true,
+                _ => subscriber.publisher.publish(event.clone()).is_ok(),
+            });
+        }
+    }
+
+    fn flush_drop_copy(&self) {
+        let mut drop_copy = self.drop_copy.lock().unwrap();
+        let DropCopy { subscribers, queued, sequence } = &mut *drop_copy;
+        for entry in queued.drain(..) {
+            *sequence += 1;
+            let message = DropCopyMessage {
+                sequence: *sequence,
+                entry,
+            };
+            subscribers.retain(|subscriber| subscriber.publish(message.clone()).is_ok());
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use super::publisher::Publisher;
+use crate::market_data::{Conflate, FeedConfig, SubscriberEvent};
+use crate::types::LatencyHistogram;
+use crossbeam::channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Publishing health of one market-data feed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeedStats {
+    /// Updates handed to subscribers
+    pub delivered: u64,
+    /// Time from the book change to its update entering a subscriber's buffer, microseconds;
+    /// includes the time conflated updates are held back
+    pub avg_lag_micros: u64,
+    pub p99_lag_micros: u64,
+    pub max_lag_micros: u64,
+    pub subscribers: Vec<SubscriberStats>,
+}
+
+/// Publishing health of each market-data feed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarketDataStats {
+    pub bbo: FeedStats,
+    pub levels: FeedStats,
+    pub orders: FeedStats,
+    pub trades: FeedStats,
+    pub snapshots: FeedStats,
+}
+
+/// One subscriber's buffer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    pub symbol: Option<String>,
+    /// Updates waiting for the subscriber to take them
+    pub queue_depth: usize,
+    pub buffer: usize,
+    /// Updates its slow-consumer policy dropped
+    pub dropped: u64,
+    /// At least half its buffer is waiting, or updates were dropped
+    pub slow: bool,
+}
+
+struct Subscriber<T> {
+    symbol: Option<String>,
+    publisher: Publisher<T>,
+    conflation: Option<Conflation<T>>,
+}
+
+/// Updates held back for a conflating subscriber, merged into one per interval
+struct Conflation<T> {
+    interval: Duration,
+    merge: fn(&mut T, T),
+    pending: Option<T>,
+    /// When the earliest change merged into `pending` happened
+    pending_since: Option<Instant>,
+    last_sent: Option<Instant>,
+}
+
+impl<T> Conflation<T> {
+    /// When the pending update may go out, if there is one; `now` if nothing was sent yet
+    fn due(&self, now: Instant) -> Option<Instant> {
+        self.pending.as_ref()?;
+        Some(self.last_sent.map_or(now, |sent| sent + self.interval))
+    }
+}
+
+impl<T> Subscriber<T> {
+    /// Deliver `update`, from a book change at `changed`, or hold it back to merge with
+    /// later ones if sent too recently. Delivery lag goes into `lag`.
+    fn publish(&mut self, update: T, changed: Instant, now: Instant, lag: &mut LatencyHistogram) -> bool {
+        let Some(conflation) = &mut self.conflation else {
+            return self.deliver(update, changed, lag);
+        };
+        match conflation.pending.as_mut() {
+            Some(pending) => (conflation.merge)(pending, update),
+            None => {
+                conflation.pending = Some(update);
+                conflation.pending_since = Some(changed);
+            }
+        }
+        self.flush(now, lag)
+    }
+
+    /// Send the pending conflated update if its interval has passed
+    fn flush(&mut self, now: Instant, lag: &mut LatencyHistogram) -> bool {
+        let Some(conflation) = &mut self.conflation else {
+            return true;
+        };
+        if conflation.due(now).is_none_or(|due| due > now) {
+            return true;
+        }
+        conflation.last_sent = Some(now);
+        let update = conflation.pending.take().unwrap();
+        let changed = conflation.pending_since.take().unwrap_or(now);
+        self.deliver(update, changed, lag)
+    }
+
+    fn deliver(&self, update: T, changed: Instant, lag: &mut LatencyHistogram) -> bool {
+        let delivered = self.publisher.publish(update).is_ok();
+        if delivered {
+            lag.record(changed.elapsed().as_micros() as u64);
+        }
+        delivered
+    }
+
+    fn stats(&self) -> SubscriberStats {
+        let (queue_depth, buffer, dropped) = (self.publisher.queue_depth(), self.publisher.capacity(), self.publisher.dropped());
+        SubscriberStats {
+            symbol: self.symbol.clone(),
+            queue_depth,
+            buffer,
+            dropped,
+            slow: dropped > 0 || queue_depth * 2 >= buffer,
+        }
+    }
+}
+
+/// Subscribers to one kind of market-data update, each optionally limited to a symbol
+pub(super) struct MarketFeed<T> {
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+    lag: Mutex<LatencyHistogram>, // book change to subscriber buffer, microseconds
+}
+
+impl<T: Clone> MarketFeed<T> {
+    pub(super) fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            lag: Mutex::new(LatencyHistogram::new()),
+        }
+    }
+
+    /// Subscribe to every update; `config.conflation` is not applied
+    pub(super) fn subscribe(&self, config: FeedConfig) -> Receiver<SubscriberEvent<T>> {
+        self.add(config, None)
+    }
+
+    /// Subscribe with updates merged per `config.conflation`, if set
+    pub(super) fn subscribe_conflated(&self, config: FeedConfig) -> Receiver<SubscriberEvent<T>>
+    where
+        T: Conflate,
+    {
+        let conflation = config.conflation.map(|interval| Conflation {
+            interval,
+            merge: T::conflate,
+            pending: None,
+            pending_since: None,
+            last_sent: None,
+        });
+        self.add(config, conflation)
+    }
+
+    fn add(&self, config: FeedConfig, conflation: Option<Conflation<T>>) -> Receiver<SubscriberEvent<T>> {
+        let (publisher, receiver) = Publisher::new(config.buffer, config.slow_consumer);
+        self.subscribers.lock().unwrap().push(Subscriber {
+            symbol: config.symbol,
+            publisher,
+            conflation,
+        });
+        receiver
+    }
+
+    /// Whether any subscriber wants `symbol`'s updates, so building them can be skipped otherwise
+    pub(super) fn wants(&self, symbol: &str) -> bool {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|subscriber| subscriber.symbol.as_deref().is_none_or(|only| only == symbol))
+    }
+
+    /// Deliver `update` for `symbol`, made from a book change at `changed`, dropping
+    /// subscribers that went away
+    pub(super) fn publish(&self, symbol: &str, update: T, changed: Instant) {
+        let now = Instant::now();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let mut lag = self.lag.lock().unwrap();
+        subscribers.retain_mut(|subscriber| match &subscriber.symbol {
+            Some(only) if only != symbol => true,
+            _ => subscriber.publish(update.clone(), changed, now, &mut lag),
+        });
+    }
+
+    /// Send conflated updates whose interval has passed; returns when the next one is due
+    pub(super) fn flush(&self) -> Option<Instant> {
+        let now = Instant::now();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let mut lag = self.lag.lock().unwrap();
+        subscribers.retain_mut(|subscriber| subscriber.flush(now, &mut lag));
+        subscribers
+            .iter()
+            .filter_map(|subscriber| subscriber.conflation.as_ref()?.due(now))
+            .min()
+    }
+
+    /// Delivery lag so far and the current subscribers' buffers
+    pub(super) fn stats(&self) -> FeedStats {
+        let subscribers = self.subscribers.lock().unwrap().iter().map(Subscriber::stats).collect();
+        let lag = self.lag.lock().unwrap();
+        FeedStats {
+            delivered: lag.count(),
+            avg_lag_micros: lag.mean().unwrap_or_default(),
+            p99_lag_micros: lag.value_at_quantile(0.99).unwrap_or_default(),
+            max_lag_micros: lag.max(),
+            subscribers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::LevelUpdate;
+    use crate::matching::{LevelAction, LevelChange};
+    use crate::types::{Price, Side};
+    use chrono::Utc;
+
+    #[test]
+    fn test_conflated_level_updates() {
+        let feed = MarketFeed::new();
+        let every = feed.subscribe(FeedConfig::default());
+        let conflated = feed.subscribe_conflated(FeedConfig {
+            conflation: Some(Duration::from_secs(3600)),
+            ..FeedConfig::default()
+        });
+
+        let change = |price: f64, action, quantity| LevelChange {
+            side: Side::Sell,
+            price: Price::from(price),
+            action,
+            quantity,
+            orders: quantity.min(1) as usize,
+        };
+        let update = |sequence, changes| LevelUpdate {
+            symbol: "BTCUSD".to_string(),
+            changes,
+            timestamp: Utc::now(),
+            sequence,
+        };
+        feed.publish("BTCUSD", update(1, vec![change(100.0, LevelAction::Add, 1)]), Instant::now());
+        feed.publish("BTCUSD", update(2, vec![change(100.0, LevelAction::Modify, 2), change(101.0, LevelAction::Add, 1)]), Instant::now());
+        feed.publish("BTCUSD", update(3, vec![change(101.0, LevelAction::Delete, 0), change(99.0, LevelAction::Add, 4)]), Instant::now());
+        feed.publish("BTCUSD", update(4, vec![change(100.0, LevelAction::Delete, 0)]), Instant::now());
+        feed.publish("BTCUSD", update(5, vec![change(100.0, LevelAction::Add, 3)]), Instant::now());
+
+        assert_eq!(every.try_iter().count(), 5);
+        // The first goes out at once; the rest wait for the interval, merged
+        let sequences: Vec<_> = conflated
+            .try_iter()
+            .map(|event| match event {
+                SubscriberEvent::Update(update) => update.sequence,
+                SubscriberEvent::DataLoss { .. } => panic!("updates lost"),
+            })
+            .collect();
+        assert_eq!(sequences, vec![1]);
+        assert!(feed.flush().is_some_and(|due| due > Instant::now()));
+
+        let mut subscribers = feed.subscribers.lock().unwrap();
+        let pending = subscribers[1].conflation.as_mut().unwrap().pending.take().unwrap();
+        assert_eq!(pending.sequence, 5);
+        assert_eq!(
+            pending.changes,
+            vec![change(99.0, LevelAction::Add, 4), change(100.0, LevelAction::Modify, 3)]
+        );
+    }
+}
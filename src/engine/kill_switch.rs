@@ -0,0 +1,127 @@
+use super::publisher::Publisher;
+use crate::market_data::{SlowConsumerPolicy, SubscriberEvent};
+use chrono::{DateTime, Utc};
+use crossbeam::channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Kill switch events buffered per subscriber; older ones are dropped beyond that
+const KILL_SWITCH_EVENT_BUFFER: usize = 64;
+
+/// Whose orders a kill switch stops
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillScope {
+    /// Every client's
+    Global,
+    Client(String),
+}
+
+impl KillScope {
+    pub fn covers(&self, client_id: &str) -> bool {
+        match self {
+            KillScope::Global => true,
+            KillScope::Client(client) => client == client_id,
+        }
+    }
+}
+
+/// An engaged kill switch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KillSwitch {
+    pub scope: KillScope,
+    /// Who engaged it, e.g. a risk officer or an automated control
+    pub triggered_by: String,
+    /// Engine clock time it was engaged
+    pub engaged_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KillSwitchAction {
+    /// New orders are rejected, and resting ones were cancelled if `cancel_resting`
+    Engaged { cancel_resting: bool },
+    Released,
+}
+
+/// A kill switch being engaged or released
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KillSwitchEvent {
+    pub scope: KillScope,
+    pub action: KillSwitchAction,
+    /// Who engaged or released it
+    pub triggered_by: String,
+    /// Engine clock time
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Engaged kill switches, and subscribers to their changes
+pub(super) struct KillSwitches {
+    engaged: Mutex<HashMap<KillScope, KillSwitch>>,
+    subscribers: Mutex<Vec<Publisher<KillSwitchEvent>>>,
+}
+
+impl KillSwitches {
+    pub(super) fn new() -> Self {
+        Self {
+            engaged: Mutex::new(HashMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(super) fn subscribe(&self) -> Receiver<SubscriberEvent<KillSwitchEvent>> {
+        let (publisher, receiver) = Publisher::new(KILL_SWITCH_EVENT_BUFFER, SlowConsumerPolicy::DropOldest);
+        self.subscribers.lock().unwrap().push(publisher);
+        receiver
+    }
+
+    /// Engage a switch for `scope`, replacing one already engaged there
+    pub(super) fn engage(&self, scope: KillScope, triggered_by: String, cancel_resting: bool, now: DateTime<Utc>) {
+        let switch = KillSwitch {
+            scope: scope.clone(),
+            triggered_by: triggered_by.clone(),
+            engaged_at: now,
+        };
+        self.engaged.lock().unwrap().insert(scope.clone(), switch);
+        self.publish(KillSwitchEvent {
+            scope,
+            action: KillSwitchAction::Engaged { cancel_resting },
+            triggered_by,
+            timestamp: now,
+        });
+    }
+
+    /// Release the switch for `scope`; `false` if none was engaged there
+    pub(super) fn release(&self, scope: &KillScope, triggered_by: String, now: DateTime<Utc>) -> bool {
+        if self.engaged.lock().unwrap().remove(scope).is_none() {
+            return false;
+        }
+        self.publish(KillSwitchEvent {
+            scope: scope.clone(),
+            action: KillSwitchAction::Released,
+            triggered_by,
+            timestamp: now,
+        });
+        true
+    }
+
+    /// The switch stopping `client_id`'s orders, if any; the global one first
+    pub(super) fn blocking(&self, client_id: &str) -> Option<KillSwitch> {
+        let engaged = self.engaged.lock().unwrap();
+        engaged
+            .get(&KillScope::Global)
+            .or_else(|| engaged.get(&KillScope::Client(client_id.to_string())))
+            .cloned()
+    }
+
+    pub(super) fn engaged(&self) -> Vec<KillSwitch> {
+        self.engaged.lock().unwrap().values().cloned().collect()
+    }
+
+    fn publish(&self, event: KillSwitchEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.publish(event.clone()).is_ok());
+    }
+}
@@ -1,10 +1,73 @@
-use crate::matching::OrderBook;
-use crate::types::{ExecutionMetrics, Order, OrderStatus, OrderType, Trade};
-use crossbeam::channel::{bounded, Receiver, Sender};
-use std::collections::HashMap;
+mod audit;
+mod blocking;
+mod books;
+mod builder;
+mod duplicates;
+mod events;
+mod feeds;
+mod kill_switch;
+#[cfg(feature = "numa")]
+mod numa;
+mod pinning;
+mod protection;
+mod publisher;
+mod scheduler;
+mod shutdown;
+mod sink;
+mod subscriptions;
+mod throttle;
+#[cfg(feature = "market-data-ws")]
+mod ws;
+
+pub use audit::{AuditReport, Invariant, InvariantViolation};
+pub use blocking::BlockingEngine;
+pub use builder::{EngineBuilder, RiskLimits};
+pub use events::{DropCopyConfig, DropCopyEntry, DropCopyMessage, OrderEvent, OrderEventConfig, OrderEventKind, OrderOutcome};
+pub use feeds::{FeedStats, MarketDataStats, SubscriberStats};
+pub use kill_switch::{KillScope, KillSwitch, KillSwitchAction, KillSwitchEvent};
+pub use protection::{ProtectionBreach, ProtectionEvent, ProtectionGroup, ProtectionLimits};
+pub use scheduler::SpeedBump;
+pub use shutdown::ShutdownReport;
+pub use sink::{FanOut, SinkError, TradeOverflow, TradeSink};
+pub use subscriptions::{Channel, MarketDataMessage, MarketDataSubscriptions, Subscription};
+pub use throttle::{MessageKind, ThrottleEvent, ThrottleEventKind, ThrottlePolicy};
+#[cfg(feature = "market-data-ws")]
+pub use ws::{ClientRequest, MarketDataServer};
+
+use crate::auction::{IndicativeAuction, TradingPhase};
+use crate::clock::Clock;
+use crate::instrument::{InstrumentConfig, InstrumentRegistry};
+use crate::market_data::{
+    BboUpdate, BookSnapshot, DailyStatistics, DailyStats, ExternalReference, FeedConfig, HeatmapConfig, HeatmapFrame, HeatmapMatrix, LadderHeatmap, LevelUpdate, OrderUpdate, ReferencePrices, SlowConsumerPolicy,
+    SubscriberEvent, TradeConditions, TradePrint,
+};
+use crate::matching::{Bbo, BookAnalytics, BookConfig, DepthSnapshot, LimitBreachAction, OrderBook};
+use crate::positions::{Position, PositionTracker};
+use crate::quoting::{QuoteTracker, QuotingMetrics};
+use crate::risk::{RiskCheck, RiskContext, RiskRejection};
+use audit::StateAudit;
+use books::BookMap;
+use duplicates::RecentClientOrderIds;
+use events::{EventStamp, OrderEvents};
+use feeds::MarketFeed;
+use kill_switch::KillSwitches;
+use protection::MarketMakerProtection;
+use publisher::Publisher;
+use scheduler::Scheduler;
+use shutdown::MatchingExit;
+use throttle::MessageThrottle;
+use crate::types::{
+    ExecutionMetrics, LatencyHistogram, Order, OrderStatus, OrderType, Price, Side, SymbolId, SymbolTable, Trade,
+};
+use chrono::{DateTime, Utc};
+use crossbeam::channel::{select, unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::{mpsc, oneshot, watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -20,242 +83,2720 @@ pub enum EngineError {
     #[error("Symbol not found: {0}")]
     SymbolNotFound(String),
     
+    #[error("Instrument already registered: {0}")]
+    InstrumentExists(String),
+
+    #[error("Price {price} has more than {decimals} decimal places")]
+    InvalidPricePrecision { price: Price, decimals: u32 },
+
+    #[error("Price {price} is not a multiple of tick size {tick_size}")]
+    InvalidTickSize { price: Price, tick_size: Price },
+
+    #[error("Quantity {quantity} is not a multiple of lot size {lot_size}")]
+    InvalidLotSize { quantity: u64, lot_size: u64 },
+
+    #[error("Quantity {quantity} is below the minimum of {min_quantity}")]
+    BelowMinQuantity { quantity: u64, min_quantity: u64 },
+
+    #[error("Notional {notional} is below the minimum of {min_notional}")]
+    BelowMinNotional { notional: f64, min_notional: f64 },
+    
+    #[error("Price {price} is more than {percent}% away from reference price {reference}")]
+    PriceOutsideCollar { price: Price, reference: Price, percent: f64 },
+
+    #[error("Quantity {quantity} is above the limit of {max_quantity}")]
+    AboveMaxQuantity { quantity: u64, max_quantity: u64 },
+
+    #[error("Notional {notional} is above the limit of {max_notional}")]
+    AboveMaxNotional { notional: f64, max_notional: f64 },
+
+    #[error("Order queue is full")]
+    QueueFull(Box<Order>),
+
     #[error("Engine is stopped")]
     EngineStopped,
 }
 
 pub type Result<T> = std::result::Result<T, EngineError>;
 
+/// Log an order lifecycle event at the level chosen with
+/// [`EngineBuilder::with_order_logging`]
+macro_rules! order_event {
+    ($state:expr, $($arg:tt)+) => {
+        if $state.log_orders {
+            info!($($arg)+)
+        } else {
+            debug!($($arg)+)
+        }
+    };
+}
+
+/// How often resting orders are checked for time-in-force expiry
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// New orders that may wait in the command queue by default
+pub const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// How soon trades held under [`TradeOverflow::Buffer`] are offered to the sink again
+const TRADE_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Soak audit reports buffered for a subscriber before the oldest are dropped
+const AUDIT_REPORT_BUFFER: usize = 64;
+
+/// Most amendments accepted in one `modify_orders` batch
+pub const MAX_BULK_AMENDMENTS: usize = 100;
+
+/// How long trades are kept for rolling VWAP/TWAP queries
+const REFERENCE_PRICE_RETENTION: Duration = Duration::from_secs(60 * 60);
+
 /// Main execution engine
 pub struct ExecutionEngine {
-    order_books: Arc<Mutex<HashMap<String, OrderBook>>>,
+    state: Arc<EngineState>,
     order_sender: Sender<EngineCommand>,
-    order_receiver: Arc<Mutex<Receiver<EngineCommand>>>,
-    trade_sender: Sender<Trade>,
-    metrics: Arc<Mutex<ExecutionMetrics>>,
-    latency_samples: Arc<Mutex<Vec<u64>>>,
-    running: Arc<Mutex<bool>>,
+    order_receiver: Receiver<EngineCommand>,
+    /// Priority lane for cancels, drained before the command queue
+    cancel_sender: Sender<EngineCommand>,
+    cancel_receiver: Receiver<EngineCommand>,
+    /// One permit per queued new order; other commands are never held back
+    queue_slots: Arc<Semaphore>,
+    queue_capacity: usize,
+    wait_strategy: WaitStrategy,
+    config: EngineConfig,
+    /// Whether the engine accepts commands; watched by background tasks to stop promptly
+    running: watch::Sender<bool>,
+    /// Resolves once the matching thread started last has exited
+    matching_exit: Mutex<Option<oneshot::Receiver<MatchingExit>>>,
+    /// Held by the matching thread for as long as it runs, so a restart never overlaps it
+    matching_thread: Arc<Semaphore>,
+}
+
+/// State shared between the engine handle and its processing loop
+struct EngineState {
+    /// Symbol names are interned as commands come in; everything past that uses the ID
+    symbols: SymbolTable,
+    order_books: BookMap,
+    trade_sink: Box<dyn TradeSink>,
+    trade_overflow: TradeOverflow,
+    /// Trades the sink refused, waiting to be retried under `TradeOverflow::Buffer`
+    pending_trades: Mutex<VecDeque<Trade>>,
+    unsent_trades: AtomicU64,
+    order_events: OrderEvents,
+    /// New orders sent to the command queue and not yet taken off it
+    queued_ids: Mutex<HashSet<Uuid>>,
+    /// Sequence number of the last command taken off the queue, carried across restarts
+    sequence: AtomicU64,
+    /// Sequence number of the command being applied, stamped on its events and trades
+    applying: AtomicU64,
+    metrics: Mutex<ExecutionMetrics>,
+    latency: Mutex<LatencyHistogram>, // order processing time, microseconds
+    record_latency: bool,
+    /// Log order lifecycle events at info rather than debug level
+    log_orders: bool,
+    positions: Mutex<PositionTracker>,
+    speed_bumps: Mutex<HashMap<SymbolId, SpeedBump>>,
+    /// Aggressive orders held by speed bumps until their release
+    deferred: Mutex<Scheduler>,
+    paused_symbols: Mutex<HashSet<SymbolId>>,
+    /// Symbols whose short sales must be priced above the best bid
+    short_sale_restricted: Mutex<HashSet<SymbolId>>,
+    halts: Mutex<HashMap<SymbolId, HaltPolicy>>,
+    instruments: InstrumentRegistry,
+    /// Book configuration for symbols without a registered instrument
+    default_book: BookConfig,
+    risk_limits: RiskLimits,
+    phases: Mutex<HashMap<SymbolId, TradingPhase>>,
+    quotes: Mutex<QuoteTracker>,
+    reference_prices: Mutex<ReferencePrices>,
+    daily_stats: Mutex<DailyStatistics>,
+    /// Pre-trade checks every new order goes through, in order
+    risk_checks: Mutex<Vec<Box<dyn RiskCheck>>>,
+    kill_switches: KillSwitches,
+    /// Whether a client's orders are cancelled when it disconnects, for clients set apart
+    /// from `cancel_on_disconnect_default`
+    cancel_on_disconnect: Mutex<HashMap<String, bool>>,
+    cancel_on_disconnect_default: bool,
+    /// Symbols each entitled client may trade; clients not listed may trade any
+    client_symbols: Mutex<HashMap<String, HashSet<String>>>,
+    /// Per-client message budgets, when messages are throttled
+    throttle: Option<MessageThrottle>,
+    /// Per-client fill counts in each protection group, when there are any
+    protection: Option<MarketMakerProtection>,
+    /// Client order IDs seen recently, when duplicates are rejected
+    client_order_ids: Option<Mutex<RecentClientOrderIds>>,
+    staged: Mutex<HashMap<Uuid, (SymbolId, Order)>>,
+    bbo_feeds: Mutex<HashMap<SymbolId, watch::Sender<Bbo>>>,
+    bbo_updates: MarketFeed<BboUpdate>,
+    /// Top of book as last seen by `settle_book`, to publish only changes
+    last_bbo: Mutex<HashMap<SymbolId, Bbo>>,
+    level_updates: MarketFeed<LevelUpdate>,
+    level_sequences: Mutex<HashMap<SymbolId, u64>>, // symbol -> sequence of its last level update
+    order_updates: MarketFeed<OrderUpdate>,
+    trade_prints: MarketFeed<TradePrint>,
+    print_sequences: Mutex<HashMap<SymbolId, u64>>, // symbol -> sequence of its last trade print
+    book_snapshots: MarketFeed<BookSnapshot>,
+    /// Time between snapshots of every book on `book_snapshots`; only on demand when `None`
+    snapshot_interval: Option<Duration>,
+    external_references: Mutex<HashMap<SymbolId, (Price, DateTime<Utc>)>>, // symbol -> (price, time received)
+    /// Age at which an external reference price goes stale; never when `None`
+    reference_staleness: Option<Duration>,
+    resting_since: Mutex<HashMap<SymbolId, HashMap<Uuid, DateTime<Utc>>>>, // symbol -> order -> time it came to rest
+    clock: Arc<dyn Clock>,
+}
+
+/// New price and/or quantity for one resting order in a bulk amend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Amendment {
+    pub order_id: Uuid,
+    pub new_price: Option<Price>,
+    pub new_quantity: Option<u64>,
+}
+
+/// What happens to new orders on a halted symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HaltPolicy {
+    /// Reject new orders until trading resumes
+    #[default]
+    Reject,
+    /// Accept limit orders into the book without matching; market orders are still rejected
+    Queue,
+}
+
+/// How the matching thread waits for its next command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaitStrategy {
+    /// Park until a command arrives or a timer is due, leaving the core to others
+    #[default]
+    Blocking,
+    /// Poll the queue without ever parking, for a core dedicated to matching: dispatch
+    /// latency drops to the cost of a poll, at the price of a core at 100%
+    BusySpin,
+}
+
+/// Placement and scheduling of the matching thread
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Core to pin the matching thread to, keeping it off cores the OS schedules other
+    /// work on (Linux only)
+    pub core_affinity: Option<usize>,
+    /// `SCHED_FIFO` priority for the matching thread, 1-99 on Linux (unix only; usually
+    /// needs root or `CAP_SYS_NICE`)
+    pub realtime_priority: Option<i32>,
+    /// NUMA node to run the matching thread on and place book storage in: the thread is
+    /// kept on the node's CPUs (within `core_affinity` if set, which should be one of them)
+    /// and allocates from its memory. Needs the `numa` feature; Linux only.
+    pub numa_node: Option<usize>,
 }
 
 enum EngineCommand {
-    NewOrder(Order),
-    CancelOrder(Uuid, String),
+    NewOrder(SymbolId, Order, OwnedSemaphorePermit),
+    CancelOrder(Uuid, SymbolId),
+    ModifyOrder {
+        order_id: Uuid,
+        symbol: SymbolId,
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+    },
+    ModifyOrders(SymbolId, Vec<Amendment>),
+    PurgeOrders {
+        symbol: SymbolId,
+        side: Side,
+        low: Price,
+        high: Price,
+    },
+    PauseMatching(SymbolId),
+    ResumeMatching(SymbolId),
+    HaltTrading(SymbolId, HaltPolicy),
+    ResumeTrading(SymbolId),
+    SetShortSaleRestriction(SymbolId, bool),
+    SetTradingPhase(SymbolId, TradingPhase),
+    StageOrder(SymbolId, Order),
+    ReleaseOrder(Uuid, String),
+    /// Cancel every resting and staged order in a scope, e.g. of a kill switch
+    MassCancel(KillScope),
+    Audit(oneshot::Sender<StateAudit>),
     Shutdown,
 }
 
 impl ExecutionEngine {
-    pub fn new(trade_sender: Sender<Trade>) -> Self {
-        let (order_sender, order_receiver) = bounded(10000);
-        
-        Self {
-            order_books: Arc::new(Mutex::new(HashMap::new())),
-            order_sender,
-            order_receiver: Arc::new(Mutex::new(order_receiver)),
-            trade_sender,
-            metrics: Arc::new(Mutex::new(ExecutionMetrics::default())),
-            latency_samples: Arc::new(Mutex::new(Vec::new())),
-            running: Arc::new(Mutex::new(false)),
-        }
+    pub fn new(trade_sink: impl TradeSink + 'static) -> Self {
+        EngineBuilder::new(trade_sink).build_engine()
+    }
+
+    /// Create an engine that stamps trades and other events with `clock`
+    pub fn with_clock(trade_sink: impl TradeSink + 'static, clock: impl Clock + 'static) -> Self {
+        EngineBuilder::new(trade_sink).with_clock(clock).build_engine()
+    }
+
+    /// Configure an engine: queue sizes, matching, instruments, risk limits and more
+    pub fn builder(trade_sink: impl TradeSink + 'static) -> EngineBuilder {
+        EngineBuilder::new(trade_sink)
+    }
+
+    /// Let up to `capacity` new orders wait in the command queue (at least one); beyond that
+    /// [`submit_order`](Self::submit_order) waits and [`try_submit_order`](Self::try_submit_order)
+    /// hands the order back
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        self.queue_slots = Arc::new(Semaphore::new(capacity));
+        self.queue_capacity = capacity;
+        self
     }
 
-    /// Start the execution engine
+    /// Wait for commands with `strategy` on matching threads started from now on
+    pub fn with_wait_strategy(mut self, strategy: WaitStrategy) -> Self {
+        self.wait_strategy = strategy;
+        self
+    }
+
+    /// Apply `config` to matching threads started from now on. Settings the platform
+    /// refuses are logged at start and skipped.
+    pub fn with_config(mut self, config: EngineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// New orders waiting in the command queue
+    pub fn queued_orders(&self) -> usize {
+        self.queue_capacity - self.queue_slots.available_permits()
+    }
+
+    /// Start the execution engine.
+    ///
+    /// The engine can be started again after [`stop`](Self::stop). Books, positions and
+    /// metrics carry over; orders held by speed bumps were cancelled on the way down. If
+    /// the previous matching thread is still draining (a `stop` that was not awaited), the
+    /// new one waits for it, and commands accepted meanwhile are matched after the drain.
     pub async fn start(&self) {
-        let mut running = self.running.lock().unwrap();
-        if *running {
+        if self.running.send_replace(true) {
             warn!("Engine already running");
             return;
         }
-        *running = true;
-        drop(running);
 
         info!("Starting execution engine");
 
-        let order_receiver = Arc::clone(&self.order_receiver);
-        let order_books = Arc::clone(&self.order_books);
-        let trade_sender = self.trade_sender.clone();
-        let metrics = Arc::clone(&self.metrics);
-        let latency_samples = Arc::clone(&self.latency_samples);
-        let running = Arc::clone(&self.running);
+        let receiver = self.order_receiver.clone();
+        let cancels = self.cancel_receiver.clone();
+        let state = Arc::clone(&self.state);
+        let wait_strategy = self.wait_strategy;
+        let config = self.config.clone();
+        let (exited, exit) = oneshot::channel();
+        *self.matching_exit.lock().unwrap() = Some(exit);
 
-        task::spawn(async move {
+        // The semaphore is never closed
+        let active = Arc::clone(&self.matching_thread).acquire_owned().await.unwrap();
+
+        // Matching owns an OS thread of its own: it waits on the command channel and must
+        // neither hold up nor wait on the async runtime. Results go out over the trade
+        // channel and the market data feeds.
+        let spawned = thread::Builder::new().name("matching".to_string()).spawn(move || {
+            pinning::configure_current_thread(&config);
+            let mut next_expiry_sweep = Instant::now() + EXPIRY_SWEEP_INTERVAL;
+            let mut next_snapshot = state.snapshot_interval.map(|interval| Instant::now() + interval);
+            // Numbering carries on from the previous run
+            let mut sequence = state.sequence.load(Ordering::Relaxed);
+
+            // Runs until the shutdown command, so commands sent before `stop` are still handled
             loop {
-                if !*running.lock().unwrap() {
-                    info!("Engine stopping");
-                    break;
+                state.retry_pending_trades();
+                let now = Instant::now();
+                if now >= next_expiry_sweep {
+                    state.process_expirations();
+                    next_expiry_sweep = now + EXPIRY_SWEEP_INTERVAL;
+                }
+                if let (Some(due), Some(interval)) = (next_snapshot, state.snapshot_interval) {
+                    if now >= due {
+                        state.publish_snapshots();
+                        next_snapshot = Some(now + interval);
+                    }
                 }
 
-                let receiver = order_receiver.lock().unwrap();
-                let command = receiver.recv_timeout(Duration::from_millis(100));
-                drop(receiver);
+                // Released orders are stamped with the command that brought them in
+                loop {
+                    let Some((command, symbol, order)) = state.deferred.lock().unwrap().pop_due(Instant::now()) else {
+                        break;
+                    };
+                    state.applying.store(command, Ordering::Relaxed);
+                    state.execute_order(symbol, order);
+                }
+                state.applying.store(sequence, Ordering::Relaxed);
+                state.order_events.flush();
+                let next_conflated = state.flush_conflated();
+
+                // Sleep until the next command, or the next sweep, snapshot, conflated update or
+                // speed-bump release
+                let now = Instant::now();
+                let sweep_due = next_expiry_sweep.saturating_duration_since(now);
+                let mut timeout = state.deferred.lock().unwrap().time_to_next(now).map_or(sweep_due, |due| due.min(sweep_due));
+                for due in next_snapshot.into_iter().chain(next_conflated) {
+                    timeout = timeout.min(due.saturating_duration_since(now));
+                }
+                if state.has_pending_trades() {
+                    timeout = timeout.min(TRADE_RETRY_INTERVAL);
+                }
 
+                // Cancels first, so a burst of new orders can't hold up risk reduction
+                let command = match cancels.try_recv() {
+                    Ok(cancel) => Ok(cancel),
+                    Err(_) => match wait_strategy {
+                        WaitStrategy::Blocking => select! {
+                            recv(cancels) -> cancel => cancel.map_err(|_| RecvTimeoutError::Disconnected),
+                            recv(receiver) -> command => command.map_err(|_| RecvTimeoutError::Disconnected),
+                            default(timeout) => Err(RecvTimeoutError::Timeout),
+                        },
+                        WaitStrategy::BusySpin => receiver.try_recv().map_err(|e| match e {
+                            TryRecvError::Empty => {
+                                std::hint::spin_loop();
+                                RecvTimeoutError::Timeout
+                            }
+                            TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+                        }),
+                    },
+                };
+                if command.is_ok() {
+                    sequence += 1;
+                    state.applying.store(sequence, Ordering::Relaxed);
+                }
                 match command {
-                    Ok(EngineCommand::NewOrder(order)) => {
-                        let start = Instant::now();
-                        Self::process_order(
-                            order,
-                            &order_books,
-                            &trade_sender,
-                            &metrics,
-                            &latency_samples,
-                        );
-                        let latency = start.elapsed().as_micros() as u64;
-                        latency_samples.lock().unwrap().push(latency);
+                    // The queue slot is freed once the order is matched or deferred
+                    Ok(EngineCommand::NewOrder(symbol, order, _slot)) => {
+                        state.queued_ids.lock().unwrap().remove(&order.id);
+                        match state.speed_bump_for(symbol, &order) {
+                            Some(bump) => state.deferred.lock().unwrap().defer(sequence, symbol, order, bump),
+                            None => state.execute_order(symbol, order),
+                        }
                     }
                     Ok(EngineCommand::CancelOrder(order_id, symbol)) => {
-                        Self::process_cancel(order_id, symbol, &order_books, &metrics);
+                        state.process_cancel(order_id, symbol);
+                    }
+                    Ok(EngineCommand::ModifyOrder {
+                        order_id,
+                        symbol,
+                        new_price,
+                        new_quantity,
+                    }) => {
+                        state.process_modify(order_id, symbol, new_price, new_quantity);
+                    }
+                    Ok(EngineCommand::ModifyOrders(symbol, amendments)) => {
+                        state.process_bulk_modify(symbol, amendments);
+                    }
+                    Ok(EngineCommand::PurgeOrders { symbol, side, low, high }) => {
+                        state.process_purge(symbol, side, low, high);
+                    }
+                    Ok(EngineCommand::PauseMatching(symbol)) => {
+                        info!("Matching paused: {}", state.symbols.name(symbol));
+                        state.paused_symbols.lock().unwrap().insert(symbol);
+                    }
+                    Ok(EngineCommand::ResumeMatching(symbol)) => {
+                        state.process_resume(symbol);
+                    }
+                    Ok(EngineCommand::HaltTrading(symbol, policy)) => {
+                        warn!("Trading halted: {} ({:?})", state.symbols.name(symbol), policy);
+                        state.halts.lock().unwrap().insert(symbol, policy);
+                    }
+                    Ok(EngineCommand::ResumeTrading(symbol)) => {
+                        state.process_resume_trading(symbol);
+                    }
+                    Ok(EngineCommand::SetShortSaleRestriction(symbol, active)) => {
+                        state.process_short_sale_restriction(symbol, active);
+                    }
+                    Ok(EngineCommand::SetTradingPhase(symbol, phase)) => {
+                        state.process_phase_change(symbol, phase);
+                    }
+                    Ok(EngineCommand::StageOrder(symbol, order)) => {
+                        state.process_stage(symbol, order);
+                    }
+                    Ok(EngineCommand::ReleaseOrder(order_id, client_id)) => {
+                        if let Some((symbol, order)) = state.process_release(order_id, &client_id) {
+                            match state.speed_bump_for(symbol, &order) {
+                                Some(bump) => state.deferred.lock().unwrap().defer(sequence, symbol, order, bump),
+                                None => state.execute_order(symbol, order),
+                            }
+                        }
+                    }
+                    Ok(EngineCommand::MassCancel(scope)) => {
+                        state.process_mass_cancel(&scope);
+                    }
+                    Ok(EngineCommand::Audit(reply)) => {
+                        let _ = reply.send(state.audit());
                     }
                     Ok(EngineCommand::Shutdown) => {
                         info!("Received shutdown command");
+                        // Cancels sent before the stop may still be on their lane
+                        while let Ok(EngineCommand::CancelOrder(order_id, symbol)) = cancels.try_recv() {
+                            sequence += 1;
+                            state.applying.store(sequence, Ordering::Relaxed);
+                            state.process_cancel(order_id, symbol);
+                        }
                         break;
                     }
-                    Err(_) => {
-                        // Timeout, continue
-                        continue;
-                    }
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
                 }
             }
+
+            let cancelled = state.cancel_deferred();
+            state.order_events.flush();
+            state.abandon_pending_trades();
+            state.sequence.store(sequence, Ordering::Relaxed);
+            drop(active);
+            let _ = exited.send(MatchingExit { sequence, cancelled });
         });
-    }
 
-    fn process_order(
-        mut order: Order,
-        order_books: &Arc<Mutex<HashMap<String, OrderBook>>>,
-        trade_sender: &Sender<Trade>,
-        metrics: &Arc<Mutex<ExecutionMetrics>>,
-        _latency_samples: &Arc<Mutex<Vec<u64>>>,
-    ) {
-        debug!("Processing order: {:?}", order.id);
+        if let Err(e) = spawned {
+            error!("Failed to spawn matching thread: {}", e);
+            self.running.send_replace(false);
+        }
+    }
 
-        // Validate order
-        if order.quantity == 0 {
-            error!("Invalid order quantity: 0");
-            order.status = OrderStatus::Rejected;
-            metrics.lock().unwrap().rejected_orders += 1;
-            return;
+    /// Submit new order.
+    ///
+    /// Orders are first checked against the symbol's tick size, lot size, minimum
+    /// quantity and minimum notional, where its instrument defines them. When the queue
+    /// is full this waits for room, unless the engine stops first.
+    pub async fn submit_order(&self, mut order: Order) -> Result<()> {
+        if !self.is_running() {
+            return Err(EngineError::EngineStopped);
         }
 
-        if order.order_type == OrderType::Limit && order.price.is_none() {
-            error!("Limit order without price");
-            order.status = OrderStatus::Rejected;
-            metrics.lock().unwrap().rejected_orders += 1;
-            return;
+        self.check_instrument_rules(&mut order)?;
+        let mut running = self.running.subscribe();
+        let slot = tokio::select! {
+            slot = Arc::clone(&self.queue_slots).acquire_owned() => slot.map_err(|_| EngineError::EngineStopped)?,
+            _ = running.wait_for(|running| !*running) => return Err(EngineError::EngineStopped),
+        };
+        self.send_order(order, slot)
+    }
+
+    /// Submit a new order and follow it: the returned stream carries its execution reports
+    /// (acceptance, each fill with its price and quantity, then its final state) and ends
+    /// after the final one. A rejection by the submission checks is also returned as the
+    /// error.
+    pub async fn submit_order_with_reports(&self, order: Order) -> Result<mpsc::UnboundedReceiver<OrderEvent>> {
+        let order_id = order.id;
+        // Listen before submitting so not even the acceptance can be missed
+        let reports = self.state.order_events.report(order_id);
+        match self.submit_order(order).await {
+            Ok(()) => Ok(reports),
+            Err(e) => {
+                self.state.order_events.end_report(order_id);
+                Err(e)
+            }
         }
+    }
 
-        let mut books = order_books.lock().unwrap();
-        let book = books
-            .entry(order.symbol.clone())
-            .or_insert_with(|| OrderBook::new(order.symbol.clone()));
+    /// Submit a new order and wait until it is filled, cancelled, rejected or expired.
+    ///
+    /// An order refused at submission resolves at once as rejected, with the error as the
+    /// reason. An order left resting never resolves while it rests, across restarts too.
+    pub async fn submit_order_and_wait(&self, order: Order) -> OrderOutcome {
+        let mut outcome = OrderOutcome::pending(order.id);
+        let mut reports = match self.submit_order_with_reports(order).await {
+            Ok(reports) => reports,
+            Err(e) => {
+                outcome.record(OrderEventKind::Rejected { reason: e.to_string() });
+                return outcome;
+            }
+        };
+        while let Some(report) = reports.recv().await {
+            outcome.record(report.kind);
+        }
+        outcome
+    }
 
-        // Add order to book
-        book.add_order(order.clone());
+    /// Submit a new order without waiting: a full queue returns [`EngineError::QueueFull`]
+    /// with the order, so the caller can retry, reroute or drop it
+    pub fn try_submit_order(&self, mut order: Order) -> Result<()> {
+        if !self.is_running() {
+            return Err(EngineError::EngineStopped);
+        }
 
-        // Try to match orders
-        let trades = book.match_orders();
+        self.check_instrument_rules(&mut order)?;
+        let Ok(slot) = Arc::clone(&self.queue_slots).try_acquire_owned() else {
+            return Err(EngineError::QueueFull(Box::new(order)));
+        };
+        self.send_order(order, slot)
+    }
 
-        // Update metrics
-        let mut metrics_guard = metrics.lock().unwrap();
-        metrics_guard.total_orders += 1;
+    /// Accept and risk-check an order but hold it outside the book until
+    /// [`release_order`](Self::release_order) is called by its owning client.
+    ///
+    /// Staged orders can be cancelled like working orders.
+    pub async fn stage_order(&self, mut order: Order) -> Result<()> {
+        self.check_instrument_rules(&mut order)?;
+        let symbol = self.state.symbols.intern(&order.symbol);
+        self.send_command(EngineCommand::StageOrder(symbol, order))
+    }
 
-        if !trades.is_empty() {
-            metrics_guard.total_trades += trades.len() as u64;
-            for trade in &trades {
-                metrics_guard.total_volume += trade.quantity as f64 * trade.price;
-            }
-            metrics_guard.filled_orders += 1;
-        }
+    /// Release a staged order to the book. Ignored unless `client_id` owns the order.
+    pub async fn release_order(&self, order_id: Uuid, client_id: String) -> Result<()> {
+        self.send_command(EngineCommand::ReleaseOrder(order_id, client_id))
+    }
 
-        drop(metrics_guard);
-        drop(books);
+    /// Number of orders currently staged
+    pub fn staged_orders(&self) -> usize {
+        self.state.staged.lock().unwrap().len()
+    }
 
-        // Send trades
-        for trade in trades {
-            if let Err(e) = trade_sender.try_send(trade) {
-                error!("Failed to send trade: {}", e);
-            }
+    /// Cancel order.
+    ///
+    /// Cancels take a priority lane, handled ahead of new orders still queued, so a burst
+    /// of orders can't hold them up. A cancel never overtakes its own order: while that is
+    /// still queued, the cancel queues behind it.
+    pub async fn cancel_order(&self, order_id: Uuid, symbol: String) -> Result<()> {
+        let command = EngineCommand::CancelOrder(order_id, self.state.symbols.intern(&symbol));
+        if self.state.queued_ids.lock().unwrap().contains(&order_id) {
+            self.send_command(command)
+        } else {
+            self.send_on(&self.cancel_sender, command)
         }
     }
 
-    fn process_cancel(
+    /// Amend price and/or quantity of a resting order, keeping its ID.
+    ///
+    /// Quantity reductions keep time priority; price changes and size increases
+    /// move the order to the back of the queue at its new price.
+    pub async fn modify_order(
+        &self,
         order_id: Uuid,
         symbol: String,
-        order_books: &Arc<Mutex<HashMap<String, OrderBook>>>,
-        metrics: &Arc<Mutex<ExecutionMetrics>>,
-    ) {
-        debug!("Cancelling order: {:?}", order_id);
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+    ) -> Result<()> {
+        if !self.is_running() {
+            return Err(EngineError::EngineStopped);
+        }
 
-        let mut books = order_books.lock().unwrap();
-        if let Some(book) = books.get_mut(&symbol) {
-            if let Some(_cancelled_order) = book.cancel_order(order_id) {
-                metrics.lock().unwrap().cancelled_orders += 1;
-                info!("Order cancelled: {:?}", order_id);
-            } else {
-                warn!("Order not found for cancellation: {:?}", order_id);
-            }
+        let mut amendment = Amendment {
+            order_id,
+            new_price,
+            new_quantity,
+        };
+        self.check_amendment(&symbol, &mut amendment)?;
+
+        self.send_command(EngineCommand::ModifyOrder {
+            order_id,
+            symbol: self.state.symbols.intern(&symbol),
+            new_price: amendment.new_price,
+            new_quantity,
+        })
+    }
+
+    /// Amend up to [`MAX_BULK_AMENDMENTS`] resting orders on one symbol in a single step.
+    ///
+    /// Either every amendment passes validation and all are applied before any matching
+    /// happens, or the whole batch is rejected. Meant for market makers replacing a full
+    /// set of quotes without stale ones trading in between.
+    pub async fn modify_orders(&self, symbol: String, mut amendments: Vec<Amendment>) -> Result<()> {
+        if amendments.is_empty() || amendments.len() > MAX_BULK_AMENDMENTS {
+            return Err(EngineError::InvalidOrder(format!(
+                "Bulk amend must contain 1 to {} amendments",
+                MAX_BULK_AMENDMENTS
+            )));
+        }
+        for amendment in &mut amendments {
+            self.check_amendment(&symbol, amendment)?;
+        }
+
+        self.send_command(EngineCommand::ModifyOrders(self.state.symbols.intern(&symbol), amendments))
+    }
+
+    /// Register the configuration (matching algorithm etc.) for a symbol.
+    ///
+    /// Must happen before the symbol's first order; unregistered symbols get a default FIFO book.
+    pub fn register_instrument(&self, instrument: InstrumentConfig) -> Result<()> {
+        let symbol = instrument.symbol.clone();
+        let registered = self.state.order_books.insert_new(self.state.symbols.intern(&symbol), || {
+            let mut book = OrderBook::with_config(instrument.symbol.clone(), instrument.book.clone());
+            book.track_level_changes(true);
+            book.track_order_changes(true);
+            book.set_clock(Arc::clone(&self.state.clock));
+            self.state.instruments.register(instrument).then_some(book)
+        });
+        if !registered {
+            return Err(EngineError::InstrumentExists(symbol));
+        }
+
+        Ok(())
+    }
+
+    /// Cancel all resting orders on one side of `symbol`'s book priced within `low..=high`.
+    ///
+    /// For maintenance, e.g. clearing stale orders far from the touch after the reference price jumps.
+    pub async fn purge_orders(&self, symbol: String, side: Side, low: Price, high: Price) -> Result<()> {
+        if low > high {
+            return Err(EngineError::InvalidOrder("Purge range is empty".to_string()));
+        }
+        let symbol = self.state.symbols.intern(&symbol);
+        self.send_command(EngineCommand::PurgeOrders { symbol, side, low, high })
+    }
+
+    /// Stop matching on `symbol` while still accepting orders into its book.
+    ///
+    /// Market orders are rejected while paused since they cannot rest.
+    pub async fn pause_matching(&self, symbol: String) -> Result<()> {
+        self.send_command(EngineCommand::PauseMatching(self.state.symbols.intern(&symbol)))
+    }
+
+    /// Resume matching on a paused symbol, uncrossing the book first
+    pub async fn resume_matching(&self, symbol: String) -> Result<()> {
+        self.send_command(EngineCommand::ResumeMatching(self.state.symbols.intern(&symbol)))
+    }
+
+    /// Halt trading on `symbol`, e.g. during an incident.
+    ///
+    /// No matching takes place while halted and cancels keep working; `policy` decides
+    /// whether new orders are rejected or queued in the book until trading resumes.
+    pub async fn halt_trading(&self, symbol: String, policy: HaltPolicy) -> Result<()> {
+        self.send_command(EngineCommand::HaltTrading(self.state.symbols.intern(&symbol), policy))
+    }
+
+    /// Resume trading on a halted symbol, uncrossing the book first
+    pub async fn resume_trading(&self, symbol: String) -> Result<()> {
+        self.send_command(EngineCommand::ResumeTrading(self.state.symbols.intern(&symbol)))
+    }
+
+    /// Whether trading on `symbol` is halted
+    pub fn is_halted(&self, symbol: &str) -> bool {
+        let Some(symbol) = self.state.symbols.get(symbol) else {
+            return false;
+        };
+        self.state.halts.lock().unwrap().contains_key(&symbol)
+    }
+
+    /// Engage a kill switch: every new order and amendment in `scope` is refused from now on,
+    /// including those already queued, until the switch is released. With `cancel_resting`,
+    /// the scope's resting, staged and speed-bumped orders are cancelled too.
+    ///
+    /// Subscribers to [`subscribe_kill_switch_events`](Self::subscribe_kill_switch_events)
+    /// learn who engaged it and when. The switch holds even while the engine is stopped;
+    /// only the cancelling needs it running, and fails with `EngineStopped` otherwise.
+    pub async fn engage_kill_switch(&self, scope: KillScope, triggered_by: impl Into<String>, cancel_resting: bool) -> Result<()> {
+        let triggered_by = triggered_by.into();
+        warn!("Kill switch engaged for {:?} by {}", scope, triggered_by);
+        self.state
+            .kill_switches
+            .engage(scope.clone(), triggered_by, cancel_resting, self.state.clock.now());
+        if !cancel_resting {
+            return Ok(());
+        }
+        self.send_command(EngineCommand::MassCancel(scope))
+    }
+
+    /// Release the kill switch engaged for `scope`, accepting its orders again; `false`
+    /// if none was engaged there. A global release leaves client switches engaged.
+    pub fn release_kill_switch(&self, scope: &KillScope, triggered_by: impl Into<String>) -> bool {
+        let triggered_by = triggered_by.into();
+        let released = self
+            .state
+            .kill_switches
+            .release(scope, triggered_by.clone(), self.state.clock.now());
+        if released {
+            info!("Kill switch released for {:?} by {}", scope, triggered_by);
+        }
+        released
+    }
+
+    /// Set whether `client_id`'s orders are cancelled when it disconnects, overriding the
+    /// engine's default
+    pub fn set_cancel_on_disconnect(&self, client_id: impl Into<String>, enabled: bool) {
+        self.state.cancel_on_disconnect.lock().unwrap().insert(client_id.into(), enabled);
+    }
+
+    /// Restrict `client_id` to trading `symbols`; its orders for any other symbol are
+    /// rejected. Replaces the client's earlier list.
+    pub fn set_client_symbols(&self, client_id: impl Into<String>, symbols: impl IntoIterator<Item = impl Into<String>>) {
+        let symbols = symbols.into_iter().map(Into::into).collect();
+        self.state.client_symbols.lock().unwrap().insert(client_id.into(), symbols);
+    }
+
+    /// Let `client_id` trade any symbol again; `false` if it wasn't restricted
+    pub fn remove_client_symbols(&self, client_id: &str) -> bool {
+        self.state.client_symbols.lock().unwrap().remove(client_id).is_some()
+    }
+
+    /// The symbols `client_id` may trade, or `None` if it may trade any
+    pub fn get_client_symbols(&self, client_id: &str) -> Option<Vec<String>> {
+        let client_symbols = self.state.client_symbols.lock().unwrap();
+        let mut symbols: Vec<_> = client_symbols.get(client_id)?.iter().cloned().collect();
+        symbols.sort();
+        Some(symbols)
+    }
+
+    /// Report from the session layer that `client_id` disconnected. With cancel-on-disconnect
+    /// on for the client, its resting, staged and speed-bumped orders are cancelled; returns
+    /// whether they are.
+    pub async fn client_disconnected(&self, client_id: &str) -> Result<bool> {
+        let cancel = self
+            .state
+            .cancel_on_disconnect
+            .lock()
+            .unwrap()
+            .get(client_id)
+            .copied()
+            .unwrap_or(self.state.cancel_on_disconnect_default);
+        if !cancel {
+            info!("Client {} disconnected; its orders stay in the book", client_id);
+            return Ok(false);
+        }
+        warn!("Client {} disconnected; cancelling its orders", client_id);
+        self.send_command(EngineCommand::MassCancel(KillScope::Client(client_id.to_string())))?;
+        Ok(true)
+    }
+
+    /// Every time a client is throttled or goes above its order-to-trade ratio, from now on;
+    /// `None` unless the engine throttles messages. A subscriber that falls behind loses
+    /// the oldest events.
+    pub fn subscribe_throttle_events(&self) -> Option<Receiver<SubscriberEvent<ThrottleEvent>>> {
+        self.state.throttle.as_ref().map(MessageThrottle::subscribe)
+    }
+
+    /// Every time a client's market maker protection trips, from now on; `None` unless the
+    /// engine has protection groups. A subscriber that falls behind loses the oldest events.
+    pub fn subscribe_protection_events(&self) -> Option<Receiver<SubscriberEvent<ProtectionEvent>>> {
+        self.state.protection.as_ref().map(MarketMakerProtection::subscribe)
+    }
+
+    /// Kill switches currently engaged
+    pub fn kill_switches(&self) -> Vec<KillSwitch> {
+        self.state.kill_switches.engaged()
+    }
+
+    /// Every kill switch engaged or released from now on; a subscriber that falls behind
+    /// loses the oldest events
+    pub fn subscribe_kill_switch_events(&self) -> Receiver<SubscriberEvent<KillSwitchEvent>> {
+        self.state.kill_switches.subscribe()
+    }
+
+    /// Turn `symbol`'s short sale restriction on or off. While on, short sales must be
+    /// priced above the best bid when they arrive and when amended, and never take
+    /// liquidity; short market orders are rejected.
+    pub async fn set_short_sale_restriction(&self, symbol: String, active: bool) -> Result<()> {
+        self.send_command(EngineCommand::SetShortSaleRestriction(self.state.symbols.intern(&symbol), active))
+    }
+
+    /// Whether `symbol`'s short sale restriction is on
+    pub fn is_short_sale_restricted(&self, symbol: &str) -> bool {
+        let Some(symbol) = self.state.symbols.get(symbol) else {
+            return false;
+        };
+        self.state.short_sale_restricted.lock().unwrap().contains(&symbol)
+    }
+
+    /// Move `symbol` to another trading phase.
+    ///
+    /// `PreOpen` (from `Continuous` or `Closed`) and `PreClose` (from `Continuous`) start
+    /// a call phase in which orders accumulate without matching. Leaving `PreOpen` for
+    /// `Continuous`, or `PreClose` for `Closed`, uncrosses the book at the equilibrium
+    /// price. If there is none, or matching is suspended and the auction is skipped, the
+    /// queued on-open or on-close orders are rejected. Other transitions are ignored.
+    pub async fn set_trading_phase(&self, symbol: String, phase: TradingPhase) -> Result<()> {
+        self.send_command(EngineCommand::SetTradingPhase(self.state.symbols.intern(&symbol), phase))
+    }
+
+    /// Indicative uncross price, matchable volume and imbalance of `symbol`'s auction.
+    ///
+    /// `None` outside call phases or when the symbol has no book.
+    pub fn get_indicative_auction(&self, symbol: &str) -> Option<IndicativeAuction> {
+        let symbol = self.state.symbols.get(symbol)?;
+        let auction = self.state.phase_of(symbol).auction_order_type()?;
+        self.state.order_books.with(symbol, |book| book.indicative_uncross(auction))
+    }
+
+    /// Current trading phase of `symbol`
+    pub fn get_trading_phase(&self, symbol: &str) -> TradingPhase {
+        self.state.symbols.get(symbol).map(|symbol| self.state.phase_of(symbol)).unwrap_or_default()
+    }
+
+    /// Quoting metrics (time at BBO, two-sided uptime, time-weighted spread) of a
+    /// client on `symbol`, for administering market-maker programs
+    pub fn get_quoting_metrics(&self, client_id: &str, symbol: &str) -> Option<QuotingMetrics> {
+        self.state.quotes.lock().unwrap().metrics(client_id, symbol, self.state.clock.now())
+    }
+
+    /// Rolling volume-weighted average trade price of `symbol` over the last `window` (up to an hour)
+    pub fn get_vwap(&self, symbol: &str, window: Duration) -> Option<f64> {
+        self.state.reference_prices.lock().unwrap().vwap(symbol, window, self.state.clock.now())
+    }
+
+    /// Volume-weighted average trade price of `symbol` since the current trading day began;
+    /// `None` if it hasn't traded since the last session rollover
+    pub fn get_session_vwap(&self, symbol: &str) -> Option<f64> {
+        self.get_daily_stats(symbol)?.vwap()
+    }
+
+    /// Rolling time-weighted average of the last trade price of `symbol` over the last `window` (up to an hour)
+    pub fn get_twap(&self, symbol: &str, window: Duration) -> Option<f64> {
+        self.state.reference_prices.lock().unwrap().twap(symbol, window, self.state.clock.now())
+    }
+
+    /// Open, high, low, last, volume and trade count of `symbol` in the current trading day;
+    /// `None` if it hasn't traded since the last session rollover
+    pub fn get_daily_stats(&self, symbol: &str) -> Option<DailyStats> {
+        self.state.daily_stats.lock().unwrap().get(symbol, self.state.clock.now())
+    }
+
+    /// Daily statistics of every symbol that traded in the current trading day
+    pub fn get_all_daily_stats(&self) -> Vec<DailyStats> {
+        self.state.daily_stats.lock().unwrap().all(self.state.clock.now())
+    }
+
+    /// Net position and traded volume of a client in `symbol`, from its fills so far
+    pub fn get_position(&self, client_id: &str, symbol: &str) -> Position {
+        self.state.positions.lock().unwrap().position(client_id, symbol)
+    }
+
+    /// Tick size and order size checks done before an order is accepted
+    fn check_instrument_rules(&self, order: &mut Order) -> Result<()> {
+        let result = self.apply_instrument_rules(order);
+        if let Err(e) = &result {
+            // Never reached the queue, so there is no sequence number to stamp
+            let stamp = EventStamp {
+                timestamp: self.state.clock.now(),
+                sequence: 0,
+            };
+            self.state.order_events.rejected(order, e.to_string(), stamp);
+            self.state.order_events.flush();
+        }
+        result
+    }
+
+    fn apply_instrument_rules(&self, order: &mut Order) -> Result<()> {
+        if let Some(price) = order.price {
+            let price = self.align_price(&order.symbol, price, order.side)?;
+            self.check_collar(&order.symbol, price)?;
+            order.price = Some(price);
+        }
+        if let Some(stop_price) = order.stop_price {
+            order.stop_price = Some(self.align_price(&order.symbol, stop_price, order.side)?);
+        }
+        self.check_size(&order.symbol, order.quantity, order.price)
+    }
+
+    /// Check `price` against the symbol's precision and align it to its tick size, where its instrument defines them
+    fn align_price(&self, symbol: &str, price: Price, side: Side) -> Result<Price> {
+        let Some(instrument) = self.state.instruments.get(symbol) else {
+            return Ok(price);
+        };
+
+        if !instrument.check_precision(price) {
+            return Err(EngineError::InvalidPricePrecision {
+                price,
+                decimals: instrument.price_decimals.unwrap_or(Price::DECIMALS),
+            });
+        }
+
+        instrument.align_price(price, side).ok_or(EngineError::InvalidTickSize {
+            price,
+            tick_size: instrument.tick_size.unwrap_or_default(),
+        })
+    }
+
+    /// Check a limit price against the symbol's price collar, if it has one and a reference price
+    fn check_collar(&self, symbol: &str, price: Price) -> Result<()> {
+        let instrument = self
+            .state
+            .instruments
+            .get(symbol)
+            .filter(|instrument| instrument.price_collar_percent.is_some());
+        let (Some(instrument), Some(reference)) = (instrument, self.reference_price(symbol)) else {
+            return Ok(());
+        };
+
+        if instrument.within_collar(price, reference) {
+            Ok(())
         } else {
-            warn!("Symbol not found: {}", symbol);
+            Err(EngineError::PriceOutsideCollar {
+                price,
+                reference,
+                percent: instrument.price_collar_percent.unwrap_or_default(),
+            })
         }
     }
 
-    /// Submit new order
-    pub async fn submit_order(&self, order: Order) -> Result<()> {
-        if !*self.running.lock().unwrap() {
-            return Err(EngineError::EngineStopped);
+    /// Validate an amendment, aligning its new price to the symbol's tick size
+    fn check_amendment(&self, symbol: &str, amendment: &mut Amendment) -> Result<()> {
+        if amendment.new_price.is_none() && amendment.new_quantity.is_none() {
+            return Err(EngineError::InvalidOrder(
+                "Modification must change price or quantity".to_string(),
+            ));
+        }
+        if amendment.new_quantity == Some(0) {
+            return Err(EngineError::InvalidOrder("Quantity must be positive".to_string()));
         }
 
-        self.order_sender
-            .send(EngineCommand::NewOrder(order))
-            .map_err(|_| EngineError::EngineStopped)?;
+        // Checks need the current order; an unknown order is reported by the processing loop
+        let current = self
+            .state
+            .symbols
+            .get(symbol)
+            .and_then(|symbol| {
+                self.state.order_books.with(symbol, |book| {
+                    book.get_order(amendment.order_id)
+                        .map(|order| (order.side, order.quantity, order.price))
+                })
+            })
+            .flatten();
+        if let Some((side, quantity, price)) = current {
+            if let Some(price) = amendment.new_price {
+                let price = self.align_price(symbol, price, side)?;
+                self.check_collar(symbol, price)?;
+                amendment.new_price = Some(price);
+            }
+            self.check_size(
+                symbol,
+                amendment.new_quantity.unwrap_or(quantity),
+                amendment.new_price.or(price),
+            )?;
+        }
 
         Ok(())
     }
 
-    /// Cancel order
-    pub async fn cancel_order(&self, order_id: Uuid, symbol: String) -> Result<()> {
-        if !*self.running.lock().unwrap() {
-            return Err(EngineError::EngineStopped);
+    /// Check lot size, minimum quantity and minimum notional, where the symbol's instrument defines them
+    fn check_size(&self, symbol: &str, quantity: u64, price: Option<Price>) -> Result<()> {
+        self.check_risk_limits(quantity, price)?;
+        let Some(instrument) = self.state.instruments.get(symbol) else {
+            return Ok(());
+        };
+
+        if let Some(lot_size) = instrument.lot_size {
+            if !quantity.is_multiple_of(lot_size) {
+                return Err(EngineError::InvalidLotSize { quantity, lot_size });
+            }
+        }
+        if let Some(min_quantity) = instrument.min_quantity {
+            if quantity < min_quantity {
+                return Err(EngineError::BelowMinQuantity { quantity, min_quantity });
+            }
+        }
+        if let (Some(min_notional), Some(price)) = (instrument.min_notional, price) {
+            let notional = instrument.notional(price, quantity).to_f64();
+            if notional < min_notional {
+                return Err(EngineError::BelowMinNotional { notional, min_notional });
+            }
         }
 
-        self.order_sender
-            .send(EngineCommand::CancelOrder(order_id, symbol))
-            .map_err(|_| EngineError::EngineStopped)?;
+        Ok(())
+    }
+
+    /// Check size against the engine-wide risk limits
+    fn check_risk_limits(&self, quantity: u64, price: Option<Price>) -> Result<()> {
+        let limits = &self.state.risk_limits;
+        if let Some(max_quantity) = limits.max_order_quantity {
+            if quantity > max_quantity {
+                return Err(EngineError::AboveMaxQuantity { quantity, max_quantity });
+            }
+        }
+        if let (Some(max_notional), Some(price)) = (limits.max_order_notional, price) {
+            let notional = price.to_f64() * quantity as f64;
+            if notional > max_notional {
+                return Err(EngineError::AboveMaxNotional { notional, max_notional });
+            }
+        }
 
         Ok(())
     }
 
+    fn send_order(&self, order: Order, slot: OwnedSemaphorePermit) -> Result<()> {
+        let symbol = self.state.symbols.intern(&order.symbol);
+        let order_id = order.id;
+        // Tracked before sending, so a cancel right behind the order finds it queued
+        self.state.queued_ids.lock().unwrap().insert(order_id);
+        let result = self.send_command(EngineCommand::NewOrder(symbol, order, slot));
+        if result.is_err() {
+            self.state.queued_ids.lock().unwrap().remove(&order_id);
+        }
+        result
+    }
+
+    fn send_command(&self, command: EngineCommand) -> Result<()> {
+        self.send_on(&self.order_sender, command)
+    }
+
+    fn send_on(&self, lane: &Sender<EngineCommand>, command: EngineCommand) -> Result<()> {
+        // Queue while holding the flag, so `stop` can't slip its shutdown in ahead of the
+        // command and leave it unprocessed
+        let running = self.running.borrow();
+        if !*running {
+            return Err(EngineError::EngineStopped);
+        }
+
+        lane.send(command).map_err(|_| EngineError::EngineStopped)
+    }
+
+    /// Set an external reference price for `symbol`'s price collar, or fall back to the
+    /// last traded price with `None`
+    pub fn set_reference_price(&self, symbol: String, price: Option<Price>) {
+        match price {
+            Some(price) => self.update_reference_price(symbol, price),
+            None => {
+                let symbol = self.state.symbols.intern(&symbol);
+                self.state.external_references.lock().unwrap().remove(&symbol);
+            }
+        }
+    }
+
+    /// Feed in the latest external index or reference price of `symbol`, e.g. from another
+    /// venue or a price provider.
+    ///
+    /// Until it goes stale (see
+    /// [`with_reference_staleness`](EngineBuilder::with_reference_staleness)) it is the
+    /// reference for the symbol's price collar and its mark price.
+    pub fn update_reference_price(&self, symbol: String, price: Price) {
+        let symbol = self.state.symbols.intern(&symbol);
+        let now = self.state.clock.now();
+        self.state.external_references.lock().unwrap().insert(symbol, (price, now));
+    }
+
+    /// The external reference price last fed in for `symbol`, and whether it is stale
+    pub fn get_external_reference(&self, symbol: &str) -> Option<ExternalReference> {
+        self.state.external_reference(symbol)
+    }
+
+    /// Reference price for `symbol`'s price collar: the external price if set and not stale,
+    /// else the last traded price
+    pub fn reference_price(&self, symbol: &str) -> Option<Price> {
+        self.state.reference_price(symbol)
+    }
+
+    /// Price to value `symbol` positions at: the external reference price if set and not
+    /// stale, else the book's mid price, else the last traded price
+    pub fn get_mark_price(&self, symbol: &str) -> Option<Price> {
+        if let Some(reference) = self.get_external_reference(symbol).filter(|reference| !reference.stale) {
+            return Some(reference.price);
+        }
+        let symbol = self.state.symbols.get(symbol)?;
+        self.state
+            .order_books
+            .with(symbol, |book| book.mid_price().or(book.last_trade_price()))?
+    }
+
+    /// Delay aggressive orders on `symbol` by a random interval, or remove the delay with `None`
+    pub fn set_speed_bump(&self, symbol: String, bump: Option<SpeedBump>) {
+        let symbol = self.state.symbols.intern(&symbol);
+        let mut speed_bumps = self.state.speed_bumps.lock().unwrap();
+        match bump {
+            Some(bump) => {
+                speed_bumps.insert(symbol, bump);
+            }
+            None => {
+                speed_bumps.remove(&symbol);
+            }
+        }
+    }
+
     /// Get current metrics
     pub fn get_metrics(&self) -> ExecutionMetrics {
-        let mut metrics = self.metrics.lock().unwrap().clone();
-        
-        // Calculate latency percentiles
-        let mut samples = self.latency_samples.lock().unwrap();
-        if !samples.is_empty() {
-            samples.sort_unstable();
-            let len = samples.len();
-            
-            metrics.avg_latency_micros = samples.iter().sum::<u64>() / len as u64;
-            metrics.p50_latency_micros = samples[len / 2];
-            metrics.p95_latency_micros = samples[(len * 95) / 100];
-            metrics.p99_latency_micros = samples[(len * 99) / 100];
-        }
-        
+        let mut metrics = self.state.metrics.lock().unwrap().clone();
+
+        // Latency percentiles come straight from the histogram's buckets
+        let latency = self.state.latency.lock().unwrap();
+        if let Some(mean) = latency.mean() {
+            metrics.avg_latency_micros = mean;
+            metrics.p50_latency_micros = latency.value_at_quantile(0.5).unwrap_or_default();
+            metrics.p95_latency_micros = latency.value_at_quantile(0.95).unwrap_or_default();
+            metrics.p99_latency_micros = latency.value_at_quantile(0.99).unwrap_or_default();
+        }
+        drop(latency);
+
         metrics
     }
 
-    /// Stop the engine
-    pub async fn stop(&self) {
-        info!("Stopping execution engine");
-        let mut running = self.running.lock().unwrap();
-        *running = false;
-        drop(running);
+    /// Follow the lifecycle of orders: acceptance, rejection, fills, cancellation and
+    /// expiry, in the order they happen.
+    ///
+    /// Events are published from the moment of subscribing. A subscriber that falls
+    /// behind is handled by its slow-consumer policy; dropping the receiver ends the
+    /// subscription.
+    pub fn subscribe_order_events(&self, config: OrderEventConfig) -> Receiver<SubscriberEvent<OrderEvent>> {
+        self.state.order_events.subscribe(config)
+    }
 
-        let _ = self.order_sender.send(EngineCommand::Shutdown);
+    /// Drop copy: every order event and execution of every client, in the order they
+    /// happen, numbered without gaps for risk and compliance systems. Independent of order
+    /// event subscriptions and execution reports; a subscriber that falls behind is handled
+    /// by its slow-consumer policy, disconnecting it by default.
+    pub fn subscribe_drop_copy(&self, config: DropCopyConfig) -> Receiver<SubscriberEvent<DropCopyMessage>> {
+        self.state.order_events.subscribe_drop_copy(config)
+    }
+
+    /// Watch the best bid and offer of `symbol`.
+    ///
+    /// The receiver always holds the latest BBO and is notified only when it changes, so
+    /// consumers can `await` updates without draining an event queue.
+    pub fn subscribe_bbo(&self, symbol: &str) -> watch::Receiver<Bbo> {
+        // Book before feeds, the order `settle_book` takes them in
+        let symbol = self.state.symbols.intern(symbol);
+        let shared = self.state.order_books.get(symbol);
+        let book = shared.as_ref().map(|book| book.lock().unwrap());
+        let mut feeds = self.state.bbo_feeds.lock().unwrap();
+        feeds
+            .entry(symbol)
+            .or_insert_with(|| watch::channel(book.as_deref().map(OrderBook::bbo).unwrap_or_default()).0)
+            .subscribe()
+    }
+
+    /// Stream every change to the best bid or offer (price or size), of every symbol or just
+    /// `config.symbol`, as it happens.
+    ///
+    /// Unlike [`subscribe_bbo`](Self::subscribe_bbo) no change is skipped, unless the
+    /// subscriber falls behind and its slow-consumer policy discards updates.
+    pub fn subscribe_bbo_updates(&self, config: FeedConfig) -> Receiver<SubscriberEvent<BboUpdate>> {
+        self.state.bbo_updates.subscribe_conflated(config)
+    }
+
+    /// Stream incremental L2 updates: the price levels each engine command added, modified or
+    /// deleted, of every symbol or just `config.symbol`.
+    ///
+    /// Each symbol's updates are numbered without gaps, so a consumer maintaining its own
+    /// book can tell when it missed one and must resynchronise from a
+    /// [book snapshot](Self::subscribe_book_snapshots).
+    pub fn subscribe_level_updates(&self, config: FeedConfig) -> Receiver<SubscriberEvent<LevelUpdate>> {
+        self.state.level_updates.subscribe_conflated(config)
+    }
+
+    /// Stream incremental L3 updates: the resting orders each engine command added,
+    /// executed, reduced or removed, of every symbol or just `config.symbol`.
+    ///
+    /// Updates are never conflated. Together with the trade prints of auctions, whose
+    /// orders never show in the book, they are what an
+    /// [`ItchEncoder`](crate::market_data::ItchEncoder) turns into ITCH messages.
+    pub fn subscribe_order_updates(&self, config: FeedConfig) -> Receiver<SubscriberEvent<OrderUpdate>> {
+        self.state.order_updates.subscribe(config)
+    }
+
+    /// Stream the public trade tape (time & sales) of every symbol or just `config.symbol`.
+    ///
+    /// Prints carry no client or order IDs, unlike the private fills on the trade channel,
+    /// and are numbered per symbol without gaps.
+    pub fn subscribe_trade_prints(&self, config: FeedConfig) -> Receiver<SubscriberEvent<TradePrint>> {
+        self.state.trade_prints.subscribe(config)
+    }
+
+    /// Lag from book changes to their updates reaching subscribers' buffers, per feed, and
+    /// how full each subscriber's buffer is. Subscribers at least half full, or that lost
+    /// updates to their slow-consumer policy, are flagged as slow.
+    pub fn market_data_stats(&self) -> MarketDataStats {
+        MarketDataStats {
+            bbo: self.state.bbo_updates.stats(),
+            levels: self.state.level_updates.stats(),
+            orders: self.state.order_updates.stats(),
+            trades: self.state.trade_prints.stats(),
+            snapshots: self.state.book_snapshots.stats(),
+        }
+    }
+
+    /// Subscribe per channel and symbol to BBO, depth, trade and candle data, all delivered
+    /// in order on the returned receiver.
+    ///
+    /// Subscriptions can be added and dropped at any time through the returned
+    /// [`MarketDataSubscriptions`]; dropping it ends the stream.
+    pub fn market_data_subscriptions(&self) -> (MarketDataSubscriptions, Receiver<MarketDataMessage>) {
+        let (sender, receiver) = unbounded();
+        let subscriptions =
+            MarketDataSubscriptions::spawn(Arc::clone(&self.state), move |message| sender.send(message).is_ok());
+        (subscriptions, receiver)
+    }
+
+    /// Stream full depth snapshots of every symbol or just `config.symbol`, published at the
+    /// engine's snapshot interval and by [`publish_book_snapshot`](Self::publish_book_snapshot).
+    ///
+    /// Each snapshot carries the sequence of the last [`LevelUpdate`] it reflects, so a
+    /// consumer joining late can buffer level updates, take the next snapshot and apply
+    /// the updates numbered after it.
+    pub fn subscribe_book_snapshots(&self, config: FeedConfig) -> Receiver<SubscriberEvent<BookSnapshot>> {
+        self.state.book_snapshots.subscribe_conflated(config)
+    }
+
+    /// Publish a snapshot of `symbol`'s book to snapshot subscribers now, e.g. for one that
+    /// just subscribed, and return it; `None` if the symbol has no book
+    pub fn publish_book_snapshot(&self, symbol: &str) -> Option<BookSnapshot> {
+        let symbol_id = self.state.symbols.get(symbol)?;
+        let snapshot = self.state.order_books.with(symbol_id, |book| self.state.snapshot_book(symbol_id, book))?;
+        self.state.book_snapshots.publish(symbol, snapshot.clone(), Instant::now());
+        Some(snapshot)
+    }
+
+    /// Periodically sample the depth ladder of `symbol` and export it as compact
+    /// price × time matrices, one per `frames_per_batch` samples.
+    ///
+    /// Matrices are buffered up to `config.buffer`; a subscriber that falls further
+    /// behind is handled per `config.slow_consumer` and told about discarded matrices
+    /// with [`SubscriberEvent::DataLoss`]. Export stops when the engine stops, the
+    /// receiver is dropped, or the subscriber is disconnected by policy.
+    pub async fn export_heatmap(
+        &self,
+        symbol: String,
+        config: HeatmapConfig,
+    ) -> Receiver<SubscriberEvent<HeatmapMatrix>> {
+        let (publisher, receiver) = Publisher::new(config.buffer, config.slow_consumer);
+        let state = Arc::clone(&self.state);
+        let mut running = self.running.subscribe();
+        let symbol_id = self.state.symbols.intern(&symbol);
+
+        task::spawn(async move {
+            let mut heatmap = LadderHeatmap::new(symbol.clone());
+            let mut ticker = tokio::time::interval(config.interval);
+
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = running.wait_for(|running| !*running) => break,
+                }
+
+                let frame = state
+                    .order_books
+                    .with(symbol_id, |book| HeatmapFrame::capture(book, config.levels))
+                    .unwrap_or_else(|| HeatmapFrame {
+                        timestamp: Utc::now(),
+                        bids: Vec::new(),
+                        asks: Vec::new(),
+                    });
+
+                heatmap.record(frame);
+                if heatmap.len() >= config.frames_per_batch && publisher.publish(heatmap.export()).is_err() {
+                    debug!("Heatmap subscriber disconnected for {}", symbol);
+                    break;
+                }
+            }
+        });
+
+        receiver
+    }
+
+    /// Soak mode: audit the engine's invariants every `interval`, for long stability runs.
+    ///
+    /// Each audit runs on the matching thread between two commands, so it never sees a
+    /// command half applied: books must be sound and uncrossed wherever matching runs,
+    /// client positions must net to zero per symbol, and metrics must only grow and agree
+    /// with each other. Violations are logged and every audit publishes a report; a
+    /// subscriber that falls behind loses the oldest reports, whose violations still count
+    /// towards `total_violations`. Auditing stops when the engine stops or the receiver
+    /// is dropped.
+    pub fn start_soak_audit(&self, interval: Duration) -> Receiver<SubscriberEvent<AuditReport>> {
+        let (publisher, receiver) = Publisher::new(AUDIT_REPORT_BUFFER, SlowConsumerPolicy::DropOldest);
+        let commands = self.order_sender.clone();
+        let mut running = self.running.subscribe();
+
+        task::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut previous = ExecutionMetrics::default();
+            let mut audits = 0;
+            let mut total_violations = 0;
+
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = running.wait_for(|running| !*running) => break,
+                }
+
+                let (reply, audit) = oneshot::channel();
+                if commands.send(EngineCommand::Audit(reply)).is_err() {
+                    break;
+                }
+                let audit = tokio::select! {
+                    audit = audit => audit,
+                    _ = running.wait_for(|running| !*running) => break,
+                };
+                let Ok(StateAudit { books, mut violations, metrics }) = audit else {
+                    break;
+                };
+
+                violations.extend(audit::audit_metrics(&metrics, &previous));
+                previous = metrics;
+                audits += 1;
+                total_violations += violations.len() as u64;
+                for violation in &violations {
+                    error!("Invariant violated: {}", violation);
+                }
+
+                let report = AuditReport {
+                    audit: audits,
+                    timestamp: Utc::now(),
+                    books,
+                    violations,
+                    total_violations,
+                };
+                if publisher.publish(report).is_err() {
+                    debug!("Soak audit subscriber disconnected");
+                    break;
+                }
+            }
+        });
+
+        receiver
+    }
+
+    /// Whether the engine is started and accepting commands
+    pub fn is_running(&self) -> bool {
+        *self.running.borrow()
+    }
+
+    /// Stop the engine, once every command accepted so far has been processed.
+    ///
+    /// Returns what was drained on the way down, also written to the log, or `None` if
+    /// the engine wasn't running.
+    pub async fn stop(&self) -> Option<ShutdownReport> {
+        info!("Stopping execution engine");
+        let trades_before = self.state.metrics.lock().unwrap().total_trades;
+        let unsent_before = self.state.unsent_trades.load(Ordering::Relaxed);
+        let mut queued = (0, 0);
+        // Flipped under the flag's write lock, which waits out commands being queued, so
+        // the shutdown lands behind every command that was accepted
+        self.running.send_if_modified(|running| {
+            let was_running = std::mem::replace(running, false);
+            if was_running {
+                let commands = self.order_sender.len() + self.cancel_sender.len();
+                queued = (commands as u64, self.queued_orders() as u64);
+                let _ = self.order_sender.send(EngineCommand::Shutdown);
+            }
+            was_running
+        });
+
+        // Wait for the matching thread to finish the commands queued ahead of the shutdown
+        let exit = self.matching_exit.lock().unwrap().take()?;
+        let exit = exit.await.ok()?;
+
+        let unsent_events = self.state.unsent_trades.load(Ordering::Relaxed);
+        let trades = self.state.metrics.lock().unwrap().total_trades - trades_before;
+        let report = ShutdownReport {
+            timestamp: Utc::now(),
+            commands_drained: queued.0,
+            orders_drained: queued.1,
+            cancels_issued: exit.cancelled,
+            trades_flushed: trades.saturating_sub(unsent_events - unsent_before),
+            last_sequence: exit.sequence,
+            unsent_events,
+            resting_orders: self.state.order_books.all().iter().map(|(_, book)| book.lock().unwrap().depth()).sum(),
+        };
+        info!(
+            "Engine stopped: {} commands drained ({} orders), {} cancels issued, {} trades flushed, last sequence {}, {} unsent events, {} orders resting",
+            report.commands_drained,
+            report.orders_drained,
+            report.cancels_issued,
+            report.trades_flushed,
+            report.last_sequence,
+            report.unsent_events,
+            report.resting_orders
+        );
+        if !report.is_clean() {
+            warn!("{} trades were never delivered", report.unsent_events);
+        }
+        Some(report)
+    }
+
+    /// L2 snapshot of the best `levels` price levels of a symbol's book
+    pub fn get_depth(&self, symbol: &str, levels: usize) -> Option<DepthSnapshot> {
+        self.state.order_books.with(self.state.symbols.get(symbol)?, |book| book.get_depth(levels))
+    }
+
+    /// Imbalance, weighted mid and book VWAP over the best `levels` levels of a symbol's book
+    pub fn get_book_analytics(&self, symbol: &str, levels: usize) -> Option<BookAnalytics> {
+        self.state.order_books.with(self.state.symbols.get(symbol)?, |book| book.analytics(levels))
     }
 
     /// Get order book for symbol
-    pub fn get_order_book(&self, symbol: &str) -> Option<(Option<f64>, Option<f64>, usize)> {
-        let books = self.order_books.lock().unwrap();
-        books.get(symbol).map(|book| {
+    pub fn get_order_book(&self, symbol: &str) -> Option<(Option<Price>, Option<Price>, usize)> {
+        self.state.order_books.with(self.state.symbols.get(symbol)?, |book| {
             (book.best_bid(), book.best_ask(), book.depth())
         })
     }
 }
+
+impl EngineState {
+    /// Speed bump to apply to `order`, if its symbol has one and the order is aggressive
+    fn speed_bump_for(&self, symbol: SymbolId, order: &Order) -> Option<SpeedBump> {
+        let bump = *self.speed_bumps.lock().unwrap().get(&symbol)?;
+        let marketable = self
+            .order_books
+            .with(symbol, |book| book.is_marketable(order))
+            .unwrap_or(false);
+        marketable.then_some(bump)
+    }
+
+    /// Create a book using the symbol's registered configuration, if any
+    fn new_book(&self, symbol: &str) -> OrderBook {
+        let mut book = match self.instruments.get(symbol) {
+            Some(instrument) => OrderBook::with_config(symbol.to_string(), instrument.book.clone()),
+            None => OrderBook::with_config(symbol.to_string(), self.default_book.clone()),
+        };
+        book.track_level_changes(true);
+        book.track_order_changes(true);
+        book.set_clock(Arc::clone(&self.clock));
+        book
+    }
+
+    fn execute_order(&self, symbol: SymbolId, order: Order) {
+        if !self.record_latency {
+            self.process_order(symbol, order);
+            return;
+        }
+        let start = Instant::now();
+        self.process_order(symbol, order);
+        let latency = start.elapsed().as_micros() as u64;
+        self.latency.lock().unwrap().record(latency);
+    }
+
+    fn external_reference(&self, symbol: &str) -> Option<ExternalReference> {
+        let symbol = self.symbols.get(symbol)?;
+        let (price, updated) = *self.external_references.lock().unwrap().get(&symbol)?;
+        let age = (self.clock.now() - updated).to_std().unwrap_or_default();
+        Some(ExternalReference {
+            price,
+            updated,
+            stale: self.reference_staleness.is_some_and(|limit| age > limit),
+        })
+    }
+
+    /// The external reference price of `symbol` if set and not stale, else its last traded
+    /// price. Locks the symbol's book.
+    fn reference_price(&self, symbol: &str) -> Option<Price> {
+        if let Some(reference) = self.external_reference(symbol).filter(|reference| !reference.stale) {
+            return Some(reference.price);
+        }
+        let symbol = self.symbols.get(symbol)?;
+        self.order_books.with(symbol, |book| book.last_trade_price())?
+    }
+
+    /// Run `order` through the engine's risk checks, in order, until one refuses it; a check
+    /// may trim it. An order already working (being amended) stands in for its own open
+    /// quantity, and `pending` is open (buy, sell) quantity of the client in the symbol not
+    /// tracked yet, such as earlier amendments of a batch.
+    fn run_risk_checks(&self, order: &mut Order, pending: (u64, u64)) -> std::result::Result<(), (String, RiskRejection)> {
+        let mut checks = self.risk_checks.lock().unwrap();
+        if checks.is_empty() {
+            return Ok(());
+        }
+        let position = self.positions.lock().unwrap().net_position(&order.client_id, &order.symbol);
+        let (mut open_buy, mut open_sell) = self.order_events.open_quantity(&order.client_id, &order.symbol);
+        let replaced = self.order_events.remaining(order.id);
+        match order.side {
+            Side::Buy => open_buy = open_buy.saturating_sub(replaced),
+            Side::Sell => open_sell = open_sell.saturating_sub(replaced),
+        }
+        let (open_buy, open_sell) = (open_buy + pending.0, open_sell + pending.1);
+        let context = RiskContext {
+            now: self.clock.now(),
+            position,
+            open_buy,
+            open_sell,
+            other_exposure: self.exposure_outside(&order.client_id, &order.symbol),
+            reference_price: self.reference_price(&order.symbol),
+        };
+        for check in checks.iter_mut() {
+            let quantity = order.quantity;
+            check.check(order, &context).map_err(|rejection| (check.name().to_string(), rejection))?;
+            if order.quantity < quantity {
+                debug!("Risk check {} trimmed order {:?} to {}", check.name(), order.id, order.quantity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sum over the client's symbols other than `symbol` of the largest position it could
+    /// hold in each, were every open order on one side filled
+    fn exposure_outside(&self, client_id: &str, symbol: &str) -> u64 {
+        let mut symbols: HashMap<String, (i64, u64, u64)> = self
+            .order_events
+            .open_quantities(client_id)
+            .into_iter()
+            .map(|(symbol, (buy, sell))| (symbol, (0, buy, sell)))
+            .collect();
+        for (symbol, position) in self.positions.lock().unwrap().client_positions(client_id) {
+            symbols.entry(symbol.to_string()).or_default().0 = position.net;
+        }
+        symbols
+            .iter()
+            .filter(|(other, _)| *other != symbol)
+            .map(|(_, &(net, buy, sell))| {
+                net.saturating_add(buy as i64)
+                    .unsigned_abs()
+                    .max(net.saturating_sub(sell as i64).unsigned_abs())
+            })
+            .sum()
+    }
+
+    /// Order checks done when an order is accepted (and again when a staged order is released).
+    ///
+    /// Rejects the order and counts it when a check fails. Reduce-only orders may be
+    /// trimmed to the client's position.
+    fn validate_order(&self, symbol: SymbolId, order: &mut Order) -> bool {
+        if let Some(switch) = self.kill_switches.blocking(&order.client_id) {
+            warn!("Order rejected by kill switch for {:?}: {:?}", switch.scope, order.id);
+            self.reject(order, format!("Kill switch engaged by {}", switch.triggered_by));
+            return false;
+        }
+
+        if !self.admit_message(&order.client_id, MessageKind::NewOrder) {
+            self.reject(order, "Message rate limit exceeded");
+            return false;
+        }
+
+        if let Some(client_order_ids) = &self.client_order_ids {
+            if !client_order_ids.lock().unwrap().record(order, self.clock.now()) {
+                let client_order_id = order.client_order_id.clone().unwrap_or_default();
+                warn!("Duplicate client order ID {} from {}: {:?}", client_order_id, order.client_id, order.id);
+                self.reject(order, format!("Duplicate client order ID {}", client_order_id));
+                return false;
+            }
+        }
+
+        let entitled = match self.client_symbols.lock().unwrap().get(&order.client_id) {
+            Some(symbols) => symbols.contains(&order.symbol),
+            None => true,
+        };
+        if !entitled {
+            warn!("Client {} is not permitted to trade {}: {:?}", order.client_id, order.symbol, order.id);
+            self.reject(order, format!("Client {} is not permitted to trade {}", order.client_id, order.symbol));
+            return false;
+        }
+
+        if order.quantity == 0 {
+            error!("Invalid order quantity: 0");
+            self.reject(order, "Quantity is zero");
+            return false;
+        }
+
+        if order.order_type == OrderType::Limit && order.price.is_none() {
+            error!("Limit order without price");
+            self.reject(order, "Limit order without price");
+            return false;
+        }
+
+        // On-open orders need a pending opening auction; on-close orders are held until the close
+        let phase = self.phase_of(symbol);
+        let accepted = match order.order_type {
+            OrderType::MarketOnOpen => phase == TradingPhase::PreOpen,
+            OrderType::MarketOnClose => matches!(phase, TradingPhase::Continuous | TradingPhase::PreClose),
+            _ => phase != TradingPhase::Closed,
+        };
+        if !accepted {
+            warn!("{:?} order not accepted during {:?} for {}: {:?}", order.order_type, phase, order.symbol, order.id);
+            self.reject(order, format!("{:?} orders are not accepted during {:?}", order.order_type, phase));
+            return false;
+        }
+
+        if self.halts.lock().unwrap().get(&symbol) == Some(&HaltPolicy::Reject) {
+            warn!("Order rejected while trading is halted on {}: {:?}", order.symbol, order.id);
+            self.reject(order, "Trading is halted");
+            return false;
+        }
+
+        let best_bid = self.order_books.with(symbol, |book| book.best_bid()).flatten();
+        if !self.short_sale_allowed(symbol, order, order.price, best_bid) {
+            warn!("Short sale at or below the best bid under restriction on {}: {:?}", order.symbol, order.id);
+            self.reject(order, "Short sale must be priced above the best bid while restricted");
+            return false;
+        }
+
+        if order.reduce_only {
+            let reducible = self.positions.lock().unwrap().reducible_quantity(order);
+            if reducible == 0 {
+                warn!("Reduce-only order would increase position: {:?}", order.id);
+                self.reject(order, "Reduce-only order would increase position");
+                return false;
+            }
+            if reducible < order.quantity {
+                debug!("Trimming reduce-only order {:?} to {}", order.id, reducible);
+                order.quantity = reducible;
+            }
+        }
+
+        if !self.positions.lock().unwrap().intent_allowed(order) {
+            warn!("{:?} order inconsistent with position: {:?}", order.position_intent, order.id);
+            self.reject(order, format!("{:?} order inconsistent with position", order.position_intent));
+            return false;
+        }
+
+        if let Err((check, rejection)) = self.run_risk_checks(order, (0, 0)) {
+            warn!("Order refused by risk check {}: {:?}: {}", check, order.id, rejection);
+            *self.metrics.lock().unwrap().risk_rejections.entry(check).or_default() += 1;
+            self.reject(order, rejection.to_string());
+            return false;
+        }
+
+        true
+    }
+
+    /// Risk checks for amending `order` to `new_price` and `new_quantity`, run on the order as
+    /// amended: size limits see its new quantity, position limits what it leaves to fill.
+    /// Returns the quantity to amend to, which a check may have trimmed; amendments that only
+    /// reduce the order aren't checked. A refusal is counted by check and returned as the reason.
+    fn check_amendment_risk(
+        &self,
+        order: &Order,
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+        pending: (u64, u64),
+    ) -> std::result::Result<Option<u64>, String> {
+        let quantity = new_quantity.unwrap_or(order.quantity);
+        let repriced = new_price.is_some_and(|price| Some(price) != order.price);
+        if quantity <= order.filled_quantity || (!repriced && quantity <= order.quantity) {
+            return Ok(new_quantity);
+        }
+
+        let mut amended = order.clone();
+        amended.price = new_price.or(order.price);
+        amended.quantity = quantity;
+        if let Err((check, rejection)) = self.run_risk_checks(&mut amended, pending) {
+            warn!("Amendment of {:?} refused by risk check {}: {}", order.id, check, rejection);
+            *self.metrics.lock().unwrap().risk_rejections.entry(check).or_default() += 1;
+            return Err(rejection.to_string());
+        }
+        Ok(if amended.quantity < quantity { Some(amended.quantity) } else { new_quantity })
+    }
+
+    /// Tell `order`'s subscribers why its amendment was refused; the order works on unchanged
+    fn refuse_amendment(&self, order: &Order, reason: impl Into<String>) {
+        self.order_events.amendment_rejected(order, reason, self.stamp());
+    }
+
+    /// Whether `order` may have `price` under `symbol`'s short sale restriction, if on: a
+    /// short sale must be priced above the best bid
+    fn short_sale_allowed(&self, symbol: SymbolId, order: &Order, price: Option<Price>, best_bid: Option<Price>) -> bool {
+        if !self.is_restricted_short_sale(symbol, order) {
+            return true;
+        }
+        match (price, best_bid) {
+            (None, _) => false,
+            (Some(price), Some(best_bid)) => price > best_bid,
+            (Some(_), None) => true,
+        }
+    }
+
+    fn is_restricted_short_sale(&self, symbol: SymbolId, order: &Order) -> bool {
+        order.sell_short && order.side == Side::Sell && self.short_sale_restricted.lock().unwrap().contains(&symbol)
+    }
+
+    fn process_short_sale_restriction(&self, symbol: SymbolId, active: bool) {
+        let mut restricted = self.short_sale_restricted.lock().unwrap();
+        if active {
+            warn!("Short sale restriction on for {}", self.symbols.name(symbol));
+            restricted.insert(symbol);
+        } else if restricted.remove(&symbol) {
+            info!("Short sale restriction lifted for {}", self.symbols.name(symbol));
+        }
+    }
+
+    /// Count a client message against the throttle, if any; `false` if it must be refused
+    fn admit_message(&self, client_id: &str, message: MessageKind) -> bool {
+        let Some(throttle) = &self.throttle else {
+            return true;
+        };
+        if throttle.admit(client_id, message, self.clock.now()) {
+            return true;
+        }
+        warn!("{:?} from {} throttled", message, client_id);
+        *self.metrics.lock().unwrap().throttled_messages.entry(client_id.to_string()).or_default() += 1;
+        false
+    }
+
+    /// Count a cancel against the throttle, if any; cancels are never refused
+    fn record_cancel(&self, client_id: &str) {
+        if let Some(throttle) = &self.throttle {
+            throttle.record_cancel(client_id, self.clock.now());
+        }
+    }
+
+    /// Mark `order` rejected, count it and tell its subscribers why
+    fn reject(&self, order: &mut Order, reason: impl Into<String>) {
+        order.status = OrderStatus::Rejected;
+        self.metrics.lock().unwrap().rejected_orders += 1;
+        self.order_events.rejected(order, reason, self.stamp());
+    }
+
+
+    fn process_order(&self, symbol: SymbolId, mut order: Order) {
+        debug!("Processing order: {:?}", order.id);
+
+        if !self.validate_order(symbol, &mut order) {
+            return;
+        }
+
+        if order.is_expired(self.clock.now()) {
+            order_event!(self, "Order expired on arrival: {:?}", order.id);
+            order.status = OrderStatus::Expired;
+            let mut metrics_guard = self.metrics.lock().unwrap();
+            metrics_guard.total_orders += 1;
+            metrics_guard.expired_orders += 1;
+            drop(metrics_guard);
+            self.order_events.expired(&order, self.stamp());
+            return;
+        }
+
+        let paused = self.matching_suspended(symbol);
+        if paused && order.order_type == OrderType::Market {
+            warn!("Market order rejected while matching is suspended: {:?}", order.id);
+            self.reject(&mut order, "Matching is suspended");
+            return;
+        }
+
+        // Queue priority goes by when the engine took the order, on its clock
+        order.timestamp = self.clock.now();
+        self.order_events.accepted(&order, self.stamp());
+        let book = self.order_books.get_or_insert_with(symbol, || self.new_book(&order.symbol));
+        let mut book = book.lock().unwrap();
+
+        let order_id = order.id;
+        let trades = if order.order_type == OrderType::Market {
+            let (trades, order) = book.execute_market_order(order);
+            self.order_events.fills(&trades, self.stamp());
+            match order.status {
+                OrderStatus::Rejected => {
+                    warn!("Market order found no liquidity: {:?}", order.id);
+                    self.metrics.lock().unwrap().rejected_orders += 1;
+                    self.order_events.rejected(&order, "No liquidity", self.stamp());
+                }
+                OrderStatus::Cancelled => {
+                    info!("Market order remainder cancelled: {:?}", order.id);
+                    self.metrics.lock().unwrap().cancelled_orders += 1;
+                    self.order_events.cancelled(&order, self.stamp());
+                }
+                _ => {}
+            }
+            trades
+        } else if paused {
+            // Rest without matching while the symbol is paused or in a call phase
+            book.add_order(order);
+            Vec::new()
+        } else if self.is_restricted_short_sale(symbol, &order) {
+            // Priced above the best bid, so it can only rest; it never takes liquidity
+            book.add_order(order);
+            Vec::new()
+        } else {
+            let trades = book.match_order(order);
+            self.order_events.fills(&trades, self.stamp());
+            trades
+        };
+
+        if book.get_order(order_id).is_some() {
+            self.resting_since
+                .lock()
+                .unwrap()
+                .entry(symbol)
+                .or_default()
+                .insert(order_id, self.clock.now());
+        }
+        self.settle_book(symbol, &mut book);
+        self.metrics.lock().unwrap().total_orders += 1;
+        drop(book);
+
+        self.record_trades(symbol, trades);
+    }
+
+    fn phase_of(&self, symbol: SymbolId) -> TradingPhase {
+        self.phases.lock().unwrap().get(&symbol).copied().unwrap_or_default()
+    }
+
+    /// Whether orders on `symbol` should rest without matching
+    pub(super) fn matching_suspended(&self, symbol: SymbolId) -> bool {
+        self.paused_symbols.lock().unwrap().contains(&symbol)
+            || self.halts.lock().unwrap().contains_key(&symbol)
+            || self.phase_of(symbol) != TradingPhase::Continuous
+    }
+
+    /// Record the resting time of every tracked order that has left `symbol`'s book
+    fn record_resting_times(&self, symbol: SymbolId, book: &OrderBook) {
+        let mut resting_since = self.resting_since.lock().unwrap();
+        let Some(orders) = resting_since.get_mut(&symbol) else {
+            return;
+        };
+
+        let now = self.clock.now();
+        let mut rested = Vec::new();
+        orders.retain(|&order_id, since| {
+            let resting = book.get_order(order_id).is_some();
+            if !resting {
+                rested.push((now - *since).num_microseconds().unwrap_or(i64::MAX).max(0) as u64);
+            }
+            resting
+        });
+        drop(resting_since);
+
+        if rested.is_empty() {
+            return;
+        }
+        let mut metrics = self.metrics.lock().unwrap();
+        let histogram = metrics.resting_time.entry(book.symbol().to_string()).or_default();
+        for micros in rested {
+            histogram.record(micros);
+        }
+    }
+
+    fn process_phase_change(&self, symbol: SymbolId, phase: TradingPhase) {
+        let name = self.symbols.name(symbol);
+        let current = self.phase_of(symbol);
+        let auction = match (current, phase) {
+            (TradingPhase::Continuous | TradingPhase::Closed, TradingPhase::PreOpen)
+            | (TradingPhase::Continuous, TradingPhase::PreClose) => None,
+            (TradingPhase::PreOpen, TradingPhase::Continuous) => Some(OrderType::MarketOnOpen),
+            (TradingPhase::PreClose, TradingPhase::Closed) => Some(OrderType::MarketOnClose),
+            _ => {
+                warn!("Invalid phase transition for {}: {:?} -> {:?}", name, current, phase);
+                return;
+            }
+        };
+
+        let suspended = self.paused_symbols.lock().unwrap().contains(&symbol) || self.halts.lock().unwrap().contains_key(&symbol);
+
+        self.phases.lock().unwrap().insert(symbol, phase);
+        info!("Trading phase for {}: {:?} -> {:?}", name, current, phase);
+
+        let Some(auction) = auction else {
+            return;
+        };
+        if suspended {
+            warn!("Auction for {} skipped: matching is paused or trading is halted", name);
+            self.reject_auction_orders(symbol, auction, "Auction skipped while matching is suspended");
+            return;
+        }
+
+        let Some(result) = self.order_books.with(symbol, |book| {
+            let result = book.uncross(auction);
+            self.order_events.fills(&result.trades, self.stamp());
+            self.settle_book(symbol, book);
+            result
+        }) else {
+            return;
+        };
+
+        info!(
+            "Auction uncross for {}: {} at {:?}",
+            name, result.volume, result.price
+        );
+        self.record_trades(symbol, result.trades);
+        if result.price.is_none() {
+            self.reject_auction_orders(symbol, auction, "Auction found no price to execute at");
+        }
+    }
+
+    /// Reject the `auction` orders queued on `symbol`, as their auction did not take place
+    fn reject_auction_orders(&self, symbol: SymbolId, auction: OrderType, reason: &str) {
+        let rejected = self
+            .order_books
+            .with(symbol, |book| book.reject_auction_orders(auction))
+            .unwrap_or_default();
+        for mut order in rejected {
+            order_event!(self, "Auction order rejected: {:?}", order.id);
+            self.reject(&mut order, reason);
+        }
+    }
+
+    /// Bookkeeping after `book` changed: act on price limit breaches, count orders the
+    /// book cancelled on its own (e.g. through self-trade prevention) and update quoting metrics
+    fn settle_book(&self, symbol: SymbolId, book: &mut OrderBook) {
+        // Settled right after the change, so market data lag counts from here
+        let changed = Instant::now();
+        self.quotes.lock().unwrap().observe(book, self.clock.now());
+
+        let bbo = book.bbo();
+        let mut feeds = self.bbo_feeds.lock().unwrap();
+        if let Some(feed) = feeds.get(&symbol) {
+            if feed.is_closed() {
+                feeds.remove(&symbol);
+            } else {
+                feed.send_if_modified(|current| std::mem::replace(current, bbo) != bbo);
+            }
+        }
+        drop(feeds);
+
+        let previous = self.last_bbo.lock().unwrap().insert(symbol, bbo);
+        if previous.unwrap_or_default() != bbo && self.bbo_updates.wants(book.symbol()) {
+            let stamp = self.stamp();
+            let update = BboUpdate {
+                symbol: book.symbol().to_string(),
+                bbo,
+                timestamp: stamp.timestamp,
+                sequence: stamp.sequence,
+            };
+            self.bbo_updates.publish(book.symbol(), update, changed);
+        }
+
+        let changes = book.take_level_changes();
+        if !changes.is_empty() {
+            // Numbered whether or not anyone listens, so late subscribers see the same sequence
+            let mut sequences = self.level_sequences.lock().unwrap();
+            let sequence = sequences.entry(symbol).or_default();
+            *sequence += 1;
+            let sequence = *sequence;
+            drop(sequences);
+
+            if self.level_updates.wants(book.symbol()) {
+                let update = LevelUpdate {
+                    symbol: book.symbol().to_string(),
+                    changes,
+                    timestamp: self.clock.now(),
+                    sequence,
+                };
+                self.level_updates.publish(book.symbol(), update, changed);
+            }
+        }
+
+        let changes = book.take_order_changes();
+        if !changes.is_empty() && self.order_updates.wants(book.symbol()) {
+            let stamp = self.stamp();
+            let update = OrderUpdate {
+                symbol: book.symbol().to_string(),
+                changes,
+                timestamp: stamp.timestamp,
+                sequence: stamp.sequence,
+            };
+            self.order_updates.publish(book.symbol(), update, changed);
+        }
+
+        self.record_resting_times(symbol, book);
+
+        match book.take_limit_breach() {
+            Some(LimitBreachAction::Reject) => info!("Trade outside price limits prevented on {}", book.symbol()),
+            Some(LimitBreachAction::Halt) => {
+                warn!("Price limit breached on {}: trading halted", book.symbol());
+                self.halts.lock().unwrap().insert(symbol, HaltPolicy::Reject);
+            }
+            Some(LimitBreachAction::Auction) => {
+                warn!("Price limit breached on {}: entering call auction", book.symbol());
+                self.phases.lock().unwrap().insert(symbol, TradingPhase::PreOpen);
+            }
+            None => {}
+        }
+
+        let removed = book.drain_cancelled();
+        if removed.is_empty() {
+            return;
+        }
+
+        let (rejected, cancelled): (Vec<Order>, Vec<Order>) =
+            removed.into_iter().partition(|order| order.status == OrderStatus::Rejected);
+        let stamp = self.stamp();
+        for order in &rejected {
+            order_event!(self, "Order rejected by the book: {:?}", order.id);
+            self.order_events.rejected(order, "Rejected by the book", stamp);
+        }
+        for order in &cancelled {
+            order_event!(self, "Order cancelled by the book: {:?}", order.id);
+            self.order_events.cancelled(order, stamp);
+        }
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.rejected_orders += rejected.len() as u64;
+        metrics.cancelled_orders += cancelled.len() as u64;
+    }
+
+    /// Stamp trades with the engine clock, update trade metrics and forward them to the trade channel
+    fn record_trades(&self, symbol: SymbolId, mut trades: Vec<Trade>) {
+        if trades.is_empty() {
+            return;
+        }
+
+        let now = self.clock.now();
+        let sequence = self.applying.load(Ordering::Relaxed);
+        for trade in &mut trades {
+            trade.timestamp = now;
+            trade.clock_source = self.clock.source();
+            trade.sequence = sequence;
+        }
+        self.print_trades(symbol, &trades);
+        self.order_events.executions(&trades);
+
+        let mut positions = self.positions.lock().unwrap();
+        let mut reference_prices = self.reference_prices.lock().unwrap();
+        let mut daily_stats = self.daily_stats.lock().unwrap();
+        for trade in &trades {
+            positions.apply_trade(trade);
+            reference_prices.record_trade(trade);
+            daily_stats.record_trade(trade);
+        }
+        drop(daily_stats);
+        drop(reference_prices);
+        drop(positions);
+
+        if let Some(throttle) = &self.throttle {
+            for trade in &trades {
+                throttle.record_trade(&trade.buy_client_id);
+                throttle.record_trade(&trade.sell_client_id);
+            }
+        }
+        self.protect_market_makers(&trades, now);
+
+        let mut metrics_guard = self.metrics.lock().unwrap();
+        metrics_guard.total_trades += trades.len() as u64;
+        for trade in &trades {
+            metrics_guard.total_volume += trade.quantity as f64 * trade.price.to_f64();
+        }
+        metrics_guard.filled_orders += 1;
+        drop(metrics_guard);
+
+        for trade in trades {
+            self.deliver_trade(trade);
+        }
+    }
+
+    /// Every price level of `book` with the sequence of its last level update
+    fn snapshot_book(&self, symbol: SymbolId, book: &OrderBook) -> BookSnapshot {
+        BookSnapshot {
+            symbol: book.symbol().to_string(),
+            depth: book.get_depth(usize::MAX),
+            timestamp: self.clock.now(),
+            sequence: self.level_sequences.lock().unwrap().get(&symbol).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Send conflated book updates whose interval has passed; returns when the next is due
+    fn flush_conflated(&self) -> Option<Instant> {
+        [self.bbo_updates.flush(), self.level_updates.flush(), self.book_snapshots.flush()]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Publish a snapshot of every book someone subscribed to
+    fn publish_snapshots(&self) {
+        for (symbol, book) in self.order_books.all() {
+            let book = book.lock().unwrap();
+            if self.book_snapshots.wants(book.symbol()) {
+                let snapshot = self.snapshot_book(symbol, &book);
+                self.book_snapshots.publish(book.symbol(), snapshot, Instant::now());
+            }
+        }
+    }
+
+    /// Put `trades` on the public tape, numbered per symbol whether or not anyone listens
+    fn print_trades(&self, symbol: SymbolId, trades: &[Trade]) {
+        let changed = Instant::now();
+        let mut sequences = self.print_sequences.lock().unwrap();
+        let sequence = sequences.entry(symbol).or_default();
+        let first = *sequence + 1;
+        *sequence += trades.len() as u64;
+        drop(sequences);
+
+        let name = self.symbols.name(symbol);
+        if !self.trade_prints.wants(&name) {
+            return;
+        }
+        let block_size = self.instruments.get(&name).and_then(|instrument| instrument.block_size);
+        for (trade, sequence) in trades.iter().zip(first..) {
+            let print = TradePrint {
+                trade_id: trade.id,
+                symbol: trade.symbol.clone(),
+                price: trade.price,
+                quantity: trade.quantity,
+                aggressor: trade.aggressor,
+                conditions: TradeConditions {
+                    auction: trade.aggressor.is_none(),
+                    dark: false,
+                    block: block_size.is_some_and(|size| trade.quantity >= size),
+                },
+                timestamp: trade.timestamp,
+                sequence,
+            };
+            self.trade_prints.publish(&name, print, changed);
+        }
+    }
+
+    /// Clock time and command sequence number for an event of the command being applied
+    fn stamp(&self) -> EventStamp {
+        EventStamp {
+            timestamp: self.clock.now(),
+            sequence: self.applying.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Hand `trade` to the sink, dealing with a refusal as `trade_overflow` says
+    fn deliver_trade(&self, trade: Trade) {
+        let result = match self.trade_overflow {
+            TradeOverflow::Drop => self.trade_sink.try_send(trade),
+            TradeOverflow::Block => self.trade_sink.send(trade),
+            TradeOverflow::Buffer { capacity } => {
+                // Behind any trades already waiting, to keep delivery in order
+                let mut pending = self.pending_trades.lock().unwrap();
+                let result = match pending.is_empty() {
+                    true => self.trade_sink.try_send(trade),
+                    false => Err(SinkError::Full(Box::new(trade))),
+                };
+                match result {
+                    Err(SinkError::Full(trade)) if pending.len() < capacity => {
+                        pending.push_back(*trade);
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+        };
+        if let Err(e) = result {
+            self.unsent_trades.fetch_add(1, Ordering::Relaxed);
+            error!("Failed to send trade: {}", e);
+        }
+    }
+
+    fn has_pending_trades(&self) -> bool {
+        matches!(self.trade_overflow, TradeOverflow::Buffer { .. }) && !self.pending_trades.lock().unwrap().is_empty()
+    }
+
+    /// Offer buffered trades to the sink again, oldest first, until it refuses one
+    fn retry_pending_trades(&self) {
+        if !matches!(self.trade_overflow, TradeOverflow::Buffer { .. }) {
+            return;
+        }
+        let mut pending = self.pending_trades.lock().unwrap();
+        while let Some(trade) = pending.pop_front() {
+            match self.trade_sink.try_send(trade) {
+                Ok(()) => {}
+                Err(SinkError::Full(trade)) => {
+                    pending.push_front(*trade);
+                    return;
+                }
+                Err(e) => {
+                    self.unsent_trades.fetch_add(1, Ordering::Relaxed);
+                    error!("Failed to send trade: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Last delivery attempt for buffered trades as matching shuts down; the rest count as unsent
+    fn abandon_pending_trades(&self) {
+        self.retry_pending_trades();
+        let mut pending = self.pending_trades.lock().unwrap();
+        if !pending.is_empty() {
+            warn!("{} buffered trades not delivered at shutdown", pending.len());
+            self.unsent_trades.fetch_add(pending.len() as u64, Ordering::Relaxed);
+            pending.clear();
+        }
+    }
+
+    /// Cancel the orders still held by speed bumps as matching shuts down
+    fn cancel_deferred(&self) -> u64 {
+        let deferred: Vec<_> = self.deferred.lock().unwrap().drain().collect();
+        let mut cancelled = 0;
+        for (_, mut order) in deferred {
+            order.status = OrderStatus::Cancelled;
+            order_event!(self, "Order held by speed bump cancelled on shutdown: {:?}", order.id);
+            self.order_events.cancelled(&order, self.stamp());
+            cancelled += 1;
+        }
+        self.metrics.lock().unwrap().cancelled_orders += cancelled;
+        cancelled
+    }
+
+    fn process_stage(&self, symbol: SymbolId, mut order: Order) {
+        if !self.validate_order(symbol, &mut order) {
+            return;
+        }
+        order_event!(self, "Order staged: {:?}", order.id);
+        self.staged.lock().unwrap().insert(order.id, (symbol, order));
+    }
+
+    /// Take a staged order for processing if `client_id` owns it
+    fn process_release(&self, order_id: Uuid, client_id: &str) -> Option<(SymbolId, Order)> {
+        let mut staged = self.staged.lock().unwrap();
+        match staged.get(&order_id) {
+            Some((_, order)) if order.client_id == client_id => {
+                info!("Staged order released: {:?}", order_id);
+                staged.remove(&order_id)
+            }
+            Some(_) => {
+                warn!("Release of {:?} by non-owner {}", order_id, client_id);
+                None
+            }
+            None => {
+                warn!("Staged order not found: {:?}", order_id);
+                None
+            }
+        }
+    }
+
+    fn process_cancel(&self, order_id: Uuid, symbol: SymbolId) {
+        debug!("Cancelling order: {:?}", order_id);
+
+        let staged = self.staged.lock().unwrap().remove(&order_id);
+        if let Some((_, order)) = staged {
+            self.record_cancel(&order.client_id);
+            self.metrics.lock().unwrap().cancelled_orders += 1;
+            info!("Staged order cancelled: {:?}", order_id);
+            self.order_events.cancelled(&order, self.stamp());
+            return;
+        }
+
+        let held = self.deferred.lock().unwrap().remove(order_id);
+        if let Some((_, mut order)) = held {
+            self.record_cancel(&order.client_id);
+            order.status = OrderStatus::Cancelled;
+            self.metrics.lock().unwrap().cancelled_orders += 1;
+            order_event!(self, "Order held by speed bump cancelled: {:?}", order_id);
+            self.order_events.cancelled(&order, self.stamp());
+            return;
+        }
+
+        if let Some(book) = self.order_books.get(symbol) {
+            let mut book = book.lock().unwrap();
+            if let Some(cancelled_order) = book.cancel_order(order_id) {
+                self.record_cancel(&cancelled_order.client_id);
+                self.settle_book(symbol, &mut book);
+                self.metrics.lock().unwrap().cancelled_orders += 1;
+                order_event!(self, "Order cancelled: {:?}", order_id);
+                self.order_events.cancelled(&cancelled_order, self.stamp());
+            } else {
+                warn!("Order not found for cancellation: {:?}", order_id);
+            }
+        } else {
+            warn!("Symbol not found: {}", self.symbols.name(symbol));
+        }
+    }
+
+    fn process_purge(&self, symbol: SymbolId, side: Side, low: Price, high: Price) {
+        let Some(cancelled) = self.order_books.with(symbol, |book| {
+            let cancelled = book.cancel_range(side, low, high);
+            self.settle_book(symbol, book);
+            cancelled
+        }) else {
+            warn!("Symbol not found: {}", self.symbols.name(symbol));
+            return;
+        };
+
+        let stamp = self.stamp();
+        for order in &cancelled {
+            order_event!(self, "Order cancelled by purge: {:?}", order.id);
+            self.order_events.cancelled(order, stamp);
+        }
+        self.metrics.lock().unwrap().cancelled_orders += cancelled.len() as u64;
+        info!("Purged {} {} orders on {} between {} and {}", cancelled.len(), side, self.symbols.name(symbol), low, high);
+    }
+
+    fn process_mass_cancel(&self, scope: &KillScope) {
+        let cancelled = self.cancel_orders(|order| scope.covers(&order.client_id));
+        warn!("Mass cancel for {:?} cancelled {} orders", scope, cancelled);
+    }
+
+    /// Cancel the staged, held and resting orders `covers` picks; returns how many
+    fn cancel_orders(&self, covers: impl Fn(&Order) -> bool) -> usize {
+        let mut cancelled = Vec::new();
+        self.staged.lock().unwrap().retain(|_, (_, order)| {
+            let covered = covers(order);
+            if covered {
+                order.status = OrderStatus::Cancelled;
+                cancelled.push(order.clone());
+            }
+            !covered
+        });
+        let held = self.deferred.lock().unwrap().drain_where(&covers);
+        cancelled.extend(held.into_iter().map(|(_, mut order)| {
+            order.status = OrderStatus::Cancelled;
+            order
+        }));
+        for (symbol, book) in self.order_books.all() {
+            let mut book = book.lock().unwrap();
+            let orders = book.cancel_where(&covers);
+            if orders.is_empty() {
+                continue;
+            }
+            self.settle_book(symbol, &mut book);
+            cancelled.extend(orders);
+        }
+
+        let stamp = self.stamp();
+        for order in &cancelled {
+            order_event!(self, "Order cancelled by mass cancel: {:?}", order.id);
+            self.order_events.cancelled(order, stamp);
+        }
+        self.metrics.lock().unwrap().cancelled_orders += cancelled.len() as u64;
+        cancelled.len()
+    }
+
+    /// Count the trades' fills towards market maker protection, pulling a client's orders
+    /// from a group whose limits it reaches
+    fn protect_market_makers(&self, trades: &[Trade], now: DateTime<Utc>) {
+        let Some(protection) = &self.protection else {
+            return;
+        };
+        for trade in trades {
+            for (client_id, side) in [(&trade.buy_client_id, Side::Buy), (&trade.sell_client_id, Side::Sell)] {
+                for (group, breach) in protection.record_fill(client_id, &trade.symbol, side, trade.quantity, now) {
+                    let cancelled = self.cancel_orders(|order| order.client_id == *client_id && group.symbols.contains(&order.symbol));
+                    warn!(
+                        "Market maker protection for {} in {} tripped ({:?}); cancelled {} orders",
+                        client_id, group.name, breach, cancelled
+                    );
+                    protection.publish(ProtectionEvent {
+                        client_id: client_id.clone(),
+                        group: group.name.clone(),
+                        breach,
+                        cancelled,
+                        timestamp: now,
+                    });
+                }
+            }
+        }
+    }
+
+    fn process_expirations(&self) {
+        let now = self.clock.now();
+        let mut expired_count = 0;
+
+        for (symbol, book) in self.order_books.all() {
+            let mut book = book.lock().unwrap();
+            let expired = book.expire_orders(now);
+            if expired.is_empty() {
+                continue;
+            }
+            for order in expired {
+                order_event!(self, "Order expired: {:?}", order.id);
+                self.order_events.expired(&order, self.stamp());
+                expired_count += 1;
+            }
+            self.settle_book(symbol, &mut book);
+        }
+
+        if expired_count > 0 {
+            self.metrics.lock().unwrap().expired_orders += expired_count;
+        }
+    }
+
+    /// Resume matching on a paused symbol and uncross whatever accumulated meanwhile
+    fn process_resume(&self, symbol: SymbolId) {
+        if !self.paused_symbols.lock().unwrap().remove(&symbol) {
+            warn!("Matching was not paused: {}", self.symbols.name(symbol));
+            return;
+        }
+        info!("Matching resumed: {}", self.symbols.name(symbol));
+        self.uncross_resumed(symbol);
+    }
+
+    /// Lift a trading halt and uncross whatever queued meanwhile
+    fn process_resume_trading(&self, symbol: SymbolId) {
+        if self.halts.lock().unwrap().remove(&symbol).is_none() {
+            warn!("Trading was not halted: {}", self.symbols.name(symbol));
+            return;
+        }
+        info!("Trading resumed: {}", self.symbols.name(symbol));
+        self.uncross_resumed(symbol);
+    }
+
+    /// Match a book that crossed while matching was suspended, unless it still is
+    fn uncross_resumed(&self, symbol: SymbolId) {
+        if self.matching_suspended(symbol) {
+            info!("Uncross of {} deferred: matching still suspended", self.symbols.name(symbol));
+            return;
+        }
+
+        let trades = self
+            .order_books
+            .with(symbol, |book| {
+                let trades = book.match_orders();
+                self.order_events.fills(&trades, self.stamp());
+                self.settle_book(symbol, book);
+                trades
+            })
+            .unwrap_or_default();
+
+        info!("Uncrossed {}: {} trades", self.symbols.name(symbol), trades.len());
+        self.record_trades(symbol, trades);
+    }
+
+    fn process_modify(
+        &self,
+        order_id: Uuid,
+        symbol: SymbolId,
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+    ) {
+        debug!("Modifying order: {:?}", order_id);
+
+        if self.amend_deferred(order_id, new_price, new_quantity) {
+            return;
+        }
+
+        let Some(book) = self.order_books.get(symbol) else {
+            warn!("Symbol not found: {}", self.symbols.name(symbol));
+            return;
+        };
+
+        // Checked with the book unlocked, as risk checks read the book themselves
+        let current = {
+            let book = book.lock().unwrap();
+            book.get_order(order_id).cloned().map(|order| (order, book.best_bid()))
+        };
+        let mut new_quantity = new_quantity;
+        if let Some((order, best_bid)) = current {
+            if let Some(switch) = self.kill_switches.blocking(&order.client_id) {
+                warn!("Amendment of {:?} refused by kill switch for {:?}", order_id, switch.scope);
+                self.refuse_amendment(&order, format!("Kill switch engaged by {}", switch.triggered_by));
+                return;
+            }
+            if !self.admit_message(&order.client_id, MessageKind::Amend) {
+                warn!("Amendment of {:?} refused: message rate limit exceeded", order_id);
+                self.refuse_amendment(&order, "Message rate limit exceeded");
+                return;
+            }
+            if !self.short_sale_allowed(symbol, &order, new_price.or(order.price), best_bid) {
+                warn!("Amendment of {:?} refused: short sale at or below the best bid under restriction", order_id);
+                self.refuse_amendment(&order, "Short sale must be priced above the best bid while restricted");
+                return;
+            }
+            match self.check_amendment_risk(&order, new_price, new_quantity, (0, 0)) {
+                Ok(quantity) => new_quantity = quantity,
+                Err(reason) => {
+                    self.refuse_amendment(&order, reason);
+                    return;
+                }
+            }
+        }
+
+        let mut book = book.lock().unwrap();
+        match book.replace_order(order_id, new_price, new_quantity) {
+            Some(order) if order.status == OrderStatus::Cancelled => {
+                self.settle_book(symbol, &mut book);
+                self.metrics.lock().unwrap().cancelled_orders += 1;
+                order_event!(self, "Order cancelled by amendment: {:?}", order_id);
+                self.order_events.cancelled(&order, self.stamp());
+            }
+            Some(order) if self.matching_suspended(symbol) => {
+                self.order_events.amended(&order);
+                self.settle_book(symbol, &mut book);
+                order_event!(self, "Order modified while matching is paused: {:?}", order_id);
+            }
+            Some(order) => {
+                order_event!(self, "Order modified: {:?}", order_id);
+                self.order_events.amended(&order);
+                // A price change may have made the book crossable
+                let trades = book.match_orders();
+                self.order_events.fills(&trades, self.stamp());
+                self.settle_book(symbol, &mut book);
+                drop(book);
+                self.record_trades(symbol, trades);
+            }
+            None => warn!("Order not found for modification: {:?}", order_id),
+        }
+    }
+
+    /// Amend an order held by a speed bump, holding it again if still aggressive. Returns false
+    /// if no such order is held.
+    fn amend_deferred(&self, order_id: Uuid, new_price: Option<Price>, new_quantity: Option<u64>) -> bool {
+        let Some(order) = self.deferred.lock().unwrap().get(order_id).cloned() else {
+            return false;
+        };
+        // Refused amendments leave the order held as it was
+        if !self.admit_message(&order.client_id, MessageKind::Amend) {
+            warn!("Amendment of {:?} refused: message rate limit exceeded", order_id);
+            self.refuse_amendment(&order, "Message rate limit exceeded");
+            return true;
+        }
+
+        // Not validated until released, so checked then
+        let Some((symbol, mut order)) = self.deferred.lock().unwrap().remove(order_id) else {
+            return false;
+        };
+        order.price = new_price.or(order.price);
+        order.quantity = new_quantity.unwrap_or(order.quantity);
+        order_event!(self, "Order held by speed bump modified: {:?}", order_id);
+        match self.speed_bump_for(symbol, &order) {
+            Some(bump) => self
+                .deferred
+                .lock()
+                .unwrap()
+                .defer(self.applying.load(Ordering::Relaxed), symbol, order, bump),
+            None => self.execute_order(symbol, order),
+        }
+        true
+    }
+
+    /// Refuse the amendment of each of `orders`, a batch amended together, with `reason`
+    fn refuse_amendments(&self, orders: &[Order], reason: impl Into<String>) {
+        let reason = reason.into();
+        for order in orders {
+            self.refuse_amendment(order, reason.clone());
+        }
+    }
+
+    fn process_bulk_modify(&self, symbol: SymbolId, mut amendments: Vec<Amendment>) {
+        let Some(book) = self.order_books.get(symbol) else {
+            warn!("Symbol not found: {}", self.symbols.name(symbol));
+            return;
+        };
+
+        // Checked with the book unlocked, as risk checks read the book themselves
+        let (orders, best_bid) = {
+            let book = book.lock().unwrap();
+            debug!("Modifying {} orders on {}", amendments.len(), book.symbol());
+            let orders: Vec<Order> = amendments
+                .iter()
+                .filter_map(|amendment| book.get_order(amendment.order_id).cloned())
+                .collect();
+            (orders, book.best_bid())
+        };
+        let amended = |amendment: &Amendment| orders.iter().find(|order| order.id == amendment.order_id);
+
+        if let Some(switch) = orders.iter().find_map(|order| self.kill_switches.blocking(&order.client_id)) {
+            warn!("Bulk amendment refused by kill switch for {:?}", switch.scope);
+            self.refuse_amendments(&orders, format!("Kill switch engaged by {}", switch.triggered_by));
+            return;
+        }
+
+        // One message, from the owner of the first order found
+        if let Some(order) = orders.first() {
+            if !self.admit_message(&order.client_id, MessageKind::Amend) {
+                warn!("Bulk amendment refused: message rate limit exceeded");
+                self.refuse_amendments(&orders, "Message rate limit exceeded");
+                return;
+            }
+        }
+        let short_sales_allowed = amendments.iter().all(|amendment| {
+            amended(amendment).is_none_or(|order| {
+                self.short_sale_allowed(symbol, order, amendment.new_price.or(order.price), best_bid)
+            })
+        });
+        if !short_sales_allowed {
+            warn!("Bulk amendment refused: short sale at or below the best bid under restriction");
+            self.refuse_amendments(&orders, "Short sale must be priced above the best bid while restricted");
+            return;
+        }
+
+        // Every amendment passes the risk checks, seeing the size the earlier ones add, or none apply
+        let mut pending: HashMap<&str, (u64, u64)> = HashMap::new();
+        for amendment in &mut amendments {
+            let Some(order) = amended(amendment) else {
+                continue;
+            };
+            let client_pending = pending.get(order.client_id.as_str()).copied().unwrap_or_default();
+            match self.check_amendment_risk(order, amendment.new_price, amendment.new_quantity, client_pending) {
+                Ok(quantity) => amendment.new_quantity = quantity,
+                Err(reason) => {
+                    warn!("Bulk amendment refused: {}", reason);
+                    self.refuse_amendments(&orders, reason);
+                    return;
+                }
+            }
+            let added = amendment.new_quantity.unwrap_or(order.quantity).saturating_sub(order.quantity);
+            let client_pending = pending.entry(&order.client_id).or_default();
+            match order.side {
+                Side::Buy => client_pending.0 += added,
+                Side::Sell => client_pending.1 += added,
+            }
+        }
+
+        // Apply every amendment before matching so no half-updated quote set can trade
+        let mut book = book.lock().unwrap();
+        let mut cancelled = 0;
+        for amendment in amendments {
+            match book.replace_order(amendment.order_id, amendment.new_price, amendment.new_quantity) {
+                Some(order) if order.status == OrderStatus::Cancelled => {
+                    cancelled += 1;
+                    order_event!(self, "Order cancelled by amendment: {:?}", amendment.order_id);
+                    self.order_events.cancelled(&order, self.stamp());
+                }
+                Some(order) => {
+                    order_event!(self, "Order modified: {:?}", amendment.order_id);
+                    self.order_events.amended(&order);
+                }
+                None => warn!("Order not found for modification: {:?}", amendment.order_id),
+            }
+        }
+        self.metrics.lock().unwrap().cancelled_orders += cancelled;
+
+        let trades = if self.matching_suspended(symbol) {
+            Vec::new()
+        } else {
+            book.match_orders()
+        };
+        self.order_events.fills(&trades, self.stamp());
+        self.settle_book(symbol, &mut book);
+        drop(book);
+        self.record_trades(symbol, trades);
+    }
+}
+
+impl Drop for ExecutionEngine {
+    fn drop(&mut self) {
+        // Ends the export tasks; the matching thread exits once the command sender is dropped
+        self.running.send_replace(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+    use crossbeam::channel::unbounded;
+
+    #[tokio::test]
+    async fn test_queue_backpressure() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender).with_queue_capacity(1);
+        engine.start().await;
+        let limit = |price: f64| Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, price, "client1".to_string());
+        engine.submit_order(limit(100.0)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        assert_eq!(engine.queued_orders(), 0);
+
+        // Stall the matching thread on the next order, before it gives up the order's slot
+        let guard = engine.state.latency.lock().unwrap();
+        engine.try_submit_order(limit(101.0)).unwrap();
+        assert_eq!(engine.queued_orders(), 1);
+
+        let order = limit(102.0);
+        let order_id = order.id;
+        match engine.try_submit_order(order) {
+            Err(EngineError::QueueFull(order)) => assert_eq!(order.id, order_id),
+            other => panic!("unexpected {:?}", other),
+        }
+        drop(guard);
+
+        // Waits for the slot instead of failing
+        engine.submit_order(limit(102.0)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        assert_eq!(engine.queued_orders(), 0);
+        assert_eq!(engine.get_metrics().total_orders, 3);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_priority_lane() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let events = engine.subscribe_order_events(OrderEventConfig::default());
+        engine.start().await;
+        let limit = |side, price: f64| Order::new_limit("BTCUSD".to_string(), side, 10, price, "client1".to_string());
+        let resting = limit(Side::Sell, 105.0);
+        let resting_id = resting.id;
+        engine.submit_order(resting).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+
+        // Stall the matching thread, then queue a burst of orders ahead of a cancel
+        let burst: Vec<_> = (0..50).map(|_| limit(Side::Buy, 91.0)).collect();
+        let first_id = burst[0].id;
+        {
+            let _guard = engine.state.latency.lock().unwrap();
+            engine.try_submit_order(limit(Side::Buy, 90.0)).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            for order in burst {
+                engine.try_submit_order(order).unwrap();
+            }
+            // What `cancel_order` sends, without awaiting under the lock
+            let symbol = engine.state.symbols.intern("BTCUSD");
+            engine.send_on(&engine.cancel_sender, EngineCommand::CancelOrder(resting_id, symbol)).unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let events: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                SubscriberEvent::Update(event) => Some((event.order_id, event.kind)),
+                SubscriberEvent::DataLoss { .. } => None,
+            })
+            .collect();
+        let position = |order_id, kind: OrderEventKind| events.iter().position(|event| event.0 == order_id && event.1 == kind);
+        let cancelled = position(resting_id, OrderEventKind::Cancelled).unwrap();
+        assert!(cancelled < position(first_id, OrderEventKind::Accepted).unwrap());
+        assert!(engine.state.queued_ids.lock().unwrap().is_empty());
+
+        // A cancel for a queued order waits for it instead of overtaking it
+        engine.cancel_order(first_id, "BTCUSD".to_string()).await.unwrap();
+        let order = limit(Side::Buy, 92.0);
+        let order_id = order.id;
+        let mut reports = engine.submit_order_with_reports(order).await.unwrap();
+        engine.cancel_order(order_id, "BTCUSD".to_string()).await.unwrap();
+        assert_eq!(reports.recv().await.unwrap().kind, OrderEventKind::Accepted);
+        assert_eq!(reports.recv().await.unwrap().kind, OrderEventKind::Cancelled);
+        assert_eq!(engine.get_metrics().cancelled_orders, 3);
+
+        engine.stop().await;
+    }
+}
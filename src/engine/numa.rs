@@ -0,0 +1,100 @@
+use std::io;
+
+/// Keep the calling thread, and the memory it allocates from now on, on NUMA `node`.
+///
+/// The thread is restricted to the node's CPUs unless `pin_cpus` is false (it is already
+/// pinned to a core), and its allocations prefer the node's memory. Book storage is first
+/// written by the matching thread, so its pages land on the same node.
+#[cfg(target_os = "linux")]
+pub(super) fn bind_to_node(node: usize, pin_cpus: bool) -> io::Result<()> {
+    if pin_cpus {
+        let cpus = node_cpus(node)?;
+        // SAFETY: `set` is a plain bit mask, zeroed before use and only touched through
+        // libc's own helpers; pid 0 targets the calling thread
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for cpu in cpus.into_iter().filter(|&cpu| cpu < libc::CPU_SETSIZE as usize) {
+                libc::CPU_SET(cpu, &mut set);
+            }
+            if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+
+    const MASK_BITS: usize = u64::BITS as usize;
+    if node >= MASK_BITS {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "NUMA node index out of range"));
+    }
+    let mask: u64 = 1 << node;
+    // SAFETY: the kernel reads `MASK_BITS` bits from `mask`, which outlives the call
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            libc::MPOL_PREFERRED,
+            &mask as *const u64,
+            MASK_BITS as libc::c_ulong,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn bind_to_node(_node: usize, _pin_cpus: bool) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "NUMA placement is only supported on Linux"))
+}
+
+/// CPUs of NUMA `node`, as listed by sysfs
+#[cfg(target_os = "linux")]
+fn node_cpus(node: usize) -> io::Result<Vec<usize>> {
+    let list = std::fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node))?;
+    let cpus = parse_cpu_list(&list)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unreadable CPU list {:?}", list)))?;
+    if cpus.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "NUMA node has no CPUs"));
+    }
+    Ok(cpus)
+}
+
+/// Parse a kernel CPU list such as `0-3,8-11,16`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8-9,16\n"), Some(vec![0, 1, 2, 3, 8, 9, 16]));
+        assert_eq!(parse_cpu_list("\n"), Some(vec![]));
+        assert_eq!(parse_cpu_list("0-x"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_bind_to_node() {
+        // Kernels built without NUMA support have no node directory
+        if !std::path::Path::new("/sys/devices/system/node/node0").exists() {
+            return;
+        }
+        std::thread::spawn(|| {
+            assert!(bind_to_node(0, true).is_ok());
+            assert!(bind_to_node(usize::MAX, false).is_err());
+        })
+        .join()
+        .unwrap();
+    }
+}
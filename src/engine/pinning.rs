@@ -0,0 +1,98 @@
+use super::EngineConfig;
+#[cfg(feature = "numa")]
+use super::numa::bind_to_node;
+use std::io;
+use tracing::{info, warn};
+
+/// Pin the calling thread and raise its priority as `config` asks. A setting the platform
+/// refuses is logged and skipped: the thread runs either way, just without the isolation.
+pub(super) fn configure_current_thread(config: &EngineConfig) {
+    // Node first, so a pinned core narrows the node's CPU set rather than being widened by it
+    if let Some(node) = config.numa_node {
+        match bind_to_node(node, config.core_affinity.is_none()) {
+            Ok(()) => info!("Matching thread placed on NUMA node {}", node),
+            Err(e) => warn!("Could not place matching thread on NUMA node {}: {}", node, e),
+        }
+    }
+    if let Some(core) = config.core_affinity {
+        match pin_to_core(core) {
+            Ok(()) => info!("Matching thread pinned to core {}", core),
+            Err(e) => warn!("Could not pin matching thread to core {}: {}", core, e),
+        }
+    }
+    if let Some(priority) = config.realtime_priority {
+        match set_realtime_priority(priority) {
+            Ok(()) => info!("Matching thread running at realtime priority {}", priority),
+            Err(e) => warn!("Could not set realtime priority {}: {}", priority, e),
+        }
+    }
+}
+
+#[cfg(not(feature = "numa"))]
+fn bind_to_node(_node: usize, _pin_cpus: bool) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "NUMA placement needs the `numa` feature"))
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "core index out of range"));
+    }
+    // SAFETY: `set` is a plain bit mask, zeroed before use and only touched through libc's
+    // own helpers; pid 0 targets the calling thread
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "core pinning is only supported on Linux"))
+}
+
+/// Move the calling thread to the `SCHED_FIFO` realtime class. Usually needs root or
+/// `CAP_SYS_NICE`.
+#[cfg(unix)]
+fn set_realtime_priority(priority: i32) -> io::Result<()> {
+    // SAFETY: `param` is zeroed (some platforms have padding fields) before the priority is
+    // set, and `pthread_self` is always a valid thread handle
+    let result = unsafe {
+        let mut param: libc::sched_param = std::mem::zeroed();
+        param.sched_priority = priority;
+        libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param)
+    };
+    match result {
+        0 => Ok(()),
+        error => Err(io::Error::from_raw_os_error(error)),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_realtime_priority(_priority: i32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "realtime priority is only supported on unix"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_to_core() {
+        std::thread::spawn(|| {
+            assert!(pin_to_core(0).is_ok());
+            assert_eq!(
+                pin_to_core(usize::MAX).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+            // Out of the FIFO range on every platform
+            assert!(set_realtime_priority(1_000).is_err());
+        })
+        .join()
+        .unwrap();
+    }
+}
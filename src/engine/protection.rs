@@ -0,0 +1,220 @@
+use super::publisher::Publisher;
+use crate::market_data::{SlowConsumerPolicy, SubscriberEvent};
+use crate::types::Side;
+use chrono::{DateTime, Utc};
+use crossbeam::channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Protection events buffered per subscriber; older ones are dropped beyond that
+const PROTECTION_EVENT_BUFFER: usize = 1024;
+
+/// How much a client may trade in a group within a rolling window before its orders there
+/// are pulled; each limit trips once reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectionLimits {
+    pub window: Duration,
+    /// Fills
+    pub fills: Option<u64>,
+    /// Quantity traded, bought and sold alike
+    pub contracts: Option<u64>,
+    /// Quantity bought minus quantity sold, either way
+    pub delta: Option<u64>,
+}
+
+impl ProtectionLimits {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            fills: None,
+            contracts: None,
+            delta: None,
+        }
+    }
+
+    pub fn with_fills(mut self, fills: u64) -> Self {
+        self.fills = Some(fills);
+        self
+    }
+
+    pub fn with_contracts(mut self, contracts: u64) -> Self {
+        self.contracts = Some(contracts);
+        self
+    }
+
+    pub fn with_delta(mut self, delta: u64) -> Self {
+        self.delta = Some(delta);
+        self
+    }
+}
+
+/// Symbols whose fills count together towards market maker protection limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectionGroup {
+    pub name: String,
+    pub symbols: HashSet<String>,
+    pub limits: ProtectionLimits,
+}
+
+impl ProtectionGroup {
+    pub fn new(name: impl Into<String>, symbols: impl IntoIterator<Item = impl Into<String>>, limits: ProtectionLimits) -> Self {
+        Self {
+            name: name.into(),
+            symbols: symbols.into_iter().map(Into::into).collect(),
+            limits,
+        }
+    }
+}
+
+/// The limit a client reached, with what it traded in the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectionBreach {
+    Fills { fills: u64, limit: u64 },
+    Contracts { contracts: u64, limit: u64 },
+    Delta { delta: i64, limit: u64 },
+}
+
+/// A client's protection tripping in a group
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectionEvent {
+    pub client_id: String,
+    pub group: String,
+    pub breach: ProtectionBreach,
+    /// The client's orders in the group cancelled as a result
+    pub cancelled: usize,
+    /// Engine clock time
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A fill counted towards a group's limits
+struct Fill {
+    time: DateTime<Utc>,
+    quantity: u64,
+    delta: i64,
+}
+
+/// Per-client fills in each protection group, and subscribers to protection events
+pub(super) struct MarketMakerProtection {
+    groups: Vec<ProtectionGroup>,
+    fills: Mutex<HashMap<(String, usize), VecDeque<Fill>>>, // (client, group index) -> fills in the window, oldest first
+    subscribers: Mutex<Vec<Publisher<ProtectionEvent>>>,
+}
+
+impl MarketMakerProtection {
+    pub(super) fn new(groups: Vec<ProtectionGroup>) -> Self {
+        Self {
+            groups,
+            fills: Mutex::new(HashMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(super) fn subscribe(&self) -> Receiver<SubscriberEvent<ProtectionEvent>> {
+        let (publisher, receiver) = Publisher::new(PROTECTION_EVENT_BUFFER, SlowConsumerPolicy::DropOldest);
+        self.subscribers.lock().unwrap().push(publisher);
+        receiver
+    }
+
+    /// Count a fill of `client_id` in every group holding `symbol`; returns the groups whose
+    /// limits it reached, whose counts for the client start over
+    pub(super) fn record_fill(
+        &self,
+        client_id: &str,
+        symbol: &str,
+        side: Side,
+        quantity: u64,
+        now: DateTime<Utc>,
+    ) -> Vec<(&ProtectionGroup, ProtectionBreach)> {
+        let mut fills = self.fills.lock().unwrap();
+        let mut breaches = Vec::new();
+        for (index, group) in self.groups.iter().enumerate() {
+            if !group.symbols.contains(symbol) {
+                continue;
+            }
+
+            let window = fills.entry((client_id.to_string(), index)).or_default();
+            let cutoff = now - chrono::Duration::from_std(group.limits.window).unwrap_or(chrono::Duration::MAX);
+            while window.front().is_some_and(|fill| fill.time <= cutoff) {
+                window.pop_front();
+            }
+            let delta = match side {
+                Side::Buy => quantity as i64,
+                Side::Sell => -(quantity as i64),
+            };
+            window.push_back(Fill { time: now, quantity, delta });
+
+            if let Some(breach) = Self::breach(&group.limits, window) {
+                window.clear();
+                breaches.push((group, breach));
+            }
+        }
+        breaches
+    }
+
+    /// The first of the limits the fills in the window reach
+    fn breach(limits: &ProtectionLimits, window: &VecDeque<Fill>) -> Option<ProtectionBreach> {
+        let fills = window.len() as u64;
+        let contracts: u64 = window.iter().map(|fill| fill.quantity).sum();
+        let delta: i64 = window.iter().map(|fill| fill.delta).sum();
+        match *limits {
+            ProtectionLimits { fills: Some(limit), .. } if fills >= limit => Some(ProtectionBreach::Fills { fills, limit }),
+            ProtectionLimits { contracts: Some(limit), .. } if contracts >= limit => {
+                Some(ProtectionBreach::Contracts { contracts, limit })
+            }
+            ProtectionLimits { delta: Some(limit), .. } if delta.unsigned_abs() >= limit => Some(ProtectionBreach::Delta { delta, limit }),
+            _ => None,
+        }
+    }
+
+    pub(super) fn publish(&self, event: ProtectionEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.publish(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protection_limits() {
+        let limits = ProtectionLimits::new(Duration::from_secs(1)).with_fills(3).with_contracts(10).with_delta(6);
+        let protection = MarketMakerProtection::new(vec![ProtectionGroup::new("BTC", ["BTCUSD", "BTCEUR"], limits)]);
+        let start = Utc::now();
+        let at = |millis| start + chrono::Duration::milliseconds(millis);
+        let breaches = |client, symbol, side, quantity, millis| {
+            protection
+                .record_fill(client, symbol, side, quantity, at(millis))
+                .into_iter()
+                .map(|(group, breach)| (group.name.clone(), breach))
+                .collect::<Vec<_>>()
+        };
+
+        // Fills across the group's symbols count together, per client
+        assert!(breaches("client1", "BTCUSD", Side::Buy, 1, 0).is_empty());
+        assert!(breaches("client1", "BTCEUR", Side::Sell, 1, 100).is_empty());
+        assert!(breaches("client2", "BTCUSD", Side::Buy, 1, 100).is_empty());
+        assert!(breaches("client1", "ETHUSD", Side::Buy, 1, 100).is_empty());
+        assert_eq!(
+            breaches("client1", "BTCUSD", Side::Buy, 1, 200),
+            vec![("BTC".to_string(), ProtectionBreach::Fills { fills: 3, limit: 3 })]
+        );
+
+        // Counts start over after a breach, and fills leave the window
+        assert!(breaches("client1", "BTCUSD", Side::Buy, 4, 300).is_empty());
+        assert_eq!(
+            breaches("client1", "BTCUSD", Side::Buy, 2, 400),
+            vec![("BTC".to_string(), ProtectionBreach::Delta { delta: 6, limit: 6 })]
+        );
+        assert!(breaches("client2", "BTCUSD", Side::Sell, 5, 1200).is_empty());
+        assert_eq!(
+            breaches("client2", "BTCUSD", Side::Buy, 5, 1300),
+            vec![("BTC".to_string(), ProtectionBreach::Contracts { contracts: 10, limit: 10 })]
+        );
+    }
+}
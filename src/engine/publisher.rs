@@ -0,0 +1,126 @@
+use crate::market_data::{SlowConsumerPolicy, SubscriberEvent};
+use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Bounded feed to one subscriber that applies its slow-consumer policy when full
+pub(crate) struct Publisher<T> {
+    sender: Sender<SubscriberEvent<T>>,
+    // Publisher-side handle on the same buffer, used to evict buffered events
+    evictor: Receiver<SubscriberEvent<T>>,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+    /// Updates evicted by the policy so far
+    dropped: AtomicU64,
+}
+
+/// The subscriber dropped its receiver or was disconnected by policy
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Disconnected;
+
+impl<T> Publisher<T> {
+    /// Create a publisher and the subscriber's receiver.
+    ///
+    /// Capacity is at least 2 so a data-loss notice and the update after it always fit.
+    pub(crate) fn new(capacity: usize, policy: SlowConsumerPolicy) -> (Self, Receiver<SubscriberEvent<T>>) {
+        let capacity = capacity.max(2);
+        let (sender, receiver) = bounded(capacity);
+        let publisher = Self {
+            sender,
+            evictor: receiver.clone(),
+            capacity,
+            policy,
+            dropped: AtomicU64::new(0),
+        };
+        (publisher, receiver)
+    }
+
+    /// Events buffered and not yet taken by the subscriber
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.sender.len()
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Updates the slow-consumer policy has dropped so far
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn publish(&self, update: T) -> Result<(), Disconnected> {
+        let update = match self.sender.try_send(SubscriberEvent::Update(update)) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(_)) => return Err(Disconnected),
+            Err(TrySendError::Full(SubscriberEvent::Update(update))) => update,
+            Err(TrySendError::Full(SubscriberEvent::DataLoss { .. })) => unreachable!(),
+        };
+
+        // Keep room for the data-loss notice plus the update
+        let keep = match self.policy {
+            SlowConsumerPolicy::Disconnect => return Err(Disconnected),
+            SlowConsumerPolicy::Conflate => 0,
+            SlowConsumerPolicy::DropOldest => self.capacity - 2,
+        };
+
+        let (mut dropped, mut evicted) = (0, 0);
+        while self.sender.len() > keep {
+            match self.evictor.try_recv() {
+                Ok(SubscriberEvent::Update(_)) => evicted += 1,
+                Ok(SubscriberEvent::DataLoss { dropped: earlier }) => dropped += earlier,
+                Err(_) => break, // the subscriber drained it meanwhile
+            }
+        }
+        self.dropped.fetch_add(evicted, Ordering::Relaxed);
+        dropped += evicted;
+
+        if dropped > 0 {
+            self.send(SubscriberEvent::DataLoss { dropped })?;
+        }
+        self.send(SubscriberEvent::Update(update))
+    }
+
+    fn send(&self, event: SubscriberEvent<T>) -> Result<(), Disconnected> {
+        // Only the publisher adds to the buffer, so room made above is still there
+        self.sender.try_send(event).map_err(|_| Disconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_consumer_policies() {
+        let (publisher, receiver) = Publisher::new(3, SlowConsumerPolicy::DropOldest);
+        for update in 1..=5 {
+            publisher.publish(update).unwrap();
+        }
+        assert_eq!((publisher.queue_depth(), publisher.dropped()), (3, 3));
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                SubscriberEvent::Update(4),
+                SubscriberEvent::DataLoss { dropped: 3 },
+                SubscriberEvent::Update(5),
+            ]
+        );
+
+        let (publisher, receiver) = Publisher::new(3, SlowConsumerPolicy::Conflate);
+        for update in 1..=6 {
+            publisher.publish(update).unwrap();
+        }
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![SubscriberEvent::DataLoss { dropped: 5 }, SubscriberEvent::Update(6)]
+        );
+
+        let (publisher, receiver) = Publisher::new(2, SlowConsumerPolicy::Disconnect);
+        publisher.publish(1).unwrap();
+        publisher.publish(2).unwrap();
+        assert_eq!(publisher.publish(3), Err(Disconnected));
+        assert_eq!(receiver.try_iter().count(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::types::{Order, SymbolId};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Randomized delay applied to aggressive orders on a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedBump {
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl SpeedBump {
+    pub fn new(min_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            min_delay,
+            max_delay: max_delay.max(min_delay),
+        }
+    }
+}
+
+/// Holds deferred orders until their release time
+pub(crate) struct Scheduler {
+    queue: BinaryHeap<Reverse<DeferredOrder>>,
+    sequence: u64,
+    rng_state: u64,
+}
+
+struct DeferredOrder {
+    release_at: Instant,
+    sequence: u64,
+    /// Sequence number of the command that brought the order in
+    command: u64,
+    symbol: SymbolId,
+    order: Order,
+}
+
+impl PartialEq for DeferredOrder {
+    fn eq(&self, other: &Self) -> bool {
+        (self.release_at, self.sequence) == (other.release_at, other.sequence)
+    }
+}
+
+impl Eq for DeferredOrder {}
+
+impl PartialOrd for DeferredOrder {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DeferredOrder {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.release_at, self.sequence).cmp(&(other.release_at, other.sequence))
+    }
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+
+        Self {
+            queue: BinaryHeap::new(),
+            sequence: 0,
+            rng_state: seed | 1, // xorshift state must be non-zero
+        }
+    }
+
+    /// Defer an order, brought in by command `command`, by a random delay drawn from the
+    /// speed bump range
+    pub(crate) fn defer(&mut self, command: u64, symbol: SymbolId, order: Order, bump: SpeedBump) {
+        let release_at = Instant::now() + self.sample_delay(bump);
+        self.sequence += 1;
+        self.queue.push(Reverse(DeferredOrder {
+            release_at,
+            sequence: self.sequence,
+            command,
+            symbol,
+            order,
+        }));
+    }
+
+    /// Next order whose release time has passed, with the sequence number of its command
+    pub(crate) fn pop_due(&mut self, now: Instant) -> Option<(u64, SymbolId, Order)> {
+        if self.queue.peek()?.0.release_at > now {
+            return None;
+        }
+        self.queue
+            .pop()
+            .map(|Reverse(deferred)| (deferred.command, deferred.symbol, deferred.order))
+    }
+
+    /// Time until the next deferred order is due, if any
+    pub(crate) fn time_to_next(&self, now: Instant) -> Option<Duration> {
+        self.queue
+            .peek()
+            .map(|Reverse(deferred)| deferred.release_at.saturating_duration_since(now))
+    }
+
+    /// Take every deferred order, due or not
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = (SymbolId, Order)> + '_ {
+        self.queue.drain().map(|Reverse(deferred)| (deferred.symbol, deferred.order))
+    }
+
+    /// A deferred order, if still held
+    pub(crate) fn get(&self, order_id: Uuid) -> Option<&Order> {
+        self.queue
+            .iter()
+            .map(|Reverse(deferred)| &deferred.order)
+            .find(|order| order.id == order_id)
+    }
+
+    /// Take a deferred order before its release, if still held
+    pub(crate) fn remove(&mut self, order_id: Uuid) -> Option<(SymbolId, Order)> {
+        self.drain_where(|order| order.id == order_id).pop()
+    }
+
+    /// Take the deferred orders `filter` picks, due or not
+    pub(crate) fn drain_where(&mut self, filter: impl Fn(&Order) -> bool) -> Vec<(SymbolId, Order)> {
+        let (taken, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.queue)
+            .into_vec()
+            .into_iter()
+            .partition(|Reverse(deferred)| filter(&deferred.order));
+        self.queue = BinaryHeap::from(kept);
+        taken
+            .into_iter()
+            .map(|Reverse(deferred)| (deferred.symbol, deferred.order))
+            .collect()
+    }
+
+    fn sample_delay(&mut self, bump: SpeedBump) -> Duration {
+        let range = (bump.max_delay - bump.min_delay).as_nanos() as u64;
+        if range == 0 {
+            return bump.min_delay;
+        }
+        bump.min_delay + Duration::from_nanos(self.next_random() % (range + 1))
+    }
+
+    // xorshift64: cheap and good enough for jitter
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        x
+    }
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What the engine did between `stop` being called and the matching thread exiting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    pub timestamp: DateTime<Utc>,
+    /// Commands queued ahead of the shutdown, all processed before the engine stopped
+    pub commands_drained: u64,
+    /// New orders among the drained commands
+    pub orders_drained: u64,
+    /// Orders still held by speed bumps, cancelled instead of being released
+    pub cancels_issued: u64,
+    /// Trades from drained commands that reached the trade channel
+    pub trades_flushed: u64,
+    /// Sequence number of the last command the matching thread took off the queue, the
+    /// shutdown included. Numbering carries on across restarts.
+    pub last_sequence: u64,
+    /// Trades the trade channel refused over the run
+    pub unsent_events: u64,
+    /// Orders left resting in the books, kept for the next start
+    pub resting_orders: usize,
+}
+
+impl ShutdownReport {
+    /// Every trade made it out: nothing was lost on the way down
+    pub fn is_clean(&self) -> bool {
+        self.unsent_events == 0
+    }
+}
+
+/// Handed back by the matching thread as it exits
+pub(super) struct MatchingExit {
+    pub(super) sequence: u64,
+    pub(super) cancelled: u64,
+}
@@ -0,0 +1,215 @@
+use crate::types::Trade;
+use crossbeam::channel::{self, Sender};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+
+/// Why a sink did not take a trade; the trade is handed back
+#[derive(Error, Debug)]
+pub enum SinkError {
+    #[error("Trade sink is full")]
+    Full(Box<Trade>),
+
+    #[error("Trade sink is disconnected")]
+    Disconnected(Box<Trade>),
+}
+
+impl SinkError {
+    pub fn into_trade(self) -> Trade {
+        match self {
+            SinkError::Full(trade) | SinkError::Disconnected(trade) => *trade,
+        }
+    }
+}
+
+/// Where the engine delivers trades.
+///
+/// Both methods are called on the matching thread, so a sink should hand trades off
+/// rather than do slow work (network, disk) inline.
+pub trait TradeSink: Send + Sync + fmt::Debug {
+    /// Deliver `trade` without waiting
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError>;
+
+    /// Deliver `trade`, waiting for room if the sink is bounded. Only used with
+    /// [`TradeOverflow::Block`]; sinks that can't wait just try.
+    fn send(&self, trade: Trade) -> Result<(), SinkError> {
+        self.try_send(trade)
+    }
+}
+
+/// What the engine does with a trade its sink refuses because it is full. Trades for a
+/// disconnected sink are always dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TradeOverflow {
+    /// Drop the trade; it is counted in the shutdown report's `unsent_events`
+    #[default]
+    Drop,
+    /// Wait for the sink to take the trade, stalling matching meanwhile
+    Block,
+    /// Hold up to `capacity` refused trades and retry them in order before newer ones;
+    /// trades beyond that are dropped
+    Buffer { capacity: usize },
+}
+
+impl TradeSink for Sender<Trade> {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        Sender::try_send(self, trade).map_err(|e| match e {
+            channel::TrySendError::Full(trade) => SinkError::Full(Box::new(trade)),
+            channel::TrySendError::Disconnected(trade) => SinkError::Disconnected(Box::new(trade)),
+        })
+    }
+
+    fn send(&self, trade: Trade) -> Result<(), SinkError> {
+        Sender::send(self, trade).map_err(|e| SinkError::Disconnected(Box::new(e.into_inner())))
+    }
+}
+
+impl TradeSink for mpsc::Sender<Trade> {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        mpsc::Sender::try_send(self, trade).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(trade) => SinkError::Full(Box::new(trade)),
+            mpsc::error::TrySendError::Closed(trade) => SinkError::Disconnected(Box::new(trade)),
+        })
+    }
+
+    fn send(&self, trade: Trade) -> Result<(), SinkError> {
+        // The matching thread is outside the runtime, where blocking is allowed
+        self.blocking_send(trade).map_err(|e| SinkError::Disconnected(Box::new(e.0)))
+    }
+}
+
+impl TradeSink for mpsc::UnboundedSender<Trade> {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        mpsc::UnboundedSender::send(self, trade).map_err(|e| SinkError::Disconnected(Box::new(e.0)))
+    }
+}
+
+/// Every receiver subscribed when a trade is sent gets it, so any number of async consumers
+/// can follow the trade stream. The channel never fills: a receiver that falls more than
+/// the channel's capacity behind skips the oldest trades and is told how many with
+/// `RecvError::Lagged`. Trades sent while nobody is subscribed are dropped.
+impl TradeSink for broadcast::Sender<Trade> {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        broadcast::Sender::send(self, trade)
+            .map(|_| ())
+            .map_err(|e| SinkError::Disconnected(Box::new(e.0)))
+    }
+}
+
+impl<S: TradeSink + ?Sized> TradeSink for Arc<S> {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        (**self).try_send(trade)
+    }
+
+    fn send(&self, trade: Trade) -> Result<(), SinkError> {
+        (**self).send(trade)
+    }
+}
+
+impl<S: TradeSink + ?Sized> TradeSink for Box<S> {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        (**self).try_send(trade)
+    }
+
+    fn send(&self, trade: Trade) -> Result<(), SinkError> {
+        (**self).send(trade)
+    }
+}
+
+/// Delivers a copy of every trade to each of its sinks.
+///
+/// A trade counts as delivered once any sink takes it; a sink that refuses its copy is
+/// logged and skipped, so one slow consumer neither holds up nor duplicates trades for
+/// the others.
+#[derive(Debug, Default)]
+pub struct FanOut {
+    sinks: Vec<Box<dyn TradeSink>>,
+}
+
+impl FanOut {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sink(mut self, sink: impl TradeSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    fn deliver(&self, trade: Trade, send: impl Fn(&dyn TradeSink, Trade) -> Result<(), SinkError>) -> Result<(), SinkError> {
+        let Some((last, rest)) = self.sinks.split_last() else {
+            return Err(SinkError::Disconnected(Box::new(trade)));
+        };
+
+        let mut delivered = false;
+        for (index, sink) in rest.iter().enumerate() {
+            match send(sink.as_ref(), trade.clone()) {
+                Ok(()) => delivered = true,
+                Err(e) => warn!("Fan-out sink {} refused trade {}: {}", index, trade.id, e),
+            }
+        }
+        match send(last.as_ref(), trade) {
+            Ok(()) => Ok(()),
+            Err(_) if delivered => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl TradeSink for FanOut {
+    fn try_send(&self, trade: Trade) -> Result<(), SinkError> {
+        self.deliver(trade, |sink, trade| sink.try_send(trade))
+    }
+
+    fn send(&self, trade: Trade) -> Result<(), SinkError> {
+        self.deliver(trade, |sink, trade| sink.send(trade))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Price;
+    use uuid::Uuid;
+
+    fn trade() -> Trade {
+        Trade::new(Uuid::new_v4(), Uuid::new_v4(), "BTCUSD".to_string(), 1, Price::from(50000.0))
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let (first, first_receiver) = channel::bounded(1);
+        let (second, second_receiver) = mpsc::unbounded_channel();
+        let fan_out = FanOut::new().with_sink(first).with_sink(second);
+
+        assert!(fan_out.try_send(trade()).is_ok());
+        // The first sink is full: the second still gets its copy
+        assert!(fan_out.try_send(trade()).is_ok());
+        assert_eq!(first_receiver.len(), 1);
+        assert_eq!(second_receiver.len(), 2);
+
+        drop(second_receiver);
+        assert!(matches!(fan_out.try_send(trade()), Err(SinkError::Disconnected(_))));
+        assert!(matches!(FanOut::new().try_send(trade()), Err(SinkError::Disconnected(_))));
+    }
+
+    #[test]
+    fn test_broadcast_sink() {
+        let (sender, mut first) = broadcast::channel(1);
+        let mut second = sender.subscribe();
+        let sent = trade();
+        assert!(TradeSink::try_send(&sender, sent.clone()).is_ok());
+        assert_eq!(first.try_recv().unwrap().id, sent.id);
+        assert_eq!(second.try_recv().unwrap().id, sent.id);
+
+        // A lagging receiver misses trades instead of holding up the sink
+        assert!(TradeSink::try_send(&sender, trade()).is_ok());
+        assert!(TradeSink::try_send(&sender, trade()).is_ok());
+        assert!(matches!(first.try_recv(), Err(broadcast::error::TryRecvError::Lagged(1))));
+
+        drop((first, second));
+        assert!(matches!(TradeSink::try_send(&sender, trade()), Err(SinkError::Disconnected(_))));
+    }
+}
@@ -0,0 +1,281 @@
+use super::EngineState;
+use crate::market_data::{
+    BboUpdate, BookSnapshot, Candle, CandleBuilder, FeedConfig, LevelUpdate, SubscriberEvent, TradePrint,
+};
+use crate::matching::DepthSnapshot;
+use crossbeam::channel::{unbounded, Receiver, Select, SelectedOperation, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::thread;
+
+/// Candle length when a subscription doesn't give one
+const DEFAULT_CANDLE_INTERVAL_SECS: u64 = 60;
+
+/// Market data stream a consumer can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// Best bid and offer changes
+    Bbo,
+    /// A full book snapshot, then incremental price level updates
+    Depth,
+    /// The public trade tape
+    Trades,
+    /// Candles built from the trade tape, updated with every trade
+    Candles,
+}
+
+/// One channel of one symbol
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Subscription {
+    pub channel: Channel,
+    pub symbol: String,
+    /// Candle length, for the candles channel; a minute when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+}
+
+/// Market data for a consumer's subscriptions, tagged with its `type` when serialized
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MarketDataMessage {
+    /// Sent before the subscription's data
+    Subscribed { subscription: Subscription },
+    /// Sent after the subscription's last data
+    Unsubscribed { subscription: Subscription },
+    /// A WebSocket client's request was malformed or could not be served
+    Error { message: String },
+    Bbo(BboUpdate),
+    /// The book to apply the depth channel's level updates to; updates it already
+    /// reflects are not sent
+    Snapshot(BookSnapshot),
+    Depth(LevelUpdate),
+    Trade(TradePrint),
+    Candle(Candle),
+    /// The consumer fell behind and `dropped` messages of the subscription were discarded;
+    /// resubscribe to depth to get a consistent book again
+    DataLoss { subscription: Subscription, dropped: u64 },
+}
+
+/// A consumer's market data subscriptions, per channel and symbol.
+///
+/// Data for every subscription arrives in order on one receiver, from a thread that runs
+/// until the subscriptions are dropped or the receiver goes away. Each subscription is a
+/// feed subscription of its own, so a consumer that falls behind only loses its own data,
+/// with a [`MarketDataMessage::DataLoss`] notice.
+pub struct MarketDataSubscriptions {
+    state: Arc<EngineState>,
+    subscriptions: HashSet<Subscription>,
+    control: Sender<Control>,
+}
+
+impl MarketDataSubscriptions {
+    /// Subscriptions whose messages go to `deliver`, until it returns `false`
+    pub(super) fn spawn(state: Arc<EngineState>, deliver: impl FnMut(MarketDataMessage) -> bool + Send + 'static) -> Self {
+        let (control, controls) = unbounded();
+        // Engine feeds are blocking receivers, so they are waited on by a thread of their own
+        thread::Builder::new()
+            .name("market-data".to_string())
+            .spawn(move || forward(controls, deliver))
+            .expect("failed to spawn market data thread");
+        Self {
+            state,
+            subscriptions: HashSet::new(),
+            control,
+        }
+    }
+
+    /// Start receiving `subscription`'s data, after a [`MarketDataMessage::Subscribed`];
+    /// `false` if already subscribed
+    pub fn subscribe(&mut self, subscription: Subscription) -> bool {
+        if self.subscriptions.contains(&subscription) {
+            return false;
+        }
+        let (feed, mut messages) = Feed::open(&self.state, &subscription);
+        messages.insert(0, MarketDataMessage::Subscribed {
+            subscription: subscription.clone(),
+        });
+        self.subscriptions.insert(subscription.clone());
+        let _ = self.control.send(Control::Subscribe(subscription, feed, messages));
+        true
+    }
+
+    /// Stop receiving `subscription`'s data, ending with a [`MarketDataMessage::Unsubscribed`];
+    /// `false` if not subscribed
+    pub fn unsubscribe(&mut self, subscription: &Subscription) -> bool {
+        if !self.subscriptions.remove(subscription) {
+            return false;
+        }
+        let _ = self.control.send(Control::Unsubscribe(subscription.clone()));
+        true
+    }
+
+    pub fn is_subscribed(&self, subscription: &Subscription) -> bool {
+        self.subscriptions.contains(subscription)
+    }
+
+    pub fn subscriptions(&self) -> impl Iterator<Item = &Subscription> {
+        self.subscriptions.iter()
+    }
+}
+
+/// An event taken off a feed's receiver
+enum Received {
+    Bbo(SubscriberEvent<BboUpdate>),
+    Depth(SubscriberEvent<LevelUpdate>),
+    Print(SubscriberEvent<TradePrint>),
+}
+
+/// Subscription changes for the forwarding thread
+enum Control {
+    Subscribe(Subscription, Feed, Vec<MarketDataMessage>),
+    Unsubscribe(Subscription),
+}
+
+/// A subscription's receiver, with what it takes to turn its events into messages
+enum Feed {
+    Bbo(Receiver<SubscriberEvent<BboUpdate>>),
+    /// Updates up to `after` are already in the snapshot sent
+    Depth {
+        updates: Receiver<SubscriberEvent<LevelUpdate>>,
+        after: u64,
+    },
+    Trades(Receiver<SubscriberEvent<TradePrint>>),
+    Candles {
+        prints: Receiver<SubscriberEvent<TradePrint>>,
+        candles: CandleBuilder,
+    },
+}
+
+impl Feed {
+    /// Subscribe to the engine feed behind `subscription`, with the messages to send first
+    fn open(state: &EngineState, subscription: &Subscription) -> (Self, Vec<MarketDataMessage>) {
+        let config = FeedConfig {
+            symbol: Some(subscription.symbol.clone()),
+            ..FeedConfig::default()
+        };
+        match subscription.channel {
+            Channel::Bbo => (Feed::Bbo(state.bbo_updates.subscribe_conflated(config)), Vec::new()),
+            Channel::Depth => {
+                // Subscribed before the snapshot is taken, so no update falls in between
+                let updates = state.level_updates.subscribe_conflated(config);
+                let snapshot = Self::snapshot(state, &subscription.symbol);
+                let after = snapshot.sequence;
+                (Feed::Depth { updates, after }, vec![MarketDataMessage::Snapshot(snapshot)])
+            }
+            Channel::Trades => (Feed::Trades(state.trade_prints.subscribe(config)), Vec::new()),
+            Channel::Candles => {
+                let interval = subscription.interval_secs.unwrap_or(DEFAULT_CANDLE_INTERVAL_SECS);
+                let feed = Feed::Candles {
+                    prints: state.trade_prints.subscribe(config),
+                    candles: CandleBuilder::new(interval),
+                };
+                (feed, Vec::new())
+            }
+        }
+    }
+
+    fn snapshot(state: &EngineState, symbol: &str) -> BookSnapshot {
+        state
+            .symbols
+            .get(symbol)
+            .and_then(|id| state.order_books.with(id, |book| state.snapshot_book(id, book)))
+            .unwrap_or_else(|| BookSnapshot {
+                symbol: symbol.to_string(),
+                depth: DepthSnapshot::default(),
+                timestamp: state.clock.now(),
+                sequence: 0,
+            })
+    }
+
+    fn register<'a>(&'a self, select: &mut Select<'a>) {
+        match self {
+            Feed::Bbo(updates) => select.recv(updates),
+            Feed::Depth { updates, .. } => select.recv(updates),
+            Feed::Trades(prints) | Feed::Candles { prints, .. } => select.recv(prints),
+        };
+    }
+
+    /// Complete the receive selected for this feed; `None` once the feed has disconnected
+    fn receive(&self, operation: SelectedOperation<'_>) -> Option<Received> {
+        let received = match self {
+            Feed::Bbo(updates) => Received::Bbo(operation.recv(updates).ok()?),
+            Feed::Depth { updates, .. } => Received::Depth(operation.recv(updates).ok()?),
+            Feed::Trades(prints) | Feed::Candles { prints, .. } => Received::Print(operation.recv(prints).ok()?),
+        };
+        Some(received)
+    }
+
+    /// Messages for an event received from this feed
+    fn messages(&mut self, received: Received, subscription: &Subscription) -> Vec<MarketDataMessage> {
+        let dropped = match received {
+            Received::Bbo(SubscriberEvent::DataLoss { dropped })
+            | Received::Depth(SubscriberEvent::DataLoss { dropped })
+            | Received::Print(SubscriberEvent::DataLoss { dropped }) => dropped,
+            Received::Bbo(SubscriberEvent::Update(update)) => return vec![MarketDataMessage::Bbo(update)],
+            Received::Depth(SubscriberEvent::Update(update)) => {
+                return match self {
+                    Feed::Depth { after, .. } if update.sequence <= *after => Vec::new(),
+                    _ => vec![MarketDataMessage::Depth(update)],
+                };
+            }
+            Received::Print(SubscriberEvent::Update(print)) => {
+                return match self {
+                    Feed::Candles { candles, .. } => {
+                        let (closed, current) = candles.record(&print);
+                        closed.into_iter().chain([current]).map(MarketDataMessage::Candle).collect()
+                    }
+                    _ => vec![MarketDataMessage::Trade(print)],
+                };
+            }
+        };
+        vec![MarketDataMessage::DataLoss {
+            subscription: subscription.clone(),
+            dropped,
+        }]
+    }
+}
+
+/// Forward the subscribed feeds to `deliver` until the subscriptions are dropped
+fn forward(controls: Receiver<Control>, mut deliver: impl FnMut(MarketDataMessage) -> bool) {
+    let mut feeds: Vec<(Subscription, Feed)> = Vec::new();
+    loop {
+        let mut select = Select::new();
+        select.recv(&controls);
+        for (_, feed) in &feeds {
+            feed.register(&mut select);
+        }
+        let operation = select.select();
+
+        let messages = match operation.index() {
+            0 => match operation.recv(&controls) {
+                Ok(Control::Subscribe(subscription, feed, messages)) => {
+                    feeds.push((subscription, feed));
+                    messages
+                }
+                Ok(Control::Unsubscribe(subscription)) => {
+                    feeds.retain(|(subscribed, _)| *subscribed != subscription);
+                    vec![MarketDataMessage::Unsubscribed { subscription }]
+                }
+                // The subscriptions were dropped
+                Err(_) => return,
+            },
+            index => match feeds[index - 1].1.receive(operation) {
+                Some(received) => {
+                    let (subscription, feed) = &mut feeds[index - 1];
+                    feed.messages(received, subscription)
+                }
+                None => {
+                    feeds.remove(index - 1);
+                    Vec::new()
+                }
+            },
+        };
+        for message in messages {
+            if !deliver(message) {
+                return;
+            }
+        }
+    }
+}
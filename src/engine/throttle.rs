@@ -0,0 +1,220 @@
+use super::publisher::Publisher;
+use crate::market_data::{SlowConsumerPolicy, SubscriberEvent};
+use chrono::{DateTime, Utc};
+use crossbeam::channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Throttle events buffered per subscriber; older ones are dropped beyond that
+const THROTTLE_EVENT_BUFFER: usize = 1024;
+
+/// Exchange-style limits on the messages each client sends: new orders, cancels and
+/// amendments all count
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThrottlePolicy {
+    /// Sustained messages per second
+    pub messages_per_second: f64,
+    /// Messages a client may send at once after being quiet; at least one
+    pub burst: u32,
+    /// Warn when a client's messages per trade go above this
+    pub max_order_to_trade_ratio: Option<f64>,
+    /// Messages a client sends before its order-to-trade ratio is watched
+    pub ratio_min_messages: u64,
+}
+
+impl ThrottlePolicy {
+    pub fn new(messages_per_second: f64, burst: u32) -> Self {
+        Self {
+            messages_per_second,
+            burst,
+            max_order_to_trade_ratio: None,
+            ratio_min_messages: 0,
+        }
+    }
+
+    /// Warn once a client has sent `min_messages` and more than `max_ratio` per trade it made
+    pub fn with_order_to_trade_ratio(mut self, max_ratio: f64, min_messages: u64) -> Self {
+        self.max_order_to_trade_ratio = Some(max_ratio);
+        self.ratio_min_messages = min_messages;
+        self
+    }
+}
+
+/// A message counted against its client's throttle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    NewOrder,
+    Cancel,
+    Amend,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ThrottleEventKind {
+    /// The message was over the client's rate and refused
+    Throttled { message: MessageKind },
+    /// The client's order-to-trade ratio went above the policy's limit
+    OrderToTradeRatio { ratio: f64, messages: u64, trades: u64 },
+}
+
+/// A client hitting its message policy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThrottleEvent {
+    pub client_id: String,
+    pub kind: ThrottleEventKind,
+    /// Engine clock time
+    pub timestamp: DateTime<Utc>,
+}
+
+/// One client's token bucket and order-to-trade counts
+struct ClientBudget {
+    tokens: f64,
+    updated: DateTime<Utc>,
+    messages: u64,
+    trades: u64,
+    /// Above the ratio limit when last checked, so the next warning waits until it drops back
+    over_ratio: bool,
+}
+
+/// Per-client message budgets under one policy, and subscribers to throttle events
+pub(super) struct MessageThrottle {
+    policy: ThrottlePolicy,
+    clients: Mutex<HashMap<String, ClientBudget>>,
+    subscribers: Mutex<Vec<Publisher<ThrottleEvent>>>,
+}
+
+impl MessageThrottle {
+    pub(super) fn new(policy: ThrottlePolicy) -> Self {
+        Self {
+            policy,
+            clients: Mutex::new(HashMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(super) fn subscribe(&self) -> Receiver<SubscriberEvent<ThrottleEvent>> {
+        let (publisher, receiver) = Publisher::new(THROTTLE_EVENT_BUFFER, SlowConsumerPolicy::DropOldest);
+        self.subscribers.lock().unwrap().push(publisher);
+        receiver
+    }
+
+    /// Count a new order or amendment of `client_id`; `false` if it is over the client's rate
+    /// and must be refused
+    pub(super) fn admit(&self, client_id: &str, message: MessageKind, now: DateTime<Utc>) -> bool {
+        debug_assert_ne!(message, MessageKind::Cancel, "cancels are recorded, not admitted");
+        self.count(client_id, message, now)
+    }
+
+    /// Count a cancel of `client_id`. Cancels are never refused, but use up the budget all the
+    /// same.
+    pub(super) fn record_cancel(&self, client_id: &str, now: DateTime<Utc>) {
+        self.count(client_id, MessageKind::Cancel, now);
+    }
+
+    fn count(&self, client_id: &str, message: MessageKind, now: DateTime<Utc>) -> bool {
+        let burst = self.policy.burst.max(1) as f64;
+        let mut clients = self.clients.lock().unwrap();
+        let budget = clients.entry(client_id.to_string()).or_insert_with(|| ClientBudget {
+            tokens: burst,
+            updated: now,
+            messages: 0,
+            trades: 0,
+            over_ratio: false,
+        });
+
+        let elapsed = (now - budget.updated).num_microseconds().unwrap_or(i64::MAX).max(0) as f64 / 1e6;
+        budget.tokens = (budget.tokens + elapsed * self.policy.messages_per_second).min(burst);
+        budget.updated = now;
+
+        let admitted = budget.tokens >= 1.0 || message == MessageKind::Cancel;
+        budget.tokens = (budget.tokens - 1.0).max(0.0);
+        budget.messages += 1;
+        let ratio = self.ratio_crossed(budget);
+        drop(clients);
+
+        if !admitted {
+            self.publish(client_id, ThrottleEventKind::Throttled { message }, now);
+        }
+        if let Some(kind) = ratio {
+            self.publish(client_id, kind, now);
+        }
+        admitted
+    }
+
+    /// Count a trade of `client_id`, towards its order-to-trade ratio
+    pub(super) fn record_trade(&self, client_id: &str) {
+        if let Some(budget) = self.clients.lock().unwrap().get_mut(client_id) {
+            budget.trades += 1;
+            self.ratio_crossed(budget);
+        }
+    }
+
+    /// The warning to send if the client's ratio just went above the limit
+    fn ratio_crossed(&self, budget: &mut ClientBudget) -> Option<ThrottleEventKind> {
+        let max_ratio = self.policy.max_order_to_trade_ratio?;
+        let ratio = budget.messages as f64 / budget.trades.max(1) as f64;
+        let over = budget.messages >= self.policy.ratio_min_messages && ratio > max_ratio;
+        let crossed = over && !budget.over_ratio;
+        budget.over_ratio = over;
+        crossed.then_some(ThrottleEventKind::OrderToTradeRatio {
+            ratio,
+            messages: budget.messages,
+            trades: budget.trades,
+        })
+    }
+
+    fn publish(&self, client_id: &str, kind: ThrottleEventKind, now: DateTime<Utc>) {
+        let event = ThrottleEvent {
+            client_id: client_id.to_string(),
+            kind,
+            timestamp: now,
+        };
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.publish(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_throttle() {
+        let throttle = MessageThrottle::new(ThrottlePolicy::new(2.0, 3).with_order_to_trade_ratio(4.0, 5));
+        let events = throttle.subscribe();
+        let start = Utc::now();
+        let at = |millis| start + chrono::Duration::milliseconds(millis);
+
+        // A burst of three, then one more every half second
+        for _ in 0..3 {
+            assert!(throttle.admit("client1", MessageKind::NewOrder, at(0)));
+        }
+        assert!(!throttle.admit("client1", MessageKind::Amend, at(0)));
+        throttle.record_cancel("client1", at(0));
+        assert!(!throttle.admit("client1", MessageKind::NewOrder, at(400)));
+        assert!(throttle.admit("client1", MessageKind::NewOrder, at(1000)));
+        assert!(throttle.admit("client2", MessageKind::NewOrder, at(0)));
+
+        let kinds: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                SubscriberEvent::Update(event) => event.kind,
+                SubscriberEvent::DataLoss { .. } => panic!("throttle events lost"),
+            })
+            .collect();
+        assert_eq!(kinds, vec![
+            ThrottleEventKind::Throttled { message: MessageKind::Amend },
+            ThrottleEventKind::OrderToTradeRatio { ratio: 5.0, messages: 5, trades: 0 },
+            ThrottleEventKind::Throttled { message: MessageKind::NewOrder },
+        ]);
+
+        // Trading brings the ratio back down, after which it may warn again
+        throttle.record_trade("client1");
+        throttle.record_trade("client1");
+        assert!(throttle.admit("client1", MessageKind::NewOrder, at(5000)));
+        assert!(events.try_recv().is_err());
+    }
+}
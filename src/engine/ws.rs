@@ -0,0 +1,207 @@
+use super::{EngineState, ExecutionEngine, MarketDataMessage, MarketDataSubscriptions, Subscription};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+/// Messages queued for a client before it counts as too slow and is disconnected
+const CONNECTION_BUFFER: usize = 4096;
+
+/// Request from a WebSocket client, as a JSON text frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientRequest {
+    Subscribe(Subscription),
+    Unsubscribe(Subscription),
+}
+
+/// WebSocket server streaming an engine's market data to clients that subscribe per
+/// channel and symbol.
+///
+/// Clients send [`ClientRequest`]s and receive [`MarketDataMessage`]s, both as JSON text.
+/// Each connection holds its own feed subscriptions and a bounded queue of messages to
+/// send, so a slow client only affects itself: once its queue is full it is disconnected,
+/// and data its subscriptions drop before that comes with a
+/// [`MarketDataMessage::DataLoss`] notice.
+pub struct MarketDataServer {
+    state: Arc<EngineState>,
+    listener: TcpListener,
+}
+
+impl MarketDataServer {
+    /// Listen on `address` for clients of `engine`'s market data
+    pub async fn bind(engine: &ExecutionEngine, address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            state: Arc::clone(&engine.state),
+            listener: TcpListener::bind(address).await?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept and serve clients until accepting fails; connections already open keep
+    /// being served. Abort the task running it to stop the server.
+    pub async fn run(self) -> io::Result<()> {
+        info!("Market data server listening on {}", self.local_addr()?);
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let state = Arc::clone(&self.state);
+            task::spawn(async move {
+                if let Err(e) = serve(state, stream).await {
+                    debug!("Market data client {} failed: {}", peer, e);
+                }
+                debug!("Market data client {} disconnected", peer);
+            });
+        }
+    }
+}
+
+/// Serve one client until it disconnects
+async fn serve(state: Arc<EngineState>, stream: TcpStream) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let (mut sink, mut source) = tokio_tungstenite::accept_async(stream).await?.split();
+    let (outgoing, mut outbox) = mpsc::channel(CONNECTION_BUFFER);
+    // Stops forwarding once the client falls a full buffer behind, which closes the outbox
+    let mut subscriptions = MarketDataSubscriptions::spawn(state, move |message| match outgoing.try_send(message) {
+        Ok(()) => true,
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            warn!("Market data client more than {} messages behind; disconnecting", CONNECTION_BUFFER);
+            false
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => false,
+    });
+
+    loop {
+        tokio::select! {
+            request = source.next() => match request {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(reply) = handle_request(&text, &mut subscriptions) {
+                        sink.send(encode(&reply)).await?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+            },
+            message = outbox.recv() => match message {
+                Some(message) => sink.send(encode(&message)).await?,
+                None => break,
+            },
+        }
+    }
+    Ok(())
+}
+
+fn encode(message: &MarketDataMessage) -> Message {
+    Message::Text(serde_json::to_string(message).expect("market data messages serialize"))
+}
+
+/// Apply a client request; returns the reply to send now, if any, as subscription replies
+/// go out in order with the subscription's data
+fn handle_request(text: &str, subscriptions: &mut MarketDataSubscriptions) -> Option<MarketDataMessage> {
+    let error = |message: String| Some(MarketDataMessage::Error { message });
+    let request = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => return error(format!("Invalid request: {}", e)),
+    };
+
+    match request {
+        ClientRequest::Subscribe(subscription) => {
+            if subscriptions.is_subscribed(&subscription) {
+                return error(format!("Already subscribed to {:?} {}", subscription.channel, subscription.symbol));
+            }
+            subscriptions.subscribe(subscription);
+            None
+        }
+        ClientRequest::Unsubscribe(subscription) => {
+            if !subscriptions.unsubscribe(&subscription) {
+                return error(format!("Not subscribed to {:?} {}", subscription.channel, subscription.symbol));
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Channel;
+    use crate::matching::LevelAction;
+    use crate::types::{Order, Price, Side};
+    use crossbeam::channel::unbounded;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_market_data_server() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+        engine.submit_order(limit(Side::Sell, 2, 100.0)).await.unwrap();
+
+        let server = MarketDataServer::bind(&engine, "127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap();
+        let server = task::spawn(server.run());
+        let (client, _) = tokio_tungstenite::connect_async(format!("ws://{}", address)).await.unwrap();
+        let (mut client, mut replies) = client.split();
+
+        let subscribe = |channel| {
+            let request = ClientRequest::Subscribe(Subscription {
+                channel,
+                symbol: "BTCUSD".to_string(),
+                interval_secs: None,
+            });
+            Message::Text(serde_json::to_string(&request).unwrap())
+        };
+        let mut next = async || loop {
+            let message = timeout(Duration::from_secs(5), replies.next()).await.unwrap().unwrap().unwrap();
+            if let Message::Text(text) = message {
+                return serde_json::from_str::<MarketDataMessage>(&text).unwrap();
+            }
+        };
+
+        client.send(subscribe(Channel::Depth)).await.unwrap();
+        assert!(matches!(next().await, MarketDataMessage::Subscribed { .. }));
+        match next().await {
+            MarketDataMessage::Snapshot(snapshot) => {
+                assert_eq!(snapshot.depth.asks, vec![(Price::from(100.0), 2, 1)]);
+                assert_eq!(snapshot.sequence, 1);
+            }
+            other => panic!("expected a snapshot, got {:?}", other),
+        }
+        client.send(subscribe(Channel::Depth)).await.unwrap();
+        assert!(matches!(next().await, MarketDataMessage::Error { .. }));
+        client.send(Message::Text("{\"op\":\"shout\"}".to_string())).await.unwrap();
+        assert!(matches!(next().await, MarketDataMessage::Error { .. }));
+        client.send(subscribe(Channel::Trades)).await.unwrap();
+        assert!(matches!(next().await, MarketDataMessage::Subscribed { .. }));
+        client.send(subscribe(Channel::Candles)).await.unwrap();
+        assert!(matches!(next().await, MarketDataMessage::Subscribed { .. }));
+
+        engine.submit_order(limit(Side::Buy, 1, 100.0)).await.unwrap();
+        let mut messages = Vec::new();
+        for _ in 0..3 {
+            messages.push(next().await);
+        }
+        let depth = messages.iter().find_map(|message| match message {
+            MarketDataMessage::Depth(update) => Some(update),
+            _ => None,
+        });
+        let depth = depth.unwrap();
+        assert_eq!(depth.sequence, 2);
+        assert_eq!((depth.changes[0].action, depth.changes[0].quantity), (LevelAction::Modify, 1));
+        assert!(messages.iter().any(|message| matches!(message, MarketDataMessage::Trade(print) if print.aggressor == Some(Side::Buy))));
+        assert!(messages.iter().any(|message| matches!(message, MarketDataMessage::Candle(candle) if candle.volume == 1)));
+
+        server.abort();
+        engine.stop().await;
+    }
+}
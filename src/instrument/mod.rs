@@ -0,0 +1,239 @@
+#[cfg(feature = "engine")]
+mod registry;
+
+#[cfg(feature = "engine")]
+pub(crate) use registry::InstrumentRegistry;
+use crate::matching::{BookConfig, CrossPolicy, DepthLimits, MatchingAlgorithm, PriceLimits, SelfTradePrevention};
+use crate::types::{Price, RoundingMode, Side};
+
+/// What to do with a price that is not a multiple of the tick size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickPolicy {
+    #[default]
+    Reject,
+    /// Round to the nearest tick that is not more aggressive (down for buys, up for sells)
+    RoundPassive,
+}
+
+/// Per-symbol configuration supplied to the engine
+#[derive(Debug, Clone)]
+pub struct InstrumentConfig {
+    pub symbol: String,
+    pub book: BookConfig,
+    /// Decimal places allowed in prices, up to `Price::DECIMALS`; any precision is accepted when `None`
+    pub price_decimals: Option<u32>,
+    /// Minimum price increment; any price is accepted when `None`
+    pub tick_size: Option<Price>,
+    pub tick_policy: TickPolicy,
+    /// Quantities must be a multiple of this
+    pub lot_size: Option<u64>,
+    pub min_quantity: Option<u64>,
+    /// Minimum price × quantity; only checked for orders with a price
+    pub min_notional: Option<f64>,
+    /// Decimal places of fee, notional and funding amounts; full price precision when `None`
+    pub amount_decimals: Option<u32>,
+    /// How fee, notional and funding amounts are rounded to `amount_decimals`
+    pub rounding: RoundingMode,
+    /// Largest distance of a limit price from the reference price, in percent; unchecked when `None`
+    pub price_collar_percent: Option<f64>,
+    /// Trades of at least this quantity are flagged as blocks on the trade tape
+    pub block_size: Option<u64>,
+}
+
+impl InstrumentConfig {
+    pub fn new(symbol: String) -> Self {
+        Self {
+            symbol,
+            book: BookConfig::default(),
+            price_decimals: None,
+            tick_size: None,
+            tick_policy: TickPolicy::default(),
+            lot_size: None,
+            min_quantity: None,
+            min_notional: None,
+            amount_decimals: None,
+            rounding: RoundingMode::default(),
+            price_collar_percent: None,
+            block_size: None,
+        }
+    }
+
+    pub fn with_matching_algorithm(mut self, algorithm: MatchingAlgorithm) -> Self {
+        self.book.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_priority_classes(mut self, priority_classes: bool) -> Self {
+        self.book.priority_classes = priority_classes;
+        self
+    }
+
+    /// Attach book context (spread, queue position, levels swept) to every trade
+    pub fn with_trade_context(mut self, trade_context: bool) -> Self {
+        self.book.trade_context = trade_context;
+        self
+    }
+
+    /// Limit up / limit down bands for continuous matching
+    pub fn with_price_limits(mut self, limits: PriceLimits) -> Self {
+        self.book.price_limits = Some(limits);
+        self
+    }
+
+    /// Pre-allocate book storage for `capacity` resting orders
+    pub fn with_order_capacity(mut self, capacity: usize) -> Self {
+        self.book.order_capacity = capacity;
+        self
+    }
+
+    /// Cap the resting orders and price levels of the symbol's book
+    pub fn with_depth_limits(mut self, limits: DepthLimits) -> Self {
+        self.book.depth_limits = Some(limits);
+        self
+    }
+
+    /// How post-only orders that would lock or cross the book are handled; repricing uses the tick size
+    pub fn with_cross_policy(mut self, policy: CrossPolicy) -> Self {
+        self.book.cross_policy = policy;
+        self
+    }
+
+    pub fn with_self_trade_prevention(mut self, policy: SelfTradePrevention) -> Self {
+        self.book.self_trade_prevention = Some(policy);
+        self
+    }
+
+    pub fn with_price_decimals(mut self, decimals: u32) -> Self {
+        self.price_decimals = Some(decimals.min(Price::DECIMALS));
+        self
+    }
+
+    pub fn with_tick_size(mut self, tick_size: impl Into<Price>, policy: TickPolicy) -> Self {
+        self.tick_size = Some(tick_size.into());
+        self.book.tick_size = self.tick_size;
+        self.tick_policy = policy;
+        self
+    }
+
+    pub fn with_lot_size(mut self, lot_size: u64) -> Self {
+        self.lot_size = Some(lot_size);
+        self
+    }
+
+    pub fn with_min_quantity(mut self, min_quantity: u64) -> Self {
+        self.min_quantity = Some(min_quantity);
+        self
+    }
+
+    pub fn with_min_notional(mut self, min_notional: f64) -> Self {
+        self.min_notional = Some(min_notional);
+        self
+    }
+
+    pub fn with_block_size(mut self, block_size: u64) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Round fee, notional and funding amounts to `decimals` places with `mode`
+    pub fn with_rounding(mut self, decimals: u32, mode: RoundingMode) -> Self {
+        self.amount_decimals = Some(decimals.min(Price::DECIMALS));
+        self.rounding = mode;
+        self
+    }
+
+    /// Notional of `quantity` at `price`, rounded per the instrument's convention
+    pub fn notional(&self, price: Price, quantity: u64) -> Price {
+        price.notional(quantity, self.amount_decimals.unwrap_or(Price::DECIMALS), self.rounding)
+    }
+
+    /// Reject limit prices more than `percent` away from the symbol's reference price
+    pub fn with_price_collar(mut self, percent: f64) -> Self {
+        self.price_collar_percent = Some(percent);
+        self
+    }
+
+    /// Whether `price` is within the price collar around `reference`
+    pub fn within_collar(&self, price: Price, reference: Price) -> bool {
+        let Some(percent) = self.price_collar_percent else {
+            return true;
+        };
+        let reference = reference.to_f64();
+        (price.to_f64() - reference).abs() <= reference.abs() * percent / 100.0
+    }
+
+    /// Whether `price` has no more decimal places than the instrument allows
+    pub fn check_precision(&self, price: Price) -> bool {
+        let Some(decimals) = self.price_decimals else {
+            return true;
+        };
+        let step = 10i64.pow(Price::DECIMALS - decimals.min(Price::DECIMALS));
+        price.units().rem_euclid(step) == 0
+    }
+
+    /// `price` aligned to the tick size per the tick policy, or `None` if it must be rejected
+    pub fn align_price(&self, price: Price, side: Side) -> Option<Price> {
+        let Some(tick_size) = self.tick_size.filter(|tick| tick.units() > 0) else {
+            return Some(price);
+        };
+
+        let tick = tick_size.units();
+        let remainder = price.units().rem_euclid(tick);
+        if remainder == 0 {
+            return Some(price);
+        }
+
+        match self.tick_policy {
+            TickPolicy::Reject => None,
+            TickPolicy::RoundPassive => {
+                let below = price.units() - remainder;
+                let units = match side {
+                    Side::Buy => below,
+                    Side::Sell => below + tick,
+                };
+                Some(Price::from_units(units))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_price() {
+        let instrument = InstrumentConfig::new("ESZ4".to_string()).with_tick_size(0.25, TickPolicy::Reject);
+        assert_eq!(instrument.align_price(5000.75.into(), Side::Buy), Some(5000.75.into()));
+        assert_eq!(instrument.align_price(5000.10.into(), Side::Buy), None);
+
+        let instrument = instrument.with_tick_size(0.25, TickPolicy::RoundPassive);
+        assert_eq!(instrument.align_price(100.1.into(), Side::Buy), Some(100.0.into()));
+        assert_eq!(instrument.align_price(100.1.into(), Side::Sell), Some(100.25.into()));
+
+        // Exact arithmetic: 0.3 is a multiple of 0.1
+        let fx = InstrumentConfig::new("EURUSD".to_string()).with_tick_size(0.1, TickPolicy::Reject);
+        assert_eq!(fx.align_price(0.3.into(), Side::Buy), Some(0.3.into()));
+
+        let jpy = InstrumentConfig::new("USDJPY".to_string()).with_price_decimals(3);
+        assert!(jpy.check_precision(151.123.into()));
+        assert!(!jpy.check_precision(151.1234.into()));
+
+        let untick = InstrumentConfig::new("BTCUSD".to_string());
+        assert_eq!(untick.align_price(1.234.into(), Side::Buy), Some(1.234.into()));
+        assert!(untick.check_precision(Price::from_units(1)));
+    }
+
+    #[test]
+    fn test_notional_rounding() {
+        let price = Price::from(10.125);
+        let banker = InstrumentConfig::new("EURUSD".to_string()).with_rounding(2, RoundingMode::HalfEven);
+        let half_up = InstrumentConfig::new("EURUSD".to_string()).with_rounding(2, RoundingMode::HalfUp);
+        let truncate = InstrumentConfig::new("EURUSD".to_string()).with_rounding(1, RoundingMode::Truncate);
+
+        assert_eq!(banker.notional(price, 1), Price::from(10.12));
+        assert_eq!(half_up.notional(price, 1), Price::from(10.13));
+        assert_eq!(truncate.notional(price, 3), Price::from(30.3));
+        assert_eq!(InstrumentConfig::new("EURUSD".to_string()).notional(price, 3), Price::from(30.375));
+    }
+}
@@ -0,0 +1,96 @@
+use super::InstrumentConfig;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+type InstrumentMap = HashMap<String, Arc<InstrumentConfig>>;
+
+/// Source of registry IDs, so thread-local caches can tell registries apart
+static NEXT_REGISTRY_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // (registry ID, epoch, snapshot) of the registry this thread read last
+    static CACHED: RefCell<Option<(u64, u64, Arc<InstrumentMap>)>> = const { RefCell::new(None) };
+}
+
+/// Instrument reference data, read without locking on the hot path.
+///
+/// Registration copies the map, swaps in the new snapshot and bumps the epoch. Each
+/// thread caches the last snapshot it read and only takes the lock again once the
+/// epoch has moved on, so validation and matching normally cost one atomic load.
+pub(crate) struct InstrumentRegistry {
+    id: u64,
+    epoch: AtomicU64,
+    current: Mutex<Arc<InstrumentMap>>,
+}
+
+impl InstrumentRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: NEXT_REGISTRY_ID.fetch_add(1, Ordering::Relaxed),
+            epoch: AtomicU64::new(0),
+            current: Mutex::new(Arc::new(HashMap::new())),
+        }
+    }
+
+    /// Add an instrument; returns `false` if its symbol is already registered
+    pub(crate) fn register(&self, instrument: InstrumentConfig) -> bool {
+        let mut current = self.current.lock().unwrap();
+        if current.contains_key(&instrument.symbol) {
+            return false;
+        }
+
+        let mut instruments = InstrumentMap::clone(&current);
+        instruments.insert(instrument.symbol.clone(), Arc::new(instrument));
+        *current = Arc::new(instruments);
+        // Published after the snapshot, so a reader never caches a snapshot older than the epoch
+        self.epoch.fetch_add(1, Ordering::Release);
+        true
+    }
+
+    pub(crate) fn get(&self, symbol: &str) -> Option<Arc<InstrumentConfig>> {
+        self.snapshot().get(symbol).cloned()
+    }
+
+    /// Current instruments, from this thread's cache while the epoch is unchanged
+    fn snapshot(&self) -> Arc<InstrumentMap> {
+        let epoch = self.epoch.load(Ordering::Acquire);
+        CACHED.with(|cached| {
+            let mut cached = cached.borrow_mut();
+            match &*cached {
+                Some((id, cached_epoch, snapshot)) if *id == self.id && *cached_epoch == epoch => Arc::clone(snapshot),
+                _ => {
+                    let snapshot = Arc::clone(&self.current.lock().unwrap());
+                    *cached = Some((self.id, epoch, Arc::clone(&snapshot)));
+                    snapshot
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_epochs() {
+        let registry = InstrumentRegistry::new();
+        assert!(registry.get("ESZ4").is_none());
+
+        assert!(registry.register(InstrumentConfig::new("ESZ4".to_string()).with_lot_size(5)));
+        assert!(!registry.register(InstrumentConfig::new("ESZ4".to_string())));
+        assert_eq!(registry.get("ESZ4").unwrap().lot_size, Some(5));
+
+        // Another registry on the same thread does not see the cached snapshot
+        let other = InstrumentRegistry::new();
+        assert!(other.get("ESZ4").is_none());
+        assert!(registry.get("ESZ4").is_some());
+
+        // A snapshot cached on this thread is replaced once the epoch moves on
+        assert!(registry.get("NQZ4").is_none());
+        registry.register(InstrumentConfig::new("NQZ4".to_string()));
+        assert!(registry.get("NQZ4").is_some());
+    }
+}
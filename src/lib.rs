@@ -10,12 +10,24 @@
 //! - **Order Matching**: FIFO price-time priority matching algorithm
 //! - **Metrics**: Comprehensive execution metrics including latency percentiles
 //!
+//! ## Feature Flags
+//!
+//! - `engine` (default): the async [`ExecutionEngine`] built on Tokio and crossbeam.
+//!   Disable default features to depend only on the matching core ([`OrderBook`] and
+//!   the order types), e.g. for embedded or backtesting use.
+//! - `market-data-ws`: a WebSocket server streaming BBO, depth, trades and candles to
+//!   clients that subscribe per channel and symbol.
+//!
 //! ## Example
 //!
 //! ```rust
+//! # #[cfg(feature = "engine")]
 //! use rust_order_execution_engine::{ExecutionEngine, Order, Side};
 //! use crossbeam::channel::unbounded;
 //!
+//! # #[cfg(not(feature = "engine"))]
+//! # fn main() {}
+//! # #[cfg(feature = "engine")]
 //! #[tokio::main]
 //! async fn main() {
 //!     let (trade_sender, trade_receiver) = unbounded();
@@ -34,21 +46,57 @@
 //!     engine.submit_order(order).await.unwrap();
 //!     
 //!     // Process trades
-//!     while let Ok(trade) = trade_receiver.recv() {
+//!     while let Ok(trade) = trade_receiver.try_recv() {
 //!         println!("Trade executed: {:?}", trade);
 //!     }
+//!
+//!     engine.stop().await;
 //! }
 //! ```
 
+#[cfg(feature = "engine")]
 pub mod engine;
+pub mod auction;
+pub mod clock;
+pub mod instrument;
+pub mod market_data;
 pub mod matching;
+pub mod positions;
+pub mod quoting;
+pub mod risk;
+pub mod scenario;
+pub mod tca;
 pub mod types;
 
-pub use engine::{ExecutionEngine, EngineError};
-pub use matching::OrderBook;
-pub use types::{ExecutionMetrics, Order, OrderStatus, OrderType, Side, Trade};
+#[cfg(feature = "engine")]
+pub use engine::{
+    Amendment, AuditReport, BlockingEngine, DropCopyConfig, DropCopyEntry, DropCopyMessage, EngineBuilder, EngineConfig, ExecutionEngine, EngineError, FanOut, FeedStats, HaltPolicy, Invariant,
+    InvariantViolation, KillScope, KillSwitch, KillSwitchAction, KillSwitchEvent, MarketDataMessage, MarketDataStats, MarketDataSubscriptions, MessageKind, OrderEvent, OrderEventConfig, OrderEventKind, OrderOutcome, ProtectionBreach,
+    ProtectionEvent, ProtectionGroup, ProtectionLimits, RiskLimits, ShutdownReport, SinkError, SpeedBump, SubscriberStats,
+    ThrottleEvent, ThrottleEventKind, ThrottlePolicy, TradeOverflow, TradeSink, WaitStrategy,
+};
+pub use auction::{AuctionResult, IndicativeAuction, TradingPhase};
+pub use clock::{Clock, ClockSource, MonotonicClock, WallClock};
+pub use instrument::{InstrumentConfig, TickPolicy};
+pub use matching::{
+    Bbo, BookAnalytics, BookConfig, CrossPolicy, DepthLimitAction, DepthLimits, DepthSnapshot, LevelAction, LevelChange,
+    LimitBreachAction, MatchingAlgorithm, OrderBook, OrderChange, PriceLimits, SelfTradePrevention,
+};
+pub use positions::{Position, PositionTracker};
+pub use quoting::{QuoteTracker, QuotingMetrics};
+pub use risk::{
+    ClientLimits, ClientLimitsCheck, OrderRateCheck, OrderSizeCheck, PositionCheck, PositionLimitCheck, PriceBandCheck, RiskCheck,
+    RiskContext, RiskRejection,
+};
+pub use scenario::{ExpectedTrade, Scenario, ScenarioFailure};
+pub use tca::{OrderTca, ParentOrder, TcaAnalyzer, TcaReport, TcaSummary};
+pub use types::{
+    ExecutionMetrics, LatencyHistogram, Order, OrderStatus, OrderType, ParentLink, ParsePriceError, PositionIntent, Price,
+    RestingTimeHistogram, RoundingMode, Side, SymbolId, SymbolTable, TimeInForce, Trade, TradeContext,
+    RESTING_TIME_BUCKETS_MICROS,
+};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "engine"))]
 mod tests {
     use super::*;
     use crossbeam::channel::unbounded;
@@ -132,4 +180,2282 @@ mod tests {
         
         engine.stop().await;
     }
+
+    #[tokio::test]
+    async fn test_modify_order() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let buy_order = Order::new_limit(
+            "BTCUSD".to_string(),
+            Side::Buy,
+            10,
+            49800.0,
+            "client1".to_string()
+        );
+        let buy_id = buy_order.id;
+        engine.submit_order(buy_order).await.unwrap();
+
+        let sell_order = Order::new_limit(
+            "BTCUSD".to_string(),
+            Side::Sell,
+            5,
+            49900.0,
+            "client2".to_string()
+        );
+        engine.submit_order(sell_order).await.unwrap();
+
+        // Raising the bid to the ask makes the book crossable
+        engine
+            .modify_order(buy_id, "BTCUSD".to_string(), Some(Price::from(49900.0)), None)
+            .await
+            .unwrap();
+
+        let result = engine
+            .modify_order(buy_id, "BTCUSD".to_string(), None, None)
+            .await;
+        assert!(matches!(result, Err(EngineError::InvalidOrder(_))));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let trade = trade_receiver.try_recv().unwrap();
+        assert_eq!(trade.buy_order_id, buy_id);
+        assert_eq!(trade.quantity, 5);
+
+        let (best_bid, best_ask, depth) = engine.get_order_book("BTCUSD").unwrap();
+        assert_eq!(best_bid, Some(Price::from(49900.0)));
+        assert_eq!(best_ask, None);
+        assert_eq!(depth, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_order_expiry() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let order = Order::new_limit(
+            "BTCUSD".to_string(),
+            Side::Buy,
+            10,
+            50000.0,
+            "client1".to_string()
+        )
+        .with_ttl(chrono::Duration::milliseconds(50));
+        engine.submit_order(order).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+        let metrics = engine.get_metrics();
+        assert_eq!(metrics.expired_orders, 1);
+        assert_eq!(metrics.cancelled_orders, 0);
+
+        let (best_bid, _, depth) = engine.get_order_book("BTCUSD").unwrap();
+        assert_eq!(best_bid, None);
+        assert_eq!(depth, 0);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_orders() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        // client1 opens a 10 lot long position
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        engine.submit_order(sell).await.unwrap();
+
+        // A reduce-only buy would increase the long and is rejected
+        let increase = Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 49000.0, "client1".to_string())
+            .with_reduce_only(true);
+        engine.submit_order(increase).await.unwrap();
+
+        // A reduce-only sell larger than the position is trimmed
+        let reduce = Order::new_limit("BTCUSD".to_string(), Side::Sell, 15, 51000.0, "client1".to_string())
+            .with_reduce_only(true);
+        engine.submit_order(reduce).await.unwrap();
+
+        let taker = Order::new_limit("BTCUSD".to_string(), Side::Buy, 20, 51000.0, "client3".to_string());
+        engine.submit_order(taker).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let quantities: Vec<u64> = trade_receiver.try_iter().map(|t| t.quantity).collect();
+        assert_eq!(quantities, vec![10, 10]);
+
+        let metrics = engine.get_metrics();
+        assert_eq!(metrics.rejected_orders, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_heatmap_export() {
+        use crate::market_data::{HeatmapConfig, SubscriberEvent};
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        engine.submit_order(order).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let config = HeatmapConfig {
+            interval: std::time::Duration::from_millis(10),
+            levels: 5,
+            frames_per_batch: 3,
+            ..HeatmapConfig::default()
+        };
+        let matrices = engine.export_heatmap("BTCUSD".to_string(), config).await;
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let Ok(SubscriberEvent::Update(matrix)) = matrices.try_recv() else {
+            panic!("expected a heatmap matrix");
+        };
+        assert_eq!(matrix.timestamps.len(), 3);
+        assert_eq!(matrix.prices, vec![Price::from(50000.0)]);
+        assert_eq!(matrix.quantity_at(2, Price::from(50000.0)), 10);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_speed_bump_delays_aggressive_orders() {
+        use std::time::Duration;
+
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+        engine.set_speed_bump(
+            "BTCUSD".to_string(),
+            Some(SpeedBump::new(Duration::from_millis(200), Duration::from_millis(250))),
+        );
+
+        // Passive orders are not delayed
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap().2, 1);
+
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert!(trade_receiver.try_recv().is_err());
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!(trade_receiver.try_recv().unwrap().quantity, 10);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_speed_bump_held_orders() {
+        use std::time::Duration;
+
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        engine.set_speed_bump("BTCUSD".to_string(), Some(SpeedBump::new(Duration::from_millis(200), Duration::from_millis(200))));
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+
+        // Cancelled, amended and mass-cancelled while held
+        let buy = |client: &str| Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 50000.0, client.to_string());
+        let (cancelled, amended) = (buy("client2"), buy("client2"));
+        let (cancelled_id, amended_id) = (cancelled.id, amended.id);
+        engine.submit_order(cancelled).await.unwrap();
+        engine.submit_order(amended).await.unwrap();
+        engine.submit_order(buy("client3")).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        engine.cancel_order(cancelled_id, "BTCUSD".to_string()).await.unwrap();
+        engine.modify_order(amended_id, "BTCUSD".to_string(), None, Some(3)).await.unwrap();
+        engine.set_cancel_on_disconnect("client3", true);
+        assert!(engine.client_disconnected("client3").await.unwrap());
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(400)).await;
+        let trades: Vec<_> = trade_receiver.try_iter().map(|trade| (trade.buy_order_id, trade.quantity)).collect();
+        assert_eq!(trades, vec![(amended_id, 3)]);
+        assert_eq!(engine.get_metrics().cancelled_orders, 2);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_matching() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+        engine.pause_matching("BTCUSD".to_string()).await.unwrap();
+
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50100.0, "client1".to_string());
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 6, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        engine.submit_order(sell).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(trade_receiver.try_recv().is_err());
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap().2, 2);
+
+        engine.resume_matching("BTCUSD".to_string()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        assert_eq!(trade_receiver.try_recv().unwrap().quantity, 6);
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap().2, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_per_symbol_matching_algorithm() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        let instrument = InstrumentConfig::new("ESZ4".to_string())
+            .with_matching_algorithm(MatchingAlgorithm::ProRata { top_order_percent: 0 });
+        engine.register_instrument(instrument.clone()).unwrap();
+        assert!(matches!(
+            engine.register_instrument(instrument),
+            Err(EngineError::InstrumentExists(_))
+        ));
+
+        engine.start().await;
+
+        for symbol in ["ESZ4", "BTCUSD"] {
+            let first = Order::new_limit(symbol.to_string(), Side::Sell, 10, 5000.0, "client1".to_string());
+            let second = Order::new_limit(symbol.to_string(), Side::Sell, 10, 5000.0, "client2".to_string());
+            let taker = Order::new_limit(symbol.to_string(), Side::Buy, 10, 5000.0, "client3".to_string());
+            engine.submit_order(first).await.unwrap();
+            engine.submit_order(second).await.unwrap();
+            engine.submit_order(taker).await.unwrap();
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let quantities: Vec<(String, u64)> = trade_receiver
+            .try_iter()
+            .map(|t| (t.symbol, t.quantity))
+            .collect();
+        assert_eq!(
+            quantities,
+            vec![
+                ("ESZ4".to_string(), 5),
+                ("ESZ4".to_string(), 5),
+                ("BTCUSD".to_string(), 10),
+            ]
+        );
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_self_trade_prevention() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        let instrument = InstrumentConfig::new("BTCUSD".to_string())
+            .with_self_trade_prevention(SelfTradePrevention::CancelBoth);
+        engine.register_instrument(instrument).unwrap();
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string());
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        engine.submit_order(buy).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        assert!(trade_receiver.try_recv().is_err());
+        assert_eq!(engine.get_metrics().cancelled_orders, 2);
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap().2, 0);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_opening_auction() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        // On-open orders need a pending opening auction
+        let early = Order::new_market_on_open("BTCUSD".to_string(), Side::Buy, 5, "client1".to_string());
+        engine.submit_order(early).await.unwrap();
+
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::PreOpen).await.unwrap();
+        let moo = Order::new_market_on_open("BTCUSD".to_string(), Side::Buy, 5, "client1".to_string());
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50100.0, "client2".to_string());
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 12, 50000.0, "client3".to_string());
+        engine.submit_order(moo).await.unwrap();
+        engine.submit_order(buy).await.unwrap();
+        engine.submit_order(sell).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(engine.get_trading_phase("BTCUSD"), TradingPhase::PreOpen);
+        assert!(trade_receiver.try_recv().is_err());
+
+        // 15 bid (5 at market) against 12 offered
+        let indicative = engine.get_indicative_auction("BTCUSD").unwrap();
+        assert_eq!(indicative.price, Some(Price::from(50100.0)));
+        assert_eq!(indicative.volume, 12);
+        assert_eq!((indicative.imbalance, indicative.imbalance_side), (3, Some(Side::Buy)));
+
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::Continuous).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let trades: Vec<Trade> = trade_receiver.try_iter().collect();
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 12);
+        assert!(trades.iter().all(|t| t.price == Price::from(50100.0)));
+        assert_eq!(engine.get_trading_phase("BTCUSD"), TradingPhase::Continuous);
+        assert!(engine.get_indicative_auction("BTCUSD").is_none());
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap(), (Some(Price::from(50100.0)), None, 1));
+        assert_eq!(engine.get_metrics().rejected_orders, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_auction_orders_rejected_without_auction() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let events = engine.subscribe_order_events(OrderEventConfig::default());
+        engine.start().await;
+
+        // Nothing to match the on-open buy against
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::PreOpen).await.unwrap();
+        let moo = Order::new_market_on_open("BTCUSD".to_string(), Side::Buy, 5, "client1".to_string());
+        let moo_id = moo.id;
+        engine.submit_order(moo).await.unwrap();
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::Continuous).await.unwrap();
+
+        // The closing auction is skipped while matching is paused
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 100.0, "client2".to_string())).await.unwrap();
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::PreClose).await.unwrap();
+        let moc = Order::new_market_on_close("BTCUSD".to_string(), Side::Sell, 5, "client1".to_string());
+        let moc_id = moc.id;
+        engine.submit_order(moc).await.unwrap();
+        engine.pause_matching("BTCUSD".to_string()).await.unwrap();
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::Closed).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let rejected: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                SubscriberEvent::Update(OrderEvent { order_id, kind: OrderEventKind::Rejected { reason }, .. }) => Some((order_id, reason)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rejected, vec![
+            (moo_id, "Auction found no price to execute at".to_string()),
+            (moc_id, "Auction skipped while matching is suspended".to_string()),
+        ]);
+        assert!(trade_receiver.try_recv().is_err());
+        assert_eq!(engine.get_trading_phase("BTCUSD"), TradingPhase::Closed);
+        assert_eq!(engine.get_metrics().rejected_orders, 2);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_quoting_metrics() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let bid = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 49900.0, "mm1".to_string());
+        let ask = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50100.0, "mm1".to_string());
+        engine.submit_order(bid).await.unwrap();
+        engine.submit_order(ask).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let metrics = engine.get_quoting_metrics("mm1", "BTCUSD").unwrap();
+        assert!(metrics.two_sided_time > std::time::Duration::ZERO);
+        assert_eq!(metrics.time_at_best_ask, metrics.two_sided_time);
+        assert!((metrics.time_weighted_spread().unwrap() - 200.0).abs() < 1e-6);
+        assert!(engine.get_quoting_metrics("client2", "BTCUSD").is_none());
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_reference_prices() {
+        use std::time::Duration;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        engine
+            .submit_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50100.0, "client1".to_string()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(Order::new_market("BTCUSD".to_string(), Side::Buy, 15, "client2".to_string()))
+            .await
+            .unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let window = Duration::from_secs(60);
+        let vwap = engine.get_vwap("BTCUSD", window).unwrap();
+        assert!((vwap - 50033.333).abs() < 0.001);
+        assert!(engine.get_twap("BTCUSD", window).is_some());
+        assert_eq!(engine.get_vwap("ETHUSD", window), None);
+        let session_vwap = engine.get_session_vwap("BTCUSD").unwrap();
+        assert!((session_vwap - vwap).abs() < 1e-9);
+        let daily = engine.get_daily_stats("BTCUSD").unwrap();
+        assert_eq!((daily.volume, daily.trades, daily.last), (15, 2, Price::from(50100.0)));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_position_intent() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let open_long = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string())
+            .with_position_intent(PositionIntent::Open);
+        let open_short = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client2".to_string())
+            .with_position_intent(PositionIntent::Open);
+        engine.submit_order(open_long).await.unwrap();
+        engine.submit_order(open_short).await.unwrap();
+
+        // client1 is long 10, so closing 15 is rejected
+        let close = Order::new_limit("BTCUSD".to_string(), Side::Sell, 15, 50000.0, "client1".to_string())
+            .with_position_intent(PositionIntent::Close);
+        engine.submit_order(close).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let trade = trade_receiver.try_recv().unwrap();
+        assert_eq!(trade.buy_intent, Some(PositionIntent::Open));
+        assert_eq!(trade.sell_intent, Some(PositionIntent::Open));
+        assert_eq!(engine.get_metrics().rejected_orders, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_tick_size_validation() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine
+            .register_instrument(InstrumentConfig::new("ESZ4".to_string()).with_tick_size(0.25, TickPolicy::Reject))
+            .unwrap();
+        engine
+            .register_instrument(InstrumentConfig::new("NQZ4".to_string()).with_tick_size(0.25, TickPolicy::RoundPassive))
+            .unwrap();
+        engine
+            .register_instrument(InstrumentConfig::new("USDJPY".to_string()).with_price_decimals(3))
+            .unwrap();
+        engine.start().await;
+
+        let too_precise = Order::new_limit("USDJPY".to_string(), Side::Buy, 1000, 151.1234, "client1".to_string());
+        assert!(matches!(
+            engine.submit_order(too_precise).await,
+            Err(EngineError::InvalidPricePrecision { decimals: 3, .. })
+        ));
+
+        let off_tick = Order::new_limit("ESZ4".to_string(), Side::Buy, 1, 5000.10, "client1".to_string());
+        assert!(matches!(
+            engine.submit_order(off_tick).await,
+            Err(EngineError::InvalidTickSize { .. })
+        ));
+
+        let on_tick = Order::new_limit("ESZ4".to_string(), Side::Buy, 1, 5000.25, "client1".to_string());
+        let on_tick_id = on_tick.id;
+        engine.submit_order(on_tick).await.unwrap();
+
+        let rounded = Order::new_limit("NQZ4".to_string(), Side::Sell, 1, 18000.10, "client1".to_string());
+        engine.submit_order(rounded).await.unwrap();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let result = engine
+            .modify_order(on_tick_id, "ESZ4".to_string(), Some(Price::from(5000.30)), None)
+            .await;
+        assert!(matches!(result, Err(EngineError::InvalidTickSize { .. })));
+        assert_eq!(engine.get_order_book("NQZ4").unwrap().1, Some(Price::from(18000.25)));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_size_validation() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        let instrument = InstrumentConfig::new("AAPL".to_string())
+            .with_lot_size(100)
+            .with_min_quantity(200)
+            .with_min_notional(50_000.0);
+        engine.register_instrument(instrument).unwrap();
+        engine.start().await;
+
+        let order = |quantity, price| Order::new_limit("AAPL".to_string(), Side::Buy, quantity, price, "client1".to_string());
+        assert!(matches!(
+            engine.submit_order(order(250, 200.0)).await,
+            Err(EngineError::InvalidLotSize { .. })
+        ));
+        assert!(matches!(
+            engine.submit_order(order(100, 200.0)).await,
+            Err(EngineError::BelowMinQuantity { .. })
+        ));
+        assert!(matches!(
+            engine.submit_order(order(200, 200.0)).await,
+            Err(EngineError::BelowMinNotional { .. })
+        ));
+
+        let valid = order(300, 200.0);
+        let valid_id = valid.id;
+        engine.submit_order(valid).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let result = engine.modify_order(valid_id, "AAPL".to_string(), None, Some(200)).await;
+        assert!(matches!(result, Err(EngineError::BelowMinNotional { .. })));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_staged_orders() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let resting = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client2".to_string());
+        engine.submit_order(resting).await.unwrap();
+
+        let staged = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "desk1".to_string());
+        let staged_id = staged.id;
+        engine.stage_order(staged).await.unwrap();
+
+        // Staged orders stay out of the book, and only their owner can release them
+        engine.release_order(staged_id, "client2".to_string()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(trade_receiver.try_recv().is_err());
+        assert_eq!(engine.staged_orders(), 1);
+
+        engine.release_order(staged_id, "desk1".to_string()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(trade_receiver.try_recv().unwrap().buy_order_id, staged_id);
+        assert_eq!(engine.staged_orders(), 0);
+
+        let cancelled = Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 49000.0, "desk1".to_string());
+        let cancelled_id = cancelled.id;
+        engine.stage_order(cancelled).await.unwrap();
+        engine.cancel_order(cancelled_id, "BTCUSD".to_string()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(engine.staged_orders(), 0);
+        assert_eq!(engine.get_metrics().cancelled_orders, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_event_clock() {
+        // Stands in for a PTP hardware clock: a fixed, reproducible time
+        #[derive(Debug)]
+        struct FixedClock(chrono::DateTime<chrono::Utc>);
+
+        impl Clock for FixedClock {
+            fn now(&self) -> chrono::DateTime<chrono::Utc> {
+                self.0
+            }
+
+            fn source(&self) -> ClockSource {
+                ClockSource::Ptp
+            }
+        }
+
+        let fixed = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::with_clock(trade_sender, FixedClock(fixed));
+
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client2".to_string());
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        engine.submit_order(buy).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        // The event time comes from the engine clock; the wall-clock time is kept for correlation
+        let trade = trade_receiver.try_recv().unwrap();
+        assert_eq!(trade.timestamp, fixed);
+        assert_eq!(trade.clock_source, ClockSource::Ptp);
+        assert!(trade.wall_timestamp > fixed);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_bulk_modify() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        let bid = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 49900.0, "mm1".to_string());
+        let ask = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50100.0, "mm1".to_string());
+        let (bid_id, ask_id) = (bid.id, ask.id);
+        engine.submit_order(bid).await.unwrap();
+        engine.submit_order(ask).await.unwrap();
+        let offer = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50150.0, "client2".to_string());
+        engine.submit_order(offer).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        // One invalid amendment rejects the whole batch
+        let invalid = vec![
+            Amendment { order_id: bid_id, new_price: Some(Price::from(50150.0)), new_quantity: None },
+            Amendment { order_id: ask_id, new_price: None, new_quantity: Some(0) },
+        ];
+        assert!(engine.modify_orders("BTCUSD".to_string(), invalid).await.is_err());
+
+        // Shift both quotes up: the bid only meets client2's offer, never mm1's old ask
+        let amendments = vec![
+            Amendment { order_id: bid_id, new_price: Some(Price::from(50150.0)), new_quantity: None },
+            Amendment { order_id: ask_id, new_price: Some(Price::from(50300.0)), new_quantity: None },
+        ];
+        engine.modify_orders("BTCUSD".to_string(), amendments).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let trades: Vec<Trade> = trade_receiver.try_iter().collect();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].sell_client_id, "client2");
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap(), (None, Some(Price::from(50300.0)), 1));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_depth() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        for (price, client) in [(49900.0, "client1"), (49900.0, "client2"), (49800.0, "client3")] {
+            let order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, price, client.to_string());
+            engine.submit_order(order).await.unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let depth = engine.get_depth("BTCUSD", 1).unwrap();
+        assert_eq!(depth.bids, vec![(Price::from(49900.0), 20, 2)]);
+        assert!(depth.asks.is_empty());
+        assert!(engine.get_depth("ETHUSD", 1).is_none());
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_purge_orders() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        engine.start().await;
+
+        for price in [49900.0, 49000.0, 48500.0] {
+            let order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, price, "client1".to_string());
+            engine.submit_order(order).await.unwrap();
+        }
+        let ask = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client2".to_string());
+        engine.submit_order(ask).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        // Only bids in the range are cancelled
+        engine
+            .purge_orders("BTCUSD".to_string(), Side::Buy, Price::from(48000.0), Price::from(49500.0))
+            .await
+            .unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let depth = engine.get_depth("BTCUSD", 5).unwrap();
+        assert_eq!(depth.bids, vec![(Price::from(49900.0), 10, 1)]);
+        assert_eq!(depth.asks.len(), 1);
+        assert_eq!(engine.get_metrics().cancelled_orders, 2);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_price_limit_breach() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        let band = Some((Price::from(49000.0), Price::from(51000.0)));
+        for (symbol, on_breach) in [("BTCUSD", LimitBreachAction::Halt), ("ETHUSD", LimitBreachAction::Auction)] {
+            let limits = PriceLimits {
+                static_band: band,
+                on_breach,
+                ..PriceLimits::default()
+            };
+            engine
+                .register_instrument(InstrumentConfig::new(symbol.to_string()).with_price_limits(limits))
+                .unwrap();
+        }
+        engine.start().await;
+
+        for symbol in ["BTCUSD", "ETHUSD"] {
+            let ask = Order::new_limit(symbol.to_string(), Side::Sell, 10, 51500.0, "client1".to_string());
+            let buy = Order::new_limit(symbol.to_string(), Side::Buy, 10, 52000.0, "client2".to_string());
+            engine.submit_order(ask).await.unwrap();
+            engine.submit_order(buy).await.unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        // Nothing prints outside the band
+        assert!(trade_receiver.try_recv().is_err());
+
+        // Halt: the aggressor is cancelled and trading stops
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap(), (None, Some(Price::from(51500.0)), 1));
+        assert!(engine.is_halted("BTCUSD"));
+        let late = Order::new_market("BTCUSD".to_string(), Side::Buy, 1, "client3".to_string());
+        engine.submit_order(late).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(engine.get_metrics().rejected_orders, 1);
+
+        // Auction: the aggressor rests and the symbol goes into a call phase
+        assert_eq!(engine.get_trading_phase("ETHUSD"), TradingPhase::PreOpen);
+        assert_eq!(engine.get_indicative_auction("ETHUSD").unwrap().volume, 10);
+
+        engine.set_trading_phase("ETHUSD".to_string(), TradingPhase::Continuous).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(trade_receiver.try_iter().map(|t| t.quantity).sum::<u64>(), 10);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_trading_halt() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let resting = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string());
+        let resting_id = resting.id;
+        engine.submit_order(resting).await.unwrap();
+        engine.submit_order(Order::new_limit("ETHUSD".to_string(), Side::Sell, 10, 3000.0, "client1".to_string())).await.unwrap();
+        engine.halt_trading("BTCUSD".to_string(), HaltPolicy::Reject).await.unwrap();
+        engine.halt_trading("ETHUSD".to_string(), HaltPolicy::Queue).await.unwrap();
+
+        // Reject: new orders bounce, cancels still work
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client2".to_string())).await.unwrap();
+        engine.cancel_order(resting_id, "BTCUSD".to_string()).await.unwrap();
+
+        // Queue: limit orders rest without matching, market orders bounce
+        engine.submit_order(Order::new_limit("ETHUSD".to_string(), Side::Buy, 10, 3000.0, "client2".to_string())).await.unwrap();
+        engine.submit_order(Order::new_market("ETHUSD".to_string(), Side::Buy, 5, "client2".to_string())).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        assert!(engine.is_halted("BTCUSD"));
+        assert_eq!(engine.get_metrics().rejected_orders, 2);
+        assert_eq!(engine.get_metrics().cancelled_orders, 1);
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap(), (None, None, 0));
+        assert_eq!(engine.get_order_book("ETHUSD").unwrap().2, 2);
+        assert!(trade_receiver.try_recv().is_err());
+
+        // Resuming uncrosses the queued orders
+        engine.resume_trading("ETHUSD".to_string()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(!engine.is_halted("ETHUSD"));
+        assert_eq!(trade_receiver.try_iter().map(|t| t.quantity).sum::<u64>(), 10);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_bbo_watch() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let mut bbo = engine.subscribe_bbo("BTCUSD");
+        assert_eq!(*bbo.borrow(), Bbo::default());
+
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 49900.0, "client1".to_string())).await.unwrap();
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50100.0, "client2".to_string())).await.unwrap();
+        tokio::time::timeout(tokio::time::Duration::from_secs(1), bbo.changed()).await.unwrap().unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(
+            *bbo.borrow_and_update(),
+            Bbo {
+                bid: Some((Price::from(49900.0), 10)),
+                ask: Some((Price::from(50100.0), 5)),
+            }
+        );
+
+        // Orders away from the touch leave the BBO unchanged and send no update
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 49000.0, "client1".to_string())).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(!bbo.has_changed().unwrap());
+
+        engine.submit_order(Order::new_market("BTCUSD".to_string(), Side::Buy, 5, "client3".to_string())).await.unwrap();
+        bbo.changed().await.unwrap();
+        assert_eq!(bbo.borrow().ask, None);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_resting_time_metrics() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let filled = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string());
+        let cancelled = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 49000.0, "client1".to_string());
+        let cancelled_id = cancelled.id;
+        engine.submit_order(filled).await.unwrap();
+        engine.submit_order(cancelled).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+
+        engine.submit_order(Order::new_market("BTCUSD".to_string(), Side::Buy, 10, "client2".to_string())).await.unwrap();
+        engine.cancel_order(cancelled_id, "BTCUSD".to_string()).await.unwrap();
+        // Still resting, so not recorded yet
+        engine.submit_order(Order::new_limit("ETHUSD".to_string(), Side::Buy, 1, 3000.0, "client1".to_string())).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let metrics = engine.get_metrics();
+        let histogram = &metrics.resting_time["BTCUSD"];
+        assert_eq!(histogram.count, 2);
+        // Both rested somewhere between 10ms and 1s
+        assert_eq!(histogram.bucket_counts[2..4].iter().sum::<u64>(), 2);
+        assert!(histogram.mean_micros().unwrap() >= 20_000);
+        assert!(histogram.quantile_micros(0.5).unwrap() <= 1_000_000);
+        assert!(!metrics.resting_time.contains_key("ETHUSD"));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_price_collar() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine
+            .register_instrument(InstrumentConfig::new("BTCUSD".to_string()).with_price_collar(5.0))
+            .unwrap();
+        engine.start().await;
+
+        // Nothing is collared before the first trade sets a reference price
+        let first = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 50000.0, "client1".to_string());
+        engine.submit_order(first).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(engine.reference_price("BTCUSD"), Some(Price::from(50000.0)));
+
+        let outside = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 52600.0, "client1".to_string());
+        assert!(matches!(
+            engine.submit_order(outside).await,
+            Err(EngineError::PriceOutsideCollar { percent, .. }) if percent == 5.0
+        ));
+        let inside = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 52500.0, "client1".to_string());
+        assert!(engine.submit_order(inside).await.is_ok());
+
+        // An external reference takes precedence over the last trade
+        engine.set_reference_price("BTCUSD".to_string(), Some(Price::from(60000.0)));
+        let stale = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 52500.0, "client1".to_string());
+        assert!(matches!(
+            engine.submit_order(stale).await,
+            Err(EngineError::PriceOutsideCollar { reference, .. }) if reference == Price::from(60000.0)
+        ));
+        engine.set_reference_price("BTCUSD".to_string(), None);
+        assert_eq!(engine.reference_price("BTCUSD"), Some(Price::from(50000.0)));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_stale_external_reference() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_reference_staleness(std::time::Duration::from_millis(50))
+            .build()
+            .unwrap();
+        engine.start().await;
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 102.0, "client1".to_string())).await.unwrap();
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 98.0, "client1".to_string())).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        assert_eq!(engine.get_mark_price("BTCUSD"), Some(Price::from(100.0)));
+
+        engine.update_reference_price("BTCUSD".to_string(), Price::from(101.0));
+        let reference = engine.get_external_reference("BTCUSD").unwrap();
+        assert_eq!((reference.price, reference.stale), (Price::from(101.0), false));
+        assert_eq!(engine.get_mark_price("BTCUSD"), Some(Price::from(101.0)));
+        assert_eq!(engine.reference_price("BTCUSD"), Some(Price::from(101.0)));
+
+        // Stale: the engine's own prices take over until the next update
+        tokio::time::sleep(tokio::time::Duration::from_millis(80)).await;
+        assert!(engine.get_external_reference("BTCUSD").unwrap().stale);
+        assert_eq!(engine.get_mark_price("BTCUSD"), Some(Price::from(100.0)));
+        assert_eq!(engine.reference_price("BTCUSD"), None);
+        engine.update_reference_price("BTCUSD".to_string(), Price::from(99.0));
+        assert_eq!(engine.get_mark_price("BTCUSD"), Some(Price::from(99.0)));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_risk_check_chain() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_risk_check(OrderSizeCheck {
+                max_quantity: Some(10),
+                max_notional: None,
+            })
+            .with_risk_check(PositionCheck { max_position: 15 })
+            .build()
+            .unwrap();
+        engine.start().await;
+
+        let limit = |side, quantity, client: &str| Order::new_limit("BTCUSD".to_string(), side, quantity, 100.0, client.to_string());
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 11, "client1")).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+        assert_eq!(outcome.reject_reason.as_deref(), Some("Quantity 11 is above the limit of 10"));
+
+        engine.submit_order(limit(Side::Sell, 10, "client2")).await.unwrap();
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 10, "client1")).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+        // Long 10 already: another 10 would take client1 past 15
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 10, "client1")).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+        assert!(outcome.reject_reason.unwrap().contains("Projected position 20"));
+        let metrics = engine.get_metrics();
+        assert_eq!(metrics.rejected_orders, 2);
+        assert_eq!((metrics.risk_rejections["order_size"], metrics.risk_rejections["position"]), (1, 1));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_position_limits() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_risk_check(PositionLimitCheck::new().with_default(10).with_aggregate(15).trimming())
+            .build()
+            .unwrap();
+        engine.start().await;
+
+        let buy = |symbol: &str, quantity| Order::new_limit(symbol.to_string(), Side::Buy, quantity, 100.0, "client1".to_string());
+        engine.submit_order(buy("BTCUSD", 6)).await.unwrap();
+        // Open buys count as filled: only 4 more fit within 10
+        engine.submit_order(buy("BTCUSD", 5)).await.unwrap();
+        let eth = buy("ETHUSD", 6);
+        let eth_id = eth.id;
+        engine.submit_order(eth).await.unwrap();
+        let outcome = engine.submit_order_and_wait(buy("ETHUSD", 1)).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+        assert_eq!(
+            outcome.reject_reason.as_deref(),
+            Some("Projected position of 16 across all symbols is beyond the limit of 15")
+        );
+
+        let total = |symbol| engine.get_depth(symbol, 10).unwrap().bids.iter().map(|&(_, quantity, _)| quantity).sum::<u64>();
+        assert_eq!((total("BTCUSD"), total("ETHUSD")), (10, 5));
+
+        // Cancelling frees the room again
+        engine.cancel_order(eth_id, "ETHUSD".to_string()).await.unwrap();
+        let outcome = engine.submit_order_and_wait(Order { order_type: OrderType::Market, price: None, ..buy("ETHUSD", 5) }).await;
+        assert_eq!(outcome.reject_reason.as_deref(), Some("No liquidity"));
+        assert_eq!(engine.get_metrics().risk_rejections["position_limit"], 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_amendment_position_limits() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_risk_check(PositionLimitCheck::new().with_default(10))
+            .build()
+            .unwrap();
+        let events = engine.subscribe_order_events(OrderEventConfig::default());
+        engine.start().await;
+
+        let buy = |price| Order::new_limit("BTCUSD".to_string(), Side::Buy, 3, price, "client1".to_string());
+        let (first, second) = (buy(99.0), buy(98.0));
+        let (first_id, second_id) = (first.id, second.id);
+        engine.submit_order(first).await.unwrap();
+        engine.submit_order(second).await.unwrap();
+        let amend = |order_id, quantity| Amendment {
+            order_id,
+            new_price: None,
+            new_quantity: Some(quantity),
+        };
+
+        // Each amendment of a batch sees the size the earlier ones add, and one refusal refuses all
+        engine.modify_orders("BTCUSD".to_string(), vec![amend(first_id, 6), amend(second_id, 6)]).await.unwrap();
+        engine.modify_orders("BTCUSD".to_string(), vec![amend(first_id, 5), amend(second_id, 5)]).await.unwrap();
+        // Growing one order past the limit is refused; the order works on unchanged
+        engine.modify_order(first_id, "BTCUSD".to_string(), None, Some(6)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let bids: Vec<_> = engine.get_depth("BTCUSD", 10).unwrap().bids.iter().map(|&(_, quantity, _)| quantity).collect();
+        assert_eq!(bids, vec![5, 5]);
+        assert_eq!(engine.get_metrics().risk_rejections["position_limit"], 2);
+        let refused: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                SubscriberEvent::Update(OrderEvent { order_id, kind: OrderEventKind::AmendmentRejected { reason }, .. }) => Some((order_id, reason)),
+                _ => None,
+            })
+            .collect();
+        let reason = |projected| format!("Projected position {} is beyond the limit of 10", projected);
+        assert_eq!(refused, vec![
+            (first_id, reason(12)),
+            (second_id, reason(12)),
+            (first_id, reason(11)),
+        ]);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_amendment_client_limits() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let limits = ClientLimits {
+            max_quantity: Some(10),
+            max_notional: Some(1500.0),
+        };
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_risk_check(ClientLimitsCheck::new().with_client("client1", limits))
+            .build()
+            .unwrap();
+        let events = engine.subscribe_order_events(OrderEventConfig::default());
+        engine.start().await;
+
+        let resting = Order::new_limit("BTCUSD".to_string(), Side::Sell, 6, 100.0, "client1".to_string());
+        let resting_id = resting.id;
+        engine.submit_order(resting).await.unwrap();
+        let outcome = engine.submit_order_and_wait(Order::new_market("BTCUSD".to_string(), Side::Buy, 2, "client2".to_string())).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+
+        // The amended quantity counts the 2 already filled
+        engine.modify_order(resting_id, "BTCUSD".to_string(), None, Some(12)).await.unwrap();
+        engine.modify_order(resting_id, "BTCUSD".to_string(), Some(Price::from(400.0)), None).await.unwrap();
+        engine.modify_order(resting_id, "BTCUSD".to_string(), Some(Price::from(150.0)), Some(10)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let refused: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                SubscriberEvent::Update(OrderEvent { kind: OrderEventKind::AmendmentRejected { reason }, .. }) => Some(reason),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(refused, vec![
+            "Quantity 12 is above client client1's limit of 10".to_string(),
+            "Notional 2400 is above client client1's limit of 1500".to_string(),
+        ]);
+        assert_eq!(engine.get_metrics().risk_rejections["client_limits"], 2);
+        assert_eq!(engine.get_depth("BTCUSD", 10).unwrap().asks, vec![(Price::from(150.0), 8, 1)]);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_client_order_ids() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_duplicate_window(std::time::Duration::from_secs(60))
+            .build()
+            .unwrap();
+        engine.start().await;
+
+        let order = |side, client: &str, client_order_id| {
+            Order::new_limit("BTCUSD".to_string(), side, 5, 100.0, client.to_string()).with_client_order_id(client_order_id)
+        };
+        engine.submit_order(order(Side::Sell, "client1", "S1")).await.unwrap();
+        // A resend of the same order under a new engine ID must not trade again
+        let outcome = engine.submit_order_and_wait(order(Side::Sell, "client1", "S1")).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+        assert_eq!(outcome.reject_reason.as_deref(), Some("Duplicate client order ID S1"));
+
+        let outcome = engine.submit_order_and_wait(order(Side::Buy, "client2", "S1")).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+        assert_eq!(engine.get_metrics().rejected_orders, 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        let kill_events = engine.subscribe_kill_switch_events();
+
+        let limit = |side, price, client: &str| Order::new_limit("BTCUSD".to_string(), side, 5, price, client.to_string());
+        engine.submit_order(limit(Side::Buy, 99.0, "client1")).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 101.0, "client1")).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 102.0, "client2")).await.unwrap();
+        engine.stage_order(limit(Side::Sell, 103.0, "client1")).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let client1 = KillScope::Client("client1".to_string());
+        engine.engage_kill_switch(client1.clone(), "risk-desk", true).await.unwrap();
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 98.0, "client1")).await;
+        assert_eq!(outcome.reject_reason.as_deref(), Some("Kill switch engaged by risk-desk"));
+        assert_eq!(engine.get_order_book("BTCUSD"), Some((None, Some(Price::from(102.0)), 1)));
+        assert_eq!(engine.staged_orders(), 0);
+        assert_eq!(engine.get_metrics().cancelled_orders, 3);
+        assert_eq!(engine.kill_switches()[0].triggered_by, "risk-desk");
+
+        // The global switch stops everyone but leaves resting orders alone
+        engine.engage_kill_switch(KillScope::Global, "operator", false).await.unwrap();
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 98.0, "client2")).await;
+        assert_eq!(outcome.reject_reason.as_deref(), Some("Kill switch engaged by operator"));
+        assert!(engine.release_kill_switch(&KillScope::Global, "operator"));
+        assert!(!engine.release_kill_switch(&KillScope::Global, "operator"));
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 102.0, "client3")).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+        let outcome = engine.submit_order_and_wait(limit(Side::Buy, 98.0, "client1")).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+
+        let actions: Vec<_> = kill_events
+            .try_iter()
+            .map(|event| match event {
+                SubscriberEvent::Update(event) => (event.scope, event.action, event.triggered_by),
+                SubscriberEvent::DataLoss { .. } => panic!("kill switch events lost"),
+            })
+            .collect();
+        assert_eq!(actions, vec![
+            (client1, KillSwitchAction::Engaged { cancel_resting: true }, "risk-desk".to_string()),
+            (KillScope::Global, KillSwitchAction::Engaged { cancel_resting: false }, "operator".to_string()),
+            (KillScope::Global, KillSwitchAction::Released, "operator".to_string()),
+        ]);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch_blocks_amendments() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let bid = Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 99.0, "client1".to_string());
+        let bid_id = bid.id;
+        engine.submit_order(bid).await.unwrap();
+        engine.submit_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 101.0, "client2".to_string())).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        // Resting orders are left alone but can't be repriced through the touch
+        let client1 = KillScope::Client("client1".to_string());
+        engine.engage_kill_switch(client1.clone(), "risk-desk", false).await.unwrap();
+        let through = Some(Price::from(101.0));
+        engine.modify_order(bid_id, "BTCUSD".to_string(), through, None).await.unwrap();
+        engine
+            .modify_orders("BTCUSD".to_string(), vec![Amendment { order_id: bid_id, new_price: through, new_quantity: None }])
+            .await
+            .unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(trade_receiver.try_recv().is_err());
+        assert_eq!(engine.get_order_book("BTCUSD"), Some((Some(Price::from(99.0)), Some(Price::from(101.0)), 2)));
+
+        assert!(engine.release_kill_switch(&client1, "risk-desk"));
+        engine.modify_order(bid_id, "BTCUSD".to_string(), through, None).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(trade_receiver.try_recv().unwrap().quantity, 5);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_disconnect() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender).with_cancel_on_disconnect(true).build().unwrap();
+        engine.set_cancel_on_disconnect("client2", false);
+        engine.start().await;
+
+        let limit = |price, client: &str| Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, price, client.to_string());
+        engine.submit_order(limit(101.0, "client1")).await.unwrap();
+        engine.submit_order(limit(102.0, "client1")).await.unwrap();
+        engine.submit_order(limit(103.0, "client2")).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        assert!(engine.client_disconnected("client1").await.unwrap());
+        assert!(!engine.client_disconnected("client2").await.unwrap());
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert_eq!(engine.get_order_book("BTCUSD"), Some((None, Some(Price::from(103.0)), 1)));
+        assert_eq!(engine.get_metrics().cancelled_orders, 2);
+
+        // Reconnected clients trade as before
+        let outcome = engine.submit_order_and_wait(Order::new_market("BTCUSD".to_string(), Side::Buy, 5, "client1".to_string())).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_message_throttle() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_message_throttle(ThrottlePolicy::new(0.001, 3))
+            .build()
+            .unwrap();
+        let throttled = engine.subscribe_throttle_events().unwrap();
+        engine.start().await;
+
+        let limit = |price, client: &str| Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, price, client.to_string());
+        let first = limit(101.0, "client1");
+        let first_id = first.id;
+        engine.submit_order(first).await.unwrap();
+        engine.submit_order(limit(102.0, "client1")).await.unwrap();
+        engine.cancel_order(first_id, "BTCUSD".to_string()).await.unwrap();
+        // The cancel used up the last message of the burst
+        let outcome = engine.submit_order_and_wait(limit(103.0, "client1")).await;
+        assert_eq!(outcome.reject_reason.as_deref(), Some("Message rate limit exceeded"));
+        let outcome = engine.submit_order_and_wait(Order::new_market("BTCUSD".to_string(), Side::Buy, 5, "client2".to_string())).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+
+        let metrics = engine.get_metrics();
+        assert_eq!((metrics.rejected_orders, metrics.throttled_messages["client1"]), (1, 1));
+        match throttled.try_recv() {
+            Ok(SubscriberEvent::Update(event)) => {
+                assert_eq!(event.client_id, "client1");
+                assert_eq!(event.kind, ThrottleEventKind::Throttled { message: MessageKind::NewOrder });
+            }
+            other => panic!("expected a throttle event, got {:?}", other),
+        }
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_short_sale_restriction() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let limit = |side, price, client: &str| Order::new_limit("BTCUSD".to_string(), side, 5, price, client.to_string());
+        engine.submit_order(limit(Side::Buy, 100.0, "client1")).await.unwrap();
+        engine.set_short_sale_restriction("BTCUSD".to_string(), true).await.unwrap();
+
+        let outcome = engine.submit_order_and_wait(limit(Side::Sell, 100.0, "client2").with_sell_short(true)).await;
+        assert_eq!(outcome.reject_reason.as_deref(), Some("Short sale must be priced above the best bid while restricted"));
+        let market = Order::new_market("BTCUSD".to_string(), Side::Sell, 5, "client2".to_string()).with_sell_short(true);
+        assert_eq!(engine.submit_order_and_wait(market).await.status, OrderStatus::Rejected);
+        // Long sales are unaffected
+        assert_eq!(engine.submit_order_and_wait(limit(Side::Sell, 100.0, "client2")).await.status, OrderStatus::Filled);
+
+        let short = limit(Side::Sell, 101.0, "client2").with_sell_short(true);
+        let short_id = short.id;
+        engine.submit_order(short).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 100.5, "client1")).await.unwrap();
+        // Repricing the short sale down onto the bid is refused
+        engine
+            .modify_order(short_id, "BTCUSD".to_string(), Some(Price::from(100.5)), None)
+            .await
+            .unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert!(engine.is_short_sale_restricted("BTCUSD"));
+        assert_eq!(
+            engine.get_order_book("BTCUSD"),
+            Some((Some(Price::from(100.5)), Some(Price::from(101.0)), 2))
+        );
+
+        engine.set_short_sale_restriction("BTCUSD".to_string(), false).await.unwrap();
+        let outcome = engine.submit_order_and_wait(limit(Side::Sell, 100.5, "client2").with_sell_short(true)).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_drop_copy() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let drop_copy = engine.subscribe_drop_copy(DropCopyConfig::default());
+        engine.start().await;
+
+        engine
+            .submit_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client1".to_string()))
+            .await
+            .unwrap();
+        let outcome = engine
+            .submit_order_and_wait(Order::new_market("BTCUSD".to_string(), Side::Buy, 5, "client2".to_string()))
+            .await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+        let outcome = engine
+            .submit_order_and_wait(Order::new_limit("ETHUSD".to_string(), Side::Buy, 0, 10.0, "client3".to_string()))
+            .await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+
+        let messages: Vec<_> = drop_copy
+            .try_iter()
+            .map(|message| match message {
+                SubscriberEvent::Update(message) => message,
+                SubscriberEvent::DataLoss { .. } => panic!("drop copy lost messages"),
+            })
+            .collect();
+        assert_eq!(messages.iter().map(|m| m.sequence).collect::<Vec<_>>(), (1..=messages.len() as u64).collect::<Vec<_>>());
+        let entries: Vec<_> = messages
+            .iter()
+            .map(|message| match &message.entry {
+                DropCopyEntry::OrderEvent(event) => (event.client_id.as_str(), Some(event.kind.clone())),
+                DropCopyEntry::Execution(trade) => {
+                    assert_eq!((trade.buy_client_id.as_str(), trade.sell_client_id.as_str(), trade.quantity), ("client2", "client1", 5));
+                    ("client2", None)
+                }
+            })
+            .collect();
+        assert_eq!(entries, vec![
+            ("client1", Some(OrderEventKind::Accepted)),
+            ("client2", Some(OrderEventKind::Accepted)),
+            ("client2", Some(OrderEventKind::Filled { quantity: 5, price: Price::from(100.0) })),
+            ("client1", Some(OrderEventKind::Filled { quantity: 5, price: Price::from(100.0) })),
+            // The execution follows the fills it caused
+            ("client2", None),
+            ("client3", Some(OrderEventKind::Rejected { reason: "Quantity is zero".to_string() })),
+        ]);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_client_symbols() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_client_symbols("client1", ["BTCUSD"])
+            .build()
+            .unwrap();
+        engine.start().await;
+
+        // Market orders into empty books, which get past validation only if permitted
+        let reason = |symbol: &str, client: &str| {
+            let order = Order::new_market(symbol.to_string(), Side::Buy, 5, client.to_string());
+            async { engine.submit_order_and_wait(order).await.reject_reason.unwrap() }
+        };
+        assert_eq!(reason("BTCUSD", "client1").await, "No liquidity");
+        assert_eq!(reason("ETHUSD", "client1").await, "Client client1 is not permitted to trade ETHUSD");
+        // Clients without a list may trade anything
+        assert_eq!(reason("ETHUSD", "client2").await, "No liquidity");
+
+        engine.set_client_symbols("client2", ["BTCUSD", "SOLUSD"]);
+        assert_eq!(engine.get_client_symbols("client2"), Some(vec!["BTCUSD".to_string(), "SOLUSD".to_string()]));
+        assert_eq!(reason("ETHUSD", "client2").await, "Client client2 is not permitted to trade ETHUSD");
+        assert!(engine.remove_client_symbols("client2"));
+        assert_eq!(engine.get_client_symbols("client2"), None);
+        assert_eq!(reason("ETHUSD", "client2").await, "No liquidity");
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mm_protection() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let limits = ProtectionLimits::new(std::time::Duration::from_secs(60)).with_fills(2);
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_mm_protection(ProtectionGroup::new("BTC", ["BTCUSD", "BTCEUR"], limits))
+            .build()
+            .unwrap();
+        let events = engine.subscribe_protection_events().unwrap();
+        engine.start().await;
+
+        let limit = |symbol: &str, side, price| Order::new_limit(symbol.to_string(), side, 5, price, "client1".to_string());
+        engine.submit_order(limit("BTCUSD", Side::Sell, 101.0)).await.unwrap();
+        engine.submit_order(limit("BTCUSD", Side::Sell, 102.0)).await.unwrap();
+        engine.submit_order(limit("BTCUSD", Side::Buy, 99.0)).await.unwrap();
+        engine.submit_order(limit("BTCEUR", Side::Buy, 90.0)).await.unwrap();
+        engine.submit_order(limit("ETHUSD", Side::Buy, 10.0)).await.unwrap();
+        for _ in 0..2 {
+            let order = Order::new_market("BTCUSD".to_string(), Side::Buy, 5, "client2".to_string());
+            assert_eq!(engine.submit_order_and_wait(order).await.status, OrderStatus::Filled);
+        }
+
+        // Both clients reached two fills; only client1 had orders left in the group
+        let breach = ProtectionBreach::Fills { fills: 2, limit: 2 };
+        let tripped: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                SubscriberEvent::Update(event) => (event.client_id, event.group, event.breach, event.cancelled),
+                SubscriberEvent::DataLoss { .. } => panic!("protection events lost"),
+            })
+            .collect();
+        assert_eq!(tripped, vec![
+            ("client2".to_string(), "BTC".to_string(), breach, 0),
+            ("client1".to_string(), "BTC".to_string(), breach, 2),
+        ]);
+        assert_eq!(engine.get_order_book("BTCUSD"), Some((None, None, 0)));
+        assert_eq!(engine.get_order_book("BTCEUR"), Some((None, None, 0)));
+        assert_eq!(engine.get_order_book("ETHUSD"), Some((Some(Price::from(10.0)), None, 1)));
+        assert_eq!(engine.get_metrics().cancelled_orders, 2);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_client_positions() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let limit = |side, quantity, price, client: &str| Order::new_limit("BTCUSD".to_string(), side, quantity, price, client.to_string());
+        engine.submit_order(limit(Side::Sell, 10, 100.0, "client2")).await.unwrap();
+        engine.submit_order_and_wait(limit(Side::Buy, 6, 100.0, "client1")).await;
+        engine.submit_order(limit(Side::Sell, 3, 99.0, "client1")).await.unwrap();
+        engine.submit_order_and_wait(limit(Side::Buy, 3, 99.0, "client2")).await;
+
+        let position = engine.get_position("client1", "BTCUSD");
+        assert_eq!((position.net, position.bought, position.sold), (3, 6, 3));
+        let position = engine.get_position("client2", "BTCUSD");
+        assert_eq!((position.net, position.volume()), (-3, 9));
+        assert_eq!(engine.get_position("client1", "ETHUSD"), Position::default());
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_stop_drains_matching_thread() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+
+        // Stopping waits for the commands queued ahead of the shutdown
+        engine.stop().await;
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+        assert!(engine.submit_order(Order::new_market("BTCUSD".to_string(), Side::Buy, 1, "client1".to_string())).await.is_err());
+
+        // The engine can be started again on a new matching thread
+        engine.start().await;
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        engine.stop().await;
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stop_signals_background_tasks() {
+        use crate::market_data::HeatmapConfig;
+        use crossbeam::channel::TryRecvError;
+
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+
+        // Stopping an engine that never started leaves nothing behind to stop the next start
+        engine.stop().await;
+        engine.start().await;
+        assert!(engine.is_running());
+
+        let config = HeatmapConfig {
+            interval: tokio::time::Duration::from_secs(60),
+            ..HeatmapConfig::default()
+        };
+        let matrices = engine.export_heatmap("BTCUSD".to_string(), config).await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+
+        // The export is mid-interval, but ends as soon as the engine stops
+        engine.stop().await;
+        assert!(!engine.is_running());
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        assert_eq!(matrices.try_recv().unwrap_err(), TryRecvError::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_soak_audit() {
+        use crate::market_data::SubscriberEvent;
+
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        let reports = engine.start_soak_audit(tokio::time::Duration::from_millis(20));
+
+        for i in 0..10 {
+            let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0 + i as f64, "client1".to_string());
+            engine.submit_order(sell).await.unwrap();
+        }
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 25, 50005.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert_eq!(trade_receiver.try_iter().count(), 3);
+
+        let reports: Vec<AuditReport> = reports
+            .try_iter()
+            .map(|event| match event {
+                SubscriberEvent::Update(report) => report,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert!(reports.len() >= 2);
+        assert_eq!(reports.last().unwrap().books, 1);
+        assert!(reports.iter().all(|report| report.violations.is_empty()));
+        assert_eq!(reports.last().unwrap().total_violations, 0);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_report() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        assert!(engine.stop().await.is_none());
+        engine.start().await;
+        // Nobody is listening for trades
+        drop(trade_receiver);
+
+        let limit = |side, quantity| Order::new_limit("BTCUSD".to_string(), side, quantity, 50000.0, "client1".to_string());
+        engine.submit_order(limit(Side::Sell, 1)).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 1)).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 5)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+
+        // Held by the speed bump when the engine stops
+        let bump = std::time::Duration::from_secs(3600);
+        engine.set_speed_bump("BTCUSD".to_string(), Some(SpeedBump::new(bump, bump)));
+        engine.submit_order(limit(Side::Buy, 2)).await.unwrap();
+
+        let report = engine.stop().await.unwrap();
+        assert_eq!(report.cancels_issued, 1);
+        assert_eq!(report.last_sequence, 5);
+        assert_eq!(report.unsent_events, 1);
+        assert!(!report.is_clean());
+        assert_eq!(report.resting_orders, 1);
+        assert_eq!(engine.get_metrics().cancelled_orders, 1);
+        assert!(engine.stop().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_busy_spin_matching() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender).with_wait_strategy(WaitStrategy::BusySpin);
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+
+        let report = engine.stop().await.unwrap();
+        assert_eq!(report.last_sequence, 3);
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_matching_thread_config() {
+        let (trade_sender, trade_receiver) = unbounded();
+        // Priority 0 is outside the realtime range: refused and logged, but matching still runs
+        let config = EngineConfig {
+            core_affinity: Some(0),
+            realtime_priority: Some(0),
+            numa_node: Some(0),
+        };
+        let engine = ExecutionEngine::new(trade_sender).with_config(config);
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+
+        engine.stop().await;
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restart_after_stop() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 2, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        engine.stop().await.unwrap();
+        assert!(matches!(
+            engine.submit_order(Order::new_market("BTCUSD".to_string(), Side::Buy, 1, "client2".to_string())).await,
+            Err(EngineError::EngineStopped)
+        ));
+
+        // The book survives the restart
+        engine.start().await;
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap().2, 1);
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        assert_eq!(engine.stop().await.unwrap().last_sequence, 4);
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+
+        // A stop that is never awaited still drains before the next start matches anything
+        engine.start().await;
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        let _ = tokio::time::timeout(std::time::Duration::ZERO, engine.stop()).await;
+        engine.start().await;
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+        engine.submit_order(buy).await.unwrap();
+        let report = engine.stop().await.unwrap();
+        assert_eq!(report.last_sequence, 8);
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+        assert_eq!(engine.get_order_book("BTCUSD").unwrap(), (Some(Price::from(50000.0)), None, 1));
+    }
+
+    #[tokio::test]
+    async fn test_engine_builder() {
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_queue_capacity(16)
+            .with_latency_recording(false)
+            .with_order_logging(false)
+            .with_matching_algorithm(MatchingAlgorithm::ProRata { top_order_percent: 0 })
+            .with_instrument(InstrumentConfig::new("ETHUSD".to_string()).with_lot_size(10))
+            .with_risk_limits(RiskLimits { max_order_quantity: Some(100), max_order_notional: Some(1_000_000.0) })
+            .build()
+            .unwrap();
+        engine.start().await;
+
+        // Pre-registered instrument rules apply from the start
+        let odd_lot = Order::new_limit("ETHUSD".to_string(), Side::Buy, 5, 3000.0, "client1".to_string());
+        assert!(matches!(engine.submit_order(odd_lot).await, Err(EngineError::InvalidLotSize { .. })));
+
+        let big = Order::new_limit("BTCUSD".to_string(), Side::Buy, 101, 1.0, "client1".to_string());
+        assert!(matches!(engine.submit_order(big).await, Err(EngineError::AboveMaxQuantity { .. })));
+        let rich = Order::new_limit("BTCUSD".to_string(), Side::Buy, 100, 50000.0, "client1".to_string());
+        assert!(matches!(engine.submit_order(rich).await, Err(EngineError::AboveMaxNotional { .. })));
+
+        // Unregistered symbols match pro rata: 8 split 3:1 across the resting sells
+        let sell = |quantity, client: &str| Order::new_limit("BTCUSD".to_string(), Side::Sell, quantity, 100.0, client.to_string());
+        engine.submit_order(sell(30, "client1")).await.unwrap();
+        engine.submit_order(sell(10, "client2")).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 8, 100.0, "client3".to_string());
+        engine.submit_order(buy).await.unwrap();
+        engine.stop().await.unwrap();
+
+        let fills: Vec<u64> = trade_receiver.try_iter().map(|trade| trade.quantity).collect();
+        assert_eq!(fills, vec![6, 2]);
+        assert_eq!(engine.get_metrics().p50_latency_micros, 0);
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let duplicate = ExecutionEngine::builder(trade_sender)
+            .with_instrument(InstrumentConfig::new("ETHUSD".to_string()))
+            .with_instrument(InstrumentConfig::new("ETHUSD".to_string()))
+            .build();
+        assert!(matches!(duplicate, Err(EngineError::InstrumentExists(_))));
+    }
+
+    #[tokio::test]
+    async fn test_trade_sink_overflow() {
+        let sell = || Order::new_limit("BTCUSD".to_string(), Side::Sell, 3, 50000.0, "client1".to_string());
+        let buy = || Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+
+        // A full sink drops trades by default
+        let (trade_sender, trade_receiver) = crossbeam::channel::bounded(1);
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        engine.submit_order(sell()).await.unwrap();
+        for _ in 0..3 {
+            engine.submit_order(buy()).await.unwrap();
+        }
+        assert_eq!(engine.stop().await.unwrap().unsent_events, 2);
+        assert_eq!(trade_receiver.len(), 1);
+
+        // Buffered trades reach the sink in order as it frees up
+        let (trade_sender, trade_receiver) = crossbeam::channel::bounded(1);
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_trade_overflow(TradeOverflow::Buffer { capacity: 8 })
+            .build()
+            .unwrap();
+        engine.start().await;
+        engine.submit_order(sell()).await.unwrap();
+        let mut buy_ids = Vec::new();
+        for _ in 0..3 {
+            let order = buy();
+            buy_ids.push(order.id);
+            engine.submit_order(order).await.unwrap();
+        }
+        let timeout = std::time::Duration::from_secs(1);
+        let delivered: Vec<_> =
+            (0..3).map(|_| trade_receiver.recv_timeout(timeout).unwrap().buy_order_id).collect();
+        assert_eq!(delivered, buy_ids);
+        assert!(engine.stop().await.unwrap().is_clean());
+
+        // Any sink will do, here a tokio channel
+        let (trade_sender, mut trade_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        engine.submit_order(sell()).await.unwrap();
+        engine.submit_order(buy()).await.unwrap();
+        assert_eq!(trade_receiver.recv().await.unwrap().quantity, 1);
+        engine.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_trade_stream() {
+        let (trade_sender, mut persistence) = tokio::sync::broadcast::channel(64);
+        let mut gateway = trade_sender.subscribe();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 2, 50000.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        for _ in 0..2 {
+            let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50000.0, "client2".to_string());
+            engine.submit_order(buy).await.unwrap();
+        }
+
+        for consumer in [&mut persistence, &mut gateway] {
+            let first = consumer.recv().await.unwrap();
+            let second = consumer.recv().await.unwrap();
+            assert_ne!(first.id, second.id);
+        }
+        assert!(engine.stop().await.unwrap().is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_order_events() {
+        use crate::market_data::SubscriberEvent;
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let all = engine.subscribe_order_events(OrderEventConfig::default());
+        let own = engine.subscribe_order_events(OrderEventConfig {
+            client_id: Some("client1".to_string()),
+            ..OrderEventConfig::default()
+        });
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client1".to_string());
+        let sell_id = sell.id;
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 2, 100.0, "client2".to_string());
+        let buy_id = buy.id;
+        engine.submit_order(buy).await.unwrap();
+        let empty = Order::new_limit("BTCUSD".to_string(), Side::Buy, 0, 100.0, "client2".to_string());
+        engine.submit_order(empty).await.unwrap();
+        engine.cancel_order(sell_id, "BTCUSD".to_string()).await.unwrap();
+        engine.stop().await.unwrap();
+
+        let events = |receiver: &crossbeam::channel::Receiver<SubscriberEvent<OrderEvent>>| -> Vec<(uuid::Uuid, OrderEventKind)> {
+            receiver
+                .try_iter()
+                .map(|event| match event {
+                    SubscriberEvent::Update(event) => (event.order_id, event.kind),
+                    SubscriberEvent::DataLoss { .. } => panic!("events lost"),
+                })
+                .collect()
+        };
+        let price = Price::from(100.0);
+        let partial = OrderEventKind::PartiallyFilled { quantity: 2, price, filled_quantity: 2, remaining: 3 };
+        assert_eq!(
+            events(&own),
+            vec![(sell_id, OrderEventKind::Accepted), (sell_id, partial.clone()), (sell_id, OrderEventKind::Cancelled)]
+        );
+
+        let all = events(&all);
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[1], (buy_id, OrderEventKind::Accepted));
+        assert_eq!(all[2], (buy_id, OrderEventKind::Filled { quantity: 2, price }));
+        assert_eq!(all[3], (sell_id, partial));
+        assert!(matches!(&all[4].1, OrderEventKind::Rejected { reason } if reason == "Quantity is zero"));
+    }
+
+    #[tokio::test]
+    async fn test_execution_reports() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_instrument(InstrumentConfig::new("ETHUSD".to_string()).with_lot_size(10))
+            .build()
+            .unwrap();
+        engine.start().await;
+
+        let sell = |quantity, price| Order::new_limit("BTCUSD".to_string(), Side::Sell, quantity, price, "client1".to_string());
+        engine.submit_order(sell(1, 100.0)).await.unwrap();
+        engine.submit_order(sell(1, 101.0)).await.unwrap();
+
+        let buy = Order::new_market("BTCUSD".to_string(), Side::Buy, 2, "client2".to_string());
+        let mut reports = engine.submit_order_with_reports(buy).await.unwrap();
+        let mut kinds = Vec::new();
+        while let Some(report) = reports.recv().await {
+            kinds.push(report.kind);
+        }
+        assert_eq!(
+            kinds,
+            vec![
+                OrderEventKind::Accepted,
+                OrderEventKind::PartiallyFilled {
+                    quantity: 1,
+                    price: Price::from(100.0),
+                    filled_quantity: 1,
+                    remaining: 1
+                },
+                OrderEventKind::Filled { quantity: 1, price: Price::from(101.0) },
+            ]
+        );
+
+        // Refused at submission: the error says why, and no stream is left behind
+        let odd_lot = Order::new_limit("ETHUSD".to_string(), Side::Buy, 5, 3000.0, "client2".to_string());
+        assert!(matches!(
+            engine.submit_order_with_reports(odd_lot).await,
+            Err(EngineError::InvalidLotSize { .. })
+        ));
+        engine.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_blocking_engine_without_runtime() {
+        use crate::market_data::SubscriberEvent;
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = BlockingEngine::new(ExecutionEngine::new(trade_sender).with_queue_capacity(1));
+        engine.start();
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 100.0, "client1".to_string());
+        let sell_id = sell.id;
+        engine.submit_order_blocking(sell).unwrap();
+        // Waits for the queue slot the sell holds
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 100.0, "client2".to_string());
+        engine.submit_order_blocking(buy).unwrap();
+
+        let first = engine.next_event(std::time::Duration::from_secs(1));
+        assert!(matches!(first, Some(SubscriberEvent::Update(OrderEvent { order_id, kind: OrderEventKind::Accepted, .. })) if order_id == sell_id));
+        assert!(engine.stop().unwrap().is_clean());
+        assert_eq!(engine.poll_events().len(), 3);
+        assert_eq!(trade_receiver.try_iter().count(), 1);
+        assert_eq!(engine.engine().get_order_book("BTCUSD").unwrap().2, 0);
+    }
+
+    #[tokio::test]
+    async fn test_submit_order_and_wait() {
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+
+        let sell = |quantity, price| Order::new_limit("BTCUSD".to_string(), Side::Sell, quantity, price, "client1".to_string());
+        engine.submit_order(sell(1, 100.0)).await.unwrap();
+        engine.submit_order(sell(3, 102.0)).await.unwrap();
+
+        let buy = Order::new_market("BTCUSD".to_string(), Side::Buy, 4, "client2".to_string());
+        let outcome = engine.submit_order_and_wait(buy).await;
+        assert_eq!(outcome.status, OrderStatus::Filled);
+        assert_eq!(outcome.filled_quantity, 4);
+        assert_eq!(outcome.average_price, Some(101.5));
+
+        // No liquidity left for a market order
+        let buy = Order::new_market("BTCUSD".to_string(), Side::Buy, 1, "client2".to_string());
+        let outcome = engine.submit_order_and_wait(buy).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+        assert_eq!(outcome.reject_reason.as_deref(), Some("No liquidity"));
+
+        engine.stop().await.unwrap();
+        let outcome = engine.submit_order_and_wait(sell(1, 100.0)).await;
+        assert_eq!(outcome.status, OrderStatus::Rejected);
+        assert_eq!(outcome.reject_reason, Some(EngineError::EngineStopped.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_command_sequence_numbers() {
+        use crate::market_data::SubscriberEvent;
+        let (trade_sender, trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_instrument(InstrumentConfig::new("ETHUSD".to_string()).with_lot_size(10))
+            .build()
+            .unwrap();
+        let events = engine.subscribe_order_events(OrderEventConfig::default());
+        engine.start().await;
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 100.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 100.0, "client2".to_string());
+        engine.submit_order_and_wait(buy).await;
+        // Refused before it reaches the queue: no sequence number
+        let odd_lot = Order::new_limit("ETHUSD".to_string(), Side::Buy, 5, 100.0, "client2".to_string());
+        assert!(engine.submit_order(odd_lot).await.is_err());
+        assert_eq!(engine.stop().await.unwrap().last_sequence, 3);
+
+        // Numbering carries on after a restart
+        engine.start().await;
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 101.0, "client1".to_string());
+        engine.submit_order(sell).await.unwrap();
+        engine.stop().await.unwrap();
+
+        assert_eq!(trade_receiver.try_iter().map(|trade| trade.sequence).collect::<Vec<_>>(), vec![2]);
+        let sequences: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                SubscriberEvent::Update(event) => event.sequence,
+                SubscriberEvent::DataLoss { .. } => panic!("events lost"),
+            })
+            .collect();
+        // Accepted, accepted and both fills, rejected, then accepted after the restart
+        assert_eq!(sequences, vec![1, 2, 2, 2, 0, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_bbo_updates() {
+        use crate::market_data::{FeedConfig, SubscriberEvent};
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let updates = engine.subscribe_bbo_updates(FeedConfig::default());
+        let other = engine.subscribe_bbo_updates(FeedConfig {
+            symbol: Some("ETHUSD".to_string()),
+            ..FeedConfig::default()
+        });
+        engine.start().await;
+
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+        engine.submit_order(limit(Side::Sell, 1, 100.0)).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 2, 100.0)).await.unwrap();
+        // Behind the best ask: no change
+        engine.submit_order(limit(Side::Sell, 1, 101.0)).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 1, 99.0)).await.unwrap();
+        let sweep = Order::new_market("BTCUSD".to_string(), Side::Buy, 3, "client2".to_string());
+        engine.submit_order_and_wait(sweep).await;
+        engine.stop().await;
+
+        let updates: Vec<_> = updates
+            .try_iter()
+            .map(|update| match update {
+                SubscriberEvent::Update(update) => (update.bbo.bid, update.bbo.ask, update.sequence),
+                SubscriberEvent::DataLoss { .. } => panic!("updates lost"),
+            })
+            .collect();
+        let level = |price: f64, quantity| Some((Price::from(price), quantity));
+        assert_eq!(
+            updates,
+            vec![
+                (None, level(100.0, 1), 1),
+                (None, level(100.0, 3), 2),
+                (level(99.0, 1), level(100.0, 3), 4),
+                (level(99.0, 1), level(101.0, 1), 5),
+            ]
+        );
+        assert!(other.try_iter().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_level_updates() {
+        use crate::market_data::{FeedConfig, SubscriberEvent};
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let updates = engine.subscribe_level_updates(FeedConfig {
+            symbol: Some("BTCUSD".to_string()),
+            ..FeedConfig::default()
+        });
+        engine.start().await;
+
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+        let resting = limit(Side::Sell, 1, 100.0);
+        let resting_id = resting.id;
+        engine.submit_order(resting).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 2, 101.0)).await.unwrap();
+        engine.submit_order(Order::new_limit("ETHUSD".to_string(), Side::Buy, 1, 10.0, "client1".to_string())).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 2, 101.0)).await.unwrap();
+        // Already filled: the cancel changes nothing
+        engine.cancel_order(resting_id, "BTCUSD".to_string()).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 1, 99.0)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        engine.stop().await;
+
+        let updates: Vec<_> = updates
+            .try_iter()
+            .map(|update| match update {
+                SubscriberEvent::Update(update) => {
+                    let changes: Vec<_> = update
+                        .changes
+                        .iter()
+                        .map(|change| (change.side, change.price.to_f64(), change.action, change.quantity))
+                        .collect();
+                    (update.sequence, changes)
+                }
+                SubscriberEvent::DataLoss { .. } => panic!("updates lost"),
+            })
+            .collect();
+        assert_eq!(
+            updates,
+            vec![
+                (1, vec![(Side::Sell, 100.0, LevelAction::Add, 1)]),
+                (2, vec![(Side::Sell, 101.0, LevelAction::Add, 2)]),
+                // Takes the best ask, then one lot of the next
+                (
+                    3,
+                    vec![
+                        (Side::Sell, 100.0, LevelAction::Delete, 0),
+                        (Side::Sell, 101.0, LevelAction::Modify, 1),
+                    ]
+                ),
+                (4, vec![(Side::Buy, 99.0, LevelAction::Add, 1)]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_order_updates_as_itch() {
+        use crate::market_data::{FeedConfig, ItchEncoder, ItchMessage, SubscriberEvent};
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let updates = engine.subscribe_order_updates(FeedConfig::default());
+        engine.start().await;
+
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+        let resting = limit(Side::Sell, 3, 100.0);
+        let resting_id = resting.id;
+        engine.submit_order(resting).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 1, 100.0)).await.unwrap();
+        engine.cancel_order(resting_id, "BTCUSD".to_string()).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        engine.stop().await;
+
+        let updates: Vec<_> = updates
+            .try_iter()
+            .map(|update| match update {
+                SubscriberEvent::Update(update) => update,
+                SubscriberEvent::DataLoss { .. } => panic!("updates lost"),
+            })
+            .collect();
+        let changes: Vec<_> = updates.iter().flat_map(|update| update.changes.clone()).collect();
+        assert!(matches!(
+            changes[..],
+            [
+                OrderChange::Added { order_id: a, quantity: 3, .. },
+                OrderChange::Executed { order_id: e, quantity: 1, .. },
+                OrderChange::Removed { order_id: d },
+            ] if [a, e, d] == [resting_id; 3]
+        ));
+
+        let mut encoder = ItchEncoder::new();
+        let messages: Vec<ItchMessage> = updates.iter().flat_map(|update| encoder.encode_order_update(update)).collect();
+        let types: Vec<u8> = messages.iter().map(ItchMessage::message_type).collect();
+        assert_eq!(types, b"AED");
+    }
+
+    #[tokio::test]
+    async fn test_market_data_subscriptions() {
+        use crate::engine::{Channel, Subscription};
+        use std::time::Duration;
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.start().await;
+        let (mut subscriptions, messages) = engine.market_data_subscriptions();
+        let subscription = |channel, symbol: &str| Subscription {
+            channel,
+            symbol: symbol.to_string(),
+            interval_secs: None,
+        };
+
+        assert!(subscriptions.subscribe(subscription(Channel::Trades, "BTCUSD")));
+        assert!(!subscriptions.subscribe(subscription(Channel::Trades, "BTCUSD")));
+        assert!(subscriptions.subscribe(subscription(Channel::Bbo, "ETHUSD")));
+        let next = || messages.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(next(), MarketDataMessage::Subscribed { .. }));
+        assert!(matches!(next(), MarketDataMessage::Subscribed { .. }));
+
+        let limit = |symbol: &str, side, price| Order::new_limit(symbol.to_string(), side, 1, price, "client1".to_string());
+        engine.submit_order(limit("BTCUSD", Side::Sell, 100.0)).await.unwrap();
+        engine.submit_order(limit("BTCUSD", Side::Buy, 100.0)).await.unwrap();
+        engine.submit_order(limit("ETHUSD", Side::Buy, 10.0)).await.unwrap();
+
+        // Neither the BTCUSD book nor ETHUSD trades were asked for
+        assert!(matches!(next(), MarketDataMessage::Trade(print) if print.symbol == "BTCUSD"));
+        assert!(matches!(next(), MarketDataMessage::Bbo(update) if update.symbol == "ETHUSD"));
+
+        assert!(subscriptions.unsubscribe(&subscription(Channel::Bbo, "ETHUSD")));
+        assert!(!subscriptions.unsubscribe(&subscription(Channel::Bbo, "ETHUSD")));
+        assert!(matches!(next(), MarketDataMessage::Unsubscribed { .. }));
+        engine.submit_order(limit("ETHUSD", Side::Buy, 11.0)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert!(messages.try_recv().is_err());
+        assert_eq!(subscriptions.subscriptions().count(), 1);
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_market_data_stats() {
+        use crate::market_data::{FeedConfig, SlowConsumerPolicy};
+
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let _prints = engine.subscribe_trade_prints(FeedConfig {
+            buffer: 2,
+            slow_consumer: SlowConsumerPolicy::DropOldest,
+            ..FeedConfig::default()
+        });
+        let levels = engine.subscribe_level_updates(FeedConfig::default());
+        engine.start().await;
+
+        // Three prints into a buffer of two nobody reads
+        let limit = |side, price| Order::new_limit("BTCUSD".to_string(), side, 1, price, "client1".to_string());
+        for price in [100.0, 101.0, 102.0] {
+            engine.submit_order(limit(Side::Sell, price)).await.unwrap();
+        }
+        let sweep = Order::new_market("BTCUSD".to_string(), Side::Buy, 3, "client2".to_string());
+        engine.submit_order_and_wait(sweep).await;
+        levels.try_iter().count();
+
+        let stats = engine.market_data_stats();
+        assert_eq!(stats.trades.delivered, 3);
+        let prints = &stats.trades.subscribers[0];
+        assert_eq!((prints.queue_depth, prints.buffer, prints.dropped, prints.slow), (2, 2, 2, true));
+        assert!(stats.trades.max_lag_micros >= stats.trades.avg_lag_micros);
+        assert_eq!(stats.levels.delivered, 4);
+        let levels = &stats.levels.subscribers[0];
+        assert_eq!((levels.queue_depth, levels.dropped, levels.slow), (0, 0, false));
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_trade_prints() {
+        use crate::market_data::{FeedConfig, SubscriberEvent, TradeConditions};
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        engine.register_instrument(InstrumentConfig::new("BTCUSD".to_string()).with_block_size(10)).unwrap();
+        let prints = engine.subscribe_trade_prints(FeedConfig::default());
+        engine.start().await;
+
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::PreOpen).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 2, 100.0)).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 2, 100.0)).await.unwrap();
+        engine.set_trading_phase("BTCUSD".to_string(), TradingPhase::Continuous).await.unwrap();
+
+        engine.submit_order(limit(Side::Sell, 1, 101.0)).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 10, 102.0)).await.unwrap();
+        let sweep = Order::new_market("BTCUSD".to_string(), Side::Buy, 11, "client2".to_string());
+        engine.submit_order_and_wait(sweep).await;
+        engine.stop().await;
+
+        let prints: Vec<_> = prints
+            .try_iter()
+            .map(|print| match print {
+                SubscriberEvent::Update(print) => (print.quantity, print.aggressor, print.conditions, print.sequence),
+                SubscriberEvent::DataLoss { .. } => panic!("prints lost"),
+            })
+            .collect();
+        let conditions = |auction, block| TradeConditions {
+            auction,
+            dark: false,
+            block,
+        };
+        assert_eq!(
+            prints,
+            vec![
+                (2, None, conditions(true, false), 1),
+                (1, Some(Side::Buy), conditions(false, false), 2),
+                (10, Some(Side::Buy), conditions(false, true), 3),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_book_snapshots() {
+        use crate::market_data::{FeedConfig, SubscriberEvent};
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::builder(trade_sender)
+            .with_snapshot_interval(std::time::Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let snapshots = engine.subscribe_book_snapshots(FeedConfig::default());
+        assert!(engine.publish_book_snapshot("BTCUSD").is_none());
+        engine.start().await;
+
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+        engine.submit_order(limit(Side::Buy, 1, 99.0)).await.unwrap();
+        engine.submit_order(limit(Side::Buy, 2, 98.0)).await.unwrap();
+        engine.submit_order(limit(Side::Sell, 3, 101.0)).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let on_demand = engine.publish_book_snapshot("BTCUSD").unwrap();
+        assert_eq!(on_demand.sequence, 3);
+        assert_eq!(
+            on_demand.depth.bids,
+            vec![(Price::from(99.0), 1, 1), (Price::from(98.0), 2, 1)]
+        );
+        assert_eq!(on_demand.depth.asks, vec![(Price::from(101.0), 3, 1)]);
+        engine.stop().await;
+
+        let snapshots: Vec<_> = snapshots
+            .try_iter()
+            .map(|snapshot| match snapshot {
+                SubscriberEvent::Update(snapshot) => snapshot,
+                SubscriberEvent::DataLoss { .. } => panic!("snapshots lost"),
+            })
+            .collect();
+        // Periodic snapshots, then the one on demand
+        assert!(snapshots.len() >= 2);
+        assert!(snapshots.windows(2).all(|pair| pair[0].sequence <= pair[1].sequence));
+        assert_eq!(snapshots.last(), Some(&on_demand));
+    }
+
+    #[tokio::test]
+    async fn test_conflated_bbo_updates() {
+        use crate::market_data::{FeedConfig, SubscriberEvent};
+        let (trade_sender, _trade_receiver) = unbounded();
+        let engine = ExecutionEngine::new(trade_sender);
+        let updates = engine.subscribe_bbo_updates(FeedConfig {
+            conflation: Some(std::time::Duration::from_millis(50)),
+            ..FeedConfig::default()
+        });
+        engine.start().await;
+
+        for price in [100.0, 99.0, 98.0, 97.0, 96.0] {
+            let ask = Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, price, "client1".to_string());
+            engine.submit_order(ask).await.unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        engine.stop().await;
+
+        let asks: Vec<_> = updates
+            .try_iter()
+            .map(|update| match update {
+                SubscriberEvent::Update(update) => update.bbo.ask,
+                SubscriberEvent::DataLoss { .. } => panic!("updates lost"),
+            })
+            .collect();
+        // The held back updates still end on the latest state
+        assert!(asks.len() < 5);
+        assert_eq!(asks.last(), Some(&Some((Price::from(96.0), 1))));
+    }
 }
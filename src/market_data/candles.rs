@@ -0,0 +1,130 @@
+use super::TradePrint;
+use crate::types::Price;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Open, high, low and close prices and the volume of a symbol's trades over one interval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    pub symbol: String,
+    /// Start of the interval, a whole multiple of `interval_secs` since the Unix epoch
+    pub start: DateTime<Utc>,
+    pub interval_secs: u64,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: u64,
+    pub trades: u64,
+    /// No more trades will be added: a trade of a later interval has printed
+    pub closed: bool,
+}
+
+/// Builds candles of one symbol from its trade prints, in print order
+#[derive(Debug, Clone)]
+pub struct CandleBuilder {
+    interval_secs: u64,
+    current: Option<Candle>,
+}
+
+impl CandleBuilder {
+    /// Candles `interval_secs` long (at least one second)
+    pub fn new(interval_secs: u64) -> Self {
+        Self {
+            interval_secs: interval_secs.max(1),
+            current: None,
+        }
+    }
+
+    /// Add `print` to its interval's candle.
+    ///
+    /// Returns the candle this print closed, when it starts a new interval, and the
+    /// candle it was added to as it stands now.
+    pub fn record(&mut self, print: &TradePrint) -> (Option<Candle>, Candle) {
+        let start = self.interval_start(print.timestamp);
+        let closed = match self.current.take() {
+            Some(mut candle) if candle.start < start => {
+                candle.closed = true;
+                Some(candle)
+            }
+            // A print stamped earlier than the current candle is counted in it
+            Some(candle) => {
+                self.current = Some(candle);
+                None
+            }
+            None => None,
+        };
+
+        let candle = self.current.get_or_insert_with(|| Candle {
+            symbol: print.symbol.clone(),
+            start,
+            interval_secs: self.interval_secs,
+            open: print.price,
+            high: print.price,
+            low: print.price,
+            close: print.price,
+            volume: 0,
+            trades: 0,
+            closed: false,
+        });
+        candle.high = candle.high.max(print.price);
+        candle.low = candle.low.min(print.price);
+        candle.close = print.price;
+        candle.volume += print.quantity;
+        candle.trades += 1;
+        (closed, candle.clone())
+    }
+
+    /// The candle currently being built, if any trade has printed
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    fn interval_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = self.interval_secs as i64;
+        let seconds = timestamp.timestamp();
+        DateTime::from_timestamp(seconds - seconds.rem_euclid(interval), 0).unwrap_or(timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::TradeConditions;
+    use crate::types::Side;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_candles() {
+        let print = |seconds, price: f64, quantity| TradePrint {
+            trade_id: Uuid::new_v4(),
+            symbol: "BTCUSD".to_string(),
+            price: Price::from(price),
+            quantity,
+            aggressor: Some(Side::Buy),
+            conditions: TradeConditions::default(),
+            timestamp: DateTime::from_timestamp(seconds, 0).unwrap(),
+            sequence: 0,
+        };
+        let mut candles = CandleBuilder::new(60);
+        assert!(candles.current().is_none());
+
+        candles.record(&print(120, 100.0, 1));
+        candles.record(&print(150, 103.0, 2));
+        let (closed, current) = candles.record(&print(179, 99.0, 3));
+        assert!(closed.is_none());
+        assert_eq!(current.start.timestamp(), 120);
+        assert_eq!(
+            (current.open, current.high, current.low, current.close),
+            (Price::from(100.0), Price::from(103.0), Price::from(99.0), Price::from(99.0))
+        );
+        assert_eq!((current.volume, current.trades, current.closed), (6, 3, false));
+
+        // A gap of several intervals: the next candle starts at its own interval
+        let (closed, current) = candles.record(&print(305, 101.0, 4));
+        let closed = closed.unwrap();
+        assert!(closed.closed);
+        assert_eq!((closed.start.timestamp(), closed.volume), (120, 6));
+        assert_eq!((current.start.timestamp(), current.open, current.volume), (300, Price::from(101.0), 4));
+    }
+}
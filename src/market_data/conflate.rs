@@ -0,0 +1,50 @@
+use super::{BboUpdate, BookSnapshot, LevelUpdate};
+use crate::matching::{LevelAction, LevelChange};
+
+/// Updates that can be merged, so a conflating subscriber receives one with the same net effect
+pub(crate) trait Conflate {
+    /// Fold `newer`, published after `self`, into `self`
+    fn conflate(&mut self, newer: Self);
+}
+
+impl Conflate for BboUpdate {
+    fn conflate(&mut self, newer: Self) {
+        *self = newer;
+    }
+}
+
+impl Conflate for BookSnapshot {
+    fn conflate(&mut self, newer: Self) {
+        *self = newer;
+    }
+}
+
+impl Conflate for LevelUpdate {
+    fn conflate(&mut self, newer: Self) {
+        for change in newer.changes {
+            let Some(index) = self
+                .changes
+                .iter()
+                .position(|earlier| (earlier.side, earlier.price) == (change.side, change.price))
+            else {
+                self.changes.push(change);
+                continue;
+            };
+            let action = match (self.changes[index].action, change.action) {
+                // Never seen by the subscriber
+                (LevelAction::Add, LevelAction::Delete) => {
+                    self.changes.remove(index);
+                    continue;
+                }
+                (LevelAction::Add, _) => LevelAction::Add,
+                (_, LevelAction::Delete) => LevelAction::Delete,
+                // The subscriber still has the level from before its deletion
+                (_, _) => LevelAction::Modify,
+            };
+            self.changes[index] = LevelChange { action, ..change };
+        }
+        self.changes.sort_by(LevelChange::book_order);
+        self.timestamp = newer.timestamp;
+        self.sequence = newer.sequence;
+    }
+}
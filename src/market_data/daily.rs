@@ -0,0 +1,140 @@
+use crate::types::{Price, Trade};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A symbol's trading over one trading day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyStats {
+    pub symbol: String,
+    /// The date the trading day's session started on
+    pub trading_day: NaiveDate,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub last: Price,
+    pub volume: u64,
+    /// Sum of price × quantity over the day's trades
+    pub notional: f64,
+    pub trades: u64,
+}
+
+impl DailyStats {
+    /// Volume-weighted average price of the day's trades: the session VWAP
+    pub fn vwap(&self) -> Option<f64> {
+        (self.volume > 0).then(|| self.notional / self.volume as f64)
+    }
+}
+
+/// Daily statistics per symbol, started afresh at each session rollover.
+///
+/// A trading day runs from one rollover time (UTC) to the next; with the default of
+/// midnight it is the calendar day.
+#[derive(Debug)]
+pub struct DailyStatistics {
+    rollover: NaiveTime,
+    stats: HashMap<String, DailyStats>,
+}
+
+impl DailyStatistics {
+    pub fn new(rollover: NaiveTime) -> Self {
+        Self {
+            rollover,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// The trading day `timestamp` falls in
+    pub fn trading_day(&self, timestamp: DateTime<Utc>) -> NaiveDate {
+        (timestamp.naive_utc() - self.rollover.signed_duration_since(NaiveTime::MIN)).date()
+    }
+
+    pub fn record_trade(&mut self, trade: &Trade) {
+        let trading_day = self.trading_day(trade.timestamp);
+        let stats = self.stats.entry(trade.symbol.clone()).or_insert_with(|| DailyStats {
+            symbol: trade.symbol.clone(),
+            trading_day,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            last: trade.price,
+            volume: 0,
+            notional: 0.0,
+            trades: 0,
+        });
+        // A trade stamped before the current day started is counted in it
+        if trading_day > stats.trading_day {
+            *stats = DailyStats {
+                trading_day,
+                open: trade.price,
+                high: trade.price,
+                low: trade.price,
+                volume: 0,
+                notional: 0.0,
+                trades: 0,
+                ..stats.clone()
+            };
+        }
+        stats.high = stats.high.max(trade.price);
+        stats.low = stats.low.min(trade.price);
+        stats.last = trade.price;
+        stats.volume += trade.quantity;
+        stats.notional += trade.price.to_f64() * trade.quantity as f64;
+        stats.trades += 1;
+    }
+
+    /// `symbol`'s statistics for the trading day of `now`; `None` before its first trade of the day
+    pub fn get(&self, symbol: &str, now: DateTime<Utc>) -> Option<DailyStats> {
+        let stats = self.stats.get(symbol)?;
+        (stats.trading_day >= self.trading_day(now)).then(|| stats.clone())
+    }
+
+    /// Every symbol that traded on the trading day of `now`
+    pub fn all(&self, now: DateTime<Utc>) -> Vec<DailyStats> {
+        let today = self.trading_day(now);
+        self.stats.values().filter(|stats| stats.trading_day >= today).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_daily_statistics_roll_over() {
+        // Sessions start at 22:00 UTC
+        let mut daily = DailyStatistics::new(NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+        let at = |day, hour| DateTime::parse_from_rfc3339(&format!("2024-03-{day:02}T{hour:02}:00:00Z")).unwrap().to_utc();
+        let trade = |timestamp, price: f64, quantity| {
+            let mut trade = Trade::new(Uuid::new_v4(), Uuid::new_v4(), "BTCUSD".to_string(), quantity, Price::from(price));
+            trade.timestamp = timestamp;
+            trade
+        };
+
+        daily.record_trade(&trade(at(4, 23), 100.0, 2));
+        daily.record_trade(&trade(at(5, 10), 104.0, 1));
+        daily.record_trade(&trade(at(5, 21), 98.0, 3));
+        let stats = daily.get("BTCUSD", at(5, 21)).unwrap();
+        assert_eq!(stats.trading_day, NaiveDate::from_ymd_opt(2024, 3, 4).unwrap());
+        assert_eq!(
+            (stats.open, stats.high, stats.low, stats.last),
+            (Price::from(100.0), Price::from(104.0), Price::from(98.0), Price::from(98.0))
+        );
+        assert_eq!((stats.volume, stats.trades), (6, 3));
+        // (200 + 104 + 294) / 6
+        assert_eq!(stats.vwap(), Some(598.0 / 6.0));
+
+        // The 22:00 trade opens the next trading day
+        daily.record_trade(&trade(at(5, 22), 99.0, 5));
+        let stats = daily.get("BTCUSD", at(5, 23)).unwrap();
+        assert_eq!(stats.trading_day, NaiveDate::from_ymd_opt(2024, 3, 5).unwrap());
+        assert_eq!((stats.open, stats.high, stats.low), (Price::from(99.0), Price::from(99.0), Price::from(99.0)));
+        assert_eq!((stats.volume, stats.trades), (5, 1));
+        assert_eq!(daily.all(at(5, 23)).len(), 1);
+
+        // Nothing traded yet in the next session
+        assert!(daily.get("BTCUSD", at(6, 22)).is_none());
+        assert!(daily.get("ETHUSD", at(5, 23)).is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use super::SlowConsumerPolicy;
+use crate::matching::{Bbo, DepthSnapshot, LevelChange, OrderChange};
+use crate::types::{Price, Side};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Settings for a market-data feed subscription
+#[derive(Debug, Clone)]
+pub struct FeedConfig {
+    /// Only deliver updates for this symbol; every symbol's when `None`
+    pub symbol: Option<String>,
+    /// Updates buffered for the subscriber before `slow_consumer` applies
+    pub buffer: usize,
+    pub slow_consumer: SlowConsumerPolicy,
+    /// Merge book updates into at most one per interval, the latest state winning; every
+    /// update is delivered when `None`. Trade prints are never conflated. Merged level
+    /// updates carry the sequence of the last update in them, so sequences skip.
+    pub conflation: Option<Duration>,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            symbol: None,
+            buffer: 1024,
+            slow_consumer: SlowConsumerPolicy::default(),
+            conflation: None,
+        }
+    }
+}
+
+/// The best bid or offer of a symbol changed, in price or size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BboUpdate {
+    pub symbol: String,
+    pub bbo: Bbo,
+    /// Event time from the engine's configured clock
+    pub timestamp: DateTime<Utc>,
+    /// Sequence number of the engine command that moved the top of book
+    pub sequence: u64,
+}
+
+/// Incremental L2 update: the price levels of a symbol's book changed by one engine command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelUpdate {
+    pub symbol: String,
+    /// Bids then asks, each best price first
+    pub changes: Vec<LevelChange>,
+    /// Event time from the engine's configured clock
+    pub timestamp: DateTime<Utc>,
+    /// Per-symbol update number, starting at 1 and without gaps, so a consumer applying
+    /// updates to its own book can tell it missed one
+    pub sequence: u64,
+}
+
+/// Incremental L3 update: the resting orders of a symbol's book changed by one engine command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderUpdate {
+    pub symbol: String,
+    /// In the order they happened
+    pub changes: Vec<OrderChange>,
+    /// Event time from the engine's configured clock
+    pub timestamp: DateTime<Utc>,
+    /// Sequence number of the engine command that changed the orders
+    pub sequence: u64,
+}
+
+/// Conditions a trade print is flagged with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeConditions {
+    /// Executed in an auction uncross rather than continuous matching
+    pub auction: bool,
+    /// Executed against liquidity that was never displayed; the engine has no dark order
+    /// types, so nothing prints with it yet
+    pub dark: bool,
+    /// At least the instrument's block size
+    pub block: bool,
+}
+
+/// A trade as printed on the public time & sales tape, without the counterparties
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradePrint {
+    pub trade_id: Uuid,
+    pub symbol: String,
+    pub price: Price,
+    pub quantity: u64,
+    /// Side that took liquidity; `None` for auction trades
+    pub aggressor: Option<Side>,
+    pub conditions: TradeConditions,
+    /// Event time from the engine's configured clock
+    pub timestamp: DateTime<Utc>,
+    /// Per-symbol print number, starting at 1 and without gaps
+    pub sequence: u64,
+}
+
+/// Every price level of a symbol's book, for a consumer of [`LevelUpdate`]s to start from
+/// or resynchronise to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub symbol: String,
+    pub depth: DepthSnapshot,
+    /// Event time from the engine's configured clock
+    pub timestamp: DateTime<Utc>,
+    /// Sequence of the last level update the snapshot reflects; apply only later ones to it
+    pub sequence: u64,
+}
@@ -0,0 +1,181 @@
+use super::SlowConsumerPolicy;
+use crate::matching::OrderBook;
+use crate::types::{Price, Side};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Sampling settings for periodic heatmap export
+#[derive(Debug, Clone, Copy)]
+pub struct HeatmapConfig {
+    /// Time between depth samples
+    pub interval: Duration,
+    /// Price levels captured per side
+    pub levels: usize,
+    /// Samples per exported matrix
+    pub frames_per_batch: usize,
+    /// Matrices buffered for the subscriber before `slow_consumer` applies
+    pub buffer: usize,
+    pub slow_consumer: SlowConsumerPolicy,
+}
+
+impl Default for HeatmapConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            levels: 20,
+            frames_per_batch: 60,
+            buffer: 16,
+            slow_consumer: SlowConsumerPolicy::default(),
+        }
+    }
+}
+
+/// Aggregated depth of one book at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapFrame {
+    pub timestamp: DateTime<Utc>,
+    pub bids: Vec<(Price, u64)>, // (price, quantity), best first
+    pub asks: Vec<(Price, u64)>,
+}
+
+impl HeatmapFrame {
+    /// Capture the top `levels` price levels on each side of the book
+    pub fn capture(book: &OrderBook, levels: usize) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            bids: book.aggregated_levels(Side::Buy, levels),
+            asks: book.aggregated_levels(Side::Sell, levels),
+        }
+    }
+}
+
+/// Compact price × time depth matrix.
+///
+/// Only non-empty cells are stored, as `(time index, price index, quantity)`.
+/// Quantity is signed: positive for bid depth, negative for ask depth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapMatrix {
+    pub symbol: String,
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub prices: Vec<Price>, // ascending
+    pub cells: Vec<(u32, u32, i64)>,
+}
+
+impl HeatmapMatrix {
+    /// Signed quantity resting at `price` in frame `time_index`
+    pub fn quantity_at(&self, time_index: usize, price: Price) -> i64 {
+        let Ok(price_index) = self.prices.binary_search(&price) else {
+            return 0;
+        };
+        self.cells
+            .iter()
+            .find(|&&(t, p, _)| t as usize == time_index && p as usize == price_index)
+            .map_or(0, |&(_, _, quantity)| quantity)
+    }
+
+    /// Render as `timestamp,price,quantity` rows
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("timestamp,price,quantity\n");
+        for &(t, p, quantity) in &self.cells {
+            let _ = writeln!(
+                csv,
+                "{},{},{}",
+                self.timestamps[t as usize].to_rfc3339(),
+                self.prices[p as usize],
+                quantity
+            );
+        }
+        csv
+    }
+}
+
+/// Accumulates depth frames for one symbol until exported
+#[derive(Debug)]
+pub struct LadderHeatmap {
+    symbol: String,
+    frames: Vec<HeatmapFrame>,
+}
+
+impl LadderHeatmap {
+    pub fn new(symbol: String) -> Self {
+        Self {
+            symbol,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, frame: HeatmapFrame) {
+        self.frames.push(frame);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Build the matrix from all recorded frames and start a new batch
+    pub fn export(&mut self) -> HeatmapMatrix {
+        let frames = std::mem::take(&mut self.frames);
+
+        let mut prices: Vec<Price> = frames
+            .iter()
+            .flat_map(|f| f.bids.iter().chain(&f.asks).map(|&(price, _)| price))
+            .collect();
+        prices.sort();
+        prices.dedup();
+
+        let price_index = |price: Price| prices.binary_search(&price).unwrap() as u32;
+
+        let mut cells = Vec::new();
+        for (t, frame) in frames.iter().enumerate() {
+            for &(price, quantity) in &frame.bids {
+                cells.push((t as u32, price_index(price), quantity as i64));
+            }
+            for &(price, quantity) in &frame.asks {
+                cells.push((t as u32, price_index(price), -(quantity as i64)));
+            }
+        }
+
+        HeatmapMatrix {
+            symbol: self.symbol.clone(),
+            timestamps: frames.iter().map(|f| f.timestamp).collect(),
+            prices,
+            cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Order;
+
+    #[test]
+    fn test_heatmap_export() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let mut heatmap = LadderHeatmap::new("BTCUSD".to_string());
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 50000.0, "client2".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 7, 50100.0, "client3".to_string()));
+        heatmap.record(HeatmapFrame::capture(&book, 10));
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 3, 50050.0, "client3".to_string()));
+        heatmap.record(HeatmapFrame::capture(&book, 10));
+
+        let matrix = heatmap.export();
+        assert!(heatmap.is_empty());
+        assert_eq!(matrix.timestamps.len(), 2);
+        assert_eq!(matrix.prices, [50000.0, 50050.0, 50100.0].map(Price::from));
+        assert_eq!(matrix.quantity_at(0, Price::from(50000.0)), 15);
+        assert_eq!(matrix.quantity_at(0, Price::from(50050.0)), 0);
+        assert_eq!(matrix.quantity_at(1, Price::from(50050.0)), -3);
+        assert_eq!(matrix.quantity_at(1, Price::from(50100.0)), -7);
+        assert_eq!(matrix.to_csv().lines().count(), 1 + matrix.cells.len());
+    }
+}
@@ -0,0 +1,310 @@
+use super::{OrderUpdate, TradePrint};
+use crate::matching::OrderChange;
+use crate::types::{Price, Side};
+use chrono::{DateTime, Timelike, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// One message in the NASDAQ TotalView-ITCH 5.0 layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItchMessage {
+    /// Per-symbol locate code assigned by the encoder, starting at 1
+    pub stock_locate: u16,
+    /// Nanoseconds since midnight UTC
+    pub timestamp: u64,
+    pub body: ItchBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItchBody {
+    /// `A`: a displayed order was added to the book
+    AddOrder { order_reference: u64, side: Side, shares: u32, stock: String, price: u32 },
+    /// `E`: `executed_shares` of a displayed order traded
+    OrderExecuted { order_reference: u64, executed_shares: u32, match_number: u64 },
+    /// `X`: an order was reduced by `cancelled_shares`
+    OrderCancel { order_reference: u64, cancelled_shares: u32 },
+    /// `D`: an order left the book
+    OrderDelete { order_reference: u64 },
+    /// `P`: a trade of orders that never showed in the book
+    Trade { side: Side, shares: u32, stock: String, price: u32, match_number: u64 },
+}
+
+impl ItchMessage {
+    /// The message type byte
+    pub fn message_type(&self) -> u8 {
+        match self.body {
+            ItchBody::AddOrder { .. } => b'A',
+            ItchBody::OrderExecuted { .. } => b'E',
+            ItchBody::OrderCancel { .. } => b'X',
+            ItchBody::OrderDelete { .. } => b'D',
+            ItchBody::Trade { .. } => b'P',
+        }
+    }
+
+    /// The message's bytes, without session-layer framing (e.g. SoupBinTCP or MoldUDP64)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(44);
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    /// Append the message's bytes to `buf`: big-endian integers, space-padded alphanumerics
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.push(self.message_type());
+        buf.extend_from_slice(&self.stock_locate.to_be_bytes());
+        // Tracking number: the engine has a single matching thread
+        buf.extend_from_slice(&0u16.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes()[2..]);
+        match &self.body {
+            ItchBody::AddOrder { order_reference, side, shares, stock, price } => {
+                buf.extend_from_slice(&order_reference.to_be_bytes());
+                buf.push(side_code(*side));
+                buf.extend_from_slice(&shares.to_be_bytes());
+                put_stock(buf, stock);
+                buf.extend_from_slice(&price.to_be_bytes());
+            }
+            ItchBody::OrderExecuted { order_reference, executed_shares, match_number } => {
+                buf.extend_from_slice(&order_reference.to_be_bytes());
+                buf.extend_from_slice(&executed_shares.to_be_bytes());
+                buf.extend_from_slice(&match_number.to_be_bytes());
+            }
+            ItchBody::OrderCancel { order_reference, cancelled_shares } => {
+                buf.extend_from_slice(&order_reference.to_be_bytes());
+                buf.extend_from_slice(&cancelled_shares.to_be_bytes());
+            }
+            ItchBody::OrderDelete { order_reference } => {
+                buf.extend_from_slice(&order_reference.to_be_bytes());
+            }
+            ItchBody::Trade { side, shares, stock, price, match_number } => {
+                // Non-displayed orders have no reference
+                buf.extend_from_slice(&0u64.to_be_bytes());
+                buf.push(side_code(*side));
+                buf.extend_from_slice(&shares.to_be_bytes());
+                put_stock(buf, stock);
+                buf.extend_from_slice(&price.to_be_bytes());
+                buf.extend_from_slice(&match_number.to_be_bytes());
+            }
+        }
+    }
+}
+
+fn side_code(side: Side) -> u8 {
+    match side {
+        Side::Buy => b'B',
+        Side::Sell => b'S',
+    }
+}
+
+/// Eight bytes, left-justified and space-padded; longer symbols are cut
+fn put_stock(buf: &mut Vec<u8>, stock: &str) {
+    let mut field = [b' '; 8];
+    for (byte, &symbol_byte) in field.iter_mut().zip(stock.as_bytes()) {
+        *byte = symbol_byte;
+    }
+    buf.extend_from_slice(&field);
+}
+
+/// ITCH `Price (4)`: four implied decimals, further decimals truncated, negative prices as 0
+fn itch_price(price: Price) -> u32 {
+    let scaled = price.units() / 10i64.pow(Price::DECIMALS - 4);
+    scaled.clamp(0, u32::MAX as i64) as u32
+}
+
+fn itch_shares(quantity: u64) -> u32 {
+    quantity.min(u32::MAX as u64) as u32
+}
+
+fn nanos_since_midnight(timestamp: DateTime<Utc>) -> u64 {
+    timestamp.num_seconds_from_midnight() as u64 * 1_000_000_000 + timestamp.nanosecond() as u64
+}
+
+/// Turns the engine's order updates and trade prints into ITCH messages.
+///
+/// Order IDs map to order reference numbers, a new one each time an order is added (an
+/// order re-added after losing priority shows as a delete and an add, as in ITCH). Shares
+/// and prices beyond ITCH's 32-bit fields saturate.
+#[derive(Debug, Default)]
+pub struct ItchEncoder {
+    locates: HashMap<String, u16>,
+    orders: HashMap<Uuid, (u64, u64)>, // order -> (reference number, shares showing)
+    next_reference: u64,
+    next_match: u64,
+}
+
+impl ItchEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The locate code of `symbol`, assigned in order of first appearance
+    pub fn locate(&mut self, symbol: &str) -> u16 {
+        if let Some(&locate) = self.locates.get(symbol) {
+            return locate;
+        }
+        let locate = self.locates.len() as u16 + 1;
+        self.locates.insert(symbol.to_string(), locate);
+        locate
+    }
+
+    /// Messages for every change in `update`; changes to orders added before the encoder saw
+    /// them are skipped
+    pub fn encode_order_update(&mut self, update: &OrderUpdate) -> Vec<ItchMessage> {
+        let stock_locate = self.locate(&update.symbol);
+        let timestamp = nanos_since_midnight(update.timestamp);
+        update
+            .changes
+            .iter()
+            .filter_map(|change| {
+                let body = self.order_change(&update.symbol, change)?;
+                Some(ItchMessage {
+                    stock_locate,
+                    timestamp,
+                    body,
+                })
+            })
+            .collect()
+    }
+
+    fn order_change(&mut self, symbol: &str, change: &OrderChange) -> Option<ItchBody> {
+        match *change {
+            OrderChange::Added { order_id, side, price, quantity } => {
+                self.next_reference += 1;
+                self.orders.insert(order_id, (self.next_reference, quantity));
+                Some(ItchBody::AddOrder {
+                    order_reference: self.next_reference,
+                    side,
+                    shares: itch_shares(quantity),
+                    stock: symbol.to_string(),
+                    price: itch_price(price),
+                })
+            }
+            OrderChange::Executed { order_id, quantity, .. } => {
+                let order_reference = self.reduce(order_id, quantity)?;
+                self.next_match += 1;
+                Some(ItchBody::OrderExecuted {
+                    order_reference,
+                    executed_shares: itch_shares(quantity),
+                    match_number: self.next_match,
+                })
+            }
+            OrderChange::Reduced { order_id, quantity } => Some(ItchBody::OrderCancel {
+                order_reference: self.reduce(order_id, quantity)?,
+                cancelled_shares: itch_shares(quantity),
+            }),
+            OrderChange::Removed { order_id } => Some(ItchBody::OrderDelete {
+                order_reference: self.orders.remove(&order_id)?.0,
+            }),
+        }
+    }
+
+    /// Take `quantity` off an order's showing shares, forgetting it once none are left
+    fn reduce(&mut self, order_id: Uuid, quantity: u64) -> Option<u64> {
+        let (reference, showing) = self.orders.get_mut(&order_id)?;
+        let reference = *reference;
+        *showing = showing.saturating_sub(quantity);
+        if *showing == 0 {
+            self.orders.remove(&order_id);
+        }
+        Some(reference)
+    }
+
+    /// A trade message for an auction print, whose orders never showed in the book; `None`
+    /// for continuous trades, already reported as executions of the resting order
+    pub fn encode_trade_print(&mut self, print: &TradePrint) -> Option<ItchMessage> {
+        if !print.conditions.auction {
+            return None;
+        }
+        self.next_match += 1;
+        Some(ItchMessage {
+            stock_locate: self.locate(&print.symbol),
+            timestamp: nanos_since_midnight(print.timestamp),
+            body: ItchBody::Trade {
+                // Auction trades have no aggressor
+                side: print.aggressor.unwrap_or(Side::Buy),
+                shares: itch_shares(print.quantity),
+                stock: print.symbol.clone(),
+                price: itch_price(print.price),
+                match_number: self.next_match,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::TradeConditions;
+
+    #[test]
+    fn test_itch_encoding() {
+        let mut encoder = ItchEncoder::new();
+        let (resting, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let timestamp = DateTime::from_timestamp(86_400 + 3_600, 5).unwrap();
+        let update = |changes| OrderUpdate {
+            symbol: "BTCUSD".to_string(),
+            changes,
+            timestamp,
+            sequence: 1,
+        };
+
+        let added = encoder.encode_order_update(&update(vec![OrderChange::Added {
+            order_id: resting,
+            side: Side::Sell,
+            price: Price::from(101.25),
+            quantity: 10,
+        }]));
+        let bytes = added[0].encode();
+        assert_eq!(bytes.len(), 36);
+        assert_eq!(bytes[0], b'A');
+        assert_eq!(&bytes[1..3], &1u16.to_be_bytes());
+        assert_eq!(&bytes[5..11], &3_600_000_000_005u64.to_be_bytes()[2..]);
+        assert_eq!(&bytes[11..19], &1u64.to_be_bytes());
+        assert_eq!(bytes[19], b'S');
+        assert_eq!(&bytes[20..24], &10u32.to_be_bytes());
+        assert_eq!(&bytes[24..32], b"BTCUSD  ");
+        assert_eq!(&bytes[32..36], &1_012_500u32.to_be_bytes());
+
+        let messages = encoder.encode_order_update(&update(vec![
+            OrderChange::Executed {
+                order_id: resting,
+                quantity: 4,
+                trade_id: Uuid::new_v4(),
+            },
+            OrderChange::Reduced { order_id: resting, quantity: 2 },
+            OrderChange::Removed { order_id: resting },
+            // Never added while the encoder was listening
+            OrderChange::Removed { order_id: other },
+        ]));
+        let types: Vec<u8> = messages.iter().map(ItchMessage::message_type).collect();
+        assert_eq!(types, b"EXD");
+        let lengths: Vec<usize> = messages.iter().map(|message| message.encode().len()).collect();
+        assert_eq!(lengths, vec![31, 23, 19]);
+        assert_eq!(
+            messages[0].body,
+            ItchBody::OrderExecuted {
+                order_reference: 1,
+                executed_shares: 4,
+                match_number: 1
+            }
+        );
+
+        let mut print = TradePrint {
+            trade_id: Uuid::new_v4(),
+            symbol: "ETHUSD".to_string(),
+            price: Price::from(50.0),
+            quantity: 3,
+            aggressor: Some(Side::Buy),
+            conditions: TradeConditions::default(),
+            timestamp,
+            sequence: 1,
+        };
+        assert!(encoder.encode_trade_print(&print).is_none());
+        print.conditions.auction = true;
+        print.aggressor = None;
+        let trade = encoder.encode_trade_print(&print).unwrap();
+        let bytes = trade.encode();
+        assert_eq!((bytes.len(), bytes[0]), (44, b'P'));
+        assert_eq!(trade.stock_locate, 2);
+        assert_eq!(&bytes[36..44], &2u64.to_be_bytes());
+    }
+}
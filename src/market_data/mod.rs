@@ -0,0 +1,19 @@
+mod candles;
+#[cfg(feature = "engine")]
+mod conflate;
+mod daily;
+mod feed;
+mod heatmap;
+mod itch;
+mod reference;
+mod subscriber;
+
+pub use candles::{Candle, CandleBuilder};
+#[cfg(feature = "engine")]
+pub(crate) use conflate::Conflate;
+pub use daily::{DailyStatistics, DailyStats};
+pub use feed::{BboUpdate, BookSnapshot, FeedConfig, LevelUpdate, OrderUpdate, TradeConditions, TradePrint};
+pub use heatmap::{HeatmapConfig, HeatmapFrame, HeatmapMatrix, LadderHeatmap};
+pub use itch::{ItchBody, ItchEncoder, ItchMessage};
+pub use reference::{ExternalReference, ReferencePrices};
+pub use subscriber::{SlowConsumerPolicy, SubscriberEvent};
@@ -0,0 +1,124 @@
+use crate::types::{Price, Trade};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// An index or reference price fed in from outside the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternalReference {
+    pub price: Price,
+    /// When the engine received it, by its configured clock
+    pub updated: DateTime<Utc>,
+    /// Not updated within the engine's staleness limit, so not used in place of its own prices
+    pub stale: bool,
+}
+
+/// Rolling VWAP and TWAP per symbol, computed from the trade stream.
+///
+/// Trades are kept for `retention`; queries may use any window up to that.
+#[derive(Debug)]
+pub struct ReferencePrices {
+    retention: Duration,
+    trades: HashMap<String, VecDeque<(DateTime<Utc>, f64, u64)>>, // (time, price, quantity), oldest first
+}
+
+impl ReferencePrices {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            trades: HashMap::new(),
+        }
+    }
+
+    pub fn record_trade(&mut self, trade: &Trade) {
+        let trades = self.trades.entry(trade.symbol.clone()).or_default();
+        trades.push_back((trade.timestamp, trade.price.to_f64(), trade.quantity));
+
+        // Keep the last trade before the cutoff: its price still applies to TWAP at the window start
+        let cutoff = trade.timestamp - chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        while trades.len() > 1 && trades[1].0 <= cutoff {
+            trades.pop_front();
+        }
+    }
+
+    /// Volume-weighted average price of trades in the last `window`
+    pub fn vwap(&self, symbol: &str, window: Duration, now: DateTime<Utc>) -> Option<f64> {
+        let start = self.window_start(window, now);
+        let (notional, volume) = self
+            .trades
+            .get(symbol)?
+            .iter()
+            .filter(|&&(time, _, _)| time > start)
+            .fold((0.0, 0u64), |(notional, volume), &(_, price, quantity)| {
+                (notional + price * quantity as f64, volume + quantity)
+            });
+
+        (volume > 0).then(|| notional / volume as f64)
+    }
+
+    /// Time-weighted average of the last traded price over the last `window`.
+    ///
+    /// Before the first trade there is no price, so only the time since then counts.
+    pub fn twap(&self, symbol: &str, window: Duration, now: DateTime<Utc>) -> Option<f64> {
+        let start = self.window_start(window, now);
+        let trades = self.trades.get(symbol)?;
+
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+        let mut current: Option<(DateTime<Utc>, f64)> = None; // price in effect and since when
+        for &(time, price, _) in trades.iter().filter(|&&(time, _, _)| time <= now) {
+            if let Some((since, last_price)) = current {
+                let seconds = (time - since.max(start)).num_microseconds().unwrap_or(0).max(0) as f64;
+                weighted += last_price * seconds;
+                total += seconds;
+            }
+            current = Some((time, price));
+        }
+
+        let (since, last_price) = current?;
+        let seconds = (now - since.max(start)).num_microseconds().unwrap_or(0) as f64;
+        weighted += last_price * seconds;
+        total += seconds;
+
+        if total > 0.0 {
+            Some(weighted / total)
+        } else {
+            Some(last_price)
+        }
+    }
+
+    fn window_start(&self, window: Duration, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - chrono::Duration::from_std(window.min(self.retention)).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_rolling_vwap_and_twap() {
+        let mut prices = ReferencePrices::new(Duration::from_secs(3600));
+        let start = Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+
+        for (secs, price, quantity) in [(0, 100.0, 10), (60, 110.0, 30), (90, 90.0, 10)] {
+            let mut trade = Trade::new(Uuid::new_v4(), Uuid::new_v4(), "BTCUSD".to_string(), quantity, Price::from(price));
+            trade.timestamp = at(secs);
+            prices.record_trade(&trade);
+        }
+
+        // Whole history: (1000 + 3300 + 900) / 50
+        assert_eq!(prices.vwap("BTCUSD", Duration::from_secs(600), at(120)), Some(104.0));
+        // Last 45s only sees the trade at 90s
+        assert_eq!(prices.vwap("BTCUSD", Duration::from_secs(45), at(120)), Some(90.0));
+
+        // Last 120s: 100 for 60s, 110 for 30s, 90 for 30s
+        assert_eq!(prices.twap("BTCUSD", Duration::from_secs(120), at(120)), Some(100.0));
+        // Last 40s: 110 for 10s, then 90 for 30s
+        assert_eq!(prices.twap("BTCUSD", Duration::from_secs(40), at(120)), Some(95.0));
+
+        assert_eq!(prices.vwap("ETHUSD", Duration::from_secs(60), at(120)), None);
+    }
+}
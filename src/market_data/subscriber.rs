@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// What the publisher does when a subscriber's buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SlowConsumerPolicy {
+    /// Stop publishing to the subscriber; its receiver disconnects once drained
+    Disconnect,
+    /// Discard everything buffered and deliver only the latest update
+    Conflate,
+    /// Discard the oldest buffered updates to make room for the new one
+    #[default]
+    DropOldest,
+}
+
+/// Item delivered to a market-data subscriber
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SubscriberEvent<T> {
+    Update(T),
+    /// `dropped` updates were discarded because the subscriber fell behind
+    DataLoss { dropped: u64 },
+}
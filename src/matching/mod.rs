@@ -1,193 +1,1551 @@
-use crate::types::{Order, OrderStatus, Side, Trade};
-use std::collections::{BTreeMap, VecDeque};
+mod slab;
+
+use crate::auction::{self, AuctionResult, IndicativeAuction};
+use crate::clock::{Clock, WallClock};
+use crate::types::{Order, OrderStatus, OrderType, Price, Side, TimeInForce, Trade, TradeContext};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use slab::{Level, OrderSlab, SlotKey};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Most used trades a book holds on to for reuse
+const MAX_POOLED_TRADES: usize = 1024;
+
+/// How an incoming order's size is allocated across resting orders at one price level
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MatchingAlgorithm {
+    /// Strict time priority
+    #[default]
+    Fifo,
+    /// Proportional to resting size, after giving the front order
+    /// `top_order_percent` of the incoming size
+    ProRata { top_order_percent: u8 },
+    /// The lead market maker's orders receive `lmm_percent` of the incoming size
+    /// first; the rest is allocated FIFO
+    FifoWithLmm { lmm_client_id: String, lmm_percent: u8 },
+}
+
+/// What happens when an order would trade against a resting order of the same client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    /// Cancel the incoming order's remaining quantity
+    CancelNewest,
+    /// Cancel the resting order and keep matching the incoming one
+    CancelOldest,
+    /// Cancel both orders
+    CancelBoth,
+    /// Reduce both orders by the smaller remaining quantity and cancel whichever is exhausted
+    DecrementAndCancel,
+}
+
+impl SelfTradePrevention {
+    /// Policy for `incoming` matching `resting`, if the match counts as a self-trade.
+    ///
+    /// Child orders of the same parent use the parent's policy when it sets one;
+    /// otherwise the book's policy applies to orders of the same client.
+    fn between(book_policy: Option<Self>, incoming: &Order, resting: &Order) -> Option<Self> {
+        if let (Some(parent), Some(resting_parent)) = (&incoming.parent, &resting.parent) {
+            if parent.id == resting_parent.id && parent.self_trade_prevention.is_some() {
+                return parent.self_trade_prevention;
+            }
+        }
+        book_policy.filter(|_| incoming.client_id == resting.client_id)
+    }
+
+    /// Apply the policy to a would-be self-match; `incoming` is always the newer order
+    fn apply(self, incoming: &mut Order, resting: &mut Order) {
+        match self {
+            SelfTradePrevention::CancelNewest => incoming.status = OrderStatus::Cancelled,
+            SelfTradePrevention::CancelOldest => resting.status = OrderStatus::Cancelled,
+            SelfTradePrevention::CancelBoth => {
+                incoming.status = OrderStatus::Cancelled;
+                resting.status = OrderStatus::Cancelled;
+            }
+            SelfTradePrevention::DecrementAndCancel => {
+                let decrement = incoming.remaining_quantity().min(resting.remaining_quantity());
+                incoming.quantity -= decrement;
+                resting.quantity -= decrement;
+                if incoming.remaining_quantity() == 0 {
+                    incoming.status = OrderStatus::Cancelled;
+                }
+                if resting.remaining_quantity() == 0 {
+                    resting.status = OrderStatus::Cancelled;
+                }
+            }
+        }
+    }
+}
+
+/// What happens when an order would trade outside the symbol's price limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LimitBreachAction {
+    /// Cancel the aggressor's remaining quantity; trading continues
+    #[default]
+    Reject,
+    /// Cancel the aggressor's remaining quantity and halt trading on the symbol; new orders
+    /// are rejected until trading resumes
+    Halt,
+    /// Rest the aggressor's remaining quantity and move the symbol into a call auction
+    Auction,
+}
+
+/// Limit up / limit down price bands
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PriceLimits {
+    /// Fixed lowest and highest tradable prices
+    pub static_band: Option<(Price, Price)>,
+    /// Maximum move from the last traded price, in percent
+    pub dynamic_percent: Option<f64>,
+    pub on_breach: LimitBreachAction,
+}
+
+impl PriceLimits {
+    /// Tradable price range given the last traded price; the static and dynamic bands both apply
+    fn band(&self, last_trade_price: Option<Price>) -> (Price, Price) {
+        let (mut low, mut high) = self.static_band.unwrap_or((Price::MIN, Price::MAX));
+        if let (Some(percent), Some(reference)) = (self.dynamic_percent, last_trade_price) {
+            let reference = reference.to_f64();
+            low = low.max(Price::from(reference * (1.0 - percent / 100.0)));
+            high = high.min(Price::from(reference * (1.0 + percent / 100.0)));
+        }
+        (low, high)
+    }
+}
+
+/// What happens to a post-only order that would lock or cross the book on arrival
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CrossPolicy {
+    /// Reject the order
+    #[default]
+    Reject,
+    /// Move the order one tick behind the opposite best price and rest it
+    Reprice,
+    /// Ignore the post-only flag and match the order immediately
+    Match,
+}
+
+/// What happens to a new passive order once the book is at its depth limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DepthLimitAction {
+    /// Reject the new order
+    #[default]
+    Reject,
+    /// Cancel the orders on the new order's side priced furthest from the touch, newest
+    /// first, to make room; the new order is rejected if none is further out than it
+    CullFarthest,
+}
+
+/// Caps on resting orders, protecting memory and matching latency from runaway quoting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DepthLimits {
+    /// Most resting orders across both sides
+    pub max_orders: Option<usize>,
+    /// Most price levels on each side
+    pub max_levels: Option<usize>,
+    pub on_limit: DepthLimitAction,
+}
+
+/// Order book construction options
+#[derive(Debug, Clone, Default)]
+pub struct BookConfig {
+    /// Rank orders at the same price by priority class before time
+    pub priority_classes: bool,
+    pub algorithm: MatchingAlgorithm,
+    /// Self-trade prevention policy; self-matches are allowed when `None`
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    /// Attach a [`TradeContext`] to every trade
+    pub trade_context: bool,
+    /// Price bands outside which continuous matching does not trade
+    pub price_limits: Option<PriceLimits>,
+    /// Resting orders to pre-allocate storage for
+    pub order_capacity: usize,
+    /// Handling of post-only orders that would lock or cross the book
+    pub cross_policy: CrossPolicy,
+    /// Price increment used to reprice post-only orders; one price unit when `None`
+    pub tick_size: Option<Price>,
+    /// Limits on resting orders and levels; unlimited when `None`
+    pub depth_limits: Option<DepthLimits>,
+}
+
+/// Aggregated top-of-book levels, best price first on each side
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DepthSnapshot {
+    pub bids: Vec<(Price, u64, usize)>, // (price, total visible quantity, order count)
+    pub asks: Vec<(Price, u64, usize)>,
+}
+
+/// Best bid and offer with their aggregated quantities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bbo {
+    pub bid: Option<(Price, u64)>,
+    pub ask: Option<(Price, u64)>,
+}
+
+/// How a price level changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelAction {
+    /// A new price level
+    Add,
+    /// The level's quantity or order count changed
+    Modify,
+    /// The level emptied
+    Delete,
+}
+
+/// A change to one aggregated price level, as in an incremental L2 feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelChange {
+    pub side: Side,
+    pub price: Price,
+    pub action: LevelAction,
+    /// Total visible quantity now at the level; 0 once deleted
+    pub quantity: u64,
+    /// Orders now at the level
+    pub orders: usize,
+}
+
+impl LevelChange {
+    /// Bids before asks, each best price first
+    pub(crate) fn book_order(&self, other: &Self) -> std::cmp::Ordering {
+        match (self.side, other.side) {
+            (Side::Buy, Side::Sell) => std::cmp::Ordering::Less,
+            (Side::Sell, Side::Buy) => std::cmp::Ordering::Greater,
+            (Side::Buy, Side::Buy) => other.price.cmp(&self.price),
+            (Side::Sell, Side::Sell) => self.price.cmp(&other.price),
+        }
+    }
+}
+
+/// A change to one resting order, as in an order-by-order (L3) feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderChange {
+    /// The order started showing `quantity` at `price`: new, back after an amendment that
+    /// lost its priority, or an iceberg's next slice
+    Added { order_id: Uuid, side: Side, price: Price, quantity: u64 },
+    /// `quantity` of the resting order traded in `trade_id`
+    Executed { order_id: Uuid, quantity: u64, trade_id: Uuid },
+    /// The order's shown quantity was reduced by `quantity`, keeping its priority
+    Reduced { order_id: Uuid, quantity: u64 },
+    /// The order left the book with quantity still showing. Orders that leave fully
+    /// executed, or with an exhausted iceberg slice, have no `Removed`.
+    Removed { order_id: Uuid },
+}
+
+/// Order book analytics over the best N levels
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BookAnalytics {
+    /// (bid volume - ask volume) / total volume, from -1 (all asks) to 1 (all bids)
+    pub imbalance: Option<f64>,
+    /// Top-of-book prices weighted by the opposite side's size (microprice)
+    pub weighted_mid: Option<f64>,
+    /// Quantity-weighted average price of the bid levels
+    pub bid_vwap: Option<f64>,
+    /// Quantity-weighted average price of the ask levels
+    pub ask_vwap: Option<f64>,
+}
+
+/// Price levels touched since their changes were last taken, with the (visible quantity,
+/// order count) each had before; `None` for levels that didn't exist
+type TouchedLevels = HashMap<(Side, Price), Option<(u64, usize)>>;
+
 /// Order book for a single symbol
 #[derive(Debug)]
 pub struct OrderBook {
     symbol: String,
-    bids: BTreeMap<u64, VecDeque<Order>>, // Price level -> Orders (sorted by price descending)
-    asks: BTreeMap<u64, VecDeque<Order>>, // Price level -> Orders (sorted by price ascending)
+    config: BookConfig,
+    orders: OrderSlab,                    // Storage for resting orders
+    bids: BTreeMap<Price, Level>,         // Price level -> order queue (best bid is the last key)
+    asks: BTreeMap<Price, Level>,         // Price level -> order queue (best ask is the first key)
+    index: HashMap<Uuid, SlotKey>,        // Resting order -> its slot
+    auction_orders: Vec<Order>,           // Auction-only orders, held out of continuous matching
+    cancelled: Vec<Order>,                // Orders cancelled or rejected by the book itself, until drained
+    last_trade_price: Option<Price>,      // Reference for dynamic price limits
+    limit_breached: bool,                 // A trade was prevented by the price limits, until taken
+    trade_pool: Vec<Trade>,               // Consumed trades handed back, reused for new ones
+    level_changes: Option<TouchedLevels>, // Levels changed since last taken, when tracking
+    order_changes: Option<Vec<OrderChange>>, // Resting order changes since last taken, when tracking
+    clock: Arc<dyn Clock>,                // Stamps orders that go back in the queue
+    scratch: Scratch,
+}
+
+/// Buffers kept between matches, so a steady flow of matches doesn't allocate
+#[derive(Debug, Default)]
+struct Scratch {
+    keys: Vec<SlotKey>,
+    allocations: Vec<u64>,
+    sizes: Vec<u64>,
+    refreshed: Vec<Order>,
+}
+
+/// A trade between `buy` and `sell`, built in a pooled trade's buffers when there is one
+fn pooled_trade(pool: &mut Vec<Trade>, symbol: &str, buy: &Order, sell: &Order, quantity: u64, price: Price) -> Trade {
+    let trade = match pool.pop() {
+        Some(mut trade) => {
+            trade.reset(buy.id, sell.id, symbol, quantity, price);
+            trade
+        }
+        None => Trade::new(buy.id, sell.id, symbol.to_string(), quantity, price),
+    };
+    trade.between(buy, sell)
 }
 
 impl OrderBook {
     pub fn new(symbol: String) -> Self {
+        Self::with_config(symbol, BookConfig::default())
+    }
+
+    pub fn with_config(symbol: String, config: BookConfig) -> Self {
         Self {
             symbol,
+            orders: OrderSlab::with_capacity(config.order_capacity),
+            index: HashMap::with_capacity(config.order_capacity),
+            config,
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
+            auction_orders: Vec::new(),
+            cancelled: Vec::new(),
+            last_trade_price: None,
+            limit_breached: false,
+            trade_pool: Vec::new(),
+            level_changes: None,
+            order_changes: None,
+            clock: Arc::new(WallClock),
+            scratch: Scratch::default(),
         }
     }
 
-    /// Add order to the book
-    pub fn add_order(&mut self, order: Order) {
-        let price_level = (order.price.unwrap_or(0.0) * 100.0) as u64; // Convert to integer for BTreeMap
-        
-        match order.side {
-            Side::Buy => {
-                self.bids
-                    .entry(price_level)
-                    .or_insert_with(VecDeque::new)
-                    .push_back(order);
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Stamp orders that lose their queue position, amended or showing a new iceberg slice,
+    /// with `clock` instead of the wall clock
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Start or stop recording price level changes for [`OrderBook::take_level_changes`]
+    pub fn track_level_changes(&mut self, enabled: bool) {
+        self.level_changes = enabled.then(HashMap::new);
+    }
+
+    /// Net change of every price level touched since the last call, bids then asks, each
+    /// best price first.
+    ///
+    /// Levels that changed and changed back are left out. Empty unless tracking was
+    /// started with [`OrderBook::track_level_changes`].
+    pub fn take_level_changes(&mut self) -> Vec<LevelChange> {
+        let Some(touched) = self.level_changes.as_mut().map(std::mem::take) else {
+            return Vec::new();
+        };
+
+        let mut changes: Vec<LevelChange> = touched
+            .into_iter()
+            .filter_map(|((side, price), before)| {
+                let after = self.level(side, price).map(|level| self.level_totals(level));
+                let action = match (before, after) {
+                    (None, Some(_)) => LevelAction::Add,
+                    (Some(before), Some(after)) if before != after => LevelAction::Modify,
+                    (Some(_), None) => LevelAction::Delete,
+                    _ => return None,
+                };
+                let (quantity, orders) = after.unwrap_or_default();
+                Some(LevelChange {
+                    side,
+                    price,
+                    action,
+                    quantity,
+                    orders,
+                })
+            })
+            .collect();
+        changes.sort_by(LevelChange::book_order);
+        changes
+    }
+
+    /// Start or stop recording resting order changes for [`OrderBook::take_order_changes`]
+    pub fn track_order_changes(&mut self, enabled: bool) {
+        self.order_changes = enabled.then(Vec::new);
+    }
+
+    /// Every change to a resting order since the last call, in the order they happened.
+    ///
+    /// Empty unless tracking was started with [`OrderBook::track_order_changes`].
+    pub fn take_order_changes(&mut self) -> Vec<OrderChange> {
+        self.order_changes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record_order_change(&mut self, change: OrderChange) {
+        if let Some(changes) = &mut self.order_changes {
+            changes.push(change);
+        }
+    }
+
+    /// Remember how a level looked before its first change since the last
+    /// [`OrderBook::take_level_changes`], if changes are tracked
+    fn touch_level(&mut self, side: Side, price_level: Price) {
+        let before = match &self.level_changes {
+            Some(touched) if !touched.contains_key(&(side, price_level)) => {
+                self.level(side, price_level).map(|level| self.level_totals(level))
+            }
+            _ => return,
+        };
+        if let Some(touched) = &mut self.level_changes {
+            touched.insert((side, price_level), before);
+        }
+    }
+
+    /// Total visible quantity and order count of a level
+    fn level_totals(&self, level: &Level) -> (u64, usize) {
+        (self.orders.orders(level).map(|o| o.visible_quantity()).sum(), level.len())
+    }
+
+    /// Add order to the book.
+    ///
+    /// Market orders never rest; execute them with [`OrderBook::execute_market_order`].
+    /// A new order beyond the book's depth limits is rejected, or makes room per the
+    /// limits' action; either way the orders taken out are left for [`OrderBook::drain_cancelled`].
+    /// The remainder of an order that already traded is cancelled rather than rejected.
+    pub fn add_order(&mut self, mut order: Order) {
+        let resting = !(order.auction_only || order.order_type.is_auction_order() || order.order_type == OrderType::Market);
+        if resting && !self.make_room(&order) {
+            order.status = if order.filled_quantity > 0 {
+                OrderStatus::Cancelled
+            } else {
+                OrderStatus::Rejected
+            };
+            self.cancelled.push(order);
+            return;
+        }
+        self.place(order);
+    }
+
+    /// Put an order in the book without depth checks, e.g. back after an amendment
+    fn place(&mut self, mut order: Order) {
+        if order.auction_only || order.order_type.is_auction_order() {
+            self.auction_orders.push(order);
+            return;
+        }
+
+        if order.order_type == OrderType::Market {
+            return;
+        }
+
+        order.refresh_slice();
+        self.rest(order, false);
+    }
+
+    /// Whether `order` fits within the depth limits, culling further-out orders first if
+    /// the limits allow it
+    fn make_room(&mut self, order: &Order) -> bool {
+        let Some(limits) = self.config.depth_limits else {
+            return true;
+        };
+        let price_level = order.price.unwrap_or_default();
+
+        loop {
+            let levels = match order.side {
+                Side::Buy => &self.bids,
+                Side::Sell => &self.asks,
+            };
+            let orders_full = limits.max_orders.is_some_and(|max| self.index.len() >= max);
+            let levels_full = !levels.contains_key(&price_level) && limits.max_levels.is_some_and(|max| levels.len() >= max);
+            if !orders_full && !levels_full {
+                return true;
+            }
+            if limits.on_limit == DepthLimitAction::Reject {
+                return false;
+            }
+
+            // Furthest level from the touch, if it is further out than the new order
+            let farthest = match order.side {
+                Side::Buy => levels.iter().next().filter(|(&price, _)| price < price_level),
+                Side::Sell => levels.iter().next_back().filter(|(&price, _)| price > price_level),
+            };
+            let Some(key) = farthest.and_then(|(_, level)| level.back()) else {
+                return false;
+            };
+            let mut culled = self.detach(key);
+            culled.status = OrderStatus::Cancelled;
+            self.cancelled.push(culled);
+        }
+    }
+
+    /// Store `order` at the back of its price level (behind its priority class, if the book
+    /// uses them), or at the front with `front`
+    fn rest(&mut self, order: Order, front: bool) {
+        let side = order.side;
+        let price_level = order.price.unwrap_or_default();
+        let priority_class = order.priority_class;
+        let order_id = order.id;
+
+        self.touch_level(side, price_level);
+        self.record_order_change(OrderChange::Added {
+            order_id,
+            side,
+            price: price_level,
+            quantity: order.visible_quantity(),
+        });
+        let key = self.orders.insert(order);
+        self.index.insert(order_id, key);
+        let level = match side {
+            Side::Buy => self.bids.entry(price_level).or_default(),
+            Side::Sell => self.asks.entry(price_level).or_default(),
+        };
+
+        if front {
+            self.orders.push_front(level, key);
+            return;
+        }
+        // Queue behind every order of the same or a better class
+        let behind = self
+            .config
+            .priority_classes
+            .then(|| {
+                self.orders
+                    .keys(level)
+                    .find(|&other| self.orders.get(other).priority_class > priority_class)
+            })
+            .flatten();
+        match behind {
+            Some(before) => self.orders.insert_before(level, before, key),
+            None => self.orders.push_back(level, key),
+        }
+    }
+
+    /// Take a resting order out of the book, dropping its level if it empties
+    fn detach(&mut self, key: SlotKey) -> Order {
+        let order = self.orders.get(key);
+        let (side, price_level, order_id) = (order.side, order.price.unwrap_or_default(), order.id);
+        if order.visible_quantity() > 0 {
+            self.record_order_change(OrderChange::Removed { order_id });
+        }
+        self.touch_level(side, price_level);
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if let Some(level) = levels.get_mut(&price_level) {
+            self.orders.unlink(level, key);
+            if level.is_empty() {
+                levels.remove(&price_level);
             }
-            Side::Sell => {
-                self.asks
-                    .entry(price_level)
-                    .or_insert_with(VecDeque::new)
-                    .push_back(order);
+        }
+        self.index.remove(&order_id);
+        self.orders.remove(key)
+    }
+
+    fn level(&self, side: Side, price_level: Price) -> Option<&Level> {
+        match side {
+            Side::Buy => self.bids.get(&price_level),
+            Side::Sell => self.asks.get(&price_level),
+        }
+    }
+
+    /// Match an arriving order against resting liquidity and rest any remainder.
+    ///
+    /// Only the opposite side up to the order's limit price is walked, unlike
+    /// [`OrderBook::match_orders`], which rescans the whole book. Market orders are
+    /// executed as by [`OrderBook::execute_market_order`]; auction orders are queued.
+    pub fn match_order(&mut self, incoming: Order) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        self.match_order_into(incoming, &mut trades);
+        trades
+    }
+
+    /// [`OrderBook::match_order`], appending the trades to `trades`.
+    ///
+    /// With the buffer reused and consumed trades handed back through
+    /// [`OrderBook::recycle_trades`], matching against a book in steady state doesn't allocate.
+    pub fn match_order_into(&mut self, mut incoming: Order, trades: &mut Vec<Trade>) {
+        if incoming.auction_only || incoming.order_type.is_auction_order() {
+            self.add_order(incoming);
+            return;
+        }
+        if incoming.order_type == OrderType::Market {
+            self.execute_market_order_into(incoming, trades);
+            return;
+        }
+        if incoming.time_in_force == TimeInForce::GoodTillCrossing && self.is_marketable(&incoming) {
+            // Unlike post-only, never rejected or repriced
+            incoming.status = OrderStatus::Cancelled;
+            self.cancelled.push(incoming);
+            return;
+        }
+        if incoming.post_only && self.is_marketable(&incoming) {
+            match self.config.cross_policy {
+                CrossPolicy::Reject => {
+                    incoming.status = OrderStatus::Rejected;
+                    self.cancelled.push(incoming);
+                    return;
+                }
+                CrossPolicy::Reprice => {
+                    incoming.price = self.passive_price(incoming.side);
+                    self.add_order(incoming);
+                    return;
+                }
+                CrossPolicy::Match => {}
             }
         }
+
+        let limit = incoming.price;
+        let earlier_breach = std::mem::take(&mut self.limit_breached);
+        self.sweep(&mut incoming, limit, trades);
+        let breached = self.limit_breached;
+        self.limit_breached |= earlier_breach;
+
+        // Don't leave the book crossed outside the price limits, unless an auction will uncross it
+        if breached && !incoming.is_fully_filled() && self.breach_action() != Some(LimitBreachAction::Auction) {
+            incoming.status = OrderStatus::Cancelled;
+        }
+        if incoming.status == OrderStatus::Cancelled {
+            // Cancelled by self-trade prevention or a price limit breach
+            self.cancelled.push(incoming);
+        } else if !incoming.is_fully_filled() {
+            self.add_order(incoming);
+        }
+    }
+
+    /// Hand back trades that have been consumed, so later matches can reuse their buffers
+    pub fn recycle_trades(&mut self, trades: impl IntoIterator<Item = Trade>) {
+        let room = MAX_POOLED_TRADES.saturating_sub(self.trade_pool.len());
+        self.trade_pool.extend(trades.into_iter().take(room));
+    }
+
+    /// Most aggressive price on `side` that neither locks nor crosses the opposite best price
+    fn passive_price(&self, side: Side) -> Option<Price> {
+        let tick = self.config.tick_size.filter(|tick| tick.units() > 0).unwrap_or(Price::from_units(1));
+        match side {
+            Side::Buy => Some(self.best_ask()? - tick),
+            Side::Sell => Some(self.best_bid()? + tick),
+        }
     }
 
-    /// Match orders and generate trades
+    /// Match crossed orders already resting in the book and generate trades.
+    ///
+    /// Used when matching resumes after orders were added without matching.
     pub fn match_orders(&mut self) -> Vec<Trade> {
         let mut trades = Vec::new();
+        let band = self.price_band();
+
+        // Passive side of the level pair being matched, kept while the pair is unchanged
+        let mut pair: Option<(Price, Price, Side)> = None;
 
         loop {
             // Get best bid and ask
-            let best_bid_price = self.bids.keys().next_back().copied();
-            let best_ask_price = self.asks.keys().next().copied();
+            let best_bid_price = self.best_bid();
+            let best_ask_price = self.best_ask();
 
             match (best_bid_price, best_ask_price) {
                 (Some(bid_price), Some(ask_price)) if bid_price >= ask_price => {
-                    // Match possible
-                    let mut bid_orders = self.bids.remove(&bid_price).unwrap();
-                    let mut ask_orders = self.asks.remove(&ask_price).unwrap();
-
-                    while let (Some(bid), Some(ask)) =
-                        (bid_orders.front_mut(), ask_orders.front_mut())
-                    {
-                        let trade_quantity = bid.remaining_quantity().min(ask.remaining_quantity());
-                        let trade_price = (ask_price as f64) / 100.0;
-
-                        // Create trade
-                        let trade = Trade::new(
-                            bid.id,
-                            ask.id,
-                            self.symbol.clone(),
-                            trade_quantity,
-                            trade_price,
-                        );
-
-                        // Update orders
-                        bid.filled_quantity += trade_quantity;
-                        ask.filled_quantity += trade_quantity;
-
-                        if bid.is_fully_filled() {
-                            bid.status = OrderStatus::Filled;
-                            bid_orders.pop_front();
-                        } else {
-                            bid.status = OrderStatus::PartiallyFilled;
-                        }
-
-                        if ask.is_fully_filled() {
-                            ask.status = OrderStatus::Filled;
-                            ask_orders.pop_front();
-                        } else {
-                            ask.status = OrderStatus::PartiallyFilled;
-                        }
-
-                        trades.push(trade);
+                    if !Self::within_band(band, ask_price) {
+                        self.limit_breached = true;
+                        break;
+                    }
 
-                        if bid_orders.is_empty() || ask_orders.is_empty() {
-                            break;
+                    // The level whose front order arrived first is the passive side
+                    let passive_side = match pair {
+                        Some((bid, ask, side)) if (bid, ask) == (bid_price, ask_price) => side,
+                        _ => {
+                            let front = |side, price| {
+                                let level = self.level(side, price).unwrap();
+                                self.orders.get(level.front().unwrap()).timestamp
+                            };
+                            if front(Side::Buy, bid_price) <= front(Side::Sell, ask_price) {
+                                Side::Buy
+                            } else {
+                                Side::Sell
+                            }
                         }
-                    }
+                    };
+                    pair = Some((bid_price, ask_price, passive_side));
+                    let (passive_price, aggressive_side, aggressive_price) = match passive_side {
+                        Side::Buy => (bid_price, Side::Sell, ask_price),
+                        Side::Sell => (ask_price, Side::Buy, bid_price),
+                    };
 
-                    // Put back remaining orders
-                    if !bid_orders.is_empty() {
-                        self.bids.insert(bid_price, bid_orders);
-                    }
-                    if !ask_orders.is_empty() {
-                        self.asks.insert(ask_price, ask_orders);
-                    }
+                    // Filled where it rests, so it keeps its place in the queue
+                    let front = self.level(aggressive_side, aggressive_price).unwrap().front().unwrap();
+                    let mut incoming = self.orders.get(front).clone();
+                    let start = trades.len();
+                    self.fill_level(&mut incoming, passive_side, passive_price, ask_price, &mut trades);
+                    self.settle_aggressor(front, incoming, &trades[start..]);
                 }
                 _ => break, // No more matches possible
             }
         }
 
+        if let Some(trade) = trades.last() {
+            self.last_trade_price = Some(trade.price);
+        }
         trades
     }
 
-    /// Cancel order by ID
-    pub fn cancel_order(&mut self, order_id: Uuid) -> Option<Order> {
-        // Search in bids
-        for orders in self.bids.values_mut() {
-            if let Some(pos) = orders.iter().position(|o| o.id == order_id) {
-                let mut order = orders.remove(pos).unwrap();
-                order.status = OrderStatus::Cancelled;
-                return Some(order);
+    /// Write back a resting order that aggressed in [`OrderBook::match_orders`], after
+    /// `trades`: it stays in place unless done, or its iceberg slice is used up
+    fn settle_aggressor(&mut self, key: SlotKey, mut order: Order, trades: &[Trade]) {
+        let shown = self.orders.get(key).visible_quantity();
+        self.touch_level(order.side, order.price.unwrap_or_default());
+        let mut executed = 0;
+        for trade in trades {
+            self.record_order_change(OrderChange::Executed {
+                order_id: order.id,
+                quantity: trade.quantity,
+                trade_id: trade.id,
+            });
+            executed += trade.quantity;
+        }
+        order.slice_remaining = order.slice_remaining.saturating_sub(executed);
+        *self.orders.get_mut(key) = order;
+
+        let order = self.orders.get(key);
+        if order.status == OrderStatus::Cancelled {
+            let order = self.detach(key);
+            self.cancelled.push(order);
+        } else if order.is_fully_filled() {
+            self.detach(key);
+        } else if order.visible_quantity() == 0 {
+            let mut order = self.detach(key);
+            order.refresh_slice();
+            order.timestamp = self.clock.now();
+            self.rest(order, false);
+        } else if shown.saturating_sub(executed) > order.visible_quantity() {
+            // Self-trade prevention took off more than traded
+            let reduced = shown.saturating_sub(executed) - order.visible_quantity();
+            self.record_order_change(OrderChange::Reduced {
+                order_id: order.id,
+                quantity: reduced,
+            });
+        }
+    }
+
+    /// Execute a market order against the opposite side, best price first.
+    ///
+    /// Any quantity left once the opposite side is exhausted is cancelled; if nothing
+    /// traded at all the order is rejected. Returns the trades and the final order state.
+    pub fn execute_market_order(&mut self, order: Order) -> (Vec<Trade>, Order) {
+        let mut trades = Vec::new();
+        let order = self.execute_market_order_into(order, &mut trades);
+        (trades, order)
+    }
+
+    /// [`OrderBook::execute_market_order`], appending the trades to `trades`
+    pub fn execute_market_order_into(&mut self, mut order: Order, trades: &mut Vec<Trade>) -> Order {
+        self.sweep(&mut order, None, trades);
+
+        if order.status == OrderStatus::Cancelled {
+            // Cancelled by self-trade prevention; a price limit breach only stops the sweep,
+            // leaving the remainder to the cases below
+        } else if order.is_fully_filled() {
+            order.status = OrderStatus::Filled;
+        } else if order.filled_quantity > 0 {
+            order.status = OrderStatus::Cancelled;
+        } else {
+            order.status = OrderStatus::Rejected;
+        }
+
+        order
+    }
+
+    /// Match an incoming order against resting orders on the opposite side.
+    ///
+    /// Levels are consumed best price first, FIFO within a level, while they are
+    /// within `limit` (any price if `None`). Trades print at the resting order's price and
+    /// are appended to `trades`.
+    fn sweep(&mut self, incoming: &mut Order, limit: Option<Price>, trades: &mut Vec<Trade>) {
+        let start = trades.len();
+        let spread = self.spread();
+        let band = self.price_band();
+
+        while !incoming.is_fully_filled() && incoming.status != OrderStatus::Cancelled {
+            let best = match incoming.side {
+                Side::Buy => self.asks.keys().next().copied(),
+                Side::Sell => self.bids.keys().next_back().copied(),
+            };
+            let Some(price_level) = best else {
+                break;
+            };
+
+            let within_limit = match (incoming.side, limit) {
+                (_, None) => true,
+                (Side::Buy, Some(limit)) => price_level <= limit,
+                (Side::Sell, Some(limit)) => price_level >= limit,
+            };
+            if !within_limit {
+                break;
+            }
+            if !Self::within_band(band, price_level) {
+                self.limit_breached = true;
+                break;
             }
+
+            let resting_side = match incoming.side {
+                Side::Buy => Side::Sell,
+                Side::Sell => Side::Buy,
+            };
+            self.fill_level(incoming, resting_side, price_level, price_level, trades);
         }
 
-        // Search in asks
-        for orders in self.asks.values_mut() {
-            if let Some(pos) = orders.iter().position(|o| o.id == order_id) {
-                let mut order = orders.remove(pos).unwrap();
-                order.status = OrderStatus::Cancelled;
-                return Some(order);
+        let swept = &mut trades[start..];
+        if self.config.trade_context {
+            Self::annotate(swept, spread);
+        }
+        if let Some(trade) = swept.last() {
+            self.last_trade_price = Some(trade.price);
+        }
+    }
+
+    /// Tradable price range under the book's price limits, if it has any
+    fn price_band(&self) -> Option<(Price, Price)> {
+        Some(self.config.price_limits?.band(self.last_trade_price))
+    }
+
+    fn within_band(band: Option<(Price, Price)>, price: Price) -> bool {
+        band.is_none_or(|(low, high)| low <= price && price <= high)
+    }
+
+    fn breach_action(&self) -> Option<LimitBreachAction> {
+        self.config.price_limits.map(|limits| limits.on_breach)
+    }
+
+    /// Price of the most recent trade, as used for dynamic price limits
+    pub fn last_trade_price(&self) -> Option<Price> {
+        self.last_trade_price
+    }
+
+    /// Whether a trade was prevented by the price limits since the last call, and what to do about it
+    pub fn take_limit_breach(&mut self) -> Option<LimitBreachAction> {
+        if std::mem::take(&mut self.limit_breached) {
+            self.breach_action()
+        } else {
+            None
+        }
+    }
+
+    /// Best ask minus best bid, when both sides are present
+    fn spread(&self) -> Option<Price> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    /// Fill in the per-aggressor part of each trade's context
+    fn annotate(trades: &mut [Trade], spread: Option<Price>) {
+        // Trades come level by level, so each price change starts a new level
+        let levels_swept = trades.windows(2).filter(|pair| pair[0].price != pair[1].price).count() + 1;
+        let depth_consumed = trades.iter().map(|t| t.quantity).sum();
+
+        for trade in trades {
+            let context = trade.context.get_or_insert_with(TradeContext::default);
+            context.spread = spread;
+            context.levels_swept = levels_swept;
+            context.depth_consumed = depth_consumed;
+        }
+    }
+
+    /// Fill `incoming` against the resting orders at `price_level` on `side`, printing at `trade_price`.
+    ///
+    /// FIFO fills the queue front to back; pro-rata splits the incoming size in
+    /// proportion to resting size after the top-order carve-out. Self-trade prevention
+    /// is applied wherever a resting order of the same client would receive a fill.
+    /// Resting icebergs only fill up to their visible slice; an exhausted slice is
+    /// refreshed from the hidden quantity and goes to the back of the level, losing
+    /// time priority. Fully filled resting orders are removed from the book, and
+    /// resting orders cancelled by self-trade prevention are moved to `cancelled`.
+    fn fill_level(&mut self, incoming: &mut Order, side: Side, price_level: Price, trade_price: Price, trades: &mut Vec<Trade>) {
+        self.touch_level(side, price_level);
+        let mut keys = std::mem::take(&mut self.scratch.keys);
+        let mut allocations = std::mem::take(&mut self.scratch.allocations);
+        keys.clear();
+        allocations.clear();
+        match self.level(side, price_level) {
+            Some(level) => keys.extend(self.orders.keys(level)),
+            None => {
+                self.scratch.keys = keys;
+                self.scratch.allocations = allocations;
+                return;
+            }
+        }
+        let quantity = incoming.remaining_quantity();
+        match &self.config.algorithm {
+            MatchingAlgorithm::Fifo => Self::allocate_fifo(quantity, &self.orders, &keys, &mut allocations),
+            MatchingAlgorithm::ProRata { top_order_percent } => Self::allocate_pro_rata(
+                quantity,
+                &self.orders,
+                &keys,
+                *top_order_percent,
+                &mut allocations,
+                &mut self.scratch.sizes,
+            ),
+            MatchingAlgorithm::FifoWithLmm { lmm_client_id, lmm_percent } => {
+                Self::allocate_lmm(quantity, &self.orders, &keys, lmm_client_id, *lmm_percent, &mut allocations)
             }
         }
 
-        None
+        for (queue_position, (&key, &trade_quantity)) in keys.iter().zip(&allocations).enumerate() {
+            if trade_quantity == 0 {
+                continue;
+            }
+            let resting = self.orders.get_mut(key);
+
+            if let Some(policy) = SelfTradePrevention::between(self.config.self_trade_prevention, incoming, resting) {
+                policy.apply(incoming, resting);
+                if incoming.status == OrderStatus::Cancelled {
+                    break;
+                }
+                // Quantity allocated to a cancelled resting order is re-allocated by the caller
+                continue;
+            }
+
+            let (buy, sell) = match incoming.side {
+                Side::Buy => (&*incoming, &*resting),
+                Side::Sell => (&*resting, &*incoming),
+            };
+            let mut trade = pooled_trade(&mut self.trade_pool, &self.symbol, buy, sell, trade_quantity, trade_price);
+            trade.aggressor = Some(incoming.side);
+            if let Some(changes) = &mut self.order_changes {
+                changes.push(OrderChange::Executed {
+                    order_id: resting.id,
+                    quantity: trade_quantity,
+                    trade_id: trade.id,
+                });
+            }
+            if self.config.trade_context {
+                trade.context = Some(TradeContext {
+                    queue_position,
+                    ..TradeContext::default()
+                });
+            }
+            trades.push(trade);
+
+            incoming.filled_quantity += trade_quantity;
+            resting.filled_quantity += trade_quantity;
+            resting.slice_remaining = resting.slice_remaining.saturating_sub(trade_quantity);
+            resting.status = if resting.is_fully_filled() {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
+        }
+
+        let mut refreshed = std::mem::take(&mut self.scratch.refreshed);
+        for &key in &keys {
+            let order = self.orders.get(key);
+            if order.status == OrderStatus::Cancelled {
+                let order = self.detach(key);
+                self.cancelled.push(order);
+            } else if order.is_fully_filled() {
+                self.detach(key);
+            } else if order.visible_quantity() == 0 {
+                refreshed.push(self.detach(key));
+            }
+        }
+        for mut order in refreshed.drain(..) {
+            order.refresh_slice();
+            order.timestamp = self.clock.now();
+            self.rest(order, false);
+        }
+        self.scratch.keys = keys;
+        self.scratch.allocations = allocations;
+        self.scratch.refreshed = refreshed;
+
+        if incoming.status != OrderStatus::Cancelled && incoming.filled_quantity > 0 {
+            incoming.status = if incoming.is_fully_filled() {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
+        }
     }
 
-    /// Get current best bid price
-    pub fn best_bid(&self) -> Option<f64> {
-        self.bids.keys().next_back().map(|&p| (p as f64) / 100.0)
+    /// Fill quantity per resting order of `keys`, front of the queue first, into `allocations`
+    fn allocate_fifo(mut quantity: u64, orders: &OrderSlab, keys: &[SlotKey], allocations: &mut Vec<u64>) {
+        allocations.extend(keys.iter().map(|&key| {
+            let fill = quantity.min(orders.get(key).visible_quantity());
+            quantity -= fill;
+            fill
+        }));
     }
 
-    /// Get current best ask price
-    pub fn best_ask(&self) -> Option<f64> {
-        self.asks.keys().next().map(|&p| (p as f64) / 100.0)
+    /// Fill quantity per resting order: the LMM's share first, then FIFO for the rest
+    fn allocate_lmm(
+        quantity: u64,
+        orders: &OrderSlab,
+        keys: &[SlotKey],
+        lmm_client_id: &str,
+        lmm_percent: u8,
+        allocations: &mut Vec<u64>,
+    ) {
+        let mut lmm_share = quantity * lmm_percent.min(100) as u64 / 100;
+        allocations.extend(keys.iter().map(|&key| {
+            let order = orders.get(key);
+            if order.client_id != lmm_client_id {
+                return 0;
+            }
+            let fill = lmm_share.min(order.visible_quantity());
+            lmm_share -= fill;
+            fill
+        }));
+
+        let mut left = quantity - allocations.iter().sum::<u64>();
+        for (allocation, &key) in allocations.iter_mut().zip(keys) {
+            let fill = left.min(orders.get(key).visible_quantity() - *allocation);
+            *allocation += fill;
+            left -= fill;
+        }
     }
 
-    /// Get mid price
-    pub fn mid_price(&self) -> Option<f64> {
-        match (self.best_bid(), self.best_ask()) {
-            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
-            _ => None,
+    /// Fill quantity per resting order, proportional to resting size.
+    ///
+    /// The front order first receives `top_order_percent` of the incoming size. Lots lost
+    /// to rounding down are handed out one at a time in queue order. `remaining` is scratch.
+    fn allocate_pro_rata(
+        quantity: u64,
+        orders: &OrderSlab,
+        keys: &[SlotKey],
+        top_order_percent: u8,
+        allocations: &mut Vec<u64>,
+        remaining: &mut Vec<u64>,
+    ) {
+        remaining.clear();
+        remaining.extend(keys.iter().map(|&key| orders.get(key).visible_quantity()));
+        let total: u64 = remaining.iter().sum();
+        if quantity >= total {
+            allocations.extend_from_slice(remaining);
+            return;
+        }
+
+        allocations.resize(remaining.len(), 0);
+        let mut left = quantity;
+
+        if let Some(top) = remaining.first_mut() {
+            let carve_out = (quantity * top_order_percent.min(100) as u64 / 100).min(*top);
+            allocations[0] = carve_out;
+            *top -= carve_out;
+            left -= carve_out;
+        }
+
+        let pool: u64 = remaining.iter().sum();
+        if pool > 0 {
+            let to_split = left;
+            for (allocation, &size) in allocations.iter_mut().zip(remaining.iter()) {
+                let share = ((to_split as u128 * size as u128) / pool as u128) as u64;
+                *allocation += share;
+                left -= share;
+            }
+        }
+
+        // Hand out rounding leftovers one lot at a time in queue order
+        while left > 0 {
+            let mut progressed = false;
+            for (allocation, &key) in allocations.iter_mut().zip(keys) {
+                if left > 0 && *allocation < orders.get(key).visible_quantity() {
+                    *allocation += 1;
+                    left -= 1;
+                    progressed = true;
+                }
+            }
+            if !progressed {
+                break;
+            }
         }
     }
 
-    /// Get total depth (number of orders)
-    pub fn depth(&self) -> usize {
-        let bid_depth: usize = self.bids.values().map(|v| v.len()).sum();
-        let ask_depth: usize = self.asks.values().map(|v| v.len()).sum();
-        bid_depth + ask_depth
+    /// Cancel order by ID
+    pub fn cancel_order(&mut self, order_id: Uuid) -> Option<Order> {
+        if let Some(&key) = self.index.get(&order_id) {
+            let mut order = self.detach(key);
+            order.status = OrderStatus::Cancelled;
+            return Some(order);
+        }
+
+        // Search in orders waiting for an auction
+        if let Some(pos) = self.auction_orders.iter().position(|o| o.id == order_id) {
+            let mut order = self.auction_orders.remove(pos);
+            order.status = OrderStatus::Cancelled;
+            return Some(order);
+        }
+
+        None
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::Side;
+    /// Cancel every resting order on `side` priced within `low..=high`
+    pub fn cancel_range(&mut self, side: Side, low: Price, high: Price) -> Vec<Order> {
+        if low > high {
+            return Vec::new();
+        }
 
-    #[test]
-    fn test_order_book_creation() {
-        let book = OrderBook::new("BTCUSD".to_string());
-        assert_eq!(book.depth(), 0);
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        let keys: Vec<SlotKey> = levels
+            .range(low..=high)
+            .flat_map(|(_, level)| self.orders.keys(level))
+            .collect();
+
+        keys.into_iter()
+            .map(|key| {
+                let mut order = self.detach(key);
+                order.status = OrderStatus::Cancelled;
+                order
+            })
+            .collect()
     }
 
-    #[test]
-    fn test_add_orders() {
-        let mut book = OrderBook::new("BTCUSD".to_string());
-        
-        let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
-        let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50100.0, "client2".to_string());
-        
-        book.add_order(buy_order);
-        book.add_order(sell_order);
-        
-        assert_eq!(book.depth(), 2);
-        assert_eq!(book.best_bid(), Some(50000.0));
-        assert_eq!(book.best_ask(), Some(50100.0));
+    /// Cancel every resting order `filter` picks, including those waiting for an auction
+    pub fn cancel_where(&mut self, filter: impl Fn(&Order) -> bool) -> Vec<Order> {
+        let keys: Vec<SlotKey> = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .flat_map(|level| self.orders.keys(level))
+            .filter(|&key| filter(self.orders.get(key)))
+            .collect();
+
+        let mut cancelled: Vec<Order> = keys.into_iter().map(|key| self.detach(key)).collect();
+        let (picked, others): (Vec<Order>, Vec<Order>) = self.auction_orders.drain(..).partition(|order| filter(order));
+        self.auction_orders = others;
+        cancelled.extend(picked);
+        for order in &mut cancelled {
+            order.status = OrderStatus::Cancelled;
+        }
+        cancelled
     }
 
-    #[test]
-    fn test_order_matching() {
-        let mut book = OrderBook::new("BTCUSD".to_string());
-        
+    /// Remove all resting orders whose time in force has lapsed at `now`
+    pub fn expire_orders(&mut self, now: DateTime<Utc>) -> Vec<Order> {
+        let lapsed: Vec<SlotKey> = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .flat_map(|level| self.orders.keys(level))
+            .filter(|&key| self.orders.get(key).is_expired(now))
+            .collect();
+
+        let mut expired = Vec::new();
+        for key in lapsed {
+            let mut order = self.detach(key);
+            order.status = OrderStatus::Expired;
+            expired.push(order);
+        }
+
+        let (lapsed, working) = self
+            .auction_orders
+            .drain(..)
+            .partition(|order| order.is_expired(now));
+        self.auction_orders = working;
+        for mut order in lapsed {
+            order.status = OrderStatus::Expired;
+            expired.push(order);
+        }
+
+        expired
+    }
+
+    /// Amend price and/or quantity of a resting order.
+    ///
+    /// A quantity reduction at the same price keeps the order's time priority.
+    /// A price change or a size increase moves it to the back of the (new) price level.
+    /// Reducing the quantity to or below what has already been filled cancels the remainder.
+    pub fn replace_order(
+        &mut self,
+        order_id: Uuid,
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+    ) -> Option<Order> {
+        let key = *self.index.get(&order_id)?;
+
+        let current = self.orders.get(key);
+        let quantity = new_quantity.unwrap_or(current.quantity);
+        let price_changed = new_price.is_some_and(|p| Some(p) != current.price);
+
+        if quantity <= current.filled_quantity {
+            let mut order = self.detach(key);
+            order.quantity = quantity;
+            order.status = OrderStatus::Cancelled;
+            return Some(order);
+        }
+
+        if !price_changed && quantity <= current.quantity {
+            let shown = current.visible_quantity();
+            self.touch_level(current.side, current.price.unwrap_or_default());
+            let order = self.orders.get_mut(key);
+            order.quantity = quantity;
+            let order = order.clone();
+            if order.visible_quantity() < shown {
+                self.record_order_change(OrderChange::Reduced {
+                    order_id,
+                    quantity: shown - order.visible_quantity(),
+                });
+            }
+            return Some(order);
+        }
+
+        let mut order = self.detach(key);
+        order.quantity = quantity;
+        if let Some(price) = new_price {
+            order.price = Some(price);
+        }
+        order.timestamp = self.clock.now();
+        self.place(order.clone());
+        Some(order)
+    }
+
+    /// Look up a resting or auction order by ID
+    pub fn get_order(&self, order_id: Uuid) -> Option<&Order> {
+        match self.index.get(&order_id) {
+            Some(&key) => Some(self.orders.get(key)),
+            None => self.auction_orders.iter().find(|o| o.id == order_id),
+        }
+    }
+
+    /// Whether the order would trade immediately against resting liquidity
+    pub fn is_marketable(&self, order: &Order) -> bool {
+        let opposite = match order.side {
+            Side::Buy => self.best_ask(),
+            Side::Sell => self.best_bid(),
+        };
+
+        match (order.price, opposite) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(price), Some(best)) => match order.side {
+                Side::Buy => price >= best,
+                Side::Sell => price <= best,
+            },
+        }
+    }
+
+    /// Get current best bid price
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bids.keys().next_back().copied()
+    }
+
+    /// Get current best ask price
+    pub fn best_ask(&self) -> Option<Price> {
+        self.asks.keys().next().copied()
+    }
+
+    /// Get mid price, rounded down to a whole price unit
+    pub fn mid_price(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some(Price::from_units((bid.units() + ask.units()).div_euclid(2))),
+            _ => None,
+        }
+    }
+
+    /// L2 snapshot of the best `levels` price levels per side
+    pub fn get_depth(&self, levels: usize) -> DepthSnapshot {
+        let aggregate = |(&price, level): (&Price, &Level)| {
+            let quantity = self.orders.orders(level).map(|o| o.visible_quantity()).sum();
+            (price, quantity, level.len())
+        };
+
+        DepthSnapshot {
+            bids: self.bids.iter().rev().take(levels).map(aggregate).collect(),
+            asks: self.asks.iter().take(levels).map(aggregate).collect(),
+        }
+    }
+
+    /// Best bid and offer with the total visible quantity at each
+    pub fn bbo(&self) -> Bbo {
+        let depth = self.get_depth(1);
+        let top = |levels: &[(Price, u64, usize)]| levels.first().map(|&(price, quantity, _)| (price, quantity));
+        Bbo {
+            bid: top(&depth.bids),
+            ask: top(&depth.asks),
+        }
+    }
+
+    /// Bid/ask volume imbalance over the best `levels` levels per side
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let bid_volume: u64 = self.aggregated_levels(Side::Buy, levels).iter().map(|&(_, q)| q).sum();
+        let ask_volume: u64 = self.aggregated_levels(Side::Sell, levels).iter().map(|&(_, q)| q).sum();
+        let total = bid_volume + ask_volume;
+        (total > 0).then(|| (bid_volume as f64 - ask_volume as f64) / total as f64)
+    }
+
+    /// Mid price weighted towards the side with less size at the touch.
+    ///
+    /// `(bid * ask_size + ask * bid_size) / (bid_size + ask_size)`, using the best level of each side.
+    pub fn weighted_mid(&self) -> Option<f64> {
+        let (bid, bid_size) = *self.aggregated_levels(Side::Buy, 1).first()?;
+        let (ask, ask_size) = *self.aggregated_levels(Side::Sell, 1).first()?;
+        let total = (bid_size + ask_size) as f64;
+        if total == 0.0 {
+            return None;
+        }
+        Some((bid.to_f64() * ask_size as f64 + ask.to_f64() * bid_size as f64) / total)
+    }
+
+    /// Quantity-weighted average price of the best `levels` levels on one side
+    pub fn book_vwap(&self, side: Side, levels: usize) -> Option<f64> {
+        let (notional, volume) = self
+            .aggregated_levels(side, levels)
+            .iter()
+            .fold((0.0, 0u64), |(notional, volume), &(price, quantity)| {
+                (notional + price.to_f64() * quantity as f64, volume + quantity)
+            });
+        (volume > 0).then(|| notional / volume as f64)
+    }
+
+    /// All analytics over the best `levels` levels
+    pub fn analytics(&self, levels: usize) -> BookAnalytics {
+        BookAnalytics {
+            imbalance: self.imbalance(levels),
+            weighted_mid: self.weighted_mid(),
+            bid_vwap: self.book_vwap(Side::Buy, levels),
+            ask_vwap: self.book_vwap(Side::Sell, levels),
+        }
+    }
+
+    /// Visible quantity per price level, best price first
+    pub(crate) fn aggregated_levels(&self, side: Side, max_levels: usize) -> Vec<(Price, u64)> {
+        let aggregate = |(&price, level): (&Price, &Level)| {
+            let quantity = self.orders.orders(level).map(|o| o.visible_quantity()).sum();
+            (price, quantity)
+        };
+
+        match side {
+            Side::Buy => self.bids.iter().rev().take(max_levels).map(aggregate).collect(),
+            Side::Sell => self.asks.iter().take(max_levels).map(aggregate).collect(),
+        }
+    }
+
+    /// Resting orders on one side, best price first and in queue order within a level
+    pub(crate) fn resting_orders(&self, side: Side) -> Box<dyn Iterator<Item = &Order> + '_> {
+        match side {
+            Side::Buy => Box::new(self.bids.values().rev().flat_map(|level| self.orders.orders(level))),
+            Side::Sell => Box::new(self.asks.values().flat_map(|level| self.orders.orders(level))),
+        }
+    }
+
+    /// Inconsistencies in the book's own structure, for invariant audits; empty when sound.
+    ///
+    /// Every resting order must sit on its own side at its own price with quantity left
+    /// to fill, and be the order the index points to for its ID. Whether the book may
+    /// be crossed depends on the trading state, so that is left to the caller.
+    pub fn check_integrity(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut resting = 0;
+
+        for (side, levels) in [(Side::Buy, &self.bids), (Side::Sell, &self.asks)] {
+            for (&price_level, level) in levels {
+                let keys: Vec<SlotKey> = self.orders.keys(level).collect();
+                if keys.is_empty() || keys.len() != level.len() {
+                    problems.push(format!("{} level {} holds {} orders but counts {}", side, price_level, keys.len(), level.len()));
+                }
+                resting += keys.len();
+
+                for key in keys {
+                    let order = self.orders.get(key);
+                    if order.side != side || order.price != Some(price_level) {
+                        problems.push(format!("Order {} ({} at {:?}) queued at {} {}", order.id, order.side, order.price, side, price_level));
+                    }
+                    if order.filled_quantity >= order.quantity {
+                        problems.push(format!("Order {} rests with {} of {} filled", order.id, order.filled_quantity, order.quantity));
+                    }
+                    if self.index.get(&order.id) != Some(&key) {
+                        problems.push(format!("Order {} is not indexed at its slot", order.id));
+                    }
+                }
+            }
+        }
+        if self.index.len() != resting {
+            problems.push(format!("Index holds {} orders but {} are resting", self.index.len(), resting));
+        }
+
+        for order in &self.auction_orders {
+            if order.filled_quantity > order.quantity {
+                problems.push(format!("Auction order {} has {} of {} filled", order.id, order.filled_quantity, order.quantity));
+            }
+        }
+        problems
+    }
+
+    /// Take the orders the book cancelled (e.g. through self-trade prevention) or rejected
+    /// (post-only orders that would cross) on its own since the last call
+    pub fn drain_cancelled(&mut self) -> Vec<Order> {
+        std::mem::take(&mut self.cancelled)
+    }
+
+    /// Orders held for the next auction uncross, in arrival order
+    pub fn auction_orders(&self) -> &[Order] {
+        &self.auction_orders
+    }
+
+    /// Reject queued orders of `order_type` whose auction did not take place
+    pub fn reject_auction_orders(&mut self, order_type: OrderType) -> Vec<Order> {
+        let (mut rejected, waiting): (Vec<Order>, Vec<Order>) = self
+            .auction_orders
+            .drain(..)
+            .partition(|order| order.order_type == order_type);
+        self.auction_orders = waiting;
+
+        for order in &mut rejected {
+            order.status = OrderStatus::Rejected;
+        }
+        rejected
+    }
+
+    /// Run the `auction` uncross (`MarketOnOpen` or `MarketOnClose`).
+    ///
+    /// Resting limit orders, auction-only limit orders and on-open/on-close orders of the
+    /// given type all execute at the single equilibrium price. Market orders have priority,
+    /// then better prices, then time. Limit remainders go back to the book (auction-only
+    /// ones wait for the next auction); on-open/on-close remainders are cancelled and can
+    /// be taken with [`OrderBook::drain_cancelled`]. Without an equilibrium price the
+    /// on-open/on-close orders stay queued, for [`OrderBook::reject_auction_orders`].
+    pub fn uncross(&mut self, auction: OrderType) -> AuctionResult {
+        let (participants, waiting): (Vec<Order>, Vec<Order>) = self
+            .auction_orders
+            .drain(..)
+            .partition(|order| order.auction_only || order.order_type == auction);
+        self.auction_orders = waiting;
+
+        let levels: Vec<(Side, Price)> = self
+            .bids
+            .keys()
+            .map(|&price| (Side::Buy, price))
+            .chain(self.asks.keys().map(|&price| (Side::Sell, price)))
+            .collect();
+        for (side, price_level) in levels {
+            self.touch_level(side, price_level);
+        }
+        let keys: Vec<SlotKey> = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .flat_map(|level| self.orders.keys(level))
+            .collect();
+        let resting: Vec<Order> = keys.into_iter().map(|key| self.orders.remove(key)).collect();
+        for order in &resting {
+            self.record_order_change(OrderChange::Removed { order_id: order.id });
+        }
+        self.bids.clear();
+        self.asks.clear();
+        self.index.clear();
+        self.orders.clear();
+        let (mut buys, mut sells): (Vec<Order>, Vec<Order>) = resting
+            .into_iter()
+            .chain(participants)
+            .partition(|order| order.side == Side::Buy);
+
+        let mut result = AuctionResult::default();
+        if let Some((price, volume)) = auction::equilibrium_price(&buys, &sells) {
+            // Market orders first, then price, then time
+            buys.sort_by(|a, b| {
+                let a_price = a.price.unwrap_or(Price::MAX);
+                let b_price = b.price.unwrap_or(Price::MAX);
+                b_price.cmp(&a_price).then(a.timestamp.cmp(&b.timestamp))
+            });
+            sells.sort_by(|a, b| {
+                let a_price = a.price.unwrap_or(Price::MIN);
+                let b_price = b.price.unwrap_or(Price::MIN);
+                a_price.cmp(&b_price).then(a.timestamp.cmp(&b.timestamp))
+            });
+
+            let (mut b, mut s) = (0, 0);
+            while b < buys.len() && s < sells.len() && result.volume < volume {
+                let (buy, sell) = (&mut buys[b], &mut sells[s]);
+                let quantity = buy.remaining_quantity().min(sell.remaining_quantity());
+                let trade = pooled_trade(&mut self.trade_pool, &self.symbol, buy, sell, quantity, price);
+                result.trades.push(trade);
+                result.volume += quantity;
+
+                for order in [&mut *buy, &mut *sell] {
+                    order.filled_quantity += quantity;
+                    order.status = if order.is_fully_filled() {
+                        OrderStatus::Filled
+                    } else {
+                        OrderStatus::PartiallyFilled
+                    };
+                }
+                if buy.is_fully_filled() {
+                    b += 1;
+                }
+                if sell.is_fully_filled() {
+                    s += 1;
+                }
+            }
+            result.price = Some(price);
+            self.last_trade_price = Some(price);
+        }
+
+        for mut order in buys.into_iter().chain(sells) {
+            if order.is_fully_filled() {
+                continue;
+            }
+            if order.order_type != auction {
+                self.place(order);
+            } else if result.price.is_none() {
+                self.auction_orders.push(order);
+            } else {
+                order.status = OrderStatus::Cancelled;
+                self.cancelled.push(order);
+            }
+        }
+
+        result
+    }
+
+    /// Where an uncross of `auction` would clear now, without executing anything
+    pub fn indicative_uncross(&self, auction: OrderType) -> IndicativeAuction {
+        let participants = self
+            .auction_orders
+            .iter()
+            .filter(|order| order.auction_only || order.order_type == auction);
+        let (buys, sells): (Vec<Order>, Vec<Order>) = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .flat_map(|level| self.orders.orders(level))
+            .chain(participants)
+            .cloned()
+            .partition(|order| order.side == Side::Buy);
+
+        auction::indicative_auction(&buys, &sells)
+    }
+
+    /// Get total depth (number of orders)
+    pub fn depth(&self) -> usize {
+        let bid_depth: usize = self.bids.values().map(Level::len).sum();
+        let ask_depth: usize = self.asks.values().map(Level::len).sum();
+        bid_depth + ask_depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+
+    #[test]
+    fn test_order_book_creation() {
+        let book = OrderBook::new("BTCUSD".to_string());
+        assert_eq!(book.depth(), 0);
+    }
+
+    #[test]
+    fn test_add_orders() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        
+        let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50100.0, "client2".to_string());
+        
+        book.add_order(buy_order);
+        book.add_order(sell_order);
+        
+        assert_eq!(book.depth(), 2);
+        assert_eq!(book.best_bid(), Some(Price::from(50000.0)));
+        assert_eq!(book.best_ask(), Some(Price::from(50100.0)));
+    }
+
+    #[test]
+    fn test_order_matching() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        
         let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
         let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 49900.0, "client2".to_string());
         
@@ -198,6 +1556,806 @@ mod tests {
         
         assert_eq!(trades.len(), 1);
         assert_eq!(trades[0].quantity, 5);
-        assert_eq!(trades[0].price, 49900.0);
+        assert_eq!(trades[0].price, Price::from(49900.0));
+    }
+
+    #[test]
+    fn test_match_orders_fills_aggressor_in_place() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        book.track_order_changes(true);
+        let limit = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+
+        let bid = limit(Side::Buy, 3, 50000.0);
+        let (first, second) = (limit(Side::Sell, 5, 49900.0), limit(Side::Sell, 2, 49900.0));
+        let (bid_id, first_id) = (bid.id, first.id);
+        book.add_order(bid);
+        book.add_order(first);
+        book.add_order(second);
+        book.take_order_changes();
+
+        // The front ask trades as it rests: executions, no delete and re-add
+        let trades = book.match_orders();
+        assert_eq!(
+            book.take_order_changes(),
+            vec![
+                OrderChange::Executed { order_id: bid_id, quantity: 3, trade_id: trades[0].id },
+                OrderChange::Executed { order_id: first_id, quantity: 3, trade_id: trades[0].id },
+            ]
+        );
+
+        book.add_order(limit(Side::Buy, 2, 49900.0));
+        assert_eq!(book.match_orders()[0].sell_order_id, first_id);
+    }
+
+    #[test]
+    fn test_replace_order_priority() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        let first = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        let second = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client2".to_string());
+        let first_id = first.id;
+        let second_id = second.id;
+        book.add_order(first);
+        book.add_order(second);
+
+        // Reducing quantity keeps the order at the front of the queue
+        let amended = book.replace_order(first_id, None, Some(4)).unwrap();
+        assert_eq!(amended.quantity, 4);
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 4, 50000.0, "client3".to_string());
+        book.add_order(sell);
+        let trades = book.match_orders();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].buy_order_id, first_id);
+
+        // Increasing quantity loses priority
+        let third = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client4".to_string());
+        let third_id = third.id;
+        book.add_order(third);
+        book.replace_order(second_id, None, Some(20)).unwrap();
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50000.0, "client3".to_string());
+        book.add_order(sell);
+        let trades = book.match_orders();
+        assert_eq!(trades[0].buy_order_id, third_id);
+    }
+
+    #[test]
+    fn test_replace_order_price() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        let order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50100.0, "client1".to_string());
+        let order_id = order.id;
+        book.add_order(order);
+
+        let amended = book.replace_order(order_id, Some(Price::from(50200.0)), None).unwrap();
+        assert_eq!(amended.price, Some(Price::from(50200.0)));
+        assert_eq!(book.best_ask(), Some(Price::from(50200.0)));
+        assert_eq!(book.depth(), 1);
+
+        // Reducing below the filled quantity cancels the order
+        let cancelled = book.replace_order(order_id, None, Some(0)).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert_eq!(book.depth(), 0);
+        assert!(book.replace_order(order_id, None, Some(5)).is_none());
+    }
+
+    #[test]
+    fn test_requeue_timestamps_from_clock() {
+        #[derive(Debug)]
+        struct FixedClock(DateTime<Utc>);
+
+        impl Clock for FixedClock {
+            fn now(&self) -> DateTime<Utc> {
+                self.0
+            }
+
+            fn source(&self) -> crate::clock::ClockSource {
+                crate::clock::ClockSource::Ptp
+            }
+        }
+
+        let fixed = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        book.set_clock(Arc::new(FixedClock(fixed)));
+
+        // Amended to a new price
+        let bid = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 49900.0, "client1".to_string());
+        let bid_id = bid.id;
+        book.add_order(bid);
+        book.replace_order(bid_id, Some(Price::from(49950.0)), None).unwrap();
+        assert_eq!(book.get_order(bid_id).unwrap().timestamp, fixed);
+
+        // Showing its next iceberg slice
+        let iceberg = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string()).with_display_quantity(4);
+        let iceberg_id = iceberg.id;
+        book.add_order(iceberg);
+        assert_ne!(book.get_order(iceberg_id).unwrap().timestamp, fixed);
+        book.match_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 4, 50000.0, "client2".to_string()));
+        assert_eq!(book.get_order(iceberg_id).unwrap().timestamp, fixed);
+    }
+
+    #[test]
+    fn test_expire_orders() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        let gtc = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client1".to_string());
+        let gtd = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client2".to_string())
+            .with_ttl(chrono::Duration::seconds(5));
+        let gtd_id = gtd.id;
+        book.add_order(gtc);
+        book.add_order(gtd);
+
+        assert!(book.expire_orders(Utc::now()).is_empty());
+
+        let expired = book.expire_orders(Utc::now() + chrono::Duration::seconds(10));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, gtd_id);
+        assert_eq!(expired[0].status, OrderStatus::Expired);
+        assert_eq!(book.depth(), 1);
+    }
+
+    #[test]
+    fn test_auction_only_orders_held_out() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50100.0, "client1".to_string())
+            .with_auction_only(true);
+        let buy_id = buy_order.id;
+        let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client2".to_string());
+
+        book.add_order(buy_order);
+        book.add_order(sell_order);
+
+        assert!(book.match_orders().is_empty());
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.auction_orders().len(), 1);
+
+        let cancelled = book.cancel_order(buy_id).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert!(book.auction_orders().is_empty());
+    }
+
+    #[test]
+    fn test_market_on_open_and_close_queued() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        let moo = Order::new_market_on_open("BTCUSD".to_string(), Side::Buy, 10, "client1".to_string());
+        let moc = Order::new_market_on_close("BTCUSD".to_string(), Side::Sell, 10, "client2".to_string());
+        let moc_id = moc.id;
+        book.add_order(moo);
+        book.add_order(moc);
+
+        assert_eq!(book.depth(), 0);
+        assert_eq!(book.auction_orders().len(), 2);
+
+        let rejected = book.reject_auction_orders(OrderType::MarketOnClose);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].id, moc_id);
+        assert_eq!(rejected[0].status, OrderStatus::Rejected);
+        assert_eq!(book.auction_orders().len(), 1);
+    }
+
+    #[test]
+    fn test_opening_uncross() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        // A crossed book built up during the call phase
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 101.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 99.0, "client2".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client3".to_string()));
+        book.add_order(Order::new_market_on_open("BTCUSD".to_string(), Side::Sell, 10, "client4".to_string()));
+        book.add_order(Order::new_market_on_open("BTCUSD".to_string(), Side::Buy, 20, "client5".to_string()));
+        book.add_order(Order::new_market_on_close("BTCUSD".to_string(), Side::Sell, 10, "client6".to_string()));
+
+        let result = book.uncross(OrderType::MarketOnOpen);
+
+        // 15 executes at both 100 and 101; buy surplus takes the higher price
+        assert_eq!(result.price, Some(Price::from(101.0)));
+        assert_eq!(result.volume, 15);
+        assert!(result.trades.iter().all(|t| t.price == Price::from(101.0)));
+        assert!(result.trades.iter().all(|t| t.buy_client_id == "client5"));
+
+        // The unfilled 5 of the buy MOO is cancelled; limit orders stay in the book
+        let cancelled = book.drain_cancelled();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].remaining_quantity(), 5);
+        assert_eq!(book.best_bid(), Some(Price::from(101.0)));
+        assert_eq!(book.best_ask(), None);
+        assert_eq!(book.depth(), 2);
+        assert_eq!(book.auction_orders().len(), 1);
+    }
+
+    #[test]
+    fn test_is_marketable() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50100.0, "client1".to_string()));
+
+        let passive = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "client2".to_string());
+        let crossing = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50100.0, "client2".to_string());
+        let market = Order::new_market("BTCUSD".to_string(), Side::Buy, 10, "client2".to_string());
+        let sell = Order::new_market("BTCUSD".to_string(), Side::Sell, 10, "client2".to_string());
+
+        assert!(!book.is_marketable(&passive));
+        assert!(book.is_marketable(&crossing));
+        assert!(book.is_marketable(&market));
+        assert!(!book.is_marketable(&sell));
+    }
+
+    #[test]
+    fn test_market_order_sweeps_levels() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50000.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50100.0, "client2".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50200.0, "client3".to_string()));
+
+        let market = Order::new_market("BTCUSD".to_string(), Side::Buy, 12, "client4".to_string());
+        let (trades, order) = book.execute_market_order(market);
+
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[0].price, Price::from(50000.0));
+        assert_eq!(trades[1].price, Price::from(50100.0));
+        assert_eq!(trades[2].price, Price::from(50200.0));
+        assert_eq!(trades[2].quantity, 2);
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(book.best_ask(), Some(Price::from(50200.0)));
+        assert_eq!(book.depth(), 1);
+    }
+
+    #[test]
+    fn test_market_order_remainder_cancelled() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+
+        let market = Order::new_market("BTCUSD".to_string(), Side::Sell, 10, "client1".to_string());
+        let (trades, order) = book.execute_market_order(market);
+        assert!(trades.is_empty());
+        assert_eq!(order.status, OrderStatus::Rejected);
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 4, 50000.0, "client2".to_string()));
+        let market = Order::new_market("BTCUSD".to_string(), Side::Sell, 10, "client1".to_string());
+        let (trades, order) = book.execute_market_order(market);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(order.filled_quantity, 4);
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert_eq!(book.depth(), 0);
+    }
+
+    #[test]
+    fn test_priority_classes() {
+        let config = BookConfig {
+            priority_classes: true,
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+
+        let proprietary = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "prop".to_string())
+            .with_priority_class(1);
+        let customer = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "customer".to_string())
+            .with_priority_class(0);
+        let customer_id = customer.id;
+        book.add_order(proprietary);
+        book.add_order(customer);
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client3".to_string()));
+        let trades = book.match_orders();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].buy_order_id, customer_id);
+
+        // Without priority classes the book is plain FIFO
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let proprietary = Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "prop".to_string())
+            .with_priority_class(1);
+        let proprietary_id = proprietary.id;
+        book.add_order(proprietary);
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 50000.0, "customer".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client3".to_string()));
+        assert_eq!(book.match_orders()[0].buy_order_id, proprietary_id);
+    }
+
+    #[test]
+    fn test_pro_rata_allocation() {
+        let config = BookConfig {
+            algorithm: MatchingAlgorithm::ProRata { top_order_percent: 0 },
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+
+        let small = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string());
+        let large = Order::new_limit("BTCUSD".to_string(), Side::Sell, 30, 50000.0, "client2".to_string());
+        let small_id = small.id;
+        let large_id = large.id;
+        book.add_order(small);
+        book.add_order(large);
+
+        let market = Order::new_market("BTCUSD".to_string(), Side::Buy, 20, "client3".to_string());
+        let (trades, _) = book.execute_market_order(market);
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].sell_order_id, small_id);
+        assert_eq!(trades[0].quantity, 5);
+        assert_eq!(trades[1].sell_order_id, large_id);
+        assert_eq!(trades[1].quantity, 15);
+    }
+
+    #[test]
+    fn test_pro_rata_top_order_carve_out() {
+        let config = BookConfig {
+            algorithm: MatchingAlgorithm::ProRata { top_order_percent: 50 },
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 20, 50000.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 20, 50000.0, "client2".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 9, 50000.0, "client3".to_string()));
+
+        let trades = book.match_orders();
+        let quantities: Vec<u64> = trades.iter().map(|t| t.quantity).collect();
+
+        // 4 to the top order, then the other 5 split 16:20 with the leftover lot going first in queue
+        assert_eq!(quantities, vec![7, 2]);
+        assert_eq!(quantities.iter().sum::<u64>(), 9);
+    }
+
+    #[test]
+    fn test_fifo_with_lmm_allocation() {
+        let config = BookConfig {
+            algorithm: MatchingAlgorithm::FifoWithLmm {
+                lmm_client_id: "lmm".to_string(),
+                lmm_percent: 40,
+            },
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "lmm".to_string()));
+
+        let market = Order::new_market("BTCUSD".to_string(), Side::Buy, 10, "client2".to_string());
+        let (trades, _) = book.execute_market_order(market);
+
+        // The LMM gets 4 lots ahead of time priority, the first order gets the other 6
+        assert_eq!(trades.len(), 2);
+        assert_eq!((trades[0].sell_client_id.as_str(), trades[0].quantity), ("client1", 6));
+        assert_eq!((trades[1].sell_client_id.as_str(), trades[1].quantity), ("lmm", 4));
+    }
+
+    fn stp_book(policy: SelfTradePrevention) -> OrderBook {
+        let config = BookConfig {
+            self_trade_prevention: Some(policy),
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50000.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50000.0, "client2".to_string()));
+        book
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancel_oldest() {
+        let mut book = stp_book(SelfTradePrevention::CancelOldest);
+
+        let buy = Order::new_market("BTCUSD".to_string(), Side::Buy, 5, "client1".to_string());
+        let (trades, order) = book.execute_market_order(buy);
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].sell_client_id, "client2");
+        assert_eq!(order.status, OrderStatus::Filled);
+
+        let cancelled = book.drain_cancelled();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].client_id, "client1");
+        assert_eq!(book.depth(), 0);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancel_newest() {
+        let mut book = stp_book(SelfTradePrevention::CancelNewest);
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 50000.0, "client1".to_string()));
+        let trades = book.match_orders();
+
+        assert!(trades.is_empty());
+        assert_eq!(book.drain_cancelled()[0].side, Side::Buy);
+        assert_eq!(book.depth(), 2);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_decrement_and_cancel() {
+        let mut book = stp_book(SelfTradePrevention::DecrementAndCancel);
+
+        let buy = Order::new_market("BTCUSD".to_string(), Side::Buy, 8, "client1".to_string());
+        let (trades, order) = book.execute_market_order(buy);
+
+        // 5 lots decremented against client1's own order, the other 3 trade with client2
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 3);
+        assert_eq!(order.quantity, 3);
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(book.drain_cancelled().len(), 1);
+        assert_eq!(book.depth(), 1);
+    }
+
+    #[test]
+    fn test_parent_self_trade_prevention() {
+        // No book-level policy: only the parent's policy applies, and only between its children
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let parent_id = Uuid::new_v4();
+        let child = |side, quantity| {
+            Order::new_limit("BTCUSD".to_string(), side, quantity, 50000.0, "algo1".to_string())
+                .with_parent(parent_id, Some(SelfTradePrevention::CancelNewest))
+        };
+
+        book.add_order(child(Side::Sell, 5));
+        book.add_order(child(Side::Buy, 5));
+        assert!(book.match_orders().is_empty());
+        assert_eq!(book.drain_cancelled().len(), 1);
+        assert_eq!(book.best_bid(), None);
+
+        // The same client outside the parent still trades with the resting child
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 50000.0, "algo1".to_string()));
+        assert_eq!(book.match_orders().len(), 1);
+    }
+
+    #[test]
+    fn test_book_analytics() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        assert_eq!(book.analytics(5), BookAnalytics::default());
+
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 30, 99.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 98.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 101.0, "client2".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 103.0, "client2".to_string()));
+
+        // Top level only: 30 bid against 10 offered
+        assert_eq!(book.imbalance(1), Some(0.5));
+        assert_eq!(book.imbalance(2), Some(1.0 / 3.0));
+        // Heavier bid pulls the mid towards the ask: (99 * 10 + 101 * 30) / 40
+        assert_eq!(book.weighted_mid(), Some(100.5));
+        assert_eq!(book.book_vwap(Side::Buy, 2), Some(98.75));
+        assert_eq!(book.book_vwap(Side::Sell, 2), Some(102.0));
+    }
+
+    #[test]
+    fn test_trade_context() {
+        let config = BookConfig {
+            trade_context: true,
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 99.0, "client1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client2".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client3".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 101.0, "client4".to_string()));
+
+        let trades = book.match_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 12, 101.0, "client5".to_string()));
+        let contexts: Vec<TradeContext> = trades.iter().map(|t| t.context.unwrap()).collect();
+        assert_eq!(contexts.iter().map(|c| c.queue_position).collect::<Vec<_>>(), vec![0, 1, 0]);
+        assert!(contexts.iter().all(|c| c.spread == Some(Price::from(1.0))));
+        assert!(contexts.iter().all(|c| c.levels_swept == 2 && c.depth_consumed == 12));
+
+        // Off by default
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client2".to_string()));
+        let trades = book.match_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 100.0, "client5".to_string()));
+        assert!(trades[0].context.is_none());
+    }
+
+    #[test]
+    fn test_dynamic_price_limits() {
+        let limits = PriceLimits {
+            dynamic_percent: Some(5.0),
+            ..PriceLimits::default()
+        };
+        let config = BookConfig {
+            price_limits: Some(limits),
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config("BTCUSD".to_string(), config);
+        for price in [100.0, 104.0, 110.0] {
+            book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, price, "client1".to_string()));
+        }
+
+        // No reference yet, so the first trade sets it at 100
+        let trades = book.match_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 100.0, "client2".to_string()));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(book.take_limit_breach(), None);
+
+        // 104 is within 5% of 100, 110 is not: the rest of the buy is cancelled
+        let trades = book.match_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 110.0, "client2".to_string()));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, Price::from(104.0));
+        assert_eq!(book.take_limit_breach(), Some(LimitBreachAction::Reject));
+        assert_eq!(book.drain_cancelled()[0].remaining_quantity(), 5);
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.best_ask(), Some(Price::from(110.0)));
+    }
+
+    #[test]
+    fn test_iceberg_refresh_loses_priority() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let iceberg = Order::new_limit("BTCUSD".to_string(), Side::Sell, 30, 100.0, "mm1".to_string())
+            .with_display_quantity(10);
+        let iceberg_id = iceberg.id;
+        let behind = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 100.0, "mm2".to_string());
+        let behind_id = behind.id;
+        book.add_order(iceberg);
+        book.add_order(behind);
+
+        // Only the displayed slice shows
+        assert_eq!(book.get_depth(1).asks, vec![(Price::from(100.0), 20, 2)]);
+
+        // The first slice fills, the refreshed one queues behind mm2
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 15, 100.0, "client1".to_string());
+        let trades = book.match_order(buy);
+        let fills: Vec<(Uuid, u64)> = trades.iter().map(|t| (t.sell_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(iceberg_id, 10), (behind_id, 5)]);
+        assert_eq!(book.get_order(iceberg_id).unwrap().visible_quantity(), 10);
+
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 20, 100.0, "client1".to_string());
+        let trades = book.match_order(buy);
+        let fills: Vec<(Uuid, u64)> = trades.iter().map(|t| (t.sell_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(behind_id, 5), (iceberg_id, 10), (iceberg_id, 5)]);
+
+        // Last 5 of the hidden quantity
+        assert_eq!(book.get_depth(1).asks, vec![(Price::from(100.0), 5, 1)]);
+        assert_eq!(book.get_order(iceberg_id).unwrap().remaining_quantity(), 5);
+    }
+
+    #[test]
+    fn test_post_only_cross_policy() {
+        let post_only_buy = |price: f64| {
+            Order::new_limit("ESZ4".to_string(), Side::Buy, 5, price, "mm1".to_string()).with_post_only(true)
+        };
+        let book_with = |cross_policy| {
+            let mut book = OrderBook::with_config(
+                "ESZ4".to_string(),
+                BookConfig {
+                    cross_policy,
+                    tick_size: Some(Price::from(0.25)),
+                    ..BookConfig::default()
+                },
+            );
+            book.add_order(Order::new_limit("ESZ4".to_string(), Side::Sell, 5, 100.0, "client1".to_string()));
+            book
+        };
+
+        // Passive post-only orders rest under every policy
+        let mut book = book_with(CrossPolicy::Reject);
+        assert!(book.match_order(post_only_buy(99.0)).is_empty());
+        assert_eq!(book.best_bid(), Some(Price::from(99.0)));
+
+        // Locking the ask is rejected
+        assert!(book.match_order(post_only_buy(100.0)).is_empty());
+        let rejected = book.drain_cancelled();
+        assert_eq!(rejected[0].status, OrderStatus::Rejected);
+        assert_eq!(book.best_bid(), Some(Price::from(99.0)));
+
+        let mut book = book_with(CrossPolicy::Reprice);
+        assert!(book.match_order(post_only_buy(101.0)).is_empty());
+        assert_eq!(book.best_bid(), Some(Price::from(99.75)));
+        assert_eq!(book.best_ask(), Some(Price::from(100.0)));
+
+        let mut book = book_with(CrossPolicy::Match);
+        assert_eq!(book.match_order(post_only_buy(101.0)).len(), 1);
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn test_good_till_crossing() {
+        let mut book = OrderBook::with_config(
+            "BTCUSD".to_string(),
+            BookConfig {
+                cross_policy: CrossPolicy::Reprice,
+                ..BookConfig::default()
+            },
+        );
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, "client1".to_string()));
+        let gtx = |price: f64| {
+            Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, price, "client2".to_string())
+                .with_time_in_force(TimeInForce::GoodTillCrossing)
+        };
+
+        // Crossing cancels the order, whatever the book's post-only policy
+        assert!(book.match_order(gtx(100.0)).is_empty());
+        let cancelled = book.drain_cancelled();
+        assert_eq!(cancelled[0].status, OrderStatus::Cancelled);
+        assert_eq!(book.best_bid(), None);
+
+        let passive = gtx(99.0);
+        let passive_id = passive.id;
+        assert!(book.match_order(passive).is_empty());
+        assert_eq!(book.get_order(passive_id).unwrap().status, OrderStatus::Pending);
+    }
+
+    #[test]
+    fn test_order_index_tracks_resting_orders() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let limit = |side, quantity, price: f64| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client".to_string());
+        let resting_count = |book: &OrderBook| book.resting_orders(Side::Buy).count() + book.resting_orders(Side::Sell).count();
+
+        let mut ids = Vec::new();
+        for i in 0..10 {
+            let order = limit(Side::Sell, 10, 100.0 + i as f64);
+            ids.push(order.id);
+            book.add_order(order);
+        }
+        book.add_order(limit(Side::Buy, 10, 90.0));
+
+        book.match_order(limit(Side::Buy, 25, 102.0)); // fills two, partially fills a third
+        book.cancel_order(ids[5]).unwrap();
+        book.replace_order(ids[6], Some(Price::from(120.0)), None).unwrap();
+        book.cancel_range(Side::Sell, Price::from(107.0), Price::from(108.0));
+        assert_eq!(book.index.len(), resting_count(&book));
+        assert!(book.get_order(ids[0]).is_none());
+        assert_eq!(book.get_order(ids[2]).unwrap().remaining_quantity(), 5);
+        assert_eq!(book.get_order(ids[6]).unwrap().price, Some(Price::from(120.0)));
+
+        // A rescan of a crossed book and an auction uncross keep the index in step
+        book.add_order(limit(Side::Buy, 15, 104.0));
+        book.match_orders();
+        assert_eq!(book.index.len(), resting_count(&book));
+        book.uncross(OrderType::MarketOnOpen);
+        assert_eq!(book.index.len(), resting_count(&book));
+        assert!(book.cancel_order(ids[9]).is_some());
+        assert!(book.check_integrity().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_where() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let limit = |side, price: f64, client: &str| Order::new_limit("BTCUSD".to_string(), side, 5, price, client.to_string());
+        book.add_order(limit(Side::Buy, 99.0, "client1"));
+        book.add_order(limit(Side::Sell, 101.0, "client1"));
+        book.add_order(limit(Side::Sell, 101.0, "client2"));
+        book.add_order(limit(Side::Buy, 100.0, "client1").with_auction_only(true));
+
+        let cancelled = book.cancel_where(|order| order.client_id == "client1");
+        assert_eq!(cancelled.len(), 3);
+        assert!(cancelled.iter().all(|order| order.status == OrderStatus::Cancelled));
+        assert_eq!(book.depth(), 1);
+        assert!(book.auction_orders().is_empty());
+        assert!(book.check_integrity().is_empty());
+    }
+
+    #[test]
+    fn test_check_integrity_reports_overfills() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 100.0, "client".to_string());
+        let order_id = order.id;
+        book.add_order(order);
+        assert!(book.check_integrity().is_empty());
+
+        let key = book.index[&order_id];
+        book.orders.get_mut(key).filled_quantity = 12;
+        let problems = book.check_integrity();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("12 of 10 filled"));
+    }
+
+    #[test]
+    fn test_depth_limits() {
+        let limit = |side, price: f64| Order::new_limit("BTCUSD".to_string(), side, 1, price, "client".to_string());
+        let with_limits = |max_orders, max_levels, on_limit| {
+            OrderBook::with_config(
+                "BTCUSD".to_string(),
+                BookConfig {
+                    depth_limits: Some(DepthLimits {
+                        max_orders,
+                        max_levels,
+                        on_limit,
+                    }),
+                    ..BookConfig::default()
+                },
+            )
+        };
+
+        // Level cap: joining an existing level is fine, a new level is rejected
+        let mut book = with_limits(None, Some(2), DepthLimitAction::Reject);
+        book.add_order(limit(Side::Buy, 100.0));
+        book.add_order(limit(Side::Buy, 99.0));
+        book.add_order(limit(Side::Buy, 99.0));
+        let rejected = limit(Side::Buy, 98.0);
+        let rejected_id = rejected.id;
+        book.add_order(rejected);
+        book.add_order(limit(Side::Sell, 101.0));
+        assert_eq!(book.get_depth(10).bids, vec![(Price::from(100.0), 1, 1), (Price::from(99.0), 2, 2)]);
+        assert_eq!(book.get_depth(10).asks.len(), 1);
+        let cancelled = book.drain_cancelled();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!((cancelled[0].id, cancelled[0].status), (rejected_id, OrderStatus::Rejected));
+
+        // Order cap with culling: the furthest order on the new order's side makes room
+        let mut book = with_limits(Some(3), None, DepthLimitAction::CullFarthest);
+        let far = limit(Side::Sell, 110.0);
+        let far_id = far.id;
+        book.add_order(far);
+        book.add_order(limit(Side::Sell, 105.0));
+        book.add_order(limit(Side::Buy, 95.0));
+        book.add_order(limit(Side::Sell, 102.0));
+        assert!(book.get_order(far_id).is_none());
+        assert_eq!(book.best_ask(), Some(Price::from(102.0)));
+        assert_eq!(book.drain_cancelled()[0].status, OrderStatus::Cancelled);
+
+        // Nothing on the buy side is further out than 90, so it is rejected
+        book.add_order(limit(Side::Buy, 90.0));
+        assert_eq!(book.drain_cancelled()[0].status, OrderStatus::Rejected);
+        assert_eq!(book.depth(), 3);
+
+        // Amendments of resting orders are not new orders and keep their place
+        let resting = book.get_depth(10).asks[1].0;
+        let id = book.resting_orders(Side::Sell).find(|o| o.price == Some(resting)).unwrap().id;
+        assert!(book.replace_order(id, Some(Price::from(120.0)), None).is_some());
+        assert!(book.drain_cancelled().is_empty());
+
+        // A partial fill whose remainder finds the book full keeps its trades
+        let mut book = with_limits(None, Some(1), DepthLimitAction::Reject);
+        book.add_order(limit(Side::Buy, 90.0));
+        book.add_order(limit(Side::Sell, 100.0));
+        let sweep = Order::new_limit("BTCUSD".to_string(), Side::Buy, 3, 100.0, "other".to_string());
+        let sweep_id = sweep.id;
+        assert_eq!(book.match_order(sweep).len(), 1);
+        let cancelled = book.drain_cancelled();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!((cancelled[0].id, cancelled[0].status, cancelled[0].filled_quantity), (sweep_id, OrderStatus::Cancelled, 1));
+        assert_eq!(book.best_bid(), Some(Price::from(90.0)));
+    }
+
+    #[test]
+    fn test_level_changes() {
+        let limit = |side, quantity, price: f64| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client".to_string());
+        let change = |side, price: f64, action, quantity, orders| LevelChange {
+            side,
+            price: Price::from(price),
+            action,
+            quantity,
+            orders,
+        };
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        book.add_order(limit(Side::Sell, 1, 100.0));
+        assert!(book.take_level_changes().is_empty(), "not tracking");
+
+        book.track_level_changes(true);
+        book.add_order(limit(Side::Sell, 2, 100.0));
+        book.add_order(limit(Side::Sell, 1, 101.0));
+        let bid = limit(Side::Buy, 1, 99.0);
+        let bid_id = bid.id;
+        book.add_order(bid);
+        assert_eq!(
+            book.take_level_changes(),
+            vec![
+                change(Side::Buy, 99.0, LevelAction::Add, 1, 1),
+                change(Side::Sell, 100.0, LevelAction::Modify, 3, 2),
+                change(Side::Sell, 101.0, LevelAction::Add, 1, 1),
+            ]
+        );
+
+        // A sweep empties both ask levels
+        book.match_order(limit(Side::Buy, 4, 101.0));
+        assert_eq!(
+            book.take_level_changes(),
+            vec![
+                change(Side::Sell, 100.0, LevelAction::Delete, 0, 0),
+                change(Side::Sell, 101.0, LevelAction::Delete, 0, 0),
+            ]
+        );
+
+        // Changed and changed back nets out
+        book.cancel_order(bid_id);
+        book.add_order(limit(Side::Buy, 1, 99.0));
+        assert!(book.take_level_changes().is_empty());
+
+        let id = book.resting_orders(Side::Buy).next().unwrap().id;
+        book.replace_order(id, None, Some(0));
+        book.add_order(limit(Side::Sell, 5, 102.0));
+        assert_eq!(
+            book.take_level_changes(),
+            vec![
+                change(Side::Buy, 99.0, LevelAction::Delete, 0, 0),
+                change(Side::Sell, 102.0, LevelAction::Add, 5, 1),
+            ]
+        );
     }
 }
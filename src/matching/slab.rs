@@ -0,0 +1,183 @@
+use crate::types::Order;
+
+/// Key of an order's slot in an [`OrderSlab`]
+pub(crate) type SlotKey = usize;
+
+/// Queue of one price level: an intrusive doubly linked list threaded through the slab
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Level {
+    head: Option<SlotKey>,
+    tail: Option<SlotKey>,
+    len: usize,
+}
+
+impl Level {
+    pub(crate) fn front(&self) -> Option<SlotKey> {
+        self.head
+    }
+
+    pub(crate) fn back(&self) -> Option<SlotKey> {
+        self.tail
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    order: Option<Order>,
+    prev: Option<SlotKey>,
+    next: Option<SlotKey>,
+}
+
+/// Pre-allocated storage for resting orders.
+///
+/// Orders live in reusable slots and price levels link them in queue order, so queue
+/// changes relink slots instead of shifting orders around.
+#[derive(Debug, Default)]
+pub(crate) struct OrderSlab {
+    slots: Vec<Slot>,
+    free: Vec<SlotKey>,
+}
+
+impl OrderSlab {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Store an order, unlinked, and return its key
+    pub(crate) fn insert(&mut self, order: Order) -> SlotKey {
+        let slot = Slot {
+            order: Some(order),
+            prev: None,
+            next: None,
+        };
+        match self.free.pop() {
+            Some(key) => {
+                self.slots[key] = slot;
+                key
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// Take an order out of its slot; it must already be unlinked from its level
+    pub(crate) fn remove(&mut self, key: SlotKey) -> Order {
+        self.free.push(key);
+        self.slots[key].order.take().expect("slot is occupied")
+    }
+
+    pub(crate) fn get(&self, key: SlotKey) -> &Order {
+        self.slots[key].order.as_ref().expect("slot is occupied")
+    }
+
+    pub(crate) fn get_mut(&mut self, key: SlotKey) -> &mut Order {
+        self.slots[key].order.as_mut().expect("slot is occupied")
+    }
+
+    /// Drop every order, keeping the allocated slots
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.free.clear();
+    }
+
+    pub(crate) fn push_back(&mut self, level: &mut Level, key: SlotKey) {
+        self.link(level, level.tail, None, key);
+    }
+
+    pub(crate) fn push_front(&mut self, level: &mut Level, key: SlotKey) {
+        self.link(level, None, level.head, key);
+    }
+
+    /// Link `key` into `level` just ahead of `before`
+    pub(crate) fn insert_before(&mut self, level: &mut Level, before: SlotKey, key: SlotKey) {
+        self.link(level, self.slots[before].prev, Some(before), key);
+    }
+
+    fn link(&mut self, level: &mut Level, prev: Option<SlotKey>, next: Option<SlotKey>, key: SlotKey) {
+        self.slots[key].prev = prev;
+        self.slots[key].next = next;
+        match prev {
+            Some(prev) => self.slots[prev].next = Some(key),
+            None => level.head = Some(key),
+        }
+        match next {
+            Some(next) => self.slots[next].prev = Some(key),
+            None => level.tail = Some(key),
+        }
+        level.len += 1;
+    }
+
+    pub(crate) fn unlink(&mut self, level: &mut Level, key: SlotKey) {
+        let Slot { prev, next, .. } = self.slots[key];
+        match prev {
+            Some(prev) => self.slots[prev].next = next,
+            None => level.head = next,
+        }
+        match next {
+            Some(next) => self.slots[next].prev = prev,
+            None => level.tail = prev,
+        }
+        self.slots[key].prev = None;
+        self.slots[key].next = None;
+        level.len -= 1;
+    }
+
+    /// Keys of a level's orders, front to back
+    pub(crate) fn keys<'a>(&'a self, level: &Level) -> impl Iterator<Item = SlotKey> + 'a {
+        std::iter::successors(level.head, move |&key| self.slots[key].next)
+    }
+
+    /// A level's orders, front to back
+    pub(crate) fn orders<'a>(&'a self, level: &Level) -> impl Iterator<Item = &'a Order> + 'a {
+        self.keys(level).map(move |key| self.get(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+
+    #[test]
+    fn test_level_links() {
+        let order = |quantity| Order::new_limit("BTCUSD".to_string(), Side::Buy, quantity, 100.0, "client".to_string());
+        let mut slab = OrderSlab::with_capacity(4);
+        let mut level = Level::default();
+        let quantities = |slab: &OrderSlab, level: &Level| slab.orders(level).map(|o| o.quantity).collect::<Vec<_>>();
+
+        let a = slab.insert(order(1));
+        let b = slab.insert(order(2));
+        let c = slab.insert(order(3));
+        slab.push_back(&mut level, a);
+        slab.push_back(&mut level, c);
+        slab.insert_before(&mut level, c, b);
+        assert_eq!(quantities(&slab, &level), vec![1, 2, 3]);
+
+        slab.unlink(&mut level, b);
+        assert_eq!(slab.remove(b).quantity, 2);
+        slab.unlink(&mut level, a);
+        slab.push_back(&mut level, a);
+        assert_eq!(quantities(&slab, &level), vec![3, 1]);
+
+        // Freed slots are reused
+        let d = slab.insert(order(4));
+        assert_eq!(d, b);
+        slab.push_front(&mut level, d);
+        assert_eq!(quantities(&slab, &level), vec![4, 3, 1]);
+        assert_eq!(level.len(), 3);
+        assert_eq!(level.front(), Some(d));
+    }
+}
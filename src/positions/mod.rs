@@ -0,0 +1,171 @@
+use crate::types::{Order, PositionIntent, Side, Trade};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A client's position in one symbol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    /// Signed net quantity (positive = long, negative = short)
+    pub net: i64,
+    /// Quantity bought in all fills
+    pub bought: u64,
+    /// Quantity sold in all fills
+    pub sold: u64,
+}
+
+impl Position {
+    /// Quantity traded either way
+    pub fn volume(&self) -> u64 {
+        self.bought + self.sold
+    }
+}
+
+/// Net position and traded volume tracking per client and symbol, updated from fills
+#[derive(Debug, Default)]
+pub struct PositionTracker {
+    positions: HashMap<String, HashMap<String, Position>>, // client -> symbol -> position
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply both sides of a trade
+    pub fn apply_trade(&mut self, trade: &Trade) {
+        let quantity = trade.quantity as i64;
+
+        let buyer = self.entry(&trade.buy_client_id, &trade.symbol);
+        buyer.net += quantity;
+        buyer.bought += trade.quantity;
+
+        let seller = self.entry(&trade.sell_client_id, &trade.symbol);
+        seller.net -= quantity;
+        seller.sold += trade.quantity;
+    }
+
+    fn entry(&mut self, client_id: &str, symbol: &str) -> &mut Position {
+        self.positions
+            .entry(client_id.to_string())
+            .or_default()
+            .entry(symbol.to_string())
+            .or_default()
+    }
+
+    /// The client's position in `symbol`; flat with no volume if it never traded it
+    pub fn position(&self, client_id: &str, symbol: &str) -> Position {
+        self.positions
+            .get(client_id)
+            .and_then(|positions| positions.get(symbol))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Every symbol the client has traded, with its position in it
+    pub fn client_positions(&self, client_id: &str) -> impl Iterator<Item = (&str, Position)> {
+        self.positions
+            .get(client_id)
+            .into_iter()
+            .flatten()
+            .map(|(symbol, position)| (symbol.as_str(), *position))
+    }
+
+    /// Signed net position (positive = long, negative = short)
+    pub fn net_position(&self, client_id: &str, symbol: &str) -> i64 {
+        self.position(client_id, symbol).net
+    }
+
+    /// Sum of every client's position per symbol; zero throughout while each applied
+    /// trade has added as much to one side as it took from the other
+    pub fn net_by_symbol(&self) -> HashMap<String, i64> {
+        let mut totals: HashMap<String, i64> = HashMap::new();
+        for (symbol, position) in self.positions.values().flatten() {
+            *totals.entry(symbol.clone()).or_default() += position.net;
+        }
+        totals
+    }
+
+    /// Largest quantity the order may trade without increasing the client's position.
+    ///
+    /// Returns 0 when the order would only open or extend a position.
+    pub fn reducible_quantity(&self, order: &Order) -> u64 {
+        let position = self.net_position(&order.client_id, &order.symbol);
+        let reducible = match order.side {
+            Side::Buy if position < 0 => position.unsigned_abs(),
+            Side::Sell if position > 0 => position as u64,
+            _ => 0,
+        };
+        reducible.min(order.quantity)
+    }
+
+    /// Whether the order's open/close intent is consistent with the client's position.
+    ///
+    /// Closing orders must not exceed the opposite position; opening orders are not
+    /// allowed while the client holds a position on the other side. Orders without an
+    /// intent are always consistent.
+    pub fn intent_allowed(&self, order: &Order) -> bool {
+        match order.position_intent {
+            Some(PositionIntent::Close) => self.reducible_quantity(order) == order.quantity,
+            Some(PositionIntent::Open) => self.reducible_quantity(order) == 0,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Price;
+    use uuid::Uuid;
+
+    fn trade(buyer: &str, seller: &str, quantity: u64) -> Trade {
+        Trade::new(Uuid::new_v4(), Uuid::new_v4(), "BTCUSD".to_string(), quantity, Price::from(50000.0))
+            .with_clients(buyer.to_string(), seller.to_string())
+    }
+
+    #[test]
+    fn test_apply_trade() {
+        let mut tracker = PositionTracker::new();
+        tracker.apply_trade(&trade("client1", "client2", 10));
+        tracker.apply_trade(&trade("client2", "client1", 4));
+
+        assert_eq!(tracker.net_position("client1", "BTCUSD"), 6);
+        assert_eq!(tracker.net_position("client2", "BTCUSD"), -6);
+        assert_eq!(tracker.net_position("client3", "BTCUSD"), 0);
+
+        let position = tracker.position("client1", "BTCUSD");
+        assert_eq!((position.bought, position.sold, position.volume()), (10, 4, 14));
+        assert_eq!(tracker.position("client3", "BTCUSD"), Position::default());
+        assert_eq!(tracker.client_positions("client2").collect::<Vec<_>>(), vec![("BTCUSD", tracker.position("client2", "BTCUSD"))]);
+    }
+
+    #[test]
+    fn test_reducible_quantity() {
+        let mut tracker = PositionTracker::new();
+        tracker.apply_trade(&trade("client1", "client2", 10));
+
+        let sell = Order::new_limit("BTCUSD".to_string(), Side::Sell, 15, 50000.0, "client1".to_string());
+        assert_eq!(tracker.reducible_quantity(&sell), 10);
+
+        let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 5, 50000.0, "client1".to_string());
+        assert_eq!(tracker.reducible_quantity(&buy), 0);
+    }
+
+    #[test]
+    fn test_position_intent() {
+        let mut tracker = PositionTracker::new();
+        tracker.apply_trade(&trade("client1", "client2", 10));
+
+        let close = Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 50000.0, "client1".to_string())
+            .with_position_intent(PositionIntent::Close);
+        assert!(tracker.intent_allowed(&close));
+
+        let oversized = Order { quantity: 11, ..close };
+        assert!(!tracker.intent_allowed(&oversized));
+
+        let open = Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 50000.0, "client1".to_string())
+            .with_position_intent(PositionIntent::Open);
+        assert!(!tracker.intent_allowed(&open));
+        assert!(tracker.intent_allowed(&Order { side: Side::Buy, ..open }));
+    }
+}
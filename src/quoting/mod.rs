@@ -0,0 +1,180 @@
+use crate::matching::OrderBook;
+use crate::types::{Price, Side};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Quoting quality of one client on one symbol, for market-maker programs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotingMetrics {
+    /// Time since the symbol was first observed
+    pub observed: Duration,
+    /// Time the client's best bid was the book's best bid
+    pub time_at_best_bid: Duration,
+    /// Time the client's best ask was the book's best ask
+    pub time_at_best_ask: Duration,
+    /// Time the client quoted both sides
+    pub two_sided_time: Duration,
+    spread_seconds: f64, // integral of the client's own spread over two-sided time
+}
+
+impl QuotingMetrics {
+    /// Fraction of observed time the client quoted both sides
+    pub fn two_sided_uptime(&self) -> f64 {
+        if self.observed.is_zero() {
+            return 0.0;
+        }
+        self.two_sided_time.as_secs_f64() / self.observed.as_secs_f64()
+    }
+
+    /// Client's own bid/ask spread averaged over the time it quoted both sides
+    pub fn time_weighted_spread(&self) -> Option<f64> {
+        if self.two_sided_time.is_zero() {
+            return None;
+        }
+        Some(self.spread_seconds / self.two_sided_time.as_secs_f64())
+    }
+
+    fn accumulate(&mut self, quote: &ClientQuote, elapsed: Duration) {
+        if quote.at_best_bid {
+            self.time_at_best_bid += elapsed;
+        }
+        if quote.at_best_ask {
+            self.time_at_best_ask += elapsed;
+        }
+        if let (Some(bid), Some(ask)) = (quote.bid, quote.ask) {
+            self.two_sided_time += elapsed;
+            self.spread_seconds += (ask - bid).to_f64() * elapsed.as_secs_f64();
+        }
+    }
+}
+
+/// A client's best quotes as of the last observation
+#[derive(Debug, Clone, Default)]
+struct ClientQuote {
+    bid: Option<Price>,
+    ask: Option<Price>,
+    at_best_bid: bool,
+    at_best_ask: bool,
+}
+
+#[derive(Debug)]
+struct SymbolQuotes {
+    started: DateTime<Utc>,
+    last_update: DateTime<Utc>,
+    current: HashMap<String, ClientQuote>,
+    totals: HashMap<String, QuotingMetrics>,
+}
+
+/// Time-weighted quoting metrics per client and symbol, updated whenever a book changes
+#[derive(Debug, Default)]
+pub struct QuoteTracker {
+    symbols: HashMap<String, SymbolQuotes>,
+}
+
+impl QuoteTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the quotes resting in `book` as of `now`.
+    ///
+    /// The quotes seen at the previous observation are credited for the time in between.
+    pub fn observe(&mut self, book: &OrderBook, now: DateTime<Utc>) {
+        let quotes = self
+            .symbols
+            .entry(book.symbol().to_string())
+            .or_insert_with(|| SymbolQuotes {
+                started: now,
+                last_update: now,
+                current: HashMap::new(),
+                totals: HashMap::new(),
+            });
+
+        let elapsed = (now - quotes.last_update).to_std().unwrap_or_default();
+        for (client_id, quote) in &quotes.current {
+            quotes
+                .totals
+                .entry(client_id.clone())
+                .or_default()
+                .accumulate(quote, elapsed);
+        }
+        quotes.last_update = now;
+
+        // Orders come best first, so the first one seen per client is its best quote
+        let best_bid = book.resting_orders(Side::Buy).next().and_then(|o| o.price);
+        let best_ask = book.resting_orders(Side::Sell).next().and_then(|o| o.price);
+        let mut current: HashMap<String, ClientQuote> = HashMap::new();
+        for order in book.resting_orders(Side::Buy) {
+            let quote = current.entry(order.client_id.clone()).or_default();
+            if quote.bid.is_none() {
+                quote.bid = order.price;
+                quote.at_best_bid = order.price == best_bid;
+            }
+        }
+        for order in book.resting_orders(Side::Sell) {
+            let quote = current.entry(order.client_id.clone()).or_default();
+            if quote.ask.is_none() {
+                quote.ask = order.price;
+                quote.at_best_ask = order.price == best_ask;
+            }
+        }
+        quotes.current = current;
+    }
+
+    /// Metrics for `client_id` on `symbol` up to `now`, or `None` if the client never quoted it
+    pub fn metrics(&self, client_id: &str, symbol: &str, now: DateTime<Utc>) -> Option<QuotingMetrics> {
+        let quotes = self.symbols.get(symbol)?;
+        let current = quotes.current.get(client_id);
+        let mut metrics = match quotes.totals.get(client_id) {
+            Some(totals) => totals.clone(),
+            None if current.is_some() => QuotingMetrics::default(),
+            None => return None,
+        };
+
+        // Credit the quotes still resting since the last observation
+        if let Some(quote) = current {
+            metrics.accumulate(quote, (now - quotes.last_update).to_std().unwrap_or_default());
+        }
+        metrics.observed = (now - quotes.started).to_std().unwrap_or_default();
+        Some(metrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Order;
+
+    #[test]
+    fn test_quoting_metrics() {
+        let mut book = OrderBook::new("BTCUSD".to_string());
+        let mut tracker = QuoteTracker::new();
+        let start = Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+
+        // mm1 quotes 99/101 for 10s, alone at the BBO
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 99.0, "mm1".to_string()));
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 10, 101.0, "mm1".to_string()));
+        tracker.observe(&book, at(0));
+
+        // mm2 improves the bid for the next 10s
+        book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 10, 100.0, "mm2".to_string()));
+        tracker.observe(&book, at(10));
+
+        let mm1 = tracker.metrics("mm1", "BTCUSD", at(20)).unwrap();
+        assert_eq!(mm1.observed, Duration::from_secs(20));
+        assert_eq!(mm1.time_at_best_bid, Duration::from_secs(10));
+        assert_eq!(mm1.time_at_best_ask, Duration::from_secs(20));
+        assert_eq!(mm1.two_sided_uptime(), 1.0);
+        assert_eq!(mm1.time_weighted_spread(), Some(2.0));
+
+        let mm2 = tracker.metrics("mm2", "BTCUSD", at(20)).unwrap();
+        assert_eq!(mm2.time_at_best_bid, Duration::from_secs(10));
+        assert_eq!(mm2.two_sided_uptime(), 0.0);
+        assert_eq!(mm2.time_weighted_spread(), None);
+
+        assert!(tracker.metrics("mm3", "BTCUSD", at(20)).is_none());
+    }
+}
@@ -0,0 +1,455 @@
+use crate::types::{Order, Price, Side};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Why a pre-trade risk check refused an order
+#[derive(Error, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RiskRejection {
+    #[error("Quantity {quantity} is above the limit of {max_quantity}")]
+    OrderSize { quantity: u64, max_quantity: u64 },
+
+    #[error("Notional {notional} is above the limit of {max_notional}")]
+    OrderNotional { notional: f64, max_notional: f64 },
+
+    #[error("Quantity {quantity} is above client {client_id}'s limit of {max_quantity}")]
+    ClientOrderSize { client_id: String, quantity: u64, max_quantity: u64 },
+
+    #[error("Notional {notional} is above client {client_id}'s limit of {max_notional}")]
+    ClientOrderNotional { client_id: String, notional: f64, max_notional: f64 },
+
+    #[error("Price {price} is more than {percent}% away from reference price {reference}")]
+    Price { price: Price, reference: Price, percent: f64 },
+
+    #[error("More than {max_orders} orders within {window:?}")]
+    Rate { max_orders: usize, window: Duration },
+
+    #[error("Projected position {projected} is beyond the limit of {limit}")]
+    Position { projected: i64, limit: u64 },
+
+    #[error("Projected position of {projected} across all symbols is beyond the limit of {limit}")]
+    AggregatePosition { projected: u64, limit: u64 },
+
+    /// Refused by a check outside this crate
+    #[error("{check}: {reason}")]
+    Custom { check: String, reason: String },
+}
+
+/// What the engine knows about an order's client and symbol when checking it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RiskContext {
+    /// Engine clock time of the check
+    pub now: DateTime<Utc>,
+    /// The client's signed net position in the order's symbol
+    pub position: i64,
+    /// Unfilled quantity of the client's working buy orders in the order's symbol
+    pub open_buy: u64,
+    /// Unfilled quantity of the client's working sell orders in the order's symbol
+    pub open_sell: u64,
+    /// Largest position the client could hold across its other symbols, summed: per
+    /// symbol, its net position were every open order on one side filled
+    pub other_exposure: u64,
+    /// The symbol's reference price (external if fresh, else last traded), if any
+    pub reference_price: Option<Price>,
+}
+
+/// A pre-trade control, run on every new order before it reaches the book.
+///
+/// Checks run in the order they were added to the engine, on the matching thread, and
+/// the first refusal rejects the order. They see orders one at a time in sequence, so a
+/// check may keep state (e.g. order counts) without further synchronisation.
+pub trait RiskCheck: Send + fmt::Debug {
+    /// Short name for logs and rejection counts
+    fn name(&self) -> &str;
+
+    /// Refuse the order, or pass it on; a check may lower the order's quantity instead of
+    /// refusing it, but should change nothing else
+    fn check(&mut self, order: &mut Order, context: &RiskContext) -> Result<(), RiskRejection>;
+}
+
+/// Largest quantity and price × quantity of a single order
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OrderSizeCheck {
+    pub max_quantity: Option<u64>,
+    /// Only checked for orders with a price
+    pub max_notional: Option<f64>,
+}
+
+impl RiskCheck for OrderSizeCheck {
+    fn name(&self) -> &str {
+        "order_size"
+    }
+
+    fn check(&mut self, order: &mut Order, _context: &RiskContext) -> Result<(), RiskRejection> {
+        if let Some(max_quantity) = self.max_quantity.filter(|&max| order.quantity > max) {
+            return Err(RiskRejection::OrderSize {
+                quantity: order.quantity,
+                max_quantity,
+            });
+        }
+        if let (Some(max_notional), Some(price)) = (self.max_notional, order.price) {
+            let notional = price.to_f64() * order.quantity as f64;
+            if notional > max_notional {
+                return Err(RiskRejection::OrderNotional { notional, max_notional });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Largest quantity and price × quantity of a single order of one client
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClientLimits {
+    pub max_quantity: Option<u64>,
+    /// Only checked for orders with a price
+    pub max_notional: Option<f64>,
+}
+
+/// Order size limits set per client, with optional limits for clients without their own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientLimitsCheck {
+    clients: HashMap<String, ClientLimits>,
+    default: Option<ClientLimits>,
+}
+
+impl ClientLimitsCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_client(mut self, client_id: impl Into<String>, limits: ClientLimits) -> Self {
+        self.clients.insert(client_id.into(), limits);
+        self
+    }
+
+    /// Limits for every client not given its own
+    pub fn with_default(mut self, limits: ClientLimits) -> Self {
+        self.default = Some(limits);
+        self
+    }
+}
+
+impl RiskCheck for ClientLimitsCheck {
+    fn name(&self) -> &str {
+        "client_limits"
+    }
+
+    fn check(&mut self, order: &mut Order, _context: &RiskContext) -> Result<(), RiskRejection> {
+        let Some(limits) = self.clients.get(&order.client_id).or(self.default.as_ref()) else {
+            return Ok(());
+        };
+        if let Some(max_quantity) = limits.max_quantity.filter(|&max| order.quantity > max) {
+            return Err(RiskRejection::ClientOrderSize {
+                client_id: order.client_id.clone(),
+                quantity: order.quantity,
+                max_quantity,
+            });
+        }
+        if let (Some(max_notional), Some(price)) = (limits.max_notional, order.price) {
+            let notional = price.to_f64() * order.quantity as f64;
+            if notional > max_notional {
+                return Err(RiskRejection::ClientOrderNotional {
+                    client_id: order.client_id.clone(),
+                    notional,
+                    max_notional,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Limit prices within `percent` of the symbol's reference price; orders pass while there
+/// is no reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceBandCheck {
+    pub percent: f64,
+}
+
+impl RiskCheck for PriceBandCheck {
+    fn name(&self) -> &str {
+        "price_band"
+    }
+
+    fn check(&mut self, order: &mut Order, context: &RiskContext) -> Result<(), RiskRejection> {
+        let (Some(price), Some(reference)) = (order.price, context.reference_price) else {
+            return Ok(());
+        };
+        let reference_value = reference.to_f64();
+        if (price.to_f64() - reference_value).abs() <= reference_value.abs() * self.percent / 100.0 {
+            return Ok(());
+        }
+        Err(RiskRejection::Price {
+            price,
+            reference,
+            percent: self.percent,
+        })
+    }
+}
+
+/// At most `max_orders` new orders per client within any `window`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRateCheck {
+    max_orders: usize,
+    window: Duration,
+    recent: HashMap<String, VecDeque<DateTime<Utc>>>, // client -> times of its accepted orders, oldest first
+}
+
+impl OrderRateCheck {
+    pub fn new(max_orders: usize, window: Duration) -> Self {
+        Self {
+            max_orders,
+            window,
+            recent: HashMap::new(),
+        }
+    }
+}
+
+impl RiskCheck for OrderRateCheck {
+    fn name(&self) -> &str {
+        "order_rate"
+    }
+
+    fn check(&mut self, order: &mut Order, context: &RiskContext) -> Result<(), RiskRejection> {
+        let cutoff = context.now - chrono::Duration::from_std(self.window).unwrap_or(chrono::Duration::MAX);
+        let recent = self.recent.entry(order.client_id.clone()).or_default();
+        while recent.front().is_some_and(|&time| time <= cutoff) {
+            recent.pop_front();
+        }
+        if recent.len() >= self.max_orders {
+            return Err(RiskRejection::Rate {
+                max_orders: self.max_orders,
+                window: self.window,
+            });
+        }
+        recent.push_back(context.now);
+        Ok(())
+    }
+}
+
+/// A client's net position in a symbol stays within `max_position` either way, were the
+/// order filled in full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionCheck {
+    pub max_position: u64,
+}
+
+impl RiskCheck for PositionCheck {
+    fn name(&self) -> &str {
+        "position"
+    }
+
+    fn check(&mut self, order: &mut Order, context: &RiskContext) -> Result<(), RiskRejection> {
+        let quantity = order.remaining_quantity() as i64;
+        let projected = match order.side {
+            Side::Buy => context.position.saturating_add(quantity),
+            Side::Sell => context.position.saturating_sub(quantity),
+        };
+        if projected.unsigned_abs() <= self.max_position {
+            return Ok(());
+        }
+        Err(RiskRejection::Position {
+            projected,
+            limit: self.max_position,
+        })
+    }
+}
+
+/// Limits on the position a client could reach in a symbol, and across symbols, counting
+/// its open orders as filled.
+///
+/// A buy is projected onto the client's net position plus all its open buys, a sell onto
+/// its net position less all its open sells. Orders that only reduce the projection always
+/// pass. Orders beyond a limit are rejected, or when trimming, cut down to fit it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PositionLimitCheck {
+    symbols: HashMap<String, u64>,
+    default: Option<u64>,
+    aggregate: Option<u64>,
+    trim: bool,
+}
+
+impl PositionLimitCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit on the position either way in `symbol`
+    pub fn with_symbol(mut self, symbol: impl Into<String>, limit: u64) -> Self {
+        self.symbols.insert(symbol.into(), limit);
+        self
+    }
+
+    /// Limit for every symbol not given its own
+    pub fn with_default(mut self, limit: u64) -> Self {
+        self.default = Some(limit);
+        self
+    }
+
+    /// Limit on the sum of the client's positions, either way, across all symbols
+    pub fn with_aggregate(mut self, limit: u64) -> Self {
+        self.aggregate = Some(limit);
+        self
+    }
+
+    /// Cut orders down to what fits the limits, rejecting only those that don't fit at all
+    pub fn trimming(mut self) -> Self {
+        self.trim = true;
+        self
+    }
+
+    /// Largest quantity the order may have with `base` as the position it projects onto
+    fn room(side: Side, base: i64, limit: u64) -> u64 {
+        let room = match side {
+            Side::Buy => (limit as i64).saturating_sub(base),
+            Side::Sell => base.saturating_add(limit as i64),
+        };
+        room.max(0) as u64
+    }
+}
+
+impl RiskCheck for PositionLimitCheck {
+    fn name(&self) -> &str {
+        "position_limit"
+    }
+
+    fn check(&mut self, order: &mut Order, context: &RiskContext) -> Result<(), RiskRejection> {
+        let base = match order.side {
+            Side::Buy => context.position.saturating_add(context.open_buy as i64),
+            Side::Sell => context.position.saturating_sub(context.open_sell as i64),
+        };
+        let projected = |quantity: u64| match order.side {
+            Side::Buy => base.saturating_add(quantity as i64),
+            Side::Sell => base.saturating_sub(quantity as i64),
+        };
+
+        let symbol_limit = self.symbols.get(&order.symbol).copied().or(self.default);
+        let symbol_room = symbol_limit.map_or(u64::MAX, |limit| Self::room(order.side, base, limit));
+        let aggregate_room = self.aggregate.map_or(u64::MAX, |limit| {
+            Self::room(order.side, base, limit.saturating_sub(context.other_exposure))
+        });
+        let room = symbol_room.min(aggregate_room);
+        let remaining = order.remaining_quantity();
+        if remaining <= room {
+            return Ok(());
+        }
+        if self.trim && room > 0 {
+            order.quantity = order.filled_quantity + room;
+            return Ok(());
+        }
+
+        match (symbol_limit, self.aggregate) {
+            (Some(limit), _) if remaining > symbol_room => Err(RiskRejection::Position {
+                projected: projected(remaining),
+                limit,
+            }),
+            (_, Some(limit)) => Err(RiskRejection::AggregatePosition {
+                projected: context.other_exposure.saturating_add(projected(remaining).unsigned_abs()),
+                limit,
+            }),
+            _ => unreachable!("an order within every limit passes"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risk_checks() {
+        let start = Utc::now();
+        let context = |secs, position| RiskContext {
+            now: start + chrono::Duration::seconds(secs),
+            position,
+            open_buy: 0,
+            open_sell: 0,
+            other_exposure: 0,
+            reference_price: Some(Price::from(100.0)),
+        };
+        let order = |side, quantity, price| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client1".to_string());
+
+        let mut size = OrderSizeCheck {
+            max_quantity: Some(10),
+            max_notional: Some(500.0),
+        };
+        assert!(size.check(&mut order(Side::Buy, 5, 100.0), &context(0, 0)).is_ok());
+        assert!(matches!(size.check(&mut order(Side::Buy, 11, 1.0), &context(0, 0)), Err(RiskRejection::OrderSize { .. })));
+        assert!(matches!(size.check(&mut order(Side::Buy, 6, 100.0), &context(0, 0)), Err(RiskRejection::OrderNotional { .. })));
+
+        let mut clients = ClientLimitsCheck::new()
+            .with_client("client1", ClientLimits {
+                max_quantity: Some(100),
+                max_notional: None,
+            })
+            .with_default(ClientLimits {
+                max_quantity: Some(1),
+                max_notional: Some(50.0),
+            });
+        assert!(clients.check(&mut order(Side::Buy, 50, 100.0), &context(0, 0)).is_ok());
+        let mut other = order(Side::Buy, 1, 100.0);
+        other.client_id = "client2".to_string();
+        assert!(matches!(
+            clients.check(&mut other, &context(0, 0)),
+            Err(RiskRejection::ClientOrderNotional { client_id, .. }) if client_id == "client2"
+        ));
+
+        let mut band = PriceBandCheck { percent: 5.0 };
+        assert!(band.check(&mut order(Side::Buy, 1, 105.0), &context(0, 0)).is_ok());
+        assert!(matches!(band.check(&mut order(Side::Sell, 1, 94.0), &context(0, 0)), Err(RiskRejection::Price { .. })));
+
+        let mut rate = OrderRateCheck::new(2, Duration::from_secs(1));
+        assert!(rate.check(&mut order(Side::Buy, 1, 100.0), &context(0, 0)).is_ok());
+        assert!(rate.check(&mut order(Side::Buy, 1, 100.0), &context(0, 0)).is_ok());
+        assert!(matches!(rate.check(&mut order(Side::Buy, 1, 100.0), &context(0, 0)), Err(RiskRejection::Rate { .. })));
+        // Refusals don't count against the client, and the window moves on
+        assert!(rate.check(&mut order(Side::Buy, 1, 100.0), &context(1, 0)).is_ok());
+
+        let mut position = PositionCheck { max_position: 10 };
+        assert!(position.check(&mut order(Side::Sell, 15, 100.0), &context(0, 8)).is_ok());
+        assert_eq!(
+            position.check(&mut order(Side::Buy, 3, 100.0), &context(0, 8)),
+            Err(RiskRejection::Position { projected: 11, limit: 10 })
+        );
+    }
+
+    #[test]
+    fn test_position_limits() {
+        let context = |position, open_buy, open_sell, other_exposure| RiskContext {
+            now: Utc::now(),
+            position,
+            open_buy,
+            open_sell,
+            other_exposure,
+            reference_price: None,
+        };
+        let order = |side, quantity| Order::new_limit("BTCUSD".to_string(), side, quantity, 100.0, "client1".to_string());
+
+        let mut limits = PositionLimitCheck::new().with_symbol("BTCUSD", 10).with_aggregate(25);
+        // Long 4 with 5 more to buy: room for 1 more buy, and for selling down to short 10
+        assert!(limits.check(&mut order(Side::Buy, 1), &context(4, 5, 0, 0)).is_ok());
+        assert_eq!(
+            limits.check(&mut order(Side::Buy, 2), &context(4, 5, 0, 0)),
+            Err(RiskRejection::Position { projected: 11, limit: 10 })
+        );
+        assert!(limits.check(&mut order(Side::Sell, 14), &context(4, 5, 0, 0)).is_ok());
+        // Open sells count towards a short position only
+        assert!(limits.check(&mut order(Side::Sell, 7), &context(4, 5, 8, 0)).is_err());
+        assert_eq!(
+            limits.check(&mut order(Side::Buy, 6), &context(0, 0, 0, 20)),
+            Err(RiskRejection::AggregatePosition { projected: 26, limit: 25 })
+        );
+
+        let mut trimming = PositionLimitCheck::new().with_default(10).trimming();
+        let mut buy = order(Side::Buy, 8);
+        assert!(trimming.check(&mut buy, &context(4, 0, 0, 0)).is_ok());
+        assert_eq!(buy.quantity, 6);
+        assert!(matches!(
+            trimming.check(&mut order(Side::Buy, 1), &context(4, 6, 0, 0)),
+            Err(RiskRejection::Position { projected: 11, .. })
+        ));
+    }
+}
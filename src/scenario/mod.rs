@@ -0,0 +1,257 @@
+//! Scenario DSL for acceptance and venue-conformance tests.
+//!
+//! A [`Scenario`] is a sequence of labelled order actions against a fresh [`OrderBook`]
+//! interleaved with expectations on the trades printed since the previous trade
+//! expectation and on the resulting book:
+//!
+//! ```rust
+//! use rust_order_execution_engine::scenario::Scenario;
+//! use rust_order_execution_engine::{Order, Side};
+//!
+//! let limit = |side, quantity, price: f64| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client".to_string());
+//!
+//! Scenario::new("BTCUSD")
+//!     .submit("ask", limit(Side::Sell, 10, 100.0))
+//!     .submit("bid", limit(Side::Buy, 4, 101.0))
+//!     .expect_trade("bid", "ask", 4, 100.0)
+//!     .expect_asks(&[(100.0, 6)])
+//!     .expect_bids(&[])
+//!     .run()
+//!     .unwrap();
+//! ```
+
+use crate::matching::{BookConfig, OrderBook};
+use crate::types::{Order, Price, Side, Trade};
+use std::collections::HashMap;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// A trade the scenario expects, by order label
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedTrade {
+    pub buy: String,
+    pub sell: String,
+    pub quantity: u64,
+    pub price: Price,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Submit(String, Order),
+    Cancel(String),
+    Modify {
+        label: String,
+        new_price: Option<Price>,
+        new_quantity: Option<u64>,
+    },
+    ExpectTrades(Vec<ExpectedTrade>),
+    ExpectLevels(Side, Vec<(Price, u64)>),
+    ExpectRemaining(String, u64),
+}
+
+/// First expectation a scenario run did not meet
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Step {step}: {message}")]
+pub struct ScenarioFailure {
+    /// Index of the failing step, in the order the steps were added
+    pub step: usize,
+    pub message: String,
+}
+
+/// Order sequence with expected trade and book outcomes, run against one book
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    symbol: String,
+    config: BookConfig,
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            config: BookConfig::default(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Run against a book with `config`, e.g. an instrument's matching configuration
+    pub fn with_config(mut self, config: BookConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Send `order` to the book as an arriving order, referring to it as `label` from now on
+    pub fn submit(mut self, label: &str, order: Order) -> Self {
+        self.steps.push(Step::Submit(label.to_string(), order));
+        self
+    }
+
+    pub fn cancel(mut self, label: &str) -> Self {
+        self.steps.push(Step::Cancel(label.to_string()));
+        self
+    }
+
+    /// Amend a resting order; the book is rescanned for crosses afterwards
+    pub fn modify(mut self, label: &str, new_price: Option<f64>, new_quantity: Option<u64>) -> Self {
+        self.steps.push(Step::Modify {
+            label: label.to_string(),
+            new_price: new_price.map(Price::from),
+            new_quantity,
+        });
+        self
+    }
+
+    /// Expect exactly this one trade since the last trade expectation
+    pub fn expect_trade(self, buy: &str, sell: &str, quantity: u64, price: f64) -> Self {
+        self.expect_trades(&[(buy, sell, quantity, price)])
+    }
+
+    /// Expect exactly these trades, in order, since the last trade expectation
+    pub fn expect_trades(mut self, trades: &[(&str, &str, u64, f64)]) -> Self {
+        let trades = trades
+            .iter()
+            .map(|&(buy, sell, quantity, price)| ExpectedTrade {
+                buy: buy.to_string(),
+                sell: sell.to_string(),
+                quantity,
+                price: Price::from(price),
+            })
+            .collect();
+        self.steps.push(Step::ExpectTrades(trades));
+        self
+    }
+
+    /// Expect no trades since the last trade expectation
+    pub fn expect_no_trades(self) -> Self {
+        self.expect_trades(&[])
+    }
+
+    /// Expect exactly these bid levels as (price, visible quantity), best first
+    pub fn expect_bids(self, levels: &[(f64, u64)]) -> Self {
+        self.expect_levels(Side::Buy, levels)
+    }
+
+    /// Expect exactly these ask levels as (price, visible quantity), best first
+    pub fn expect_asks(self, levels: &[(f64, u64)]) -> Self {
+        self.expect_levels(Side::Sell, levels)
+    }
+
+    fn expect_levels(mut self, side: Side, levels: &[(f64, u64)]) -> Self {
+        let levels = levels.iter().map(|&(price, quantity)| (Price::from(price), quantity)).collect();
+        self.steps.push(Step::ExpectLevels(side, levels));
+        self
+    }
+
+    /// Expect the order to rest with this remaining quantity; 0 means it is no longer in the book
+    pub fn expect_remaining(mut self, label: &str, quantity: u64) -> Self {
+        self.steps.push(Step::ExpectRemaining(label.to_string(), quantity));
+        self
+    }
+
+    /// Play the steps in order and stop at the first unmet expectation
+    pub fn run(&self) -> Result<OrderBook, ScenarioFailure> {
+        let mut book = OrderBook::with_config(self.symbol.clone(), self.config.clone());
+        let mut ids: HashMap<String, Uuid> = HashMap::new();
+        let mut labels: HashMap<Uuid, String> = HashMap::new();
+        let mut trades: Vec<Trade> = Vec::new();
+
+        for (step, action) in self.steps.iter().enumerate() {
+            let fail = |message: String| ScenarioFailure { step, message };
+            let id_of = |label: &str| ids.get(label).copied().ok_or_else(|| fail(format!("Unknown order {label:?}")));
+
+            match action {
+                Step::Submit(label, order) => {
+                    ids.insert(label.clone(), order.id);
+                    labels.insert(order.id, label.clone());
+                    trades.extend(book.match_order(order.clone()));
+                }
+                Step::Cancel(label) => {
+                    let id = id_of(label)?;
+                    book.cancel_order(id).ok_or_else(|| fail(format!("{label:?} is not in the book")))?;
+                }
+                Step::Modify {
+                    label,
+                    new_price,
+                    new_quantity,
+                } => {
+                    let id = id_of(label)?;
+                    book.replace_order(id, *new_price, *new_quantity)
+                        .ok_or_else(|| fail(format!("{label:?} is not in the book")))?;
+                    trades.extend(book.match_orders());
+                }
+                Step::ExpectTrades(expected) => {
+                    let label = |id: Uuid| labels.get(&id).cloned().unwrap_or_else(|| id.to_string());
+                    let actual: Vec<ExpectedTrade> = trades
+                        .drain(..)
+                        .map(|trade| ExpectedTrade {
+                            buy: label(trade.buy_order_id),
+                            sell: label(trade.sell_order_id),
+                            quantity: trade.quantity,
+                            price: trade.price,
+                        })
+                        .collect();
+                    if &actual != expected {
+                        return Err(fail(format!("expected trades {expected:?}, got {actual:?}")));
+                    }
+                }
+                Step::ExpectLevels(side, expected) => {
+                    let actual = book.aggregated_levels(*side, usize::MAX);
+                    if &actual != expected {
+                        return Err(fail(format!("expected {side} levels {expected:?}, got {actual:?}")));
+                    }
+                }
+                Step::ExpectRemaining(label, expected) => {
+                    let id = id_of(label)?;
+                    let actual = book.get_order(id).map_or(0, Order::remaining_quantity);
+                    if actual != *expected {
+                        return Err(fail(format!("expected {label:?} to have {expected} remaining, got {actual}")));
+                    }
+                }
+            }
+        }
+
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::MatchingAlgorithm;
+
+    fn limit(side: Side, quantity: u64, price: f64) -> Order {
+        Order::new_limit("ESZ4".to_string(), side, quantity, price, "client".to_string())
+    }
+
+    #[test]
+    fn test_scenario() {
+        Scenario::new("ESZ4")
+            .with_config(BookConfig {
+                algorithm: MatchingAlgorithm::ProRata { top_order_percent: 0 },
+                ..BookConfig::default()
+            })
+            .submit("a", limit(Side::Sell, 30, 100.0))
+            .submit("b", limit(Side::Sell, 10, 100.0))
+            .submit("c", limit(Side::Buy, 20, 100.0))
+            .expect_trades(&[("c", "a", 15, 100.0), ("c", "b", 5, 100.0)])
+            .modify("b", Some(101.0), None)
+            .expect_no_trades()
+            .expect_asks(&[(100.0, 15), (101.0, 5)])
+            .cancel("a")
+            .expect_remaining("a", 0)
+            .expect_remaining("b", 5)
+            .run()
+            .unwrap();
+
+        let failure = Scenario::new("ESZ4")
+            .submit("a", limit(Side::Sell, 10, 100.0))
+            .expect_bids(&[(100.0, 10)])
+            .run()
+            .unwrap_err();
+        assert_eq!(failure.step, 1);
+
+        let failure = Scenario::new("ESZ4").cancel("missing").run().unwrap_err();
+        assert!(failure.message.contains("Unknown order"));
+    }
+}
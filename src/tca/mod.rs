@@ -0,0 +1,261 @@
+use crate::types::{Order, Price, Side, Trade};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A parent order and its arrival benchmark
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParentOrder {
+    pub id: Uuid,
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: u64,
+    pub arrival_time: DateTime<Utc>,
+    /// Benchmark price when the parent arrived, usually the mid
+    pub arrival_price: Price,
+}
+
+/// Transaction costs of one parent order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderTca {
+    pub parent_id: Uuid,
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: u64,
+    pub filled_quantity: u64,
+    pub avg_fill_price: Option<f64>,
+    /// Average fill price against the arrival price, in basis points; positive is a cost
+    pub arrival_slippage_bps: Option<f64>,
+    /// Share of the symbol's traded volume between the first and last fill
+    pub participation_rate: Option<f64>,
+    /// Move of the last traded price one horizon after the last fill, against the average
+    /// fill price, in basis points; positive means the price came back (temporary impact)
+    pub reversion_bps: Option<f64>,
+    /// Average spread at execution, where trades carry book context
+    pub avg_spread: Option<f64>,
+}
+
+/// Quantity-weighted averages over all parent orders with fills
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TcaSummary {
+    pub orders: usize,
+    pub filled_quantity: u64,
+    pub arrival_slippage_bps: Option<f64>,
+    pub participation_rate: Option<f64>,
+    pub reversion_bps: Option<f64>,
+}
+
+/// Per-order and aggregate TCA results
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TcaReport {
+    pub orders: Vec<OrderTca>,
+    pub summary: TcaSummary,
+}
+
+impl TcaReport {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Render the per-order rows as CSV; missing values are left empty
+    pub fn to_csv(&self) -> String {
+        let field = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        let mut csv = String::from(
+            "parent_id,symbol,side,quantity,filled_quantity,avg_fill_price,arrival_slippage_bps,participation_rate,reversion_bps,avg_spread\n",
+        );
+        for order in &self.orders {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{}",
+                order.parent_id,
+                order.symbol,
+                order.side,
+                order.quantity,
+                order.filled_quantity,
+                field(order.avg_fill_price),
+                field(order.arrival_slippage_bps),
+                field(order.participation_rate),
+                field(order.reversion_bps),
+                field(order.avg_spread),
+            );
+        }
+        csv
+    }
+}
+
+/// Collects parent orders, their child orders and the trade stream to compute TCA.
+///
+/// Children are linked to their parent through [`Order::parent`]; every trade on a
+/// symbol counts towards market volume for participation and reversion.
+#[derive(Debug)]
+pub struct TcaAnalyzer {
+    reversion_horizon: Duration,
+    parents: HashMap<Uuid, ParentOrder>,
+    children: HashMap<Uuid, Uuid>,       // child order -> parent
+    fills: HashMap<Uuid, Vec<Trade>>,    // parent -> child fills
+    market: HashMap<String, Vec<Trade>>, // symbol -> all trades, in arrival order
+}
+
+impl TcaAnalyzer {
+    pub fn new(reversion_horizon: Duration) -> Self {
+        Self {
+            reversion_horizon,
+            parents: HashMap::new(),
+            children: HashMap::new(),
+            fills: HashMap::new(),
+            market: HashMap::new(),
+        }
+    }
+
+    pub fn add_parent(&mut self, parent: ParentOrder) {
+        self.parents.insert(parent.id, parent);
+    }
+
+    /// Register a child order; ignored unless it links to a known parent
+    pub fn record_child(&mut self, order: &Order) {
+        if let Some(parent) = order.parent.filter(|p| self.parents.contains_key(&p.id)) {
+            self.children.insert(order.id, parent.id);
+        }
+    }
+
+    pub fn record_trade(&mut self, trade: &Trade) {
+        for order_id in [trade.buy_order_id, trade.sell_order_id] {
+            if let Some(&parent_id) = self.children.get(&order_id) {
+                self.fills.entry(parent_id).or_default().push(trade.clone());
+            }
+        }
+        self.market.entry(trade.symbol.clone()).or_default().push(trade.clone());
+    }
+
+    pub fn report(&self) -> TcaReport {
+        let mut orders: Vec<OrderTca> = self.parents.values().map(|parent| self.order_tca(parent)).collect();
+        orders.sort_by_key(|order| order.parent_id);
+
+        let filled: Vec<&OrderTca> = orders.iter().filter(|o| o.filled_quantity > 0).collect();
+        let filled_quantity = filled.iter().map(|o| o.filled_quantity).sum();
+        let weighted = |metric: fn(&OrderTca) -> Option<f64>| {
+            let (sum, weight) = filled
+                .iter()
+                .filter_map(|o| metric(o).map(|value| (value, o.filled_quantity as f64)))
+                .fold((0.0, 0.0), |(sum, weight), (value, w)| (sum + value * w, weight + w));
+            (weight > 0.0).then(|| sum / weight)
+        };
+
+        let summary = TcaSummary {
+            orders: filled.len(),
+            filled_quantity,
+            arrival_slippage_bps: weighted(|o| o.arrival_slippage_bps),
+            participation_rate: weighted(|o| o.participation_rate),
+            reversion_bps: weighted(|o| o.reversion_bps),
+        };
+        TcaReport { orders, summary }
+    }
+
+    fn order_tca(&self, parent: &ParentOrder) -> OrderTca {
+        let fills = self.fills.get(&parent.id).map(Vec::as_slice).unwrap_or_default();
+        let filled_quantity: u64 = fills.iter().map(|t| t.quantity).sum();
+        let notional: f64 = fills.iter().map(|t| t.price.to_f64() * t.quantity as f64).sum();
+        let avg_fill_price = (filled_quantity > 0).then(|| notional / filled_quantity as f64);
+
+        // Positive when the price moved against the parent's side
+        let sign = match parent.side {
+            Side::Buy => 1.0,
+            Side::Sell => -1.0,
+        };
+        let arrival = parent.arrival_price.to_f64();
+        let arrival_slippage_bps = avg_fill_price
+            .filter(|_| arrival > 0.0)
+            .map(|avg| sign * (avg - arrival) / arrival * 10_000.0);
+
+        let market = self.market.get(&parent.symbol).map(Vec::as_slice).unwrap_or_default();
+        let window = fills.iter().map(|t| t.timestamp).min().zip(fills.iter().map(|t| t.timestamp).max());
+        let participation_rate = window.and_then(|(first, last)| {
+            let volume: u64 = market
+                .iter()
+                .filter(|t| t.timestamp >= first && t.timestamp <= last)
+                .map(|t| t.quantity)
+                .sum();
+            (volume > 0).then(|| filled_quantity as f64 / volume as f64)
+        });
+
+        let reversion_bps = window.zip(avg_fill_price).and_then(|((_, last), avg)| {
+            let horizon = last + chrono::Duration::from_std(self.reversion_horizon).ok()?;
+            let later = market.iter().rev().find(|t| t.timestamp > last && t.timestamp <= horizon)?;
+            Some(sign * (avg - later.price.to_f64()) / avg * 10_000.0)
+        });
+
+        let spreads: Vec<f64> = fills
+            .iter()
+            .filter_map(|t| t.context?.spread)
+            .map(Price::to_f64)
+            .collect();
+        let avg_spread = (!spreads.is_empty()).then(|| spreads.iter().sum::<f64>() / spreads.len() as f64);
+
+        OrderTca {
+            parent_id: parent.id,
+            symbol: parent.symbol.clone(),
+            side: parent.side,
+            quantity: parent.quantity,
+            filled_quantity,
+            avg_fill_price,
+            arrival_slippage_bps,
+            participation_rate,
+            reversion_bps,
+            avg_spread,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tca_report() {
+        let start = Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        let parent = ParentOrder {
+            id: Uuid::new_v4(),
+            symbol: "BTCUSD".to_string(),
+            side: Side::Buy,
+            quantity: 20,
+            arrival_time: at(0),
+            arrival_price: Price::from(100.0),
+        };
+        let mut tca = TcaAnalyzer::new(Duration::from_secs(60));
+        tca.add_parent(parent.clone());
+
+        let child = Order::new_limit("BTCUSD".to_string(), Side::Buy, 20, 102.0, "algo1".to_string())
+            .with_parent(parent.id, None);
+        tca.record_child(&child);
+
+        let mut trade = |buy_order_id, quantity, price: f64, secs| {
+            let mut trade = Trade::new(buy_order_id, Uuid::new_v4(), "BTCUSD".to_string(), quantity, Price::from(price));
+            trade.timestamp = at(secs);
+            tca.record_trade(&trade);
+        };
+        trade(child.id, 10, 101.0, 1);
+        trade(Uuid::new_v4(), 20, 101.5, 2); // someone else
+        trade(child.id, 10, 102.0, 3);
+        trade(Uuid::new_v4(), 5, 100.98, 30);
+
+        let report = tca.report();
+        let order = &report.orders[0];
+        assert_eq!(order.filled_quantity, 20);
+        assert_eq!(order.avg_fill_price, Some(101.5));
+        // Paid 1.5 over a 100 arrival
+        assert!((order.arrival_slippage_bps.unwrap() - 150.0).abs() < 1e-9);
+        // 20 of the 40 traded between the first and last fill
+        assert_eq!(order.participation_rate, Some(0.5));
+        assert!(order.reversion_bps.unwrap() > 0.0);
+
+        assert_eq!(report.summary.orders, 1);
+        assert_eq!(report.summary.filled_quantity, 20);
+        assert_eq!(report.to_csv().lines().count(), 2);
+        assert!(report.to_json().unwrap().contains("arrival_slippage_bps"));
+    }
+}
@@ -0,0 +1,122 @@
+/// Values below this are counted exactly; above it each power of two is split into
+/// `SUB_BUCKETS / 2` buckets, so a bucket spans at most 1/64 (~1.6%) of its values
+const SUB_BUCKETS: usize = 128;
+const HALF: usize = SUB_BUCKETS / 2;
+const SUB_BUCKET_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+/// Enough buckets to cover every `u64`
+const BUCKETS: usize = SUB_BUCKETS + (64 - SUB_BUCKET_BITS as usize) * HALF;
+
+/// Latency distribution in fixed memory, HDR histogram style.
+///
+/// Buckets are log-linear: exact below 128, then 64 per power of two, so recording is
+/// a couple of bit operations and any quantile is read with bounded relative error
+/// without keeping or sorting samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    total: u128,
+    max: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            count: 0,
+            total: 0,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.counts[Self::bucket(value)] += 1;
+        self.count += 1;
+        self.total += value as u128;
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> Option<u64> {
+        (self.count > 0).then(|| (self.total / self.count as u128) as u64)
+    }
+
+    /// Value at the `q` quantile (0.0..=1.0): the highest value of the bucket holding it,
+    /// capped at the largest value recorded
+    pub fn value_at_quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::highest_in(bucket).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    fn bucket(value: u64) -> usize {
+        if value < SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        // Shift that brings the value into [HALF, SUB_BUCKETS)
+        let shift = 64 - value.leading_zeros() - SUB_BUCKET_BITS;
+        SUB_BUCKETS + (shift as usize - 1) * HALF + ((value >> shift) as usize - HALF)
+    }
+
+    fn highest_in(bucket: usize) -> u64 {
+        if bucket < SUB_BUCKETS {
+            return bucket as u64;
+        }
+        let shift = ((bucket - SUB_BUCKETS) / HALF + 1) as u32;
+        let sub_bucket = ((bucket - SUB_BUCKETS) % HALF + HALF) as u64;
+        // The top bucket ends at u64::MAX, so add the width instead of shifting past it
+        (sub_bucket << shift) + ((1u64 << shift) - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_quantiles() {
+        let mut histogram = LatencyHistogram::new();
+        assert_eq!(histogram.value_at_quantile(0.5), None);
+
+        for value in 1..=100_000 {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.count(), 100_000);
+        assert_eq!(histogram.mean(), Some(50_000));
+        assert_eq!(histogram.value_at_quantile(1.0), Some(100_000));
+        for (q, exact) in [(0.5, 50_000.0), (0.95, 95_000.0), (0.99, 99_000.0)] {
+            let value = histogram.value_at_quantile(q).unwrap() as f64;
+            assert!(value >= exact && value <= exact * (1.0 + 1.0 / HALF as f64), "{q}: {value}");
+        }
+
+        // Small values are exact and the largest values still land in a bucket
+        let mut histogram = LatencyHistogram::new();
+        histogram.record(3);
+        histogram.record(u64::MAX);
+        assert_eq!(histogram.value_at_quantile(0.5), Some(3));
+        assert_eq!(histogram.value_at_quantile(1.0), Some(u64::MAX));
+        assert_eq!(LatencyHistogram::bucket(u64::MAX), BUCKETS - 1);
+    }
+}
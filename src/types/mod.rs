@@ -1,10 +1,21 @@
-use chrono::{DateTime, Utc};
+mod latency;
+mod price;
+mod symbol;
+
+pub use latency::LatencyHistogram;
+pub use price::{ParsePriceError, Price, RoundingMode};
+pub use symbol::{SymbolId, SymbolTable};
+
+use crate::clock::ClockSource;
+use crate::matching::SelfTradePrevention;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use uuid::Uuid;
 
 /// Order side (Buy or Sell)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Side {
     Buy,
     Sell,
@@ -26,6 +37,17 @@ pub enum OrderType {
     Limit,
     StopLoss,
     StopLimit,
+    /// Executes at the opening auction price
+    MarketOnOpen,
+    /// Executes at the closing auction price
+    MarketOnClose,
+}
+
+impl OrderType {
+    /// Whether orders of this type only participate in an auction uncross
+    pub fn is_auction_order(&self) -> bool {
+        matches!(self, OrderType::MarketOnOpen | OrderType::MarketOnClose)
+    }
 }
 
 /// Order status
@@ -36,6 +58,33 @@ pub enum OrderStatus {
     Filled,
     Cancelled,
     Rejected,
+    Expired,
+}
+
+/// Whether an order opens or closes a position (futures-style open/close flag)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PositionIntent {
+    Open,
+    Close,
+}
+
+/// Link from a child order to the parent order (strategy or routed order) that submitted it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParentLink {
+    pub id: Uuid,
+    /// Self-trade prevention between children of this parent; the book's policy applies when `None`
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+}
+
+/// How long an order remains working
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    #[default]
+    GoodTillCancel,
+    GoodTillDate(DateTime<Utc>),
+    /// Good till crossing (GTX): rests passively like good-till-cancel, but is cancelled
+    /// instead of trading if it would cross the book on arrival
+    GoodTillCrossing,
 }
 
 /// Financial order representation
@@ -46,12 +95,39 @@ pub struct Order {
     pub side: Side,
     pub order_type: OrderType,
     pub quantity: u64,
-    pub price: Option<f64>,
-    pub stop_price: Option<f64>,
+    pub price: Option<Price>,
+    pub stop_price: Option<Price>,
     pub filled_quantity: u64,
     pub status: OrderStatus,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub reduce_only: bool,
+    /// A sell the client doesn't own, subject to short sale restrictions
+    #[serde(default)]
+    pub sell_short: bool,
+    #[serde(default)]
+    pub auction_only: bool,
+    /// Only add liquidity; what happens when the order would lock or cross the book is up to the book's cross policy
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub priority_class: u8,
+    #[serde(default)]
+    pub position_intent: Option<PositionIntent>,
+    #[serde(default)]
+    pub parent: Option<ParentLink>,
+    /// Peak size shown in the book for an iceberg order; the whole remaining quantity shows when `None`
+    #[serde(default)]
+    pub display_quantity: Option<u64>,
+    /// Unfilled part of the iceberg slice currently shown in the book
+    #[serde(default)]
+    pub(crate) slice_remaining: u64,
     pub timestamp: DateTime<Utc>,
     pub client_id: String,
+    /// The client's own ID for the order, unique per client; recent ones are checked for duplicates
+    #[serde(default)]
+    pub client_order_id: Option<String>,
 }
 
 impl Order {
@@ -66,8 +142,33 @@ impl Order {
             stop_price: None,
             filled_quantity: 0,
             status: OrderStatus::Pending,
+            time_in_force: TimeInForce::GoodTillCancel,
+            reduce_only: false,
+            sell_short: false,
+            auction_only: false,
+            post_only: false,
+            priority_class: 0,
+            position_intent: None,
+            parent: None,
+            display_quantity: None,
+            slice_remaining: 0,
             timestamp: Utc::now(),
             client_id,
+            client_order_id: None,
+        }
+    }
+
+    pub fn new_market_on_open(symbol: String, side: Side, quantity: u64, client_id: String) -> Self {
+        Self {
+            order_type: OrderType::MarketOnOpen,
+            ..Self::new_market(symbol, side, quantity, client_id)
+        }
+    }
+
+    pub fn new_market_on_close(symbol: String, side: Side, quantity: u64, client_id: String) -> Self {
+        Self {
+            order_type: OrderType::MarketOnClose,
+            ..Self::new_market(symbol, side, quantity, client_id)
         }
     }
 
@@ -75,7 +176,7 @@ impl Order {
         symbol: String,
         side: Side,
         quantity: u64,
-        price: f64,
+        price: impl Into<Price>,
         client_id: String,
     ) -> Self {
         Self {
@@ -84,12 +185,99 @@ impl Order {
             side,
             order_type: OrderType::Limit,
             quantity,
-            price: Some(price),
+            price: Some(price.into()),
             stop_price: None,
             filled_quantity: 0,
             status: OrderStatus::Pending,
+            time_in_force: TimeInForce::GoodTillCancel,
+            reduce_only: false,
+            sell_short: false,
+            auction_only: false,
+            post_only: false,
+            priority_class: 0,
+            position_intent: None,
+            parent: None,
+            display_quantity: None,
+            slice_remaining: 0,
             timestamp: Utc::now(),
             client_id,
+            client_order_id: None,
+        }
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    /// Only allow the order to reduce the client's current position
+    pub fn with_reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = reduce_only;
+        self
+    }
+
+    /// Mark a sell as a short sale, which must be priced above the best bid while the
+    /// symbol's short sale restriction is active
+    pub fn with_sell_short(mut self, sell_short: bool) -> Self {
+        self.sell_short = sell_short;
+        self
+    }
+
+    /// Restrict the order to auction phases; it is held out of continuous matching
+    pub fn with_auction_only(mut self, auction_only: bool) -> Self {
+        self.auction_only = auction_only;
+        self
+    }
+
+    /// Only let the order rest passively, never take liquidity on arrival
+    pub fn with_post_only(mut self, post_only: bool) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    /// Priority class at a price level; lower classes match first when the book uses priority classes
+    pub fn with_priority_class(mut self, priority_class: u8) -> Self {
+        self.priority_class = priority_class;
+        self
+    }
+
+    /// Declare whether the order opens or closes a position; closing orders must not exceed the position
+    pub fn with_position_intent(mut self, intent: PositionIntent) -> Self {
+        self.position_intent = Some(intent);
+        self
+    }
+
+    /// Mark the order as a child of `parent_id`, with the parent's self-trade prevention policy for its children
+    pub fn with_parent(mut self, parent_id: Uuid, self_trade_prevention: Option<SelfTradePrevention>) -> Self {
+        self.parent = Some(ParentLink {
+            id: parent_id,
+            self_trade_prevention,
+        });
+        self
+    }
+
+    /// Make the order an iceberg that shows at most `display_quantity` in the book at a time
+    pub fn with_display_quantity(mut self, display_quantity: u64) -> Self {
+        self.display_quantity = Some(display_quantity.max(1));
+        self
+    }
+
+    /// Tag the order with the client's own ID for it
+    pub fn with_client_order_id(mut self, client_order_id: impl Into<String>) -> Self {
+        self.client_order_id = Some(client_order_id.into());
+        self
+    }
+
+    /// Expire the order once `ttl` has elapsed since it was created
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        let expire_at = self.timestamp + ttl;
+        self.with_time_in_force(TimeInForce::GoodTillDate(expire_at))
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        match self.time_in_force {
+            TimeInForce::GoodTillCancel | TimeInForce::GoodTillCrossing => false,
+            TimeInForce::GoodTillDate(expire_at) => now >= expire_at,
         }
     }
 
@@ -97,21 +285,76 @@ impl Order {
         self.quantity.saturating_sub(self.filled_quantity)
     }
 
+    /// Quantity shown in the book: the current slice for an iceberg, otherwise the remaining quantity
+    pub fn visible_quantity(&self) -> u64 {
+        match self.display_quantity {
+            Some(_) => self.slice_remaining.min(self.remaining_quantity()),
+            None => self.remaining_quantity(),
+        }
+    }
+
+    /// Show the next iceberg slice
+    pub(crate) fn refresh_slice(&mut self) {
+        if let Some(display_quantity) = self.display_quantity {
+            self.slice_remaining = display_quantity.min(self.remaining_quantity());
+        }
+    }
+
     pub fn is_fully_filled(&self) -> bool {
         self.filled_quantity >= self.quantity
     }
 }
 
+/// Book state around a trade, for transaction cost analysis.
+///
+/// Complete for orders matched on arrival. Trades from a rescan of an already crossed
+/// book (after resuming or amending) only carry `queue_position`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeContext {
+    /// Best ask minus best bid just before the aggressor arrived
+    pub spread: Option<Price>,
+    /// Position of the resting order in its price level's queue (0 = front)
+    pub queue_position: usize,
+    /// Price levels the aggressor traded through
+    pub levels_swept: usize,
+    /// Total quantity the aggressor took from the book
+    pub depth_consumed: u64,
+}
+
 /// Trade execution result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub id: Uuid,
     pub buy_order_id: Uuid,
     pub sell_order_id: Uuid,
+    #[serde(default)]
+    pub buy_client_id: String,
+    #[serde(default)]
+    pub sell_client_id: String,
+    #[serde(default)]
+    pub buy_intent: Option<PositionIntent>,
+    #[serde(default)]
+    pub sell_intent: Option<PositionIntent>,
     pub symbol: String,
     pub quantity: u64,
-    pub price: f64,
+    pub price: Price,
+    /// Event time from the engine's configured clock
     pub timestamp: DateTime<Utc>,
+    /// Clock that produced `timestamp`
+    #[serde(default)]
+    pub clock_source: ClockSource,
+    /// Wall-clock time of the match, for correlating events stamped by different clocks
+    #[serde(default)]
+    pub wall_timestamp: DateTime<Utc>,
+    /// Book context at execution, when the book is configured to attach it
+    #[serde(default)]
+    pub context: Option<TradeContext>,
+    /// Side of the order that took liquidity; `None` for auction trades
+    #[serde(default)]
+    pub aggressor: Option<Side>,
+    /// Sequence number of the engine command that caused the trade; 0 outside the engine
+    #[serde(default)]
+    pub sequence: u64,
 }
 
 impl Trade {
@@ -120,18 +363,66 @@ impl Trade {
         sell_order_id: Uuid,
         symbol: String,
         quantity: u64,
-        price: f64,
+        price: Price,
     ) -> Self {
+        let now = Utc::now();
         Self {
             id: Uuid::new_v4(),
             buy_order_id,
             sell_order_id,
+            buy_client_id: String::new(),
+            sell_client_id: String::new(),
+            buy_intent: None,
+            sell_intent: None,
             symbol,
             quantity,
             price,
-            timestamp: Utc::now(),
+            timestamp: now,
+            clock_source: ClockSource::Wall,
+            wall_timestamp: now,
+            context: None,
+            aggressor: None,
+            sequence: 0,
         }
     }
+
+    pub fn with_clients(mut self, buy_client_id: String, sell_client_id: String) -> Self {
+        self.buy_client_id = buy_client_id;
+        self.sell_client_id = sell_client_id;
+        self
+    }
+
+    /// Carry both orders' client IDs and position intents onto the trade
+    pub(crate) fn between(mut self, buy: &Order, sell: &Order) -> Self {
+        self.buy_intent = buy.position_intent;
+        self.sell_intent = sell.position_intent;
+        self.buy_client_id.clone_from(&buy.client_id);
+        self.sell_client_id.clone_from(&sell.client_id);
+        self
+    }
+
+    /// Turn a used trade into a new one, as [`Trade::new`] would build it, keeping its
+    /// string buffers
+    pub(crate) fn reset(&mut self, buy_order_id: Uuid, sell_order_id: Uuid, symbol: &str, quantity: u64, price: Price) {
+        let now = Utc::now();
+        self.id = Uuid::new_v4();
+        self.buy_order_id = buy_order_id;
+        self.sell_order_id = sell_order_id;
+        self.buy_client_id.clear();
+        self.sell_client_id.clear();
+        self.buy_intent = None;
+        self.sell_intent = None;
+        self.symbol.clear();
+        self.symbol.push_str(symbol);
+        self.quantity = quantity;
+        self.price = price;
+        self.timestamp = now;
+        self.clock_source = ClockSource::Wall;
+        self.wall_timestamp = now;
+        self.context = None;
+        self.aggressor = None;
+        self.sequence = 0;
+    }
 }
 
 /// Execution metrics
@@ -141,12 +432,77 @@ pub struct ExecutionMetrics {
     pub filled_orders: u64,
     pub cancelled_orders: u64,
     pub rejected_orders: u64,
+    pub expired_orders: u64,
     pub total_trades: u64,
     pub total_volume: f64,
     pub avg_latency_micros: u64,
     pub p50_latency_micros: u64,
     pub p95_latency_micros: u64,
     pub p99_latency_micros: u64,
+    /// How long orders rested in the book before leaving it (filled, cancelled or expired), per symbol
+    pub resting_time: HashMap<String, RestingTimeHistogram>,
+    /// Orders refused by each pre-trade risk check, by check name; included in `rejected_orders`
+    #[serde(default)]
+    pub risk_rejections: HashMap<String, u64>,
+    /// Messages refused by the message throttle, by client; refused new orders are also in `rejected_orders`
+    #[serde(default)]
+    pub throttled_messages: HashMap<String, u64>,
+}
+
+/// Upper bounds of the resting time buckets, in microseconds; the last bucket is unbounded
+pub const RESTING_TIME_BUCKETS_MICROS: [u64; 8] = [
+    1_000,         // 1ms
+    10_000,        // 10ms
+    100_000,       // 100ms
+    1_000_000,     // 1s
+    10_000_000,    // 10s
+    60_000_000,    // 1m
+    600_000_000,   // 10m
+    3_600_000_000, // 1h
+];
+
+/// Distribution of order resting times over [`RESTING_TIME_BUCKETS_MICROS`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestingTimeHistogram {
+    /// Orders per bucket; one more entry than there are bounds, for longer rests
+    pub bucket_counts: Vec<u64>,
+    pub count: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+impl RestingTimeHistogram {
+    pub fn record(&mut self, micros: u64) {
+        if self.bucket_counts.is_empty() {
+            self.bucket_counts = vec![0; RESTING_TIME_BUCKETS_MICROS.len() + 1];
+        }
+        let bucket = RESTING_TIME_BUCKETS_MICROS.partition_point(|&bound| bound < micros);
+        self.bucket_counts[bucket] += 1;
+        self.count += 1;
+        self.total_micros += micros;
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    pub fn mean_micros(&self) -> Option<u64> {
+        (self.count > 0).then(|| self.total_micros / self.count)
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile (0.0..=1.0); the maximum for the last bucket
+    pub fn quantile_micros(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.bucket_counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = RESTING_TIME_BUCKETS_MICROS.get(bucket).copied().unwrap_or(u64::MAX);
+                return Some(bound.min(self.max_micros));
+            }
+        }
+        Some(self.max_micros)
+    }
 }
 
 impl ExecutionMetrics {
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+/// Exact fixed-point price with 8 decimal places.
+///
+/// Prices are stored as an integer number of `1e-8` units, so comparisons, book keys
+/// and tick arithmetic are exact. Convert from `f64` at the edges with [`Price::from`];
+/// the value is rounded to the nearest unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Price(i64);
+
+/// How an amount is rounded to fewer decimal places
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RoundingMode {
+    /// Round half to even (banker's rounding)
+    #[default]
+    HalfEven,
+    /// Round half away from zero
+    HalfUp,
+    /// Drop the extra digits, rounding toward zero
+    Truncate,
+}
+
+impl RoundingMode {
+    /// Round `units` to a multiple of `step`
+    fn round(self, units: i128, step: i128) -> i128 {
+        let quotient = units / step;
+        let remainder = (units % step).abs();
+        if remainder == 0 {
+            return units;
+        }
+
+        let away_from_zero = match self {
+            RoundingMode::HalfEven => remainder * 2 > step || (remainder * 2 == step && quotient % 2 != 0),
+            RoundingMode::HalfUp => remainder * 2 >= step,
+            RoundingMode::Truncate => false,
+        };
+        (quotient + if away_from_zero { units.signum() } else { 0 }) * step
+    }
+}
+
+impl Price {
+    /// Decimal places carried by a price
+    pub const DECIMALS: u32 = 8;
+    /// Units per 1.0
+    pub const SCALE: i64 = 10i64.pow(Self::DECIMALS);
+    pub const ZERO: Price = Price(0);
+    pub const MIN: Price = Price(i64::MIN);
+    pub const MAX: Price = Price(i64::MAX);
+
+    /// Price from a raw number of `1e-8` units
+    pub const fn from_units(units: i64) -> Self {
+        Self(units)
+    }
+
+    /// Raw number of `1e-8` units
+    pub const fn units(self) -> i64 {
+        self.0
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    /// Round to `decimals` decimal places
+    pub fn round_dp(self, decimals: u32, mode: RoundingMode) -> Price {
+        Self::rounded(self.0 as i128, decimals, mode)
+    }
+
+    /// `self × quantity` rounded to `decimals` decimal places, saturating at the price range
+    pub fn notional(self, quantity: u64, decimals: u32, mode: RoundingMode) -> Price {
+        Self::rounded(self.0 as i128 * quantity as i128, decimals, mode)
+    }
+
+    fn rounded(units: i128, decimals: u32, mode: RoundingMode) -> Price {
+        let step = 10i128.pow(Self::DECIMALS - decimals.min(Self::DECIMALS));
+        let units = mode.round(units, step);
+        Price(units.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+}
+
+impl From<f64> for Price {
+    fn from(value: f64) -> Self {
+        Self((value * Self::SCALE as f64).round() as i64)
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        Price(self.0 + other.0)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        Price(self.0 - other.0)
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let units = self.0.unsigned_abs();
+        let whole = units / Self::SCALE as u64;
+        let fraction = units % Self::SCALE as u64;
+        if fraction == 0 {
+            return write!(f, "{sign}{whole}");
+        }
+
+        let digits = format!("{:0width$}", fraction, width = Self::DECIMALS as usize);
+        write!(f, "{sign}{whole}.{}", digits.trim_end_matches('0'))
+    }
+}
+
+/// Error parsing a decimal string into a [`Price`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePriceError(String);
+
+impl fmt::Display for ParsePriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid price: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePriceError {}
+
+impl FromStr for Price {
+    type Err = ParsePriceError;
+
+    /// Parse a decimal string exactly; more than 8 decimal places is an error
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParsePriceError(s.to_string());
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || fraction.len() > Self::DECIMALS as usize
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| invalid())? };
+        let fraction: i64 = if fraction.is_empty() {
+            0
+        } else {
+            let padded = format!("{:0<width$}", fraction, width = Self::DECIMALS as usize);
+            padded.parse().map_err(|_| invalid())?
+        };
+
+        let units = whole
+            .checked_mul(Self::SCALE)
+            .and_then(|units| units.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Self(if negative { -units } else { units }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_conversions() {
+        // 0.1 + 0.2 is not 0.3 in f64, but it is as a Price
+        assert_eq!(Price::from(0.1) + Price::from(0.2), Price::from(0.3));
+        assert_eq!(Price::from(50000.01).units(), 5_000_001_000_000);
+        assert_eq!(Price::from(1.005), "1.005".parse().unwrap());
+
+        assert_eq!(Price::from(50000.0).to_string(), "50000");
+        assert_eq!(Price::from(0.00012345).to_string(), "0.00012345");
+        assert_eq!((Price::from(99.5) - Price::from(100.0)).to_string(), "-0.5");
+
+        assert_eq!("-12.5".parse(), Ok(Price::from(-12.5)));
+        assert!("1.123456789".parse::<Price>().is_err());
+        assert!("abc".parse::<Price>().is_err());
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let round = |value: &str, mode| value.parse::<Price>().unwrap().round_dp(2, mode).to_string();
+
+        assert_eq!(round("1.005", RoundingMode::HalfEven), "1");
+        assert_eq!(round("1.015", RoundingMode::HalfEven), "1.02");
+        assert_eq!(round("1.005", RoundingMode::HalfUp), "1.01");
+        assert_eq!(round("1.0099", RoundingMode::Truncate), "1");
+        assert_eq!(round("-1.005", RoundingMode::HalfUp), "-1.01");
+        assert_eq!(round("-1.0099", RoundingMode::Truncate), "-1");
+
+        // 0.333 × 3 = 0.999
+        let notional = |mode| Price::from(0.333).notional(3, 2, mode).to_string();
+        assert_eq!(notional(RoundingMode::HalfEven), "1");
+        assert_eq!(notional(RoundingMode::Truncate), "0.99");
+    }
+}
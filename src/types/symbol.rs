@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// Numeric ID of an interned symbol, dense from 0 in order of first sight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SymbolId(u32);
+
+impl SymbolId {
+    /// Position of the symbol in its table, for indexing per-symbol arrays
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub(crate) fn from_index(index: usize) -> Self {
+        Self(u32::try_from(index).expect("symbol ID out of range"))
+    }
+}
+
+impl fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Symbol names interned to [`SymbolId`]s.
+///
+/// Names are hashed once, where they enter the engine; from there on lookups are by ID.
+/// IDs are never reused, so one stays valid for the life of the table.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    ids: RwLock<HashMap<Arc<str>, SymbolId>>,
+    names: RwLock<Vec<Arc<str>>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ID of `symbol`, assigning the next one if it hasn't been seen
+    pub fn intern(&self, symbol: &str) -> SymbolId {
+        if let Some(id) = self.get(symbol) {
+            return id;
+        }
+        let mut ids = self.ids.write().unwrap();
+        if let Some(&id) = ids.get(symbol) {
+            return id;
+        }
+        let mut names = self.names.write().unwrap();
+        let id = SymbolId::from_index(names.len());
+        let name: Arc<str> = Arc::from(symbol);
+        names.push(Arc::clone(&name));
+        ids.insert(name, id);
+        id
+    }
+
+    /// ID of `symbol`, if it has been interned
+    pub fn get(&self, symbol: &str) -> Option<SymbolId> {
+        self.ids.read().unwrap().get(symbol).copied()
+    }
+
+    /// Name of an interned symbol.
+    ///
+    /// # Panics
+    ///
+    /// If `id` was issued by another table.
+    pub fn name(&self, id: SymbolId) -> Arc<str> {
+        Arc::clone(&self.names.read().unwrap()[id.index()])
+    }
+
+    /// Number of symbols interned
+    pub fn len(&self) -> usize {
+        self.names.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_interning() {
+        let symbols = SymbolTable::new();
+        assert_eq!(symbols.get("BTCUSD"), None);
+
+        let btc = symbols.intern("BTCUSD");
+        let eth = symbols.intern("ETHUSD");
+        assert_eq!(symbols.intern("BTCUSD"), btc);
+        assert_eq!((btc.index(), eth.index()), (0, 1));
+        assert_eq!(symbols.get("ETHUSD"), Some(eth));
+        assert_eq!(&*symbols.name(eth), "ETHUSD");
+        assert_eq!(symbols.len(), 2);
+    }
+}
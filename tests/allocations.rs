@@ -0,0 +1,86 @@
+//! Allocation counting on the matching path. Kept in its own test binary, as it installs
+//! a global allocator.
+
+use rust_order_execution_engine::{Order, OrderBook, Side};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Only the test's own thread is counted, not the harness around it
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.with(Cell::get) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if COUNTING.with(Cell::get) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Allocations made by `f` on this thread
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    COUNTING.with(|counting| counting.set(true));
+    f();
+    COUNTING.with(|counting| counting.set(false));
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// Three resting sells and the buy that takes all of them
+fn round(i: u64) -> (Vec<Order>, Order) {
+    let sells = (0..3)
+        .map(|maker| Order::new_limit("BTCUSD".to_string(), Side::Sell, 5, 100.0, format!("maker{}", maker)))
+        .collect();
+    let buy = Order::new_limit("BTCUSD".to_string(), Side::Buy, 15, 100.0, format!("taker{}", i % 2));
+    (sells, buy)
+}
+
+#[test]
+fn test_steady_state_matching_does_not_allocate() {
+    let mut book = OrderBook::new("BTCUSD".to_string());
+    // Levels away from the touch keep both sides of the book in place throughout
+    book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 1, 50.0, "anchor".to_string()));
+    book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Sell, 1, 200.0, "anchor".to_string()));
+
+    let mut trades = Vec::new();
+    let play = |book: &mut OrderBook, rounds: &mut Vec<(Vec<Order>, Order)>, trades: &mut Vec<_>| {
+        for (sells, buy) in rounds.drain(..) {
+            for sell in sells {
+                book.add_order(sell);
+            }
+            book.match_order_into(buy, trades);
+            assert_eq!(trades.len(), 3);
+            book.recycle_trades(trades.drain(..));
+        }
+    };
+
+    // Warm up the trade pool and the book's buffers
+    let mut warm_up: Vec<_> = (0..100).map(round).collect();
+    play(&mut book, &mut warm_up, &mut trades);
+
+    let mut rounds: Vec<_> = (0..1_000).map(round).collect();
+    let allocations = count_allocations(|| play(&mut book, &mut rounds, &mut trades));
+    assert_eq!(allocations, 0);
+    assert_eq!(book.depth(), 2);
+}
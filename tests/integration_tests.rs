@@ -2,148 +2,116 @@ use rust_order_execution_engine::*;
 
 #[test]
 fn test_order_creation() {
-    let order = Order::new(
-        "order1".to_string(),
+    let order = Order::new_limit(
         "BTCUSD".to_string(),
         Side::Buy,
-        OrderType::Limit,
-        100.0,
-        Some(50000.0),
+        100,
+        50000.0,
+        "client1".to_string(),
     );
-    
-    assert_eq!(order.id, "order1");
+
     assert_eq!(order.symbol, "BTCUSD");
-    assert_eq!(order.quantity, 100.0);
-    assert_eq!(order.price, Some(50000.0));
+    assert_eq!(order.order_type, OrderType::Limit);
+    assert_eq!(order.quantity, 100);
+    assert_eq!(order.price, Some(Price::from(50000.0)));
+    assert_eq!(order.status, OrderStatus::Pending);
 }
 
 #[test]
 fn test_order_matching() {
     let mut order_book = OrderBook::new("BTCUSD".to_string());
-    
+
     // Add buy order
-    let buy_order = Order::new(
-        "buy1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Buy,
-        OrderType::Limit,
-        100.0,
-        Some(50000.0),
-    );
+    let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 100, 50000.0, "client1".to_string());
     order_book.add_order(buy_order);
-    
+
     // Add sell order that matches
-    let sell_order = Order::new(
-        "sell1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Sell,
-        OrderType::Limit,
-        100.0,
-        Some(50000.0),
-    );
-    
+    let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 100, 50000.0, "client2".to_string());
+
     let trades = order_book.match_order(sell_order);
     assert_eq!(trades.len(), 1);
-    assert_eq!(trades[0].quantity, 100.0);
-    assert_eq!(trades[0].price, 50000.0);
+    assert_eq!(trades[0].quantity, 100);
+    assert_eq!(trades[0].price, Price::from(50000.0));
+    assert_eq!(order_book.depth(), 0);
 }
 
 #[test]
 fn test_partial_fill() {
     let mut order_book = OrderBook::new("BTCUSD".to_string());
-    
+
     // Add buy order for 100
-    let buy_order = Order::new(
-        "buy1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Buy,
-        OrderType::Limit,
-        100.0,
-        Some(50000.0),
-    );
+    let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 100, 50000.0, "client1".to_string());
+    let buy_id = buy_order.id;
     order_book.add_order(buy_order);
-    
+
     // Add sell order for 50 (partial fill)
-    let sell_order = Order::new(
-        "sell1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Sell,
-        OrderType::Limit,
-        50.0,
-        Some(50000.0),
-    );
-    
+    let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 50, 50000.0, "client2".to_string());
+
     let trades = order_book.match_order(sell_order);
     assert_eq!(trades.len(), 1);
-    assert_eq!(trades[0].quantity, 50.0);
-    
+    assert_eq!(trades[0].quantity, 50);
+
     // Check remaining quantity in order book
     let depth = order_book.get_depth(5);
     assert_eq!(depth.bids.len(), 1);
-    assert_eq!(depth.bids[0].1, 50.0); // Remaining 50
+    assert_eq!(depth.bids[0].1, 50); // Remaining 50
+    assert_eq!(order_book.get_order(buy_id).unwrap().remaining_quantity(), 50);
+}
+
+#[test]
+fn test_remainder_rests() {
+    let mut order_book = OrderBook::new("BTCUSD".to_string());
+
+    let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 50, 50000.0, "client1".to_string());
+    order_book.add_order(sell_order);
+
+    // Crosses one level, then rests the rest at its limit
+    let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 80, 50100.0, "client2".to_string());
+    let trades = order_book.match_order(buy_order);
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].price, Price::from(50000.0));
+    assert_eq!(order_book.best_bid(), Some(Price::from(50100.0)));
+    assert_eq!(order_book.best_ask(), None);
 }
 
 #[test]
 fn test_market_order() {
     let mut order_book = OrderBook::new("BTCUSD".to_string());
-    
+
     // Add limit order
-    let limit_order = Order::new(
-        "limit1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Sell,
-        OrderType::Limit,
-        100.0,
-        Some(50000.0),
-    );
+    let limit_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 100, 50000.0, "client1".to_string());
     order_book.add_order(limit_order);
-    
+
     // Add market order
-    let market_order = Order::new(
-        "market1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Buy,
-        OrderType::Market,
-        100.0,
-        None,
-    );
-    
+    let market_order = Order::new_market("BTCUSD".to_string(), Side::Buy, 100, "client2".to_string());
+
     let trades = order_book.match_order(market_order);
     assert_eq!(trades.len(), 1);
-    assert_eq!(trades[0].quantity, 100.0);
+    assert_eq!(trades[0].quantity, 100);
 }
 
 #[test]
 fn test_order_book_depth() {
     let mut order_book = OrderBook::new("BTCUSD".to_string());
-    
+
     // Add multiple orders
     for i in 0..5 {
-        let buy_order = Order::new(
-            format!("buy{}", i),
-            "BTCUSD".to_string(),
-            Side::Buy,
-            OrderType::Limit,
-            100.0,
-            Some(50000.0 - (i as f64 * 10.0)),
-        );
+        let buy_price = 50000.0 - (i as f64 * 10.0);
+        let buy_order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 100, buy_price, format!("buyer{}", i));
         order_book.add_order(buy_order);
-        
-        let sell_order = Order::new(
-            format!("sell{}", i),
-            "BTCUSD".to_string(),
-            Side::Sell,
-            OrderType::Limit,
-            100.0,
-            Some(50100.0 + (i as f64 * 10.0)),
-        );
+
+        let sell_price = 50100.0 + (i as f64 * 10.0);
+        let sell_order = Order::new_limit("BTCUSD".to_string(), Side::Sell, 100, sell_price, format!("seller{}", i));
         order_book.add_order(sell_order);
     }
-    
+    // A second order at the best bid is aggregated into its level
+    order_book.add_order(Order::new_limit("BTCUSD".to_string(), Side::Buy, 50, 50000.0, "buyer5".to_string()));
+
     let depth = order_book.get_depth(3);
     assert_eq!(depth.bids.len(), 3);
     assert_eq!(depth.asks.len(), 3);
-    
+    assert_eq!(depth.bids[0], (Price::from(50000.0), 150, 2));
+
     // Check price ordering
     assert!(depth.bids[0].0 > depth.bids[1].0);
     assert!(depth.asks[0].0 < depth.asks[1].0);
@@ -152,22 +120,34 @@ fn test_order_book_depth() {
 #[test]
 fn test_cancel_order() {
     let mut order_book = OrderBook::new("BTCUSD".to_string());
-    
-    let order = Order::new(
-        "order1".to_string(),
-        "BTCUSD".to_string(),
-        Side::Buy,
-        OrderType::Limit,
-        100.0,
-        Some(50000.0),
-    );
+
+    let order = Order::new_limit("BTCUSD".to_string(), Side::Buy, 100, 50000.0, "client1".to_string());
+    let order_id = order.id;
     order_book.add_order(order);
-    
+
     let depth_before = order_book.get_depth(5);
     assert_eq!(depth_before.bids.len(), 1);
-    
-    order_book.cancel_order("order1");
-    
+
+    let cancelled = order_book.cancel_order(order_id).unwrap();
+    assert_eq!(cancelled.status, OrderStatus::Cancelled);
+
     let depth_after = order_book.get_depth(5);
     assert_eq!(depth_after.bids.len(), 0);
+    assert_eq!(order_book.best_bid(), None);
+}
+
+#[test]
+fn test_scenario_partial_fill_and_cancel() {
+    let limit = |side, quantity, price: f64| Order::new_limit("BTCUSD".to_string(), side, quantity, price, "client".to_string());
+
+    Scenario::new("BTCUSD")
+        .submit("bid1", limit(Side::Buy, 100, 50000.0))
+        .submit("bid2", limit(Side::Buy, 50, 49990.0))
+        .submit("sweep", Order::new_market("BTCUSD".to_string(), Side::Sell, 120, "client".to_string()))
+        .expect_trades(&[("bid1", "sweep", 100, 50000.0), ("bid2", "sweep", 20, 49990.0)])
+        .expect_bids(&[(49990.0, 30)])
+        .cancel("bid2")
+        .expect_bids(&[])
+        .run()
+        .unwrap();
 }